@@ -0,0 +1,283 @@
+// 压测工具 - 按比例混合上传、下载和列表请求压测一台正在运行的服务器, 输出各类请求的延迟分位数和吞吐量
+//
+// 用法: cargo run --release --example load -- --target http://127.0.0.1:3000 --duration 30 --concurrency 32
+use clap::Parser;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Parser, Debug, Clone)]
+#[command(name = "load", about = "文件服务器压测工具")]
+struct Args {
+    #[arg(long, default_value = "http://127.0.0.1:3000", help = "服务器地址")]
+    target: String,
+    #[arg(long, default_value_t = 30, help = "压测时长 (秒)")]
+    duration: u64,
+    #[arg(long, default_value_t = 16, help = "并发请求数")]
+    concurrency: usize,
+    #[arg(
+        long,
+        default_value = "upload=1,download=6,listing=3",
+        help = "请求比例"
+    )]
+    mix: String,
+    #[arg(long, default_value_t = 256, help = "每次上传的大小 (KB), 最大 1024")]
+    upload_kb: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Upload,
+    Download,
+    Listing,
+}
+
+impl Operation {
+    const ALL: [Operation; 3] = [Operation::Upload, Operation::Download, Operation::Listing];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Upload => "upload",
+            Operation::Download => "download",
+            Operation::Listing => "listing",
+        }
+    }
+}
+
+// 单类请求的结果, 延迟单位为微秒
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<u64>,
+    errors: u64,
+    bytes: u64,
+}
+
+struct Shared {
+    args: Args,
+    client: reqwest::Client,
+    weights: Vec<(Operation, u32)>,
+    // 已上传内容的 SHA-256, 下载请求从中选取
+    uploaded: Mutex<Vec<String>>,
+    samples: [Mutex<Samples>; 3],
+    seed: AtomicU64,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let weights = match parse_mix(&args.mix) {
+        Ok(weights) => weights,
+        Err(e) => {
+            eprintln!("请求比例无效: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if args.upload_kb == 0 || args.upload_kb > 1024 {
+        eprintln!("上传大小必须在 1-1024 KB 之间");
+        std::process::exit(2);
+    }
+
+    let shared = Arc::new(Shared {
+        client: reqwest::Client::new(),
+        weights,
+        uploaded: Mutex::new(Vec::new()),
+        samples: Default::default(),
+        seed: AtomicU64::new(0x9E37_79B9_7F4A_7C15),
+        args: args.clone(),
+    });
+
+    // 先上传一个文件, 保证下载请求有内容可取, 同时确认服务器可达
+    if let Err(e) = upload(&shared).await {
+        eprintln!("无法连接 {}: {}", args.target, e);
+        std::process::exit(1);
+    }
+
+    println!(
+        "压测 {}: {} 秒, 并发 {}, 比例 {}",
+        args.target, args.duration, args.concurrency, args.mix
+    );
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                while Instant::now() < deadline {
+                    let operation = shared.pick();
+                    let begin = Instant::now();
+                    let result = match operation {
+                        Operation::Upload => upload(&shared).await,
+                        Operation::Download => download(&shared).await,
+                        Operation::Listing => listing(&shared).await,
+                    };
+                    let elapsed = begin.elapsed().as_micros() as u64;
+
+                    let mut samples = shared.samples[operation as usize].lock().unwrap();
+                    match result {
+                        Ok(bytes) => {
+                            samples.latencies.push(elapsed);
+                            samples.bytes += bytes;
+                        }
+                        Err(_) => samples.errors += 1,
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.await;
+    }
+
+    report(&shared, started.elapsed());
+}
+
+impl Shared {
+    // xorshift 伪随机数, 按权重选出下一个请求
+    fn next_random(&self) -> u64 {
+        let mut x = self
+            .seed
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        x
+    }
+
+    fn pick(&self) -> Operation {
+        let total: u32 = self.weights.iter().map(|(_, weight)| weight).sum();
+        let mut roll = (self.next_random() % u64::from(total)) as u32;
+        for (operation, weight) in &self.weights {
+            if roll < *weight {
+                return *operation;
+            }
+            roll -= weight;
+        }
+        self.weights[0].0
+    }
+}
+
+// 解析 "upload=1,download=6,listing=3", 未列出的请求比例为 0
+fn parse_mix(mix: &str) -> Result<Vec<(Operation, u32)>, String> {
+    let mut weights = Vec::new();
+    for part in mix.split(',').filter(|part| !part.trim().is_empty()) {
+        let (name, weight) = part
+            .split_once('=')
+            .ok_or_else(|| format!("缺少 '=': {}", part))?;
+        let operation = Operation::ALL
+            .into_iter()
+            .find(|operation| operation.name() == name.trim())
+            .ok_or_else(|| format!("未知的请求类型: {}", name))?;
+        let weight: u32 = weight
+            .trim()
+            .parse()
+            .map_err(|_| format!("比例不是整数: {}", weight))?;
+        if weight > 0 {
+            weights.push((operation, weight));
+        }
+    }
+    if weights.is_empty() {
+        return Err("至少需要一种请求".to_string());
+    }
+    Ok(weights)
+}
+
+// 以文本片段接口上传生成的数据, 返回上传的字节数
+async fn upload(shared: &Shared) -> Result<u64, String> {
+    let size = shared.args.upload_kb * 1024;
+    let mut text = format!("{:016x}\n", shared.next_random()).repeat(size / 17 + 1);
+    text.truncate(size);
+
+    let response = shared
+        .client
+        .post(format!("{}/api/paste?syntax=txt", shared.args.target))
+        .body(text)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    let sha256 = body["data"]["sha256"]
+        .as_str()
+        .ok_or_else(|| format!("上传失败: {} {}", status, body["error"]))?;
+
+    shared.uploaded.lock().unwrap().push(sha256.to_string());
+    Ok(size as u64)
+}
+
+// 按哈希下载一个已上传的文件, 返回收到的字节数
+async fn download(shared: &Shared) -> Result<u64, String> {
+    let sha256 = {
+        let uploaded = shared.uploaded.lock().unwrap();
+        let index = (shared.next_random() % uploaded.len().max(1) as u64) as usize;
+        uploaded.get(index).cloned().ok_or("没有可下载的文件")?
+    };
+    fetch(shared, &format!("/files/by-hash/{}", sha256)).await
+}
+
+async fn listing(shared: &Shared) -> Result<u64, String> {
+    fetch(shared, "/api/files?limit=50").await
+}
+
+async fn fetch(shared: &Shared, path: &str) -> Result<u64, String> {
+    let response = shared
+        .client
+        .get(format!("{}{}", shared.args.target, path))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    Ok(bytes.len() as u64)
+}
+
+fn report(shared: &Shared, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    println!();
+    // 表头用英文, 中文字符在终端占两列会导致对不齐
+    println!(
+        "{:<10} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "op", "count", "errors", "req/s", "MB/s", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+
+    let mut total_requests = 0;
+    for operation in Operation::ALL {
+        let mut samples = shared.samples[operation as usize].lock().unwrap();
+        let count = samples.latencies.len();
+        if count == 0 && samples.errors == 0 {
+            continue;
+        }
+        total_requests += count;
+        samples.latencies.sort_unstable();
+        let latencies = &samples.latencies;
+        let percentile = |p: f64| {
+            if latencies.is_empty() {
+                return 0.0;
+            }
+            let index =
+                ((latencies.len() as f64 * p).ceil() as usize).clamp(1, latencies.len()) - 1;
+            latencies[index] as f64 / 1000.0
+        };
+        println!(
+            "{:<10} {:>8} {:>7} {:>9.1} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            operation.name(),
+            count,
+            samples.errors,
+            count as f64 / seconds,
+            samples.bytes as f64 / seconds / 1024.0 / 1024.0,
+            percentile(0.50),
+            percentile(0.90),
+            percentile(0.99),
+            latencies.last().copied().unwrap_or(0) as f64 / 1000.0,
+        );
+    }
+    println!();
+    println!(
+        "共 {} 次成功请求, {:.1} 秒, {:.1} req/s",
+        total_requests,
+        seconds,
+        total_requests as f64 / seconds
+    );
+}
@@ -0,0 +1,162 @@
+// 启动自检 - 部署前检查配置、数据库、存储目录和外部工具, 输出报告
+use crate::config::Config;
+use crate::storage::FileManager;
+use crate::video::toolchain::{ToolInfo, VideoToolchain};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Ok => "OK",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+        };
+        f.pad(label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    // 存储目录所在磁盘的最小可用空间 (字节)
+    pub min_free_space: u64,
+    // 严格模式下警告也视为失败
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub results: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    pub fn passed(&self, strict: bool) -> bool {
+        self.results.iter().all(|r| match r.status {
+            CheckStatus::Ok => true,
+            CheckStatus::Warn => !strict,
+            CheckStatus::Fail => false,
+        })
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            writeln!(f, "[{:<4}] {}: {}", result.status, result.name, result.detail)?;
+        }
+        Ok(())
+    }
+}
+
+// 依次执行所有检查, 单项失败不影响后续检查
+pub async fn run_checks(config: &Config, options: &CheckOptions) -> CheckReport {
+    let mut results = vec![match config.validate() {
+        Ok(()) => CheckResult::new("配置", CheckStatus::Ok, "配置有效"),
+        Err(e) => CheckResult::new("配置", CheckStatus::Fail, e.to_string()),
+    }];
+
+    results.push(match FileManager::new(&config.database.database_url(), config.storage.upload_dir.clone()).await {
+        Ok(_) => CheckResult::new("数据库", CheckStatus::Ok, config.database.database_url()),
+        Err(e) => CheckResult::new("数据库", CheckStatus::Fail, e.to_string()),
+    });
+
+    results.push(check_storage_writable(&config.storage.upload_dir));
+    results.push(check_free_space(&config.storage.upload_dir, options.min_free_space));
+
+    let toolchain = VideoToolchain::detect(&config.video).await;
+    results.push(check_tool("ffmpeg", toolchain.ffmpeg.as_ref()));
+    results.push(check_tool("ffprobe", toolchain.ffprobe.as_ref()));
+
+    CheckReport { results }
+}
+
+fn check_storage_writable(dir: &Path) -> CheckResult {
+    const NAME: &str = "存储目录";
+
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return CheckResult::new(NAME, CheckStatus::Fail, format!("无法创建 {:?}: {}", dir, e));
+    }
+
+    let probe = dir.join(format!(".check-{}", uuid::Uuid::new_v4()));
+    let result = std::fs::write(&probe, b"check").and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => CheckResult::new(NAME, CheckStatus::Ok, format!("{:?} 可写", dir)),
+        Err(e) => CheckResult::new(NAME, CheckStatus::Fail, format!("{:?} 不可写: {}", dir, e)),
+    }
+}
+
+fn check_free_space(dir: &Path, min_free_space: u64) -> CheckResult {
+    const NAME: &str = "磁盘空间";
+
+    match available_space(dir) {
+        Some(free) if free >= min_free_space => {
+            CheckResult::new(NAME, CheckStatus::Ok, format!("可用 {} MB", free / 1024 / 1024))
+        }
+        Some(free) => CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("可用 {} MB, 低于要求的 {} MB", free / 1024 / 1024, min_free_space / 1024 / 1024),
+        ),
+        None => CheckResult::new(NAME, CheckStatus::Warn, "无法获取可用空间"),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path 是以 NUL 结尾的有效字符串, stat 指向足够大的未初始化内存, 成功时由 statvfs 填充
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+// 缺少视频工具时只影响缩略图和视频元数据, 因此只给出警告
+fn check_tool(name: &str, tool: Option<&ToolInfo>) -> CheckResult {
+    match tool {
+        Some(tool) => CheckResult::new(
+            name,
+            CheckStatus::Ok,
+            format!("{} ({:?}, 来源: {:?})", tool.version, tool.path, tool.source),
+        ),
+        None => CheckResult::new(name, CheckStatus::Warn, "未找到"),
+    }
+}
@@ -0,0 +1,152 @@
+// 文件集合 - 有序的文件列表, 整个集合通过一个地址分享
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{Collection, FileRecord};
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// 单个集合最多包含的文件数
+const MAX_COLLECTION_FILES: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct CollectionRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub file_ids: Vec<String>,
+}
+
+// 集合详情: 按集合中的顺序附带文件记录, 供播放列表直接使用
+#[derive(Debug, Serialize)]
+pub struct CollectionDetail {
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub files: Vec<FileRecord>,
+}
+
+async fn validate_request(state: &AppState, req: &CollectionRequest) -> crate::Result<()> {
+    if req.name.trim().is_empty() {
+        return Err(ServerError::validation("集合名称不能为空"));
+    }
+    if req.file_ids.len() > MAX_COLLECTION_FILES {
+        return Err(ServerError::validation(format!("集合最多包含 {} 个文件", MAX_COLLECTION_FILES)));
+    }
+
+    let files = state.files.get_files_by_ids(&req.file_ids).await?;
+    match req.file_ids.iter().find(|id| !files.iter().any(|f| &f.id == *id)) {
+        Some(missing) => Err(ServerError::validation(format!("文件 {} 不存在", missing))),
+        None => Ok(()),
+    }
+}
+
+pub async fn list_collections(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<Collection>>>, ApiError> {
+    state
+        .files
+        .list_collections()
+        .await
+        .map(|collections| Json(ApiResponse::success(collections)))
+        .map_err(|e| api_error("获取集合列表失败", e))
+}
+
+pub async fn create_collection(
+    State(state): State<AppState>,
+    Json(req): Json<CollectionRequest>,
+) -> std::result::Result<Json<ApiResponse<Collection>>, ApiError> {
+    const CONTEXT: &str = "创建集合失败";
+
+    validate_request(&state, &req).await.map_err(|e| api_error(CONTEXT, e))?;
+
+    let now = Utc::now();
+    let collection = Collection {
+        id: Uuid::new_v4().to_string(),
+        name: req.name.trim().to_string(),
+        description: req.description,
+        file_ids: req.file_ids,
+        created_at: now,
+        updated_at: now,
+    };
+    state
+        .files
+        .create_collection(&collection)
+        .await
+        .map(|_| Json(ApiResponse::success(collection)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+pub async fn get_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<CollectionDetail>>, ApiError> {
+    const CONTEXT: &str = "获取集合失败";
+
+    let collection = state
+        .files
+        .get_collection(&collection_id)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .ok_or_else(|| api_error(CONTEXT, ServerError::not_found(format!("集合 {}", collection_id))))?;
+
+    let records = state
+        .files
+        .get_files_by_ids(&collection.file_ids)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let files = collection
+        .file_ids
+        .iter()
+        .filter_map(|id| records.iter().find(|record| &record.id == id).cloned())
+        .collect();
+
+    Ok(Json(ApiResponse::success(CollectionDetail { collection, files })))
+}
+
+// 整体替换集合内容, 文件顺序以请求中的 file_ids 为准
+pub async fn update_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(req): Json<CollectionRequest>,
+) -> std::result::Result<Json<ApiResponse<Collection>>, ApiError> {
+    const CONTEXT: &str = "更新集合失败";
+
+    validate_request(&state, &req).await.map_err(|e| api_error(CONTEXT, e))?;
+
+    let existing = state
+        .files
+        .get_collection(&collection_id)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .ok_or_else(|| api_error(CONTEXT, ServerError::not_found(format!("集合 {}", collection_id))))?;
+
+    let collection = Collection {
+        name: req.name.trim().to_string(),
+        description: req.description,
+        file_ids: req.file_ids,
+        updated_at: Utc::now(),
+        ..existing
+    };
+    match state.files.update_collection(&collection).await {
+        Ok(true) => Ok(Json(ApiResponse::success(collection))),
+        Ok(false) => Err(api_error(CONTEXT, ServerError::not_found(format!("集合 {}", collection_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
+
+pub async fn delete_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "删除集合失败";
+
+    match state.files.delete_collection(&collection_id).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err(api_error(CONTEXT, ServerError::not_found(format!("集合 {}", collection_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
@@ -1,13 +1,134 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use crate::error::{Result, ServerError};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub storage: StorageConfig,
     pub video: VideoConfig,
+    pub image: ImageConfig,
+    pub web: WebConfig,
+    #[serde(default)]
+    pub profiling: ProfilingConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub federation: FederationConfig,
+}
+
+// API 密钥认证, 没有配置密钥时不启用. 启用后所有 /api/ 接口都需要携带密钥
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+    #[serde(default)]
+    pub policy: Option<PolicyConfig>,
+}
+
+impl AuthConfig {
+    pub fn enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    // 密钥的名称, 用于日志, 不参与认证
+    pub name: String,
+    // 密钥可以直接写在配置中, 也可以在启动时从 key_file 或 key_command 读取, 三者只能设置一个
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    #[serde(default)]
+    pub key_command: Option<Vec<String>>,
+    #[serde(default)]
+    pub access: KeyAccess,
+}
+
+// 多实例部署, 配置 upstream 后本实例作为上传入口, 上传的文件转发到上游实例保存
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FederationConfig {
+    #[serde(default)]
+    pub upstream: Option<UpstreamConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamConfig {
+    // 上游实例的地址, 例如 https://files.example.com
+    pub url: String,
+    // 上游启用了密钥认证时使用的密钥, 与 auth.keys 一样可以从文件或外部命令读取
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_key_file: Option<PathBuf>,
+    #[serde(default)]
+    pub api_key_command: Option<Vec<String>>,
+    // 连接上游的超时时间 (秒), 上传本身不限时
+    #[serde(default = "default_upstream_connect_timeout")]
+    pub connect_timeout: u64,
+}
+
+// 外部授权服务, 配置后修改类接口、管理接口和强制删除执行前先向该服务查询是否允许 (OPA 风格的 input)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    // 决策接口地址, 例如 http://opa:8181/v1/data/files/allow
+    pub url: String,
+    // 单次查询的超时时间 (秒)
+    #[serde(default = "default_policy_timeout")]
+    pub timeout: u64,
+    // 决策结果的缓存时间 (秒), 0 表示不缓存
+    #[serde(default = "default_policy_cache_ttl")]
+    pub cache_ttl: u64,
+    #[serde(default = "default_policy_cache_max_entries")]
+    pub cache_max_entries: u64,
+    // 授权服务不可用时放行请求, 默认拒绝
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAccess {
+    // 只能调用不修改数据的接口
+    Read,
+    #[default]
+    ReadWrite,
+    // 在读写权限之外, 还可以强制删除受保护的文件
+    Admin,
+}
+
+// 单次操作可以读入内存的数据量上限 (字节)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    // 列出或读取归档时的 zip 中央目录、tar 成员列表
+    #[serde(default = "default_memory_archive_index")]
+    pub archive_index: u64,
+    // 缩放图和缩略图解码后的像素数据
+    #[serde(default = "default_memory_image_decode")]
+    pub image_decode: u64,
+    // OCR 命令输出的文字
+    #[serde(default = "default_memory_ocr_output")]
+    pub ocr_output: u64,
+}
+
+// 运行时诊断, 需要以 profiling / tokio-console 特性编译才生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilingConfig {
+    // 启用 tokio-console 埋点
+    #[serde(default)]
+    pub tokio_console: bool,
+    // tokio-console 连接的监听地址
+    #[serde(default = "default_console_address")]
+    pub console_address: String,
+    // 单次 CPU 采样的最长秒数
+    #[serde(default = "default_max_profile_seconds")]
+    pub max_profile_seconds: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +141,12 @@ pub struct ServerConfig {
     pub max_body_size: usize,
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
+    // 修改和删除文件时是否必须携带 If-Match 或 version
+    #[serde(default)]
+    pub require_if_match: bool,
+    // 只读模式: 仅开放列表、下载和预览接口
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +155,9 @@ pub struct DatabaseConfig {
     pub url: String,
     #[serde(default = "default_max_connections")]
     pub max_connections: u32,
+    // 每天执行数据库维护的时刻 (本地时间 0-23 点), 为空时不自动维护
+    #[serde(default = "default_maintenance_hour")]
+    pub maintenance_hour: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +170,151 @@ pub struct StorageConfig {
     pub max_file_size: u64,
     #[serde(default = "default_chunk_size")]
     pub chunk_size: usize,
+    // 搜索结果打包下载和 /api/download/zip 的总大小上限
+    #[serde(default = "default_max_archive_size")]
+    pub max_archive_size: u64,
+    #[serde(default)]
+    pub naming: NamingConfig,
+    #[serde(default)]
+    pub temp: TempConfig,
+    #[serde(default)]
+    pub derived: DerivedConfig,
+    #[serde(default)]
+    pub stream_buffer: StreamBufferConfig,
+    #[serde(default)]
+    pub record_cache: RecordCacheConfig,
+    #[serde(default)]
+    pub delete_protection: DeleteProtectionConfig,
+    // 上传内容与已有文件的大小和 SHA-256 都相同时不再保存副本, 新记录指向已有文件
+    #[serde(default = "default_deduplicate")]
+    pub deduplicate: bool,
+    #[serde(default)]
+    pub trash: TrashConfig,
+    #[serde(default)]
+    pub upload_sessions: UploadSessionConfig,
+    #[serde(default)]
+    pub multipart: MultipartConfig,
+}
+
+// 存储文件的命名方式, 只影响新保存的文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamingConfig {
+    #[serde(default)]
+    pub policy: NamingPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamingPolicy {
+    // <uuid>.<扩展名>
+    #[default]
+    Uuid,
+    // <年>/<月>/<uuid>.<扩展名>
+    DatePrefixed,
+    // 保留原文件名, 重名时追加 -1、-2 等后缀
+    OriginalName,
+    // <sha256>.<扩展名>, 内容哈希未知时退回 uuid
+    Hash,
+}
+
+// 临时目录, 位于存储目录下的 .tmp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempConfig {
+    // 临时文件总大小上限 (字节), 超过后拒绝新的临时文件
+    #[serde(default = "default_temp_max_size")]
+    pub max_size: u64,
+    // 超过该时间 (秒) 未修改的临时文件视为遗留文件
+    #[serde(default = "default_temp_max_age")]
+    pub max_age: u64,
+    // 后台清理间隔 (秒)
+    #[serde(default = "default_temp_cleanup_interval")]
+    pub cleanup_interval: u64,
+}
+
+// 派生文件 (缩略图、缩放图等) 的孤儿清理
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedConfig {
+    // 后台清理间隔 (秒), 0 表示只能手动清理
+    #[serde(default = "default_derived_cleanup_interval")]
+    pub cleanup_interval: u64,
+    // 修改时间不满该秒数的派生文件不清理, 避免删掉正在保存的文件的缩略图
+    #[serde(default = "default_derived_min_age")]
+    pub min_age: u64,
+}
+
+// 回收站, 位于存储目录下的 .trash. 删除的文件先移入回收站, 可以恢复, 过期后自动清除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashConfig {
+    // 关闭时删除立即生效, 不可恢复
+    #[serde(default = "default_trash_enabled")]
+    pub enabled: bool,
+    // 在回收站中超过该时间 (秒) 的文件被永久删除, 0 表示不自动清除
+    #[serde(default = "default_trash_max_age")]
+    pub max_age: u64,
+    // 后台清除间隔 (秒)
+    #[serde(default = "default_trash_cleanup_interval")]
+    pub cleanup_interval: u64,
+}
+
+// 分块上传会话. 创建会话时为声明的大小预留空间, 完成、取消或过期后释放
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSessionConfig {
+    // 超过该时间 (秒) 没有收到新分块的会话被删除, 0 表示不自动删除. Idempotency-Key 也保留这么久
+    #[serde(default = "default_upload_session_max_age")]
+    pub max_age: u64,
+    // 后台清理间隔 (秒)
+    #[serde(default = "default_upload_session_cleanup_interval")]
+    pub cleanup_interval: u64,
+    // 所有会话预留空间的总上限 (字节), 为空时只受磁盘剩余空间限制
+    #[serde(default)]
+    pub max_reserved: Option<u64>,
+}
+
+// multipart 上传表单的限制, 文件字段的大小由 max_file_size 限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipartConfig {
+    // 表单中字段的最大数量, 包括文件字段
+    #[serde(default = "default_multipart_max_parts")]
+    pub max_parts: usize,
+    // 普通 (非文件) 字段的最大字节数
+    #[serde(default = "default_multipart_max_field_size")]
+    pub max_field_size: u64,
+    // 接收文件字段时每隔多少字节记录一次进度和速率
+    #[serde(default = "default_multipart_progress_interval")]
+    pub progress_interval: u64,
+}
+
+// 下载时每次读取的字节数范围, 实际取值随文件大小、并发下载数和客户端的接收速度变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamBufferConfig {
+    #[serde(default = "default_stream_buffer_min")]
+    pub min_size: usize,
+    #[serde(default = "default_stream_buffer_max")]
+    pub max_size: usize,
+}
+
+// 按 ID 查询文件记录的内存缓存, 视频拖动时的大量 Range 请求不必每次查询数据库
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordCacheConfig {
+    // 缓存有效期 (秒), 0 表示不缓存
+    #[serde(default = "default_record_cache_ttl")]
+    pub ttl: u64,
+    #[serde(default = "default_record_cache_max_entries")]
+    pub max_entries: u64,
+}
+
+// 删除保护规则, 命中任一规则的文件只能由本机带 force=true 删除
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeleteProtectionConfig {
+    // 大于该大小 (字节) 的文件
+    #[serde(default)]
+    pub larger_than: Option<u64>,
+    // 上传后不满该小时数的文件
+    #[serde(default)]
+    pub younger_than_hours: Option<u64>,
+    // 文件名包含其中任一字符串的文件, 不区分大小写
+    #[serde(default)]
+    pub name_contains: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +323,330 @@ pub struct VideoConfig {
     pub thumbnail_size: String,
     #[serde(default = "default_supported_formats")]
     pub supported_formats: Vec<String>,
+    // 每个尺寸按这些格式各生成一张, 请求缩略图时按 Accept 选择, 都不接受时返回第一种
+    #[serde(default = "default_thumbnail_formats")]
+    pub thumbnail_formats: Vec<ThumbnailFormat>,
+    #[serde(default = "default_thumbnail_quality")]
+    pub thumbnail_quality: u8,
+    // 额外的命名尺寸, 例如 small = "160x120"
+    #[serde(default)]
+    pub thumbnail_sizes: BTreeMap<String, String>,
+    // ffmpeg/ffprobe 路径, 为空时从 PATH 和可执行文件旁的 tools 目录查找
+    #[serde(default)]
+    pub ffmpeg_path: Option<PathBuf>,
+    #[serde(default)]
+    pub ffprobe_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConfig {
+    #[serde(default = "default_thumbnail_size")]
+    pub thumbnail_size: String,
+    #[serde(default = "default_thumbnail_formats")]
+    pub thumbnail_formats: Vec<ThumbnailFormat>,
+    #[serde(default = "default_thumbnail_quality")]
+    pub thumbnail_quality: u8,
+    #[serde(default)]
+    pub thumbnail_sizes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub ocr: OcrConfig,
+    #[serde(default)]
+    pub heif: HeifConfig,
+}
+
+// 图片文字识别, 调用外部 tesseract 命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_ocr_command")]
+    pub command: String,
+    // tesseract 语言包名称, 例如 eng、chi_sim
+    #[serde(default = "default_ocr_languages")]
+    pub languages: Vec<String>,
+    // 单张图片的识别超时时间 (秒)
+    #[serde(default = "default_ocr_timeout")]
+    pub timeout: u64,
+}
+
+// HEIC/HEIF 兼容处理, 调用外部 heif-convert 命令生成 JPEG 副本, 原文件保持不变
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeifConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_heif_command")]
+    pub command: String,
+    #[serde(default = "default_heif_quality")]
+    pub quality: u8,
+    // 按哈希下载时默认返回 JPEG 副本, 加 ?original=true 取原文件
+    #[serde(default)]
+    pub download_jpeg: bool,
+    // 单张图片的转换超时时间 (秒)
+    #[serde(default = "default_heif_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebConfig {
+    #[serde(default)]
+    pub branding: BrandingConfig,
+    #[serde(default)]
+    pub cache_control: CacheControlConfig,
+    // 额外挂载的主机目录, 按原样浏览和下载, 不导入数据库
+    #[serde(default)]
+    pub mounts: Vec<MountConfig>,
+    // 查看器映射, 排在内置映射之前, 可以覆盖内置的选择
+    #[serde(default)]
+    pub viewers: Vec<ViewerMapping>,
+}
+
+// MIME 类型到查看器的映射. pattern 可以是 video/mp4、video/* 或 */*
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewerMapping {
+    pub pattern: String,
+    pub viewer: ViewerKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewerKind {
+    Video,
+    Image,
+    Pdf,
+    Text,
+    Table,
+    Hex,
+}
+
+// 目录挂载, 例如 route = "/datasets", path = "/mnt/datasets"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountConfig {
+    pub route: String,
+    pub path: PathBuf,
+    // 目前只支持只读挂载
+    #[serde(default = "default_mount_read_only")]
+    pub read_only: bool,
+}
+
+// 各类响应的 Cache-Control 取值, 空字符串表示不设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheControlConfig {
+    // 内容由地址决定的响应: 按哈希下载
+    #[serde(default = "default_cache_immutable")]
+    pub immutable: String,
+    // 列表、统计、订阅等随时会变化的响应
+    #[serde(default = "default_cache_listing")]
+    pub listing: String,
+    // 缩略图、缩放图、文本、压缩包条目等预览内容
+    #[serde(default = "default_cache_preview")]
+    pub preview: String,
+}
+
+// 界面品牌定制, 通过 /api/ui-config 提供给前端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrandingConfig {
+    #[serde(default = "default_branding_title")]
+    pub title: String,
+    #[serde(default)]
+    pub logo_path: Option<PathBuf>,
+    #[serde(default = "default_accent_color")]
+    pub accent_color: String,
+    #[serde(default)]
+    pub footer_text: Option<String>,
+    #[serde(default = "default_language")]
+    pub default_language: String,
+}
+
+// 缩略图输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Webp,
+}
+
+impl ThumbnailFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    // 根据 Accept 请求头从已生成的格式中选择 q 值最高的, 相同时按配置顺序, 都不接受时返回第一个.
+    // 每种格式取最具体的匹配项的 q 值, 例如 image/webp 优先于 image/*
+    pub fn negotiate(accept: &str, available: &[ThumbnailFormat]) -> Option<ThumbnailFormat> {
+        let ranges: Vec<(&str, f32)> = accept
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let mime = parts.next().filter(|mime| !mime.is_empty())?;
+                let quality = parts
+                    .find_map(|p| p.strip_prefix("q=")?.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((mime, quality))
+            })
+            .collect();
+        let quality = |format: ThumbnailFormat| {
+            [format.mime_type(), "image/*", "*/*"]
+                .into_iter()
+                .find_map(|range| ranges.iter().find(|(mime, _)| *mime == range).map(|(_, q)| *q))
+                .unwrap_or(0.0)
+        };
+
+        let mut best: Option<(ThumbnailFormat, f32)> = None;
+        for format in available.iter().copied() {
+            let q = quality(format);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((format, q));
+            }
+        }
+        best.map(|(format, _)| format).or_else(|| available.first().copied())
+    }
+}
+
+// 解析 "320x240" 形式的尺寸
+pub fn parse_dimensions(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let width = width.trim().parse().ok().filter(|w| *w > 0)?;
+    let height = height.trim().parse().ok().filter(|h| *h > 0)?;
+    Some((width, height))
+}
+
+// 需要生成的全部命名尺寸, 默认尺寸命名为 default
+fn thumbnail_variants(default_size: &str, sizes: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut variants = sizes.clone();
+    variants.insert("default".to_string(), default_size.to_string());
+    variants
+}
+
+// 挂载路由保留给内置接口的第一段
+const RESERVED_MOUNT_ROUTES: &[&str] = &["api", "files", "paste", "health", "ui"];
+
+// 从文件或外部命令读取敏感配置, 两者都未设置时返回 None. 末尾的换行被去掉.
+// 命令直接执行, 不经过 shell, 第一个元素是程序, 其余是参数
+fn read_secret(
+    name: &str,
+    inline: &str,
+    file: Option<&std::path::Path>,
+    command: Option<&[String]>,
+) -> Result<Option<String>> {
+    let secret = match (file, command) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(ServerError::validation(format!("{} 不能同时从文件和命令读取", name)));
+        }
+        _ if !inline.is_empty() => {
+            return Err(ServerError::validation(format!("{} 已直接配置, 不能再从文件或命令读取", name)));
+        }
+        (Some(file), None) => std::fs::read_to_string(file)
+            .map_err(|e| ServerError::validation(format!("无法读取 {} 的文件 {}: {}", name, file.display(), e)))?,
+        (None, Some(command)) => {
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| ServerError::validation(format!("{} 的命令不能为空", name)))?;
+            let output = std::process::Command::new(program)
+                .args(args)
+                .stdin(std::process::Stdio::null())
+                .output()
+                .map_err(|e| ServerError::validation(format!("无法执行 {} 的命令 {}: {}", name, program, e)))?;
+            if !output.status.success() {
+                return Err(ServerError::validation(format!(
+                    "{} 的命令 {} 执行失败 ({}): {}",
+                    name,
+                    program,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            String::from_utf8(output.stdout)
+                .map_err(|_| ServerError::validation(format!("{} 的命令输出不是 UTF-8 文本", name)))?
+        }
+    };
+    Ok(Some(secret.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn validate_mount(mount: &MountConfig) -> Result<()> {
+    let route = mount.route.as_str();
+    let segments: Vec<&str> = route.strip_prefix('/').unwrap_or("").split('/').collect();
+    let valid = route.starts_with('/')
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && *segment != "."
+                && *segment != ".."
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        });
+    if !valid {
+        return Err(ServerError::validation(format!("挂载路由无效: {}", route)));
+    }
+    if RESERVED_MOUNT_ROUTES.contains(&segments[0]) {
+        return Err(ServerError::validation(format!("挂载路由与内置接口冲突: {}", route)));
+    }
+    if !mount.read_only {
+        return Err(ServerError::validation(format!("暂不支持可写挂载: {}", route)));
+    }
+    if !mount.path.is_dir() {
+        return Err(ServerError::validation(format!("挂载目录不存在: {}", mount.path.display())));
+    }
+    Ok(())
+}
+
+fn validate_viewer_pattern(pattern: &str) -> Result<()> {
+    let valid = match pattern.split_once('/') {
+        Some(("*", subtype)) => subtype == "*",
+        Some((kind, subtype)) => {
+            let token = |value: &str| {
+                !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "-+._".contains(c))
+            };
+            token(kind) && (subtype == "*" || token(subtype))
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(ServerError::validation(format!("查看器映射的 MIME 模式无效: {}", pattern)));
+    }
+    Ok(())
+}
+
+fn validate_thumbnails(
+    section: &str,
+    default_size: &str,
+    sizes: &BTreeMap<String, String>,
+    formats: &[ThumbnailFormat],
+    quality: u8,
+) -> Result<()> {
+    // 名称用于缩略图文件名和请求参数
+    for name in sizes.keys() {
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+        if !valid || name == "default" {
+            return Err(ServerError::validation(format!("{} 缩略图尺寸名称无效: {}", section, name)));
+        }
+    }
+    for (name, size) in thumbnail_variants(default_size, sizes) {
+        if parse_dimensions(&size).is_none() {
+            return Err(ServerError::validation(format!(
+                "{} 缩略图尺寸 {} 格式无效: {}",
+                section, name, size
+            )));
+        }
+    }
+
+    if formats.is_empty() {
+        return Err(ServerError::validation(format!("{} 至少需要一种缩略图格式", section)));
+    }
+
+    if quality == 0 || quality > 100 {
+        return Err(ServerError::validation(format!("{} 缩略图质量必须在 1-100 之间", section)));
+    }
+
+    Ok(())
 }
 
 impl Config {
@@ -63,7 +662,8 @@ impl Config {
             .build()
             .map_err(ServerError::from)?;
 
-        let config: Config = settings.try_deserialize().map_err(ServerError::from)?;
+        let mut config: Config = settings.try_deserialize().map_err(ServerError::from)?;
+        config.resolve_secrets()?;
         
         // 验证配置
         config.validate()?;
@@ -71,12 +671,38 @@ impl Config {
         Ok(config)
     }
 
-    fn validate(&self) -> Result<()> {
+    // 读取来自文件或外部命令的敏感配置, 读取后写入对应的字段
+    pub fn resolve_secrets(&mut self) -> Result<()> {
+        for key in &mut self.auth.keys {
+            let name = format!("auth.keys.{}", key.name);
+            if let Some(secret) = read_secret(&name, &key.key, key.key_file.as_deref(), key.key_command.as_deref())? {
+                key.key = secret;
+            }
+        }
+        if let Some(upstream) = &mut self.federation.upstream {
+            let secret = read_secret(
+                "federation.upstream.api_key",
+                &upstream.api_key,
+                upstream.api_key_file.as_deref(),
+                upstream.api_key_command.as_deref(),
+            )?;
+            if let Some(secret) = secret {
+                upstream.api_key = secret;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
         // 验证服务器配置
         if self.server.port == 0 {
             return Err(ServerError::validation("端口号不能为0"));
         }
 
+        if self.database.maintenance_hour.is_some_and(|hour| hour > 23) {
+            return Err(ServerError::validation("数据库维护时刻必须在 0-23 之间"));
+        }
+
         // 验证存储路径
         if !self.storage.path.exists() {
             std::fs::create_dir_all(&self.storage.path)
@@ -87,6 +713,169 @@ impl Config {
         if self.storage.max_file_size == 0 {
             return Err(ServerError::validation("最大文件大小不能为0"));
         }
+        if self.storage.chunk_size == 0 {
+            return Err(ServerError::validation("分块大小不能为0"));
+        }
+
+        // 验证临时目录配置
+        if self.storage.temp.max_size == 0 {
+            return Err(ServerError::validation("临时目录大小上限不能为0"));
+        }
+        if self.storage.temp.max_age == 0 || self.storage.temp.cleanup_interval == 0 {
+            return Err(ServerError::validation("临时文件过期时间和清理间隔不能为0"));
+        }
+        if self.storage.trash.cleanup_interval == 0 {
+            return Err(ServerError::validation("回收站清除间隔不能为0"));
+        }
+        if self.storage.upload_sessions.cleanup_interval == 0 {
+            return Err(ServerError::validation("上传会话清理间隔不能为0"));
+        }
+        let multipart = &self.storage.multipart;
+        if multipart.max_parts == 0 || multipart.progress_interval == 0 {
+            return Err(ServerError::validation("上传表单的字段数量上限和进度记录间隔不能为0"));
+        }
+
+        // 验证下载读缓冲范围
+        let stream_buffer = &self.storage.stream_buffer;
+        if stream_buffer.min_size == 0 || stream_buffer.min_size > stream_buffer.max_size {
+            return Err(ServerError::validation("下载读缓冲的最小值必须大于0且不超过最大值"));
+        }
+
+        // 验证内存预算
+        let memory = &self.memory;
+        if memory.archive_index == 0 || memory.image_decode == 0 || memory.ocr_output == 0 {
+            return Err(ServerError::validation("内存预算不能为0"));
+        }
+
+        // 验证诊断配置
+        if self.profiling.max_profile_seconds == 0 {
+            return Err(ServerError::validation("CPU 采样最长时间不能为0"));
+        }
+        if self.profiling.tokio_console && self.profiling.console_address.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ServerError::validation(format!(
+                "tokio-console 监听地址无效: {}",
+                self.profiling.console_address
+            )));
+        }
+
+        // 验证缓存策略, 取值会直接作为响应头
+        let cache_control = &self.web.cache_control;
+        for (name, policy) in [
+            ("immutable", &cache_control.immutable),
+            ("listing", &cache_control.listing),
+            ("preview", &cache_control.preview),
+        ] {
+            if policy.chars().any(|c| !c.is_ascii() || c.is_ascii_control()) {
+                return Err(ServerError::validation(format!("缓存策略 {} 包含无效字符", name)));
+            }
+        }
+
+        // 验证目录挂载, 路由不能与已有接口冲突
+        let mut routes = std::collections::HashSet::new();
+        for mount in &self.web.mounts {
+            validate_mount(mount)?;
+            if !routes.insert(mount.route.as_str()) {
+                return Err(ServerError::validation(format!("挂载路由重复: {}", mount.route)));
+            }
+        }
+
+        for mapping in &self.web.viewers {
+            validate_viewer_pattern(&mapping.pattern)?;
+        }
+
+        // 验证 API 密钥, 名称和密钥都不能重复
+        let mut names = std::collections::HashSet::new();
+        let mut keys = std::collections::HashSet::new();
+        for key in &self.auth.keys {
+            if key.name.trim().is_empty() || key.key.trim().is_empty() {
+                return Err(ServerError::validation("API 密钥的名称和密钥不能为空"));
+            }
+            if !names.insert(key.name.as_str()) {
+                return Err(ServerError::validation(format!("API 密钥名称重复: {}", key.name)));
+            }
+            if !keys.insert(key.key.as_str()) {
+                return Err(ServerError::validation(format!("API 密钥 {} 与其他密钥相同", key.name)));
+            }
+        }
+
+        // 验证缩略图配置
+        validate_thumbnails(
+            "video",
+            &self.video.thumbnail_size,
+            &self.video.thumbnail_sizes,
+            &self.video.thumbnail_formats,
+            self.video.thumbnail_quality,
+        )?;
+        validate_thumbnails(
+            "image",
+            &self.image.thumbnail_size,
+            &self.image.thumbnail_sizes,
+            &self.image.thumbnail_formats,
+            self.image.thumbnail_quality,
+        )?;
+
+        // 验证授权服务配置
+        if let Some(policy) = &self.auth.policy {
+            if !(policy.url.starts_with("http://") || policy.url.starts_with("https://")) {
+                return Err(ServerError::validation(format!("授权服务地址无效: {}", policy.url)));
+            }
+            if policy.timeout == 0 {
+                return Err(ServerError::validation("授权服务超时时间不能为0"));
+            }
+        }
+
+        // 验证上游实例配置
+        if let Some(upstream) = &self.federation.upstream {
+            if !(upstream.url.starts_with("http://") || upstream.url.starts_with("https://")) {
+                return Err(ServerError::validation(format!("上游实例地址无效: {}", upstream.url)));
+            }
+            if upstream.connect_timeout == 0 {
+                return Err(ServerError::validation("上游实例连接超时时间不能为0"));
+            }
+        }
+
+        // 验证文字识别配置
+        if self.image.ocr.enabled {
+            let ocr = &self.image.ocr;
+            if ocr.command.trim().is_empty() {
+                return Err(ServerError::validation("OCR 命令不能为空"));
+            }
+            if ocr.languages.is_empty() {
+                return Err(ServerError::validation("OCR 至少需要一种语言"));
+            }
+            if ocr.timeout == 0 {
+                return Err(ServerError::validation("OCR 超时时间不能为0"));
+            }
+        }
+
+        // 验证 HEIF 转换配置
+        if self.image.heif.enabled {
+            let heif = &self.image.heif;
+            if heif.command.trim().is_empty() {
+                return Err(ServerError::validation("HEIF 转换命令不能为空"));
+            }
+            if !(1..=100).contains(&heif.quality) {
+                return Err(ServerError::validation("HEIF 转换质量必须在 1-100 之间"));
+            }
+            if heif.timeout == 0 {
+                return Err(ServerError::validation("HEIF 转换超时时间不能为0"));
+            }
+        }
+
+        // 验证界面配置
+        let accent = &self.web.branding.accent_color;
+        let is_hex_color = accent.len() == 7
+            && accent.starts_with('#')
+            && accent[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if !is_hex_color {
+            return Err(ServerError::validation(format!("主题色格式无效: {}", accent)));
+        }
+
+        if let Some(logo) = &self.web.branding.logo_path {
+            if !logo.is_file() {
+                return Err(ServerError::validation(format!("Logo 文件不存在: {:?}", logo)));
+            }
+        }
 
         Ok(())
     }
@@ -102,14 +891,36 @@ impl DatabaseConfig {
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            database: DatabaseConfig::default(),
-            storage: StorageConfig::default(),
-            video: VideoConfig::default(),
+impl DeleteProtectionConfig {
+    // 返回命中的第一条规则的说明, 未命中时返回 None
+    pub fn matching_rule(&self, name: &str, size: u64, age: chrono::Duration) -> Option<String> {
+        if let Some(limit) = self.larger_than.filter(|limit| size > *limit) {
+            return Some(format!("大于 {} 字节", limit));
         }
+
+        if let Some(hours) = self.younger_than_hours {
+            if age < chrono::Duration::hours(hours as i64) {
+                return Some(format!("上传不到 {} 小时", hours));
+            }
+        }
+
+        let lower = name.to_lowercase();
+        self.name_contains
+            .iter()
+            .find(|pattern| !pattern.is_empty() && lower.contains(&pattern.to_lowercase()))
+            .map(|pattern| format!("文件名包含 {}", pattern))
+    }
+}
+
+impl VideoConfig {
+    pub fn thumbnail_variants(&self) -> BTreeMap<String, String> {
+        thumbnail_variants(&self.thumbnail_size, &self.thumbnail_sizes)
+    }
+}
+
+impl ImageConfig {
+    pub fn thumbnail_variants(&self) -> BTreeMap<String, String> {
+        thumbnail_variants(&self.thumbnail_size, &self.thumbnail_sizes)
     }
 }
 
@@ -120,6 +931,8 @@ impl Default for ServerConfig {
             port: default_port(),
             max_body_size: default_max_body_size(),
             request_timeout: default_request_timeout(),
+            require_if_match: false,
+            read_only: false,
         }
     }
 }
@@ -129,6 +942,7 @@ impl Default for DatabaseConfig {
         Self {
             url: default_database_url(),
             max_connections: default_max_connections(),
+            maintenance_hour: default_maintenance_hour(),
         }
     }
 }
@@ -140,6 +954,84 @@ impl Default for StorageConfig {
             upload_dir: default_storage_path(),
             max_file_size: default_max_file_size(),
             chunk_size: default_chunk_size(),
+            max_archive_size: default_max_archive_size(),
+            naming: NamingConfig::default(),
+            temp: TempConfig::default(),
+            derived: DerivedConfig::default(),
+            stream_buffer: StreamBufferConfig::default(),
+            record_cache: RecordCacheConfig::default(),
+            delete_protection: DeleteProtectionConfig::default(),
+            deduplicate: default_deduplicate(),
+            trash: TrashConfig::default(),
+            upload_sessions: UploadSessionConfig::default(),
+            multipart: MultipartConfig::default(),
+        }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_trash_enabled(),
+            max_age: default_trash_max_age(),
+            cleanup_interval: default_trash_cleanup_interval(),
+        }
+    }
+}
+
+impl Default for UploadSessionConfig {
+    fn default() -> Self {
+        Self {
+            max_age: default_upload_session_max_age(),
+            cleanup_interval: default_upload_session_cleanup_interval(),
+            max_reserved: None,
+        }
+    }
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self {
+            max_parts: default_multipart_max_parts(),
+            max_field_size: default_multipart_max_field_size(),
+            progress_interval: default_multipart_progress_interval(),
+        }
+    }
+}
+
+impl Default for TempConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_temp_max_size(),
+            max_age: default_temp_max_age(),
+            cleanup_interval: default_temp_cleanup_interval(),
+        }
+    }
+}
+
+impl Default for DerivedConfig {
+    fn default() -> Self {
+        Self {
+            cleanup_interval: default_derived_cleanup_interval(),
+            min_age: default_derived_min_age(),
+        }
+    }
+}
+
+impl Default for StreamBufferConfig {
+    fn default() -> Self {
+        Self {
+            min_size: default_stream_buffer_min(),
+            max_size: default_stream_buffer_max(),
+        }
+    }
+}
+
+impl Default for RecordCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: default_record_cache_ttl(),
+            max_entries: default_record_cache_max_entries(),
         }
     }
 }
@@ -149,6 +1041,89 @@ impl Default for VideoConfig {
         Self {
             thumbnail_size: default_thumbnail_size(),
             supported_formats: default_supported_formats(),
+            thumbnail_formats: default_thumbnail_formats(),
+            thumbnail_quality: default_thumbnail_quality(),
+            thumbnail_sizes: BTreeMap::new(),
+            ffmpeg_path: None,
+            ffprobe_path: None,
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            archive_index: default_memory_archive_index(),
+            image_decode: default_memory_image_decode(),
+            ocr_output: default_memory_ocr_output(),
+        }
+    }
+}
+
+impl Default for ProfilingConfig {
+    fn default() -> Self {
+        Self {
+            tokio_console: false,
+            console_address: default_console_address(),
+            max_profile_seconds: default_max_profile_seconds(),
+        }
+    }
+}
+
+impl Default for CacheControlConfig {
+    fn default() -> Self {
+        Self {
+            immutable: default_cache_immutable(),
+            listing: default_cache_listing(),
+            preview: default_cache_preview(),
+        }
+    }
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        Self {
+            title: default_branding_title(),
+            logo_path: None,
+            accent_color: default_accent_color(),
+            footer_text: None,
+            default_language: default_language(),
+        }
+    }
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: default_thumbnail_size(),
+            thumbnail_formats: default_thumbnail_formats(),
+            thumbnail_quality: default_thumbnail_quality(),
+            thumbnail_sizes: BTreeMap::new(),
+            ocr: OcrConfig::default(),
+            heif: HeifConfig::default(),
+        }
+    }
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: default_ocr_command(),
+            languages: default_ocr_languages(),
+            timeout: default_ocr_timeout(),
+        }
+    }
+}
+
+impl Default for HeifConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: default_heif_command(),
+            quality: default_heif_quality(),
+            download_jpeg: false,
+            timeout: default_heif_timeout(),
         }
     }
 }
@@ -178,6 +1153,10 @@ fn default_max_connections() -> u32 {
     10
 }
 
+fn default_maintenance_hour() -> Option<u32> {
+    Some(3)
+}
+
 fn default_storage_path() -> PathBuf {
     PathBuf::from("./storage")
 }
@@ -190,10 +1169,142 @@ fn default_chunk_size() -> usize {
     8 * 1024 * 1024 // 8MB
 }
 
+fn default_stream_buffer_min() -> usize {
+    16 * 1024 // 16KB
+}
+
+fn default_stream_buffer_max() -> usize {
+    1024 * 1024 // 1MB
+}
+
+fn default_record_cache_ttl() -> u64 {
+    30
+}
+
+fn default_record_cache_max_entries() -> u64 {
+    10_000
+}
+
+fn default_memory_archive_index() -> u64 {
+    64 * 1024 * 1024 // 64MB
+}
+
+fn default_memory_image_decode() -> u64 {
+    512 * 1024 * 1024 // 512MB
+}
+
+fn default_memory_ocr_output() -> u64 {
+    16 * 1024 * 1024 // 16MB
+}
+
+fn default_console_address() -> String {
+    "127.0.0.1:6669".to_string()
+}
+
+fn default_max_profile_seconds() -> u64 {
+    60
+}
+
+fn default_cache_immutable() -> String {
+    "public, max-age=31536000, immutable".to_string()
+}
+
+fn default_cache_listing() -> String {
+    "no-cache".to_string()
+}
+
+fn default_cache_preview() -> String {
+    "public, max-age=300".to_string()
+}
+
+fn default_mount_read_only() -> bool {
+    true
+}
+
+fn default_derived_cleanup_interval() -> u64 {
+    24 * 60 * 60 // 1天
+}
+
+fn default_derived_min_age() -> u64 {
+    60 * 60 // 1小时
+}
+
+fn default_max_archive_size() -> u64 {
+    10 * 1024 * 1024 * 1024 // 10GB
+}
+
+fn default_deduplicate() -> bool {
+    true
+}
+
+fn default_temp_max_size() -> u64 {
+    50 * 1024 * 1024 * 1024 // 50GB
+}
+
+fn default_temp_max_age() -> u64 {
+    24 * 60 * 60 // 1天
+}
+
+fn default_temp_cleanup_interval() -> u64 {
+    60 * 60 // 1小时
+}
+
+fn default_trash_enabled() -> bool {
+    true
+}
+
+fn default_trash_max_age() -> u64 {
+    30 * 24 * 60 * 60 // 30天
+}
+
+fn default_trash_cleanup_interval() -> u64 {
+    60 * 60 // 1小时
+}
+
+fn default_upload_session_max_age() -> u64 {
+    24 * 60 * 60 // 1天
+}
+
+fn default_upload_session_cleanup_interval() -> u64 {
+    60 * 60 // 1小时
+}
+
+fn default_multipart_max_parts() -> usize {
+    16
+}
+
+fn default_multipart_max_field_size() -> u64 {
+    64 * 1024 // 64KB
+}
+
+fn default_multipart_progress_interval() -> u64 {
+    64 * 1024 * 1024 // 64MB
+}
+
 fn default_thumbnail_size() -> String {
     "320x240".to_string()
 }
 
+fn default_branding_title() -> String {
+    "内网文件服务器".to_string()
+}
+
+fn default_accent_color() -> String {
+    "#1677ff".to_string()
+}
+
+fn default_language() -> String {
+    "zh-CN".to_string()
+}
+
+fn default_thumbnail_formats() -> Vec<ThumbnailFormat> {
+    vec![ThumbnailFormat::Jpeg]
+}
+
+fn default_thumbnail_quality() -> u8 {
+    80
+}
+
 fn default_supported_formats() -> Vec<String> {
     vec![
         "mp4".to_string(),
@@ -204,4 +1315,44 @@ fn default_supported_formats() -> Vec<String> {
         "flv".to_string(),
         "webm".to_string(),
     ]
-}
\ No newline at end of file
+}
+
+fn default_ocr_command() -> String {
+    "tesseract".to_string()
+}
+
+fn default_upstream_connect_timeout() -> u64 {
+    10
+}
+
+fn default_policy_timeout() -> u64 {
+    2
+}
+
+fn default_policy_cache_ttl() -> u64 {
+    60
+}
+
+fn default_policy_cache_max_entries() -> u64 {
+    10_000
+}
+
+fn default_ocr_languages() -> Vec<String> {
+    vec!["eng".to_string(), "chi_sim".to_string()]
+}
+
+fn default_ocr_timeout() -> u64 {
+    60
+}
+
+fn default_heif_command() -> String {
+    "heif-convert".to_string()
+}
+
+fn default_heif_quality() -> u8 {
+    90
+}
+
+fn default_heif_timeout() -> u64 {
+    60
+}
@@ -0,0 +1,358 @@
+// 归档成员下载 - 列出 zip/tar 文件的内容并单独下载其中一个成员, 不需要下载整个归档
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::memory::{BudgetKind, MemoryBudgets};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path as FsPath, PathBuf};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+
+// 流式读取成员时每次读取的字节数
+const READ_CHUNK: usize = 64 * 1024;
+
+// 估算内存时每个成员除路径以外的开销
+const ENTRY_OVERHEAD: u64 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+}
+
+impl ArchiveKind {
+    // 按 MIME 类型或扩展名识别, 压缩过的 tar (tar.gz 等) 需要完整解压, 不支持
+    pub fn detect(record: &FileRecord) -> Option<ArchiveKind> {
+        let name = record.original_name.to_ascii_lowercase();
+        if record.mime_type == "application/zip" || name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if record.mime_type == "application/x-tar" || name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+// tar 成员的位置, offset 为数据起始位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarEntry {
+    pub entry: ArchiveEntry,
+    pub offset: u64,
+}
+
+// 列出归档中的全部成员
+pub async fn list_entries(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Json<ApiResponse<Vec<ArchiveEntry>>>, ApiError> {
+    const CONTEXT: &str = "读取归档内容失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    // 成员列表同样来自文件内容
+    canary::check_download(&state, &record, &download).await;
+    let kind = archive_kind(&record).map_err(|e| api_error(CONTEXT, e))?;
+    let path = PathBuf::from(&record.file_path);
+    let budgets = state.memory.clone();
+
+    let entries = tokio::task::spawn_blocking(move || -> Result<Vec<ArchiveEntry>> {
+        let mut file = File::open(&path).map_err(ServerError::Io)?;
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive = open_zip(file, &budgets)?;
+                (0..archive.len())
+                    .map(|index| {
+                        let member = archive.by_index_raw(index).map_err(zip_error)?;
+                        Ok(ArchiveEntry {
+                            path: member.name().to_string(),
+                            size: member.size(),
+                            is_dir: member.is_dir(),
+                        })
+                    })
+                    .collect()
+            }
+            ArchiveKind::Tar => Ok(read_tar_entries(&mut file, &budgets)?.into_iter().map(|e| e.entry).collect()),
+        }
+    })
+    .await
+    .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+    .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(Json(ApiResponse::success(entries)))
+}
+
+// 下载归档中的单个成员, 读取时只访问该成员的数据
+pub async fn download_entry(
+    Path((file_id, entry_path)): Path<(String, String)>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "下载归档成员失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    let kind = archive_kind(&record).map_err(|e| api_error(CONTEXT, e))?;
+    let path = PathBuf::from(&record.file_path);
+    let budgets = state.memory.clone();
+
+    // 先通过 ready 返回成员大小或错误, 再通过 chunks 发送数据
+    let (ready_tx, ready_rx) = oneshot::channel::<Result<u64>>();
+    let (chunk_tx, chunk_rx) = mpsc::channel::<std::io::Result<Bytes>>(4);
+    let name = entry_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut ready = Some(ready_tx);
+        let result = with_member(&path, kind, &name, &budgets, |reader, size| {
+            if ready.take().is_some_and(|ready| ready.send(Ok(size)).is_ok()) {
+                pump(reader, &chunk_tx);
+            }
+        });
+        if let (Err(e), Some(ready)) = (result, ready.take()) {
+            let _ = ready.send(Err(e));
+        }
+    });
+
+    let size = ready_rx
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let mime = mime_guess::from_path(&entry_path).first_or_octet_stream();
+    let file_name = entry_path.rsplit('/').next().unwrap_or(&entry_path).to_string();
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime.to_string()),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name.replace('"', ""))),
+        ],
+        Body::from_stream(ReceiverStream::new(chunk_rx)),
+    )
+        .into_response())
+}
+
+fn archive_kind(record: &FileRecord) -> Result<ArchiveKind> {
+    ArchiveKind::detect(record).ok_or_else(|| ServerError::validation("文件不是 zip 或 tar 归档"))
+}
+
+fn zip_error(err: zip::result::ZipError) -> ServerError {
+    ServerError::file_operation(format!("无法读取 zip 归档: {}", err))
+}
+
+// 定位归档成员并交给 f 读取, zip 通过中央目录定位, tar 通过成员头定位
+fn with_member(
+    path: &FsPath,
+    kind: ArchiveKind,
+    name: &str,
+    budgets: &MemoryBudgets,
+    f: impl FnOnce(&mut dyn Read, u64),
+) -> Result<()> {
+    let mut file = File::open(path).map_err(ServerError::Io)?;
+    let not_found = || ServerError::not_found(format!("归档成员 {}", name));
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = open_zip(file, budgets)?;
+            let mut member = match archive.by_name(name) {
+                Ok(member) if !member.is_dir() => member,
+                Ok(_) | Err(zip::result::ZipError::FileNotFound) => return Err(not_found()),
+                Err(e) => return Err(zip_error(e)),
+            };
+            let size = member.size();
+            f(&mut member, size);
+        }
+        ArchiveKind::Tar => {
+            let entry = read_tar_entries(&mut file, budgets)?
+                .into_iter()
+                .find(|e| e.entry.path == name && !e.entry.is_dir)
+                .ok_or_else(not_found)?;
+            file.seek(SeekFrom::Start(entry.offset)).map_err(ServerError::Io)?;
+            f(&mut file.take(entry.entry.size), entry.entry.size);
+        }
+    }
+
+    Ok(())
+}
+
+// zip 库会把整个中央目录读入内存, 打开前先按目录结尾记录估算占用
+fn open_zip(mut file: File, budgets: &MemoryBudgets) -> Result<zip::ZipArchive<File>> {
+    if let Some(estimate) = zip_index_estimate(&mut file)? {
+        budgets.check(BudgetKind::ArchiveIndex, estimate)?;
+    }
+    file.seek(SeekFrom::Start(0)).map_err(ServerError::Io)?;
+    zip::ZipArchive::new(file).map_err(zip_error)
+}
+
+// 从目录结尾记录 (必要时从 zip64 记录) 读出中央目录大小和成员数, 估算读入后占用的内存.
+// 找不到目录结尾时返回 None, 由 zip 库报告格式错误
+pub fn zip_index_estimate<R: Read + Seek>(reader: &mut R) -> Result<Option<u64>> {
+    const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const ZIP64_LOCATOR_SIGNATURE: &[u8] = b"PK\x06\x07";
+    const ZIP64_EOCD_SIGNATURE: &[u8] = b"PK\x06\x06";
+
+    // 目录结尾记录 22 字节, 之后最多跟 65535 字节的注释
+    let len = reader.seek(SeekFrom::End(0)).map_err(ServerError::Io)?;
+    let tail_len = len.min(22 + 65535);
+    let tail_start = len - tail_len;
+    reader.seek(SeekFrom::Start(tail_start)).map_err(ServerError::Io)?;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.read_exact(&mut tail).map_err(ServerError::Io)?;
+
+    let Some(eocd) = (0..tail.len().saturating_sub(21)).rev().find(|&i| &tail[i..i + 4] == EOCD_SIGNATURE) else {
+        return Ok(None);
+    };
+    let u16_at = |data: &[u8], at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as u64;
+    let u32_at = |data: &[u8], at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as u64;
+    let u64_at = |data: &[u8], at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+    let mut entries = u16_at(&tail, eocd + 10);
+    let mut directory_size = u32_at(&tail, eocd + 12);
+
+    // 成员数或目录大小溢出时真实值在 zip64 目录结尾记录中
+    let overflowed = entries == 0xFFFF || directory_size == 0xFFFF_FFFF;
+    if overflowed && eocd >= 20 && &tail[eocd - 20..eocd - 16] == ZIP64_LOCATOR_SIGNATURE {
+        let zip64_offset = u64_at(&tail, eocd - 20 + 8);
+        let mut record = [0u8; 56];
+        reader.seek(SeekFrom::Start(zip64_offset)).map_err(ServerError::Io)?;
+        if reader.read_exact(&mut record).is_ok() && &record[0..4] == ZIP64_EOCD_SIGNATURE {
+            entries = u64_at(&record, 32);
+            directory_size = u64_at(&record, 40);
+        }
+    }
+
+    Ok(Some(directory_size.saturating_add(entries.saturating_mul(ENTRY_OVERHEAD))))
+}
+
+// 把成员数据逐块发送给响应体, 客户端断开时停止
+fn pump(reader: &mut dyn Read, chunks: &mpsc::Sender<std::io::Result<Bytes>>) {
+    let mut buffer = vec![0u8; READ_CHUNK];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                if chunks.blocking_send(Ok(Bytes::copy_from_slice(&buffer[..n]))).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                let _ = chunks.blocking_send(Err(e));
+                break;
+            }
+        }
+    }
+}
+
+// 顺序读取 tar 成员头, 跳过成员数据. 支持 ustar 前缀、GNU 长文件名和 pax 的 path 字段.
+// 成员列表的估算大小超过归档目录预算时停止读取
+pub fn read_tar_entries<R: Read + Seek>(reader: &mut R, budgets: &MemoryBudgets) -> Result<Vec<TarEntry>> {
+    let mut entries = Vec::new();
+    let mut index_size = 0u64;
+    let mut offset = 0u64;
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; 512];
+
+    loop {
+        reader.seek(SeekFrom::Start(offset)).map_err(ServerError::Io)?;
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // 没有结束标记的归档也接受
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(ServerError::Io(e)),
+        }
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let size = parse_tar_size(&header[124..136])
+            .ok_or_else(|| ServerError::file_operation("tar 成员头中的大小无效"))?;
+        let data_offset = offset + 512;
+        let type_flag = header[156];
+
+        match type_flag {
+            // GNU 长文件名和 pax 扩展头, 作用于下一个成员
+            b'L' | b'x' => {
+                let mut data = vec![0u8; size.min(64 * 1024) as usize];
+                reader.read_exact(&mut data).map_err(ServerError::Io)?;
+                long_name = if type_flag == b'L' {
+                    Some(tar_string(&data))
+                } else {
+                    pax_path(&data).or(long_name)
+                };
+            }
+            // pax 全局头
+            b'g' => {}
+            _ => {
+                let name = match long_name.take() {
+                    Some(name) => name,
+                    None => {
+                        let name = tar_string(&header[0..100]);
+                        let prefix = if &header[257..262] == b"ustar" {
+                            tar_string(&header[345..500])
+                        } else {
+                            String::new()
+                        };
+                        if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+                    }
+                };
+                let is_dir = type_flag == b'5' || name.ends_with('/');
+                // 只有普通文件带数据, 链接和设备等条目大小为 0
+                let entry_size = if matches!(type_flag, b'0' | 0 | b'7') { size } else { 0 };
+                index_size += name.len() as u64 + ENTRY_OVERHEAD;
+                budgets.check(BudgetKind::ArchiveIndex, index_size)?;
+                entries.push(TarEntry {
+                    entry: ArchiveEntry { path: name, size: entry_size, is_dir },
+                    offset: data_offset,
+                });
+            }
+        }
+
+        offset = data_offset + size.div_ceil(512) * 512;
+    }
+
+    Ok(entries)
+}
+
+// 八进制数字, 或最高位置 1 的 base-256 编码 (超过 8GB 的成员)
+fn parse_tar_size(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return field[1..].iter().try_fold(u64::from(field[0] & 0x7f), |acc, b| {
+            acc.checked_mul(256)?.checked_add(u64::from(*b))
+        });
+    }
+
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn tar_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+// pax 记录格式为 "<长度> <键>=<值>\n"
+fn pax_path(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .rev()
+        .find_map(|line| line.split_once(' ')?.1.strip_prefix("path="))
+        .map(str::to_string)
+}
@@ -0,0 +1,181 @@
+// 打包下载 - 把指定的文件或集合中的文件边读边写成 zip 发送, 归档不在内存或磁盘上整体缓存.
+// 搜索结果打包也使用这里的写入方式
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, ApiError, AppState};
+use crate::storage::FileRecord;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::Utc;
+use futures::{stream, AsyncWriteExt};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path as FsPath;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::task::JoinHandle;
+
+// 单次打包最多包含的文件数
+const MAX_BUNDLE_FILES: usize = 10_000;
+
+// 写入归档和发送响应之间的管道容量, 也是每次读取的字节数
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+// file_ids 和 collection_id 只能设置一个
+#[derive(Debug, Deserialize)]
+pub struct BundleRequest {
+    #[serde(default)]
+    pub file_ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub collection_id: Option<String>,
+}
+
+pub async fn download_bundle(
+    State(state): State<AppState>,
+    download: DownloadContext,
+    Json(req): Json<BundleRequest>,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "打包下载失败";
+
+    let file_ids = match (req.file_ids, req.collection_id) {
+        (Some(file_ids), None) => file_ids,
+        (None, Some(collection_id)) => state
+            .files
+            .get_collection(&collection_id)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?
+            .ok_or_else(|| api_error(CONTEXT, ServerError::not_found(format!("集合 {}", collection_id))))?
+            .file_ids,
+        _ => {
+            return Err(api_error(
+                CONTEXT,
+                ServerError::validation("file_ids 和 collection_id 必须且只能设置一个"),
+            ))
+        }
+    };
+
+    // 重复的 ID 只打包一次, 顺序以第一次出现为准
+    let mut seen = HashSet::new();
+    let file_ids: Vec<String> = file_ids.into_iter().filter(|id| seen.insert(id.clone())).collect();
+    if file_ids.len() > MAX_BUNDLE_FILES {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("单次最多打包 {} 个文件", MAX_BUNDLE_FILES)),
+        ));
+    }
+
+    let found = state
+        .files
+        .get_files_by_ids(&file_ids)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let mut records = Vec::with_capacity(file_ids.len());
+    for id in &file_ids {
+        match found.iter().find(|record| &record.id == id) {
+            Some(record) => records.push(record.clone()),
+            None => return Err(api_error(CONTEXT, ServerError::not_found(format!("文件 {}", id)))),
+        }
+    }
+
+    // 与搜索结果打包一致, 跳过当前不可下载或已不在磁盘上的文件
+    let now = Utc::now();
+    records.retain(|record| record.check_availability(now).is_ok() && FsPath::new(&record.file_path).is_file());
+    if records.is_empty() {
+        return Err(api_error(CONTEXT, ServerError::not_found("可打包的文件")));
+    }
+
+    let total_size = records.iter().map(|r| r.file_size.max(0) as u64).sum::<u64>();
+    let max_size = state.config.storage.max_archive_size;
+    if total_size > max_size {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("打包大小 {} 字节超过上限 {} 字节", total_size, max_size)),
+        ));
+    }
+
+    for record in &records {
+        canary::check_download(&state, record, &download).await;
+    }
+
+    Ok(archive_response(records, &format!("files-{}.zip", now.format("%Y%m%d-%H%M%S"))))
+}
+
+// 边写边发送的 zip 响应, 总大小事先未知, 不带 Content-Length
+pub(crate) fn archive_response(records: Vec<FileRecord>, filename: &str) -> Response {
+    let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+    let task = tokio::spawn(async move { write_archive(writer, &records).await });
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(archive_stream(reader, task)),
+    )
+        .into_response()
+}
+
+// 读完管道后检查写入结果, 写入失败时以错误结束响应, 客户端不会把截断的归档当成完整文件
+fn archive_stream(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    task: JoinHandle<Result<()>>,
+) -> impl futures::Stream<Item = std::io::Result<Bytes>> {
+    stream::unfold(Some((reader, task)), |state| async move {
+        let (mut reader, task) = state?;
+        let mut buffer = vec![0u8; PIPE_CAPACITY];
+        match reader.read(&mut buffer).await {
+            Ok(0) => match task.await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some((Err(std::io::Error::other(e.to_string())), None)),
+                Err(e) => Some((Err(std::io::Error::other(e)), None)),
+            },
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok(Bytes::from(buffer)), Some((reader, task))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+// 不压缩直接存储, 存储的文件大多已经压缩过
+async fn write_archive(writer: impl AsyncWrite + Unpin, records: &[FileRecord]) -> Result<()> {
+    let zip_error = |e: async_zip::error::ZipError| ServerError::file_operation(format!("无法写入 zip 归档: {}", e));
+
+    let mut archive = ZipFileWriter::with_tokio(writer);
+    let mut names = HashSet::new();
+    let mut buffer = vec![0u8; PIPE_CAPACITY];
+
+    for record in records {
+        let entry = ZipEntryBuilder::new(entry_name(&mut names, record).into(), Compression::Stored)
+            .last_modification_date(ZipDateTime::from_chrono(&record.upload_time));
+        let mut entry_writer = archive.write_entry_stream(entry).await.map_err(zip_error)?;
+        let mut source = tokio::fs::File::open(&record.file_path).await.map_err(ServerError::Io)?;
+        loop {
+            let n = source.read(&mut buffer).await.map_err(ServerError::Io)?;
+            if n == 0 {
+                break;
+            }
+            entry_writer.write_all(&buffer[..n]).await.map_err(ServerError::Io)?;
+        }
+        entry_writer.close().await.map_err(zip_error)?;
+    }
+
+    archive.close().await.map_err(zip_error)?;
+    Ok(())
+}
+
+// 归档中的文件名, 同名文件以文件 ID 前缀区分
+fn entry_name(names: &mut HashSet<String>, record: &FileRecord) -> String {
+    let mut name = record.original_name.clone();
+    if !names.insert(name.clone()) {
+        name = format!("{}-{}", record.id, record.original_name);
+        names.insert(name.clone());
+    }
+    name
+}
@@ -0,0 +1,97 @@
+// 按内容哈希下载 - 同样的 SHA-256 总是返回同样的字节, 与文件名和记录无关
+use crate::download::canary::{self, DownloadContext};
+use crate::download::stream::buffer_size;
+use crate::error::ServerError;
+use crate::preview::heif;
+use crate::server::{api_error, ApiError, AppState};
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue},
+    response::Response,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::path::Path as FsPath;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ByHashQuery {
+    #[serde(default)]
+    pub original: bool,
+}
+
+pub async fn download_by_hash(
+    Path(sha256): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+    request: Request,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "按哈希下载文件失败";
+
+    let sha256 = sha256.to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("无效的 SHA-256: {}", sha256))));
+    }
+
+    // 同一内容可能有多条记录, 使用第一条当前可下载且文件仍在磁盘上的
+    let now = Utc::now();
+    let record = state
+        .files
+        .find_files_by_sha256(&sha256)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .find(|record| record.check_availability(now).is_ok() && FsPath::new(&record.file_path).is_file())
+        .ok_or_else(|| api_error(CONTEXT, ServerError::not_found(format!("SHA-256 为 {} 的文件", sha256))))?;
+
+    // 配置了 download_jpeg 时 HEIF 图片默认返回 JPEG 副本, ?original=true 取原文件
+    let Query(query) = Query::<ByHashQuery>::try_from_uri(request.uri())
+        .map_err(|e| api_error(CONTEXT, ServerError::validation(e.body_text())))?;
+    let heif = &state.config.image.heif;
+    let jpeg = if heif.enabled && heif.download_jpeg && !query.original && heif::is_heif(&record) {
+        Some(heif::ensure_jpeg(&state, &record).await.map_err(|e| api_error(CONTEXT, e))?)
+    } else {
+        None
+    };
+    let (path, mime, etag) = match &jpeg {
+        Some(path) => (path.as_path(), mime::IMAGE_JPEG, format!("\"{}-jpeg\"", sha256)),
+        None => (
+            FsPath::new(&record.file_path),
+            record.mime_type.parse().unwrap_or(mime::APPLICATION_OCTET_STREAM),
+            format!("\"{}\"", sha256),
+        ),
+    };
+
+    canary::check_download(&state, &record, &download).await;
+
+    let ranged = request.headers().contains_key(header::RANGE);
+    let guard = state.streams.start();
+    let buffer = buffer_size(
+        &state.config.storage.stream_buffer,
+        record.file_size.max(0) as u64,
+        ranged,
+        state.streams.active(),
+    );
+
+    // ServeFile 负责 Range 和条件请求, 读取失败时它自己返回错误状态码
+    let service = ServeFile::new_with_mime(path, &mime).with_buf_chunk_size(buffer);
+    let Ok(response) = service.oneshot(request).await;
+    // 响应体发送完或连接断开时守卫随之释放
+    let mut response = response.map(|body| {
+        Body::from_stream(Body::new(body).into_data_stream().map(move |chunk| {
+            let _ = &guard;
+            chunk
+        }))
+    });
+
+    // 内容由哈希决定, 可以永久缓存, Cache-Control 由缓存策略中间件按 web.cache_control.immutable 设置
+    let headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, etag);
+    }
+
+    Ok(response)
+}
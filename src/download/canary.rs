@@ -0,0 +1,183 @@
+// 蜜罐文件 - 放在不应被访问的目录中的诱饵文件, 任何下载都立即记录告警和完整的客户端信息.
+// 下载本身照常完成, 访问者不会察觉
+use crate::error::ServerError;
+use crate::server::{api_error, missing_file_error, ApiError, ApiResponse, AppState};
+use crate::storage::{CanaryAlert, CanaryFile, FileRecord};
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, Method, Uri},
+    response::Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use tracing::{error, warn};
+
+const DEFAULT_ALERT_LIMIT: i64 = 100;
+const MAX_ALERT_LIMIT: i64 = 1000;
+
+// 告警中不保存的请求头
+const REDACTED_HEADERS: [header::HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+    header::HeaderName::from_static("x-api-key"),
+];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MarkCanaryRequest {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertQuery {
+    pub file_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// 告警记录的请求信息, 加到所有返回文件内容 (包括预览、转换和打包) 的处理函数上
+#[derive(Debug, Clone)]
+pub struct DownloadContext {
+    pub client: Option<SocketAddr>,
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for DownloadContext {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> std::result::Result<Self, Self::Rejection> {
+        Ok(Self {
+            client: parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(client)| *client),
+            method: parts.method.clone(),
+            uri: parts.uri.clone(),
+            headers: parts.headers.clone(),
+        })
+    }
+}
+
+// 发送文件内容前调用. 检查或记录失败时只写日志, 不影响下载
+pub async fn check_download(state: &AppState, record: &FileRecord, request: &DownloadContext) {
+    let canary = match state.file_manager.get_canary(&record.id).await {
+        Ok(Some(canary)) => canary,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("检查蜜罐文件 {} 失败: {}", record.id, e);
+            return;
+        }
+    };
+
+    let mut alert = CanaryAlert {
+        id: 0,
+        file_id: record.id.clone(),
+        client_ip: request.client.map_or_else(|| "-".to_string(), |client| client.ip().to_string()),
+        method: request.method.to_string(),
+        path: request.uri.to_string(),
+        user_agent: request
+            .headers
+            .get(header::USER_AGENT)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string()),
+        headers: request
+            .headers
+            .iter()
+            .filter(|(name, _)| !REDACTED_HEADERS.contains(name))
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+            .collect(),
+        triggered_at: Utc::now(),
+    };
+    match state.file_manager.record_canary_alert(&alert).await {
+        Ok(id) => alert.id = id,
+        Err(e) => warn!("保存蜜罐告警失败: {}", e),
+    }
+
+    error!(
+        "蜜罐文件被访问: {} ({}), 备注 {:?}, 客户端 {}, {} {}, User-Agent {:?}, 请求头 {:?}",
+        record.original_name,
+        record.id,
+        canary.note,
+        alert.client_ip,
+        alert.method,
+        alert.path,
+        alert.user_agent,
+        alert.headers
+    );
+}
+
+// 全部蜜罐文件
+pub async fn list_canaries(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<CanaryFile>>>, ApiError> {
+    state
+        .file_manager
+        .list_canaries()
+        .await
+        .map(|canaries| Json(ApiResponse::success(canaries)))
+        .map_err(|e| api_error("获取蜜罐文件列表失败", e))
+}
+
+// 把文件标记为蜜罐, 请求体可以为空
+pub async fn mark_canary(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    request: Option<Json<MarkCanaryRequest>>,
+) -> std::result::Result<Json<ApiResponse<CanaryFile>>, ApiError> {
+    const CONTEXT: &str = "标记蜜罐文件失败";
+
+    match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    }
+
+    let Json(request) = request.unwrap_or_default();
+    let note = request.note.as_deref().map(str::trim).filter(|note| !note.is_empty());
+    state
+        .file_manager
+        .mark_canary(&file_id, note)
+        .await
+        .map(|canary| Json(ApiResponse::success(canary)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+pub async fn unmark_canary(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "取消蜜罐标记失败";
+
+    if !state
+        .file_manager
+        .unmark_canary(&file_id)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+    {
+        return Err(api_error(CONTEXT, ServerError::not_found(format!("蜜罐文件 {}", file_id))));
+    }
+    Ok(Json(ApiResponse::success(())))
+}
+
+// 最近的告警, 可按文件过滤
+pub async fn list_alerts(
+    Query(params): Query<AlertQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<CanaryAlert>>>, ApiError> {
+    const CONTEXT: &str = "获取蜜罐告警失败";
+
+    let limit = params.limit.unwrap_or(DEFAULT_ALERT_LIMIT);
+    if !(1..=MAX_ALERT_LIMIT).contains(&limit) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("数量必须在 1-{} 之间", MAX_ALERT_LIMIT)),
+        ));
+    }
+
+    state
+        .file_manager
+        .list_canary_alerts(params.file_id.as_deref(), limit)
+        .await
+        .map(|alerts| Json(ApiResponse::success(alerts)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
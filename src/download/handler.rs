@@ -1,15 +1,39 @@
-// 下载处理器占位符
-use crate::error::Result;
+// 文件下载 - 按文件 ID 发送文件内容, 支持 Range 请求, 供视频拖动和下载工具断点续传使用
+use crate::download::canary::{self, DownloadContext};
+use crate::download::range::{serve_record, Disposition};
+use crate::server::{api_error, get_downloadable_file, ApiError, AppState};
+use axum::{
+    extract::{Path, State},
+    response::Response,
+};
 
-pub struct DownloadHandler;
+// 作为附件下载
+pub async fn download_file(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "下载文件失败";
 
-impl DownloadHandler {
-    pub fn new() -> Self {
-        Self
-    }
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    serve_record(&state, &record, &download.headers, Disposition::Attachment)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))
+}
 
-    pub async fn handle_download(&self) -> Result<()> {
-        // TODO: 实现文件下载逻辑
-        Ok(())
-    }
-}
\ No newline at end of file
+// 在浏览器中直接打开, 地址为 /files/<文件 ID>, 后面可以跟任意文件名, 例如 /files/<ID>/report.pdf
+pub async fn serve_file(
+    Path(path): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取文件内容失败";
+
+    let file_id = path.split('/').next().unwrap_or_default();
+    let record = get_downloadable_file(&state, file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    serve_record(&state, &record, &download.headers, Disposition::Inline)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))
+}
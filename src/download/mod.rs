@@ -1,4 +1,9 @@
-// 文件下载模块占位符
+// 文件下载模块
+pub mod archive;
+pub mod bundle;
+pub mod by_hash;
+pub mod canary;
 pub mod handler;
-
-pub use handler::DownloadHandler;
\ No newline at end of file
+pub mod range;
+pub mod share;
+pub mod stream;
@@ -0,0 +1,263 @@
+// 按范围发送文件 - 处理 Range、If-Range 和 If-None-Match, 支持单段和多段范围, 内容用 tokio::fs 边读边发
+use crate::download::stream::{buffer_size, AdaptiveBuffer, StreamGuard};
+use crate::error::ServerError;
+use crate::server::AppState;
+use crate::storage::FileRecord;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use std::io::SeekFrom;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+// 一次请求最多的范围数, 超过时忽略 Range 头返回完整内容
+const MAX_RANGES: usize = 32;
+
+// HTTP-date, 例如 Sun, 06 Nov 1994 08:49:37 GMT
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    // 在浏览器中直接打开, 例如视频播放
+    Inline,
+    Attachment,
+}
+
+// 解析 Range 头. 返回 None 表示应忽略该头 (格式无效或不是字节范围),
+// 返回空列表表示所有范围都超出文件长度
+pub fn parse_range(value: &str, len: u64) -> Option<Vec<RangeInclusive<u64>>> {
+    let specs = value.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+        let range = if start.is_empty() {
+            // 后缀范围: 最后 n 个字节
+            let suffix: u64 = end.parse().ok()?;
+            (suffix > 0 && len > 0).then(|| len.saturating_sub(suffix)..=len - 1)
+        } else {
+            let start: u64 = start.parse().ok()?;
+            let end = if end.is_empty() { u64::MAX } else { end.parse().ok()? };
+            if end < start {
+                return None;
+            }
+            (start < len).then(|| start..=end.min(len - 1))
+        };
+        ranges.extend(range);
+    }
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(ranges)
+}
+
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format(HTTP_DATE).to_string()
+}
+
+// If-Range 为实体标签时要求强匹配, 为日期时要求与 Last-Modified 相同
+fn if_range_matches(value: &str, etag: &str, last_modified: &str) -> bool {
+    let value = value.trim();
+    if value.starts_with('"') || value.starts_with("W/") {
+        return value == etag;
+    }
+    value == last_modified
+}
+
+pub fn if_none_match_matches(value: &str, etag: &str) -> bool {
+    value
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+// 文件名中的非 ASCII 字符用 filename* 传递, filename 中替换为下划线供旧客户端使用
+pub fn content_disposition(disposition: Disposition, file_name: &str) -> String {
+    let kind = match disposition {
+        Disposition::Inline => "inline",
+        Disposition::Attachment => "attachment",
+    };
+    let fallback: String = file_name
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    format!("{}; filename=\"{}\"; filename*=UTF-8''{}", kind, fallback, encoded)
+}
+
+// 响应体的一段: 多段范围的分隔头, 或文件中的一段
+enum Segment {
+    Bytes(Vec<u8>),
+    File { start: u64, length: u64 },
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Bytes(bytes) => bytes.len() as u64,
+            Self::File { length, .. } => *length,
+        }
+    }
+}
+
+// 发送文件记录对应的磁盘文件
+pub async fn serve_record(
+    state: &AppState,
+    record: &FileRecord,
+    headers: &HeaderMap,
+    disposition: Disposition,
+) -> crate::Result<Response> {
+    let path = Path::new(&record.file_path);
+    let file = tokio::fs::File::open(path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ServerError::not_found(format!("文件 {} 的内容", record.id)),
+        _ => ServerError::Io(e),
+    })?;
+    let metadata = file.metadata().await.map_err(ServerError::Io)?;
+    let len = metadata.len();
+    let etag = record.etag();
+    let last_modified = metadata.modified().map(|time| http_date(time.into())).ok();
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag);
+    if let Some(last_modified) = &last_modified {
+        builder = builder.header(header::LAST_MODIFIED, last_modified);
+    }
+    if let Ok(value) = HeaderValue::from_bytes(content_disposition(disposition, &record.original_name).as_bytes()) {
+        builder = builder.header(header::CONTENT_DISPOSITION, value);
+    }
+
+    let header_str = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok());
+    if header_str(header::IF_NONE_MATCH).is_some_and(|value| if_none_match_matches(value, &etag)) {
+        return Ok(builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
+    }
+
+    // If-Range 不匹配时说明文件已变化, 忽略 Range 返回完整内容
+    let range_applies = header_str(header::IF_RANGE)
+        .is_none_or(|value| if_range_matches(value, &etag, last_modified.as_deref().unwrap_or_default()));
+    let ranges = header_str(header::RANGE)
+        .filter(|_| range_applies)
+        .and_then(|value| parse_range(value, len));
+
+    let mime_type = &record.mime_type;
+    let (status, segments) = match ranges.as_deref() {
+        None => {
+            builder = builder.header(header::CONTENT_TYPE, mime_type);
+            (StatusCode::OK, vec![Segment::File { start: 0, length: len }])
+        }
+        Some([]) => {
+            let response = builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())
+                .unwrap();
+            return Ok(response);
+        }
+        Some([range]) => {
+            builder = builder
+                .header(header::CONTENT_TYPE, mime_type)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", range.start(), range.end(), len));
+            (StatusCode::PARTIAL_CONTENT, vec![file_segment(range)])
+        }
+        Some(ranges) => {
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            builder = builder.header(header::CONTENT_TYPE, format!("multipart/byteranges; boundary={}", boundary));
+            let mut segments = Vec::with_capacity(ranges.len() * 2 + 1);
+            for (index, range) in ranges.iter().enumerate() {
+                let part_header = format!(
+                    "{}--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    if index == 0 { "" } else { "\r\n" },
+                    boundary,
+                    mime_type,
+                    range.start(),
+                    range.end(),
+                    len
+                );
+                segments.push(Segment::Bytes(part_header.into_bytes()));
+                segments.push(file_segment(range));
+            }
+            segments.push(Segment::Bytes(format!("\r\n--{}--\r\n", boundary).into_bytes()));
+            (StatusCode::PARTIAL_CONTENT, segments)
+        }
+    };
+
+    let content_length: u64 = segments.iter().map(Segment::len).sum();
+    let guard = state.streams.start();
+    let ranged = status == StatusCode::PARTIAL_CONTENT;
+    let config = &state.config.storage.stream_buffer;
+    let active = state.streams.active();
+    let buffer = AdaptiveBuffer::new(config, buffer_size(config, len, ranged, active), active);
+    let body = Body::from_stream(segment_stream(file, segments, buffer, guard));
+    Ok(builder
+        .status(status)
+        .header(header::CONTENT_LENGTH, content_length)
+        .body(body)
+        .unwrap())
+}
+
+fn file_segment(range: &RangeInclusive<u64>) -> Segment {
+    Segment::File { start: *range.start(), length: range.end() - range.start() + 1 }
+}
+
+// 发送中的状态: 文件、剩余的段、当前文件段剩余的字节数、读取大小、上一块的大小和交出的时间、守卫
+type SegmentState = (
+    tokio::fs::File,
+    std::vec::IntoIter<Segment>,
+    u64,
+    AdaptiveBuffer,
+    Option<(usize, Instant)>,
+    StreamGuard,
+);
+
+// 依次发送各段, 文件段分块读取, 每块的大小按客户端的接收速度调整. 守卫随流一起释放
+fn segment_stream(
+    file: tokio::fs::File,
+    segments: Vec<Segment>,
+    buffer: AdaptiveBuffer,
+    guard: StreamGuard,
+) -> impl futures::Stream<Item = std::io::Result<Vec<u8>>> {
+    let state: SegmentState = (file, segments.into_iter(), 0, buffer, None, guard);
+    futures::stream::unfold(Some(state), move |state| async move {
+        let (mut file, mut segments, mut remaining, mut buffer, last, guard) = state?;
+        if let Some((sent, at)) = last {
+            buffer.adjust(sent, at.elapsed());
+        }
+        while remaining == 0 {
+            match segments.next()? {
+                Segment::Bytes(bytes) => {
+                    return Some((Ok(bytes), Some((file, segments, remaining, buffer, None, guard))))
+                }
+                Segment::File { length: 0, .. } => {}
+                Segment::File { start, length } => {
+                    if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+                        return Some((Err(e), None));
+                    }
+                    remaining = length;
+                }
+            }
+        }
+
+        let mut chunk = vec![0; (buffer.size() as u64).min(remaining) as usize];
+        match file.read(&mut chunk).await {
+            // 文件在发送过程中变短, 以错误结束响应, 客户端不会把截断的内容当成完整文件
+            Ok(0) => Some((Err(std::io::ErrorKind::UnexpectedEof.into()), None)),
+            Ok(read) => {
+                chunk.truncate(read);
+                let last = Some((read, Instant::now()));
+                Some((Ok(chunk), Some((file, segments, remaining - read as u64, buffer, last, guard))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
@@ -0,0 +1,75 @@
+// 分享二维码 - 把文件的永久下载地址编码为 PNG 二维码, 手机扫码即可下载
+use crate::error::ServerError;
+use crate::server::{api_error, base_url, get_downloadable_file, ApiError, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use image::{GrayImage, ImageFormat, Luma};
+use qrcodegen::{QrCode, QrCodeEcc};
+use serde::Deserialize;
+use std::io::Cursor;
+
+// 每个模块的像素数默认值和上限
+const DEFAULT_SCALE: u32 = 8;
+const MAX_SCALE: u32 = 32;
+
+// 二维码四周的空白 (模块数), 规范要求至少 4
+const QUIET_ZONE: u32 = 4;
+
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    pub scale: Option<u32>,
+}
+
+// 生成文件下载地址的二维码
+pub async fn get_share_qr(
+    Path(file_id): Path<String>,
+    Query(params): Query<QrQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "生成分享二维码失败";
+
+    let scale = params.scale.unwrap_or(DEFAULT_SCALE);
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("二维码缩放倍数必须在 1-{} 之间", MAX_SCALE)),
+        ));
+    }
+
+    // 永久下载地址按内容哈希寻址, 还没有哈希的文件无法分享. 启用密钥认证时该地址同样需要密钥
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    let sha256 = record.sha256.as_deref().ok_or_else(|| {
+        api_error(CONTEXT, ServerError::conflict(format!("文件 {} 没有内容哈希, 无法生成下载地址", file_id)))
+    })?;
+    let url = format!("{}/files/by-hash/{}", base_url(&headers), sha256);
+
+    let png = render_qr_png(&url, scale).map_err(|e| api_error(CONTEXT, e))?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+// 把文本编码为二维码并渲染成 PNG
+pub fn render_qr_png(text: &str, scale: u32) -> crate::Result<Vec<u8>> {
+    let qr = QrCode::encode_text(text, QrCodeEcc::Medium)
+        .map_err(|e| ServerError::validation(format!("无法生成二维码: {:?}", e)))?;
+
+    let modules = qr.size() as u32 + QUIET_ZONE * 2;
+    let image = GrayImage::from_fn(modules * scale, modules * scale, |x, y| {
+        let x = (x / scale) as i32 - QUIET_ZONE as i32;
+        let y = (y / scale) as i32 - QUIET_ZONE as i32;
+        if qr.get_module(x, y) {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    });
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| ServerError::file_operation(format!("无法编码图片: {}", e)))?;
+    Ok(png)
+}
@@ -0,0 +1,86 @@
+// 下载读缓冲 - 按文件大小、是否 Range 请求和当前并发下载数选择初始的读取大小,
+// 发送过程中再按客户端取走数据的速度调整
+use crate::config::StreamBufferConfig;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 顺序下载时大约分成多少次读取
+const READS_PER_FILE: u64 = 64;
+
+// 每次读取的数据量大约够客户端消耗这么久
+const TARGET_INTERVAL: Duration = Duration::from_millis(100);
+
+// 正在发送的下载数
+#[derive(Debug, Default)]
+pub struct StreamTracker {
+    active: AtomicUsize,
+}
+
+impl StreamTracker {
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // 开始一次下载, 返回的守卫在响应体发送完或被丢弃时结束计数
+    pub fn start(self: &Arc<Self>) -> StreamGuard {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        StreamGuard { tracker: self.clone() }
+    }
+}
+
+pub struct StreamGuard {
+    tracker: Arc<StreamTracker>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.tracker.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Range 请求多是播放器拖动和分段读取, 用最小缓冲; 完整下载按文件大小放大,
+// 再按并发数平分上限, 几百个连接同时播放时每个连接只占用少量内存
+pub fn buffer_size(config: &StreamBufferConfig, file_size: u64, ranged: bool, active: usize) -> usize {
+    if ranged {
+        return config.min_size;
+    }
+
+    let by_size = (file_size / READS_PER_FILE).min(usize::MAX as u64) as usize;
+    let budget = config.max_size / active.max(1);
+    by_size.min(budget).clamp(config.min_size, config.max_size)
+}
+
+// 发送过程中的读取大小. 根据上一块从交给响应体到客户端要下一块之间的间隔估算客户端的接收速度,
+// 读取大小向 速度 x TARGET_INTERVAL 靠拢, 每次最多放大或缩小一倍, 并保持在 [min_size, 并发上限] 之间.
+// 慢速客户端不必占着大缓冲, 单个快速的顺序下载逐步放大到上限
+#[derive(Debug, Clone)]
+pub struct AdaptiveBuffer {
+    size: usize,
+    min: usize,
+    max: usize,
+}
+
+impl AdaptiveBuffer {
+    pub fn new(config: &StreamBufferConfig, initial: usize, active: usize) -> Self {
+        let max = (config.max_size / active.max(1)).clamp(config.min_size, config.max_size).max(initial);
+        Self { size: initial, min: config.min_size, max }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // 客户端用 waited 取走了 sent 字节, 返回下一次读取的大小
+    pub fn adjust(&mut self, sent: usize, waited: Duration) -> usize {
+        let target = if waited.is_zero() {
+            f64::INFINITY
+        } else {
+            sent as f64 / waited.as_secs_f64() * TARGET_INTERVAL.as_secs_f64()
+        };
+        let target = target.clamp((self.size / 2) as f64, self.size.saturating_mul(2) as f64) as usize;
+        self.size = target.clamp(self.min, self.max);
+        self.size
+    }
+}
+
@@ -34,9 +34,33 @@ pub enum ServerError {
     #[error("未找到资源: {resource}")]
     NotFound { resource: String },
 
+    #[error("未认证: {message}")]
+    Unauthorized { message: String },
+
     #[error("权限不足: {action}")]
     PermissionDenied { action: String },
 
+    #[error("资源已失效: {resource}")]
+    Gone { resource: String },
+
+    #[error("资源冲突: {message}")]
+    Conflict { message: String },
+
+    #[error("前置条件不满足: {message}")]
+    PreconditionFailed { message: String },
+
+    #[error("缺少前置条件: {message}")]
+    PreconditionRequired { message: String },
+
+    #[error("存储空间不足: {message}")]
+    InsufficientStorage { message: String },
+
+    #[error("超出内存预算: {message}")]
+    PayloadTooLarge { message: String },
+
+    #[error("依赖的服务不可用: {message}")]
+    ServiceUnavailable { message: String },
+
     #[error("内部服务器错误: {0}")]
     Internal(#[from] anyhow::Error),
 }
@@ -66,11 +90,59 @@ impl ServerError {
         }
     }
 
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Unauthorized {
+            message: message.into(),
+        }
+    }
+
     pub fn permission_denied(action: impl Into<String>) -> Self {
         Self::PermissionDenied {
             action: action.into(),
         }
     }
+
+    pub fn gone(resource: impl Into<String>) -> Self {
+        Self::Gone {
+            resource: resource.into(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn precondition_failed(message: impl Into<String>) -> Self {
+        Self::PreconditionFailed {
+            message: message.into(),
+        }
+    }
+
+    pub fn precondition_required(message: impl Into<String>) -> Self {
+        Self::PreconditionRequired {
+            message: message.into(),
+        }
+    }
+
+    pub fn insufficient_storage(message: impl Into<String>) -> Self {
+        Self::InsufficientStorage {
+            message: message.into(),
+        }
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::PayloadTooLarge {
+            message: message.into(),
+        }
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self::ServiceUnavailable {
+            message: message.into(),
+        }
+    }
 }
 
 // Axum 错误转换
@@ -86,7 +158,15 @@ impl ServerError {
         match self {
             Self::NotFound { .. } => 404,
             Self::Validation { .. } => 400,
+            Self::Unauthorized { .. } => 401,
             Self::PermissionDenied { .. } => 403,
+            Self::Conflict { .. } => 409,
+            Self::Gone { .. } => 410,
+            Self::PayloadTooLarge { .. } => 413,
+            Self::PreconditionFailed { .. } => 412,
+            Self::PreconditionRequired { .. } => 428,
+            Self::ServiceUnavailable { .. } => 503,
+            Self::InsufficientStorage { .. } => 507,
             Self::Config(_) | Self::Axum(_) => 500,
             Self::Database(_) | Self::Io(_) => 500,
             Self::Serde(_) | Self::Http(_) => 500,
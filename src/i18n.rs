@@ -0,0 +1,414 @@
+// 国际化模块 - 按 Accept-Language 或配置选择语言, 翻译接口返回的消息
+//
+// 源码中的中文消息即为消息键, 其他语言的目录把中文原文映射为译文.
+// 带 {} 占位符的条目用于匹配动态生成的消息, 参数按位置代入译文.
+use crate::server::AppState;
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    ZhCn,
+    En,
+}
+
+tokio::task_local! {
+    static CURRENT_LOCALE: Locale;
+}
+
+impl Locale {
+    // 解析语言标签, 例如 zh-CN、zh、en-US
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Some(Locale::ZhCn),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    // 按 q 值从 Accept-Language 中选出第一个支持的语言
+    pub fn from_accept_language(value: &str) -> Option<Locale> {
+        let mut candidates: Vec<(f32, Locale)> = value
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let locale = Locale::from_tag(parts.next()?)?;
+                let quality = parts
+                    .find_map(|p| p.strip_prefix("q=")?.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect();
+
+        // 稳定排序, 相同 q 值保持请求头中的顺序
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.first().map(|(_, locale)| *locale)
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::ZhCn => &[],
+            Locale::En => EN_CATALOG,
+        }
+    }
+}
+
+// 当前请求的语言, 不在请求上下文中时使用中文
+pub fn current_locale() -> Locale {
+    CURRENT_LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+// 为每个请求确定语言, 并在处理期间设置为当前语言
+pub async fn scope_locale(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .or_else(|| Locale::from_tag(&state.config.web.branding.default_language))
+        .unwrap_or_default();
+
+    CURRENT_LOCALE.scope(locale, next.run(request)).await
+}
+
+// 翻译消息, 以 ": " 分隔的各段分别查找目录, 找不到的段保持原样
+pub fn translate(locale: Locale, message: &str) -> String {
+    let catalog = locale.catalog();
+    if catalog.is_empty() {
+        return message.to_string();
+    }
+
+    message
+        .split(": ")
+        .map(|segment| translate_segment(catalog, segment))
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+fn translate_segment(catalog: &[(&str, &str)], segment: &str) -> String {
+    if let Some((_, translated)) = catalog.iter().find(|(source, _)| *source == segment) {
+        return translated.to_string();
+    }
+
+    for (source, translated) in catalog.iter().filter(|(source, _)| source.contains("{}")) {
+        if let Some(args) = match_template(source, segment) {
+            let mut result = String::new();
+            let mut args = args.into_iter();
+            let mut parts = translated.split("{}").peekable();
+            while let Some(part) = parts.next() {
+                result.push_str(part);
+                if parts.peek().is_some() {
+                    let arg = args.next().unwrap_or_default();
+                    result.push_str(&translate_segment(catalog, arg));
+                }
+            }
+            return result;
+        }
+    }
+
+    segment.to_string()
+}
+
+// 用模板匹配消息, 成功时返回各占位符对应的参数
+fn match_template<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let literals: Vec<&str> = template.split("{}").collect();
+    let (first, rest) = literals.split_first()?;
+    let mut remaining = message.strip_prefix(first)?;
+    let mut args = Vec::with_capacity(rest.len());
+
+    for (index, literal) in rest.iter().enumerate() {
+        if index == rest.len() - 1 {
+            let arg = remaining.strip_suffix(literal)?;
+            args.push(arg);
+            remaining = "";
+        } else {
+            let position = remaining.find(literal)?;
+            args.push(&remaining[..position]);
+            remaining = &remaining[position + literal.len()..];
+        }
+    }
+
+    remaining.is_empty().then_some(args)
+}
+
+// 英文消息目录
+const EN_CATALOG: &[(&str, &str)] = &[
+    // 错误类别
+    ("配置错误", "Configuration error"),
+    ("数据库错误", "Database error"),
+    ("IO错误", "IO error"),
+    ("序列化错误", "Serialization error"),
+    ("HTTP错误", "HTTP error"),
+    ("Axum错误", "Axum error"),
+    ("文件操作错误", "File operation error"),
+    ("视频处理错误", "Video processing error"),
+    ("验证错误", "Validation error"),
+    ("未找到资源", "Resource not found"),
+    ("未认证", "Unauthorized"),
+    ("权限不足", "Permission denied"),
+    ("资源已失效", "Resource gone"),
+    ("资源冲突", "Conflict"),
+    ("前置条件不满足", "Precondition failed"),
+    ("缺少前置条件", "Precondition required"),
+    ("存储空间不足", "Insufficient storage"),
+    ("超出内存预算", "Memory budget exceeded"),
+    ("依赖的服务不可用", "Dependent service unavailable"),
+    ("内部服务器错误", "Internal server error"),
+    // 接口上下文
+    ("请求被拒绝", "Request rejected"),
+    ("获取文件列表失败", "Failed to list files"),
+    ("导出文件列表失败", "Failed to export files"),
+    ("批量查询文件失败", "Failed to look up files"),
+    ("获取文件信息失败", "Failed to get file info"),
+    ("删除文件失败", "Failed to delete file"),
+    ("设置文件可用时间失败", "Failed to set file availability"),
+    ("获取统计信息失败", "Failed to get statistics"),
+    ("获取 Logo 失败", "Failed to get logo"),
+    ("图片缩放失败", "Failed to resize image"),
+    ("获取照片时间线失败", "Failed to get photo timeline"),
+    ("重建照片索引失败", "Failed to reindex photos"),
+    ("查找相似图片失败", "Failed to find similar images"),
+    ("图片文字识别失败", "Failed to run OCR"),
+    ("获取文件文本失败", "Failed to get file text"),
+    ("锁定文件失败", "Failed to lock file"),
+    ("获取文件锁失败", "Failed to get file lock"),
+    ("解锁文件失败", "Failed to unlock file"),
+    ("替换文件内容失败", "Failed to replace file content"),
+    ("数据库维护失败", "Database maintenance failed"),
+    ("查询流量统计失败", "Failed to get usage"),
+    ("保存播放进度失败", "Failed to save playback position"),
+    ("获取播放进度失败", "Failed to get playback position"),
+    ("获取集合列表失败", "Failed to list collections"),
+    ("创建集合失败", "Failed to create collection"),
+    ("获取集合失败", "Failed to get collection"),
+    ("更新集合失败", "Failed to update collection"),
+    ("删除集合失败", "Failed to delete collection"),
+    ("添加文件关联失败", "Failed to link files"),
+    ("按哈希下载文件失败", "Failed to download file by hash"),
+    ("对比文件失败", "Failed to diff files"),
+    ("读取归档内容失败", "Failed to list archive entries"),
+    ("下载归档成员失败", "Failed to download archive entry"),
+    ("打包搜索结果失败", "Failed to archive search results"),
+    ("生成订阅失败", "Failed to build feed"),
+    ("生成分享二维码失败", "Failed to generate share QR code"),
+    ("保存文本片段失败", "Failed to save paste"),
+    ("查看文本片段失败", "Failed to view paste"),
+    ("保存截图失败", "Failed to save screenshot"),
+    ("获取缩略图失败", "Failed to get thumbnail"),
+    // 消息内容
+    ("未知", "unknown"),
+    ("服务器处于只读模式", "the server is in read-only mode"),
+    ("管理接口仅允许本机访问", "admin endpoints are only available from localhost"),
+    ("Logo 未配置", "no logo configured"),
+    ("文件已被其他请求修改", "the file was modified by another request"),
+    ("请携带 If-Match 请求头或 version 字段", "send an If-Match header or a version field"),
+    ("available_from 必须早于 available_until", "available_from must be earlier than available_until"),
+    ("文件 {} ({}) 已于 {} 被 {} 删除", "file {} ({}) was deleted at {} by {}"),
+    ("文件 {} 的文本", "text of file {}"),
+    ("文件 {} 的锁", "lock of file {}"),
+    ("文件 {} 不存在", "file {} does not exist"),
+    ("文件 {}", "file {}"),
+    ("文件将于 {} 开放下载", "the file becomes available at {}"),
+    ("文件已于 {} 停止提供下载", "the file stopped being available at {}"),
+    ("不支持的导出格式", "unsupported export format"),
+    ("单次最多查询 {} 个文件", "at most {} files can be looked up at once"),
+    ("无效的 If-Match", "invalid If-Match"),
+    ("版本不匹配", "version mismatch"),
+    ("当前版本为 {}, 请求版本为 {}", "current version is {}, requested version is {}"),
+    ("文件不是图片", "the file is not an image"),
+    ("宽度和高度至少需要指定一个", "at least one of width and height is required"),
+    ("图片尺寸必须在 1-{} 之间", "image dimensions must be between 1 and {}"),
+    ("无法解码图片", "failed to decode image"),
+    ("无法编码图片", "failed to encode image"),
+    ("无效的坐标范围: {}", "invalid bounding box: {}"),
+    ("OCR 未启用", "OCR is not enabled"),
+    ("锁的持有者不能为空", "lock owner must not be empty"),
+    ("锁的有效期必须在 1-{} 秒之间", "lock TTL must be between 1 and {} seconds"),
+    ("请通过 X-Lock-Owner 请求头指定锁的持有者", "specify the lock owner in the X-Lock-Owner header"),
+    ("文件已被 {} 锁定至 {}", "the file is locked by {} until {}"),
+    ("文件大小超过上限 {} 字节", "file size exceeds the limit of {} bytes"),
+    ("无效的日期: {}", "invalid date: {}"),
+    ("文件不是视频", "the file is not a video"),
+    ("观看者标识不能为空", "viewer must not be empty"),
+    ("播放位置必须是非负数", "playback position must be a non-negative number"),
+    ("集合 {}", "collection {}"),
+    ("集合名称不能为空", "collection name must not be empty"),
+    ("集合最多包含 {} 个文件", "a collection can contain at most {} files"),
+    ("无效的关联类型: {}", "invalid link kind: {}"),
+    ("文件不能关联自身", "a file cannot be linked to itself"),
+    ("无效的 SHA-256: {}", "invalid SHA-256: {}"),
+    ("SHA-256 为 {} 的文件", "file with SHA-256 {}"),
+    ("文件受删除保护 ({}), 请由管理员使用 force=true 删除", "the file is delete-protected ({}); an administrator must delete it with force=true"),
+    ("大于 {} 字节", "larger than {} bytes"),
+    ("上传不到 {} 小时", "uploaded less than {} hours ago"),
+    ("文件名包含 {}", "name contains {}"),
+    ("强制删除仅允许本机访问", "forced deletion is only allowed from localhost"),
+    ("强制删除需要管理员密钥", "forced deletion requires an admin API key"),
+    ("上下文行数不能超过 {}", "context cannot exceed {} lines"),
+    ("文件 {} 超过对比大小上限 {} 字节", "file {} exceeds the diff size limit of {} bytes"),
+    ("文件 {} 不是文本文件", "file {} is not a text file"),
+    ("文件不是 zip 或 tar 归档", "the file is not a zip or tar archive"),
+    ("归档成员 {}", "archive entry {}"),
+    ("无法读取 zip 归档", "cannot read zip archive"),
+    ("tar 成员头中的大小无效", "invalid size in tar header"),
+    ("匹配的文件", "matching files"),
+    ("打包大小 {} 字节超过上限 {} 字节", "archive size of {} bytes exceeds the {} byte limit"),
+    ("无法写入 zip 归档", "cannot write zip archive"),
+    ("二维码缩放倍数必须在 1-{} 之间", "QR code scale must be between 1 and {}"),
+    ("文件 {} 没有内容哈希, 无法生成下载地址", "file {} has no content hash, so it has no download URL"),
+    ("无法生成二维码", "cannot generate QR code"),
+    ("无效的语法提示: {}", "invalid syntax hint: {}"),
+    ("过期时间必须在 1-{} 秒之间", "expiry must be between 1 and {} seconds"),
+    ("文本片段不能为空", "the paste is empty"),
+    ("文本片段必须是 UTF-8 文本", "the paste must be UTF-8 text"),
+    ("文本片段 {}", "paste {}"),
+    ("请求体不是 PNG、JPEG、WebP 或 GIF 图片", "the request body is not a PNG, JPEG, WebP or GIF image"),
+    ("缩略图尺寸格式无效: {}", "invalid thumbnail size: {}"),
+    ("文件 {} 的缩略图", "thumbnail of file {}"),
+    ("文件 {} 的缩略图尺寸 {}", "thumbnail of file {} in size {}"),
+    ("未配置的缩略图尺寸", "thumbnail size is not configured"),
+    ("临时目录已用 {} 字节, 超过上限 {} 字节", "temp directory uses {} bytes, exceeding the {} byte limit"),
+    ("清理派生文件失败", "Failed to clean up derived files"),
+    ("缓存策略 {} 包含无效字符", "cache policy {} contains invalid characters"),
+    ("挂载路由重复: {}", "Duplicate mount route: {}"),
+    ("挂载路由无效: {}", "Invalid mount route: {}"),
+    ("挂载路由与内置接口冲突: {}", "Mount route conflicts with a built-in route: {}"),
+    ("暂不支持可写挂载: {}", "Writable mounts are not supported: {}"),
+    ("挂载目录不存在: {}", "Mount directory does not exist: {}"),
+    ("访问挂载目录失败", "Failed to access mount"),
+    ("挂载路径 /{}", "mount path /{}"),
+    ("上级目录", "Parent directory"),
+    ("名称", "Name"),
+    ("大小", "Size"),
+    ("修改时间", "Modified"),
+    ("上传测速失败", "Upload speed test failed"),
+    ("内存预算不能为0", "memory budgets cannot be 0"),
+    ("{} 需要约 {} 字节内存, 超过预算 {} 字节", "{} needs about {} bytes of memory, exceeding the {} byte budget"),
+    ("OCR 输出超过 {} 字节", "OCR output exceeds {} bytes"),
+    ("下载读缓冲的最小值必须大于0且不超过最大值", "download buffer minimum must be greater than 0 and not exceed the maximum"),
+    ("CPU 采样最长时间不能为0", "maximum CPU profile duration cannot be 0"),
+    ("tokio-console 监听地址无效: {}", "invalid tokio-console listen address: {}"),
+    ("CPU 采样失败", "CPU profiling failed"),
+    ("采样时间必须在 1-{} 秒之间", "profile duration must be between 1 and {} seconds"),
+    ("采样频率必须在 1-1000 Hz 之间", "sampling frequency must be between 1 and 1000 Hz"),
+    ("无法启动 CPU 采样, 可能已有采样在进行: {}", "cannot start CPU profiling, another profile may be running: {}"),
+    ("生成火焰图失败: {}", "failed to render flamegraph: {}"),
+    ("下载测速失败", "Download speed test failed"),
+    ("测速数据不能超过 {} MB", "speed test data must not exceed {} MB"),
+    ("测速大小必须在 1-{} MB 之间", "speed test size must be between 1 and {} MB"),
+    ("文件记录已存在", "file record already exists"),
+    ("集合已存在", "collection already exists"),
+    ("提取音频失败", "Failed to extract audio"),
+    ("获取音频失败", "Failed to get audio"),
+    ("未找到 ffmpeg", "ffmpeg not found"),
+    ("ffmpeg 不支持编码器 {}", "ffmpeg does not support the {} encoder"),
+    ("无法启动 {}", "cannot start {}"),
+    ("{} 执行失败", "{} failed"),
+    ("截取视频片段失败", "Failed to create video clip"),
+    ("获取视频片段失败", "Failed to get video clip"),
+    ("片段起止时间无效, 需要 0 <= start < end", "invalid clip range, expected 0 <= start < end"),
+    ("片段起点超出视频时长 {} 秒", "clip start is beyond the video duration of {} seconds"),
+    ("文件 {} 的片段 {}-{}", "file {} clip {}-{}"),
+    ("图片格式转换失败", "Failed to convert image"),
+    ("HEIF 转换命令不能为空", "HEIF conversion command cannot be empty"),
+    ("HEIF 转换质量必须在 1-100 之间", "HEIF conversion quality must be between 1 and 100"),
+    ("HEIF 转换超时时间不能为0", "HEIF conversion timeout cannot be 0"),
+    ("HEIF 转换超时", "HEIF conversion timed out"),
+    ("无法启动 HEIF 转换命令", "cannot start the HEIF conversion command"),
+    ("HEIF 转换失败", "HEIF conversion failed"),
+    ("HEIF 转换命令没有生成输出文件", "the HEIF conversion command produced no output"),
+    ("生成 HEIF 兼容副本失败", "Failed to generate HEIF compatible copies"),
+    ("HEIF 转换未启用", "HEIF conversion is not enabled"),
+    ("预览表格失败", "Failed to preview table"),
+    ("预览行数必须在 1-{} 之间", "preview rows must be between 1 and {}"),
+    ("暂不支持 Parquet 文件", "Parquet files are not supported yet"),
+    ("文件不是 CSV 或 TSV 表格", "the file is not a CSV or TSV table"),
+    ("上传文件失败", "Failed to upload file"),
+    ("每次请求只能上传一个文件", "only one file can be uploaded per request"),
+    ("表单中没有文件字段", "the form contains no file field"),
+    ("上传的文件名不能为空", "the uploaded file name must not be empty"),
+    ("上传表单格式无效", "invalid upload form"),
+    ("读取文件末尾失败", "Failed to read end of file"),
+    ("行数必须在 1-{} 之间", "lines must be between 1 and {}"),
+    ("生成十六进制预览失败", "Failed to build hex preview"),
+    ("长度必须在 1-{} 之间", "length must be between 1 and {}"),
+    ("偏移量 {} 超出文件大小 {} 字节", "offset {} is beyond the file size of {} bytes"),
+    ("创建上传会话失败", "Failed to create upload session"),
+    ("获取上传会话失败", "Failed to get upload session"),
+    ("上传分块失败", "Failed to upload chunk"),
+    ("完成分块上传失败", "Failed to complete chunked upload"),
+    ("上传会话 {}", "upload session {}"),
+    ("文件大小必须在 1-{} 字节之间", "file size must be between 1 and {} bytes"),
+    ("分块序号必须在 0-{} 之间", "chunk index must be between 0 and {}"),
+    ("还有 {} 个分块没有上传", "{} chunks have not been uploaded yet"),
+    ("分块长度应为 {} 字节, 实际收到 {} 字节", "chunk should be {} bytes, received {} bytes"),
+    ("分块大小不能为0", "chunk size must not be 0"),
+    ("查看器映射的 MIME 模式无效", "Invalid MIME pattern in viewer mapping"),
+    ("下载文件失败", "Failed to download file"),
+    ("获取文件内容失败", "Failed to get file content"),
+    ("文件 {} 的内容", "content of file {}"),
+    ("重建视频元数据失败", "Failed to reindex video metadata"),
+    ("未找到 ffprobe", "ffprobe not found"),
+    ("无法解析 ffprobe 输出", "Cannot parse ffprobe output"),
+    ("获取界面文件失败", "Failed to get UI asset"),
+    ("界面文件 {}", "UI asset {}"),
+    ("获取蜜罐文件列表失败", "Failed to list canary files"),
+    ("标记蜜罐文件失败", "Failed to mark canary file"),
+    ("取消蜜罐标记失败", "Failed to unmark canary file"),
+    ("蜜罐文件 {}", "canary file {}"),
+    ("获取蜜罐告警失败", "Failed to list canary alerts"),
+    ("数量必须在 1-{} 之间", "limit must be between 1 and {}"),
+    ("API 密钥的名称和密钥不能为空", "API key name and key must not be empty"),
+    ("API 密钥名称重复", "Duplicate API key name"),
+    ("API 密钥 {} 与其他密钥相同", "API key {} is the same as another key"),
+    ("缺少 API 密钥", "missing API key"),
+    ("API 密钥无效", "invalid API key"),
+    ("只读密钥不能调用修改类接口", "read-only API key cannot call write endpoints"),
+    ("搜索文件失败", "Failed to search files"),
+    ("偏移量不能为负数", "offset must not be negative"),
+    ("最小大小不能超过最大大小", "min_size must not exceed max_size"),
+    ("{} 不能同时从文件和命令读取", "{} cannot be read from both a file and a command"),
+    ("{} 已直接配置, 不能再从文件或命令读取", "{} is set inline and cannot also be read from a file or command"),
+    ("无法读取 {} 的文件 {}", "cannot read the {} file {}"),
+    ("{} 的命令不能为空", "command for {} must not be empty"),
+    ("无法执行 {} 的命令 {}", "cannot run the {} command {}"),
+    ("{} 的命令 {} 执行失败 ({})", "the {} command {} failed ({})"),
+    ("{} 的命令输出不是 UTF-8 文本", "command output for {} is not UTF-8 text"),
+    ("回收站清除间隔不能为0", "trash cleanup interval must not be 0"),
+    ("获取回收站失败", "failed to list trash"),
+    ("恢复文件失败", "failed to restore file"),
+    ("永久删除文件失败", "failed to purge file"),
+    ("回收站中的文件 {}", "file {} in trash"),
+    ("授权服务拒绝了该请求", "the authorization service denied the request"),
+    ("无法连接授权服务", "cannot reach the authorization service"),
+    ("授权服务返回 {}", "the authorization service returned {}"),
+    ("授权服务的响应无效", "invalid response from the authorization service"),
+    ("无法创建授权服务客户端", "cannot create the authorization service client"),
+    ("授权服务地址无效", "invalid authorization service URL"),
+    ("授权服务超时时间不能为0", "authorization service timeout must not be 0"),
+    ("打包下载失败", "failed to download files as zip"),
+    ("file_ids 和 collection_id 必须且只能设置一个", "exactly one of file_ids and collection_id must be set"),
+    ("单次最多打包 {} 个文件", "at most {} files can be downloaded at once"),
+    ("可打包的文件", "files to download"),
+    ("上游实例地址无效", "invalid upstream instance URL"),
+    ("上游实例连接超时时间不能为0", "upstream instance connect timeout must not be 0"),
+    ("无法创建上游实例客户端", "cannot create the upstream instance client"),
+    ("转发上传失败", "failed to forward upload"),
+    ("无法连接上游实例", "cannot reach the upstream instance"),
+    ("上游实例", "upstream instance"),
+    ("取消上传失败", "Failed to abort upload"),
+    ("上传会话清理间隔不能为0", "upload session cleanup interval must not be 0"),
+    ("上传表单的字段数量上限和进度记录间隔不能为0", "multipart part limit and progress interval must not be 0"),
+    ("表单字段数量超过上限 {}", "the form has more than {} parts"),
+    ("表单字段 {} 超过 {} 字节", "form field {} exceeds {} bytes"),
+    ("SHA-256 不一致, 声明为 {}, 实际为 {}", "SHA-256 mismatch, declared {} but received {}"),
+    ("Idempotency-Key 已用于其他请求", "Idempotency-Key was already used for another request"),
+    ("相同 Idempotency-Key 的请求正在处理", "a request with the same Idempotency-Key is still in progress"),
+    ("可用空间不足, 无法为 {} 字节的上传预留空间", "not enough free space to reserve {} bytes for the upload"),
+];
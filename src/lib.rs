@@ -1,8 +1,23 @@
+pub mod check;
+pub mod collections;
 pub mod config;
 pub mod error;
+pub mod i18n;
+pub mod memory;
+pub mod middleware;
+pub mod policy;
+pub mod preview;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod search;
+pub mod seed;
 pub mod server;
+pub mod speedtest;
 pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod upload;
+pub mod usage;
 pub mod download;
 pub mod video;
 pub mod web;
@@ -63,6 +78,14 @@ mod tests {
             thumbnail_path: None,
             video_duration: None,
             video_resolution: None,
+            available_from: None,
+            available_until: None,
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: None,
         };
         
         assert!(file_manager.save_file_record(&file_record).await.is_ok());
@@ -80,6 +103,379 @@ mod tests {
         assert_eq!(stats.video_count, 0);
     }
 
+    #[tokio::test]
+    async fn test_delete_file_leaves_tombstone() {
+        use tempfile::tempdir;
+        use chrono::Utc;
+
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().to_path_buf();
+        let file_manager = storage::FileManager::new("sqlite::memory:", storage_path).await.unwrap();
+
+        let file_record = storage::FileRecord {
+            id: "deleted-id".to_string(),
+            original_name: "build.zip".to_string(),
+            stored_name: "deleted-id.zip".to_string(),
+            file_path: temp_dir.path().join("deleted-id.zip").to_string_lossy().to_string(),
+            file_size: 10,
+            mime_type: "application/zip".to_string(),
+            upload_time: Utc::now(),
+            is_video: false,
+            thumbnail_path: None,
+            video_duration: None,
+            video_resolution: None,
+            available_from: None,
+            available_until: None,
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: None,
+        };
+        file_manager.save_file_record(&file_record).await.unwrap();
+
+        assert!(file_manager.get_tombstone("deleted-id").await.unwrap().is_none());
+        assert!(file_manager.delete_file("deleted-id", Some("10.0.0.8"), None).await.unwrap());
+        assert!(!file_manager.delete_file("deleted-id", None, None).await.unwrap());
+
+        let tombstone = file_manager.get_tombstone("deleted-id").await.unwrap().unwrap();
+        assert_eq!(tombstone.original_name, "build.zip");
+        assert_eq!(tombstone.deleted_by.as_deref(), Some("10.0.0.8"));
+    }
+
+    #[tokio::test]
+    async fn test_save_shared_record() {
+        use crate::storage::{FileRepository, MemoryFileRepository};
+        use chrono::Utc;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_manager = storage::FileManager::new("sqlite::memory:", temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryFileRepository::new();
+        let repositories: [&dyn FileRepository; 2] = [&file_manager, &memory];
+
+        let record = |id: &str| storage::FileRecord {
+            id: id.to_string(),
+            original_name: "build.zip".to_string(),
+            stored_name: "shared.zip".to_string(),
+            file_path: temp_dir.path().join("shared.zip").to_string_lossy().to_string(),
+            file_size: 10,
+            mime_type: "application/zip".to_string(),
+            upload_time: Utc::now(),
+            is_video: false,
+            thumbnail_path: None,
+            video_duration: None,
+            video_resolution: None,
+            available_from: None,
+            available_until: None,
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: Some("ab".repeat(32)),
+        };
+        for files in repositories {
+            // 仍有相同内容的记录使用该文件时写入
+            files.save_file_record(&record("first")).await.unwrap();
+            assert!(files.save_shared_record(&record("second")).await.unwrap());
+            assert!(files.get_file_by_id("second").await.unwrap().is_some());
+
+            // 内容已被替换的文件不能复用
+            let mut other = record("third");
+            other.sha256 = Some("cd".repeat(32));
+            assert!(!files.save_shared_record(&other).await.unwrap());
+
+            // 最后一条引用删除后不再写入
+            for id in ["first", "second"] {
+                assert!(files.delete_file(id, None, None).await.unwrap());
+            }
+            assert!(!files.save_shared_record(&record("fourth")).await.unwrap());
+            assert!(files.get_file_by_id("fourth").await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_cache() {
+        use crate::config::RecordCacheConfig;
+        use chrono::Utc;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database_url = format!("sqlite://{}", temp_dir.path().join("files.db").display());
+        let cached = storage::FileManager::new(&database_url, temp_dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_record_cache(&RecordCacheConfig::default());
+        // 共享同一个数据库但不带缓存, 用来模拟绕过缓存的修改
+        let direct = storage::FileManager::new(&database_url, temp_dir.path().to_path_buf()).await.unwrap();
+
+        let record = storage::FileRecord {
+            id: "cached-id".to_string(),
+            original_name: "movie.mp4".to_string(),
+            stored_name: "cached-id.mp4".to_string(),
+            file_path: temp_dir.path().join("cached-id.mp4").to_string_lossy().to_string(),
+            file_size: 10,
+            mime_type: "video/mp4".to_string(),
+            upload_time: Utc::now(),
+            is_video: true,
+            thumbnail_path: None,
+            video_duration: None,
+            video_resolution: None,
+            available_from: None,
+            available_until: None,
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: None,
+        };
+        assert!(cached.get_file_by_id("cached-id").await.unwrap().is_none());
+        cached.save_file_record(&record).await.unwrap();
+        assert_eq!(cached.get_file_by_id("cached-id").await.unwrap().unwrap().version, 1);
+
+        // 之后的读取命中缓存, 看不到绕过缓存的修改
+        assert!(direct.set_availability("cached-id", None, None, None).await.unwrap());
+        assert_eq!(cached.get_file_by_id("cached-id").await.unwrap().unwrap().version, 1);
+
+        // 通过带缓存的实例修改时, 版本检查读取数据库, 修改后缓存失效
+        assert!(cached.set_availability("cached-id", None, None, Some(2)).await.unwrap());
+        assert_eq!(cached.get_file_by_id("cached-id").await.unwrap().unwrap().version, 3);
+
+        assert!(cached.delete_file("cached-id", None, Some(3)).await.unwrap());
+        assert!(cached.get_file_by_id("cached-id").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_query() {
+        use crate::storage::{FileQuery, FileSearch, FileSort, GeoBounds, SortField};
+        use chrono::{Duration, Utc};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_manager = storage::FileManager::new("sqlite::memory:", temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let files = [
+            ("a", "report_50%.pdf", "application/pdf", 300, None),
+            ("b", "report_final.pdf", "application/pdf", 100, Some((35.0, 179.5))),
+            ("c", "photo.jpg", "image/jpeg", 200, Some((35.0, -179.5))),
+            ("d", "photo-2.jpg", "image/jpeg", 200, Some((35.0, 10.0))),
+        ];
+        for (index, (id, name, mime_type, size, location)) in files.into_iter().enumerate() {
+            let record = storage::FileRecord {
+                id: id.to_string(),
+                original_name: name.to_string(),
+                stored_name: name.to_string(),
+                file_path: temp_dir.path().join(name).to_string_lossy().to_string(),
+                file_size: size,
+                mime_type: mime_type.to_string(),
+                upload_time: now - Duration::minutes(index as i64),
+                is_video: false,
+                thumbnail_path: None,
+                video_duration: None,
+                video_resolution: None,
+                available_from: None,
+                available_until: None,
+                version: 1,
+                capture_time: None,
+                latitude: location.map(|(lat, _)| lat),
+                longitude: location.map(|(_, lon)| lon),
+                perceptual_hash: None,
+                sha256: None,
+            };
+            file_manager.save_file_record(&record).await.unwrap();
+        }
+        let ids = |records: Vec<storage::FileRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        // 默认最新上传的在前, 通配符按字面匹配
+        assert_eq!(ids(file_manager.query_files(&FileQuery::default()).await.unwrap()), ["a", "b", "c", "d"]);
+        let search = FileSearch {
+            name: Some("_50%".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(file_manager.search_files(&search, 10).await.unwrap()), ["a"]);
+
+        // 排序字段相同时按 id 排序, 分页结果稳定
+        let query = FileQuery {
+            search: FileSearch {
+                mime_type: Some("image/".to_string()),
+                ..Default::default()
+            },
+            sort: FileSort::ascending(SortField::Size),
+            ..Default::default()
+        };
+        assert_eq!(ids(file_manager.query_files(&query).await.unwrap()), ["c", "d"]);
+        let query = FileQuery {
+            sort: FileSort::descending(SortField::Size),
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(file_manager.query_files(&query).await.unwrap()), ["d", "c"]);
+
+        // 跨越 180 度经线的范围
+        let bounds = GeoBounds::parse("179,30,-179,40").unwrap();
+        assert_eq!(ids(file_manager.list_files_in_bounds(&bounds, None, None).await.unwrap()), ["b", "c"]);
+
+        let query = FileQuery {
+            ids: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert!(file_manager.query_files(&query).await.unwrap().is_empty());
+        let found = file_manager.get_files_by_ids(&["d".to_string(), "a".to_string()]).await.unwrap();
+        assert_eq!(ids(found), ["a", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_repository() {
+        use crate::storage::{FileQuery, FileRepository, FileSearch, FileSort, GeoBounds, MemoryFileRepository, SortField};
+        use chrono::{Duration, Utc};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_manager = storage::FileManager::new("sqlite::memory:", temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryFileRepository::new();
+        let repositories: [&dyn FileRepository; 2] = [&file_manager, &memory];
+
+        let now = Utc::now();
+        let files = [
+            ("a", "Report_50%.pdf", 300, None),
+            ("b", "report_final.pdf", 100, Some((35.0, 179.5))),
+            ("c", "photo.jpg", 200, Some((35.0, -179.5))),
+            ("d", "photo-2.jpg", 200, Some((35.0, 10.0))),
+        ];
+        for (index, (id, name, size, location)) in files.into_iter().enumerate() {
+            let record = storage::FileRecord {
+                id: id.to_string(),
+                original_name: name.to_string(),
+                stored_name: name.to_string(),
+                file_path: temp_dir.path().join(name).to_string_lossy().to_string(),
+                file_size: size,
+                mime_type: mime_guess::from_path(name).first_or_octet_stream().to_string(),
+                upload_time: now - Duration::minutes(index as i64),
+                is_video: false,
+                thumbnail_path: None,
+                video_duration: None,
+                video_resolution: None,
+                available_from: None,
+                available_until: None,
+                version: 1,
+                capture_time: None,
+                latitude: location.map(|(lat, _)| lat),
+                longitude: location.map(|(_, lon)| lon),
+                perceptual_hash: None,
+                sha256: Some(if id == "d" { "same" } else { "other" }.to_string()),
+            };
+            for repository in repositories {
+                repository.save_file_record(&record).await.unwrap();
+            }
+        }
+        let ids = |records: Vec<storage::FileRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        // 两种实现对同一组查询返回相同的结果
+        let queries = [
+            FileQuery::default(),
+            FileQuery {
+                search: FileSearch {
+                    name: Some("REPORT_5".to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            FileQuery {
+                search: FileSearch {
+                    mime_type: Some("image/".to_string()),
+                    ..Default::default()
+                },
+                sort: FileSort::ascending(SortField::Size),
+                ..Default::default()
+            },
+            FileQuery {
+                sort: FileSort::descending(SortField::Name),
+                limit: Some(2),
+                offset: Some(1),
+                ..Default::default()
+            },
+            FileQuery {
+                bounds: Some(GeoBounds::parse("179,30,-179,40").unwrap()),
+                ..Default::default()
+            },
+            FileQuery {
+                sha256: Some("other".to_string()),
+                ids: Some(vec!["a".to_string(), "c".to_string(), "d".to_string()]),
+                ..Default::default()
+            },
+            FileQuery {
+                search: FileSearch {
+                    text: Some("PHOTO jp".to_string()),
+                    min_size: Some(150),
+                    is_video: Some(false),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            FileQuery {
+                search: FileSearch {
+                    text: Some("port \"pdf".to_string()),
+                    max_size: Some(200),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ];
+        for query in &queries {
+            let expected = ids(file_manager.query_files(query).await.unwrap());
+            assert_eq!(ids(memory.query_files(query).await.unwrap()), expected, "{:?}", query);
+        }
+
+        // 提取出的元数据及其上的查询
+        for repository in repositories {
+            assert!(repository.set_photo_metadata("c", Some(now - Duration::days(40)), None).await.unwrap());
+            assert!(repository.set_perceptual_hash("d", 7).await.unwrap());
+            assert!(!repository.set_thumbnail_path("d", 2, "thumb.jpg").await.unwrap());
+            assert!(repository.set_thumbnail_path("d", 1, "thumb.jpg").await.unwrap());
+        }
+        let timeline = |entries: Vec<storage::PhotoTimelineEntry>| {
+            entries.into_iter().map(|e| (e.period, e.count, e.cover_id)).collect::<Vec<_>>()
+        };
+        let summaries = |summaries: Vec<storage::FileSummary>| {
+            summaries.into_iter().map(|s| (s.id, s.has_thumbnail)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            timeline(memory.photo_timeline(7, None, None).await.unwrap()),
+            timeline(file_manager.photo_timeline(7, None, None).await.unwrap())
+        );
+        assert_eq!(
+            summaries(memory.list_file_summaries(Some(3), Some(1)).await.unwrap()),
+            summaries(file_manager.list_file_summaries(Some(3), Some(1)).await.unwrap())
+        );
+        assert_eq!(memory.list_perceptual_hashes().await.unwrap(), [("d".to_string(), 7)]);
+        assert_eq!(ids(memory.list_images_missing_photo_metadata().await.unwrap()), ["c", "d"]);
+        assert_eq!(ids(file_manager.list_images_missing_photo_metadata().await.unwrap()), ["c", "d"]);
+
+        for repository in repositories {
+            assert!(repository.save_file_record(&repository.get_file_by_id("a").await.unwrap().unwrap()).await.is_err());
+            assert_eq!(ids(repository.find_files_by_sha256("same").await.unwrap()), ["d"]);
+
+            // 版本号不匹配时拒绝修改, 修改成功后版本号递增
+            assert!(repository.set_availability("a", None, Some(now), Some(2)).await.is_err());
+            assert!(repository.set_availability("a", None, Some(now), Some(1)).await.unwrap());
+            assert!(!repository.set_availability("missing", None, None, None).await.unwrap());
+            assert_eq!(repository.get_file_by_id("a").await.unwrap().unwrap().version, 2);
+
+            assert!(repository.delete_file("a", Some("tester"), Some(1)).await.is_err());
+            assert!(repository.delete_file("a", Some("tester"), Some(2)).await.unwrap());
+            assert!(repository.get_file_by_id("a").await.unwrap().is_none());
+            let tombstone = repository.get_tombstone("a").await.unwrap().unwrap();
+            assert_eq!(tombstone.deleted_by.as_deref(), Some("tester"));
+        }
+    }
+
     #[test]
     fn test_generate_stored_name() {
         use tempfile::tempdir;
@@ -101,6 +497,44 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn test_naming_policies() {
+        use crate::config::NamingPolicy;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path().to_path_buf();
+        let file_manager = storage::FileManager::new("sqlite::memory:", storage_path.clone())
+            .await
+            .unwrap();
+
+        // 日期前缀: 年/月/uuid.扩展名, 目录已创建
+        let dated = file_manager
+            .clone()
+            .with_naming_policy(NamingPolicy::DatePrefixed)
+            .generate_stored_name("report.pdf");
+        let parts: Vec<&str> = dated.split('/').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].len(), 4);
+        assert_eq!(parts[1].len(), 2);
+        assert!(parts[2].ends_with(".pdf"));
+        assert!(storage_path.join(parts[0]).join(parts[1]).is_dir());
+
+        // 保留原名, 重名时追加后缀, 路径分隔符被替换
+        let original = file_manager.clone().with_naming_policy(NamingPolicy::OriginalName);
+        assert_eq!(original.generate_stored_name("notes.txt"), "notes.txt");
+        assert_eq!(original.generate_stored_name("notes.txt"), "notes-1.txt");
+        assert_eq!(original.generate_stored_name("notes.txt"), "notes-2.txt");
+        assert_eq!(original.generate_stored_name("../etc/passwd"), "_etc_passwd");
+
+        // 按哈希命名, 没有哈希时退回 uuid
+        let hashed = file_manager.clone().with_naming_policy(NamingPolicy::Hash);
+        let sha256 = "ab".repeat(32);
+        assert_eq!(hashed.generate_stored_name_with_hash("a.png", Some(&sha256)), format!("{}.png", sha256));
+        assert_eq!(hashed.generate_stored_name_with_hash("b.png", Some(&sha256)), format!("{}-1.png", sha256));
+        let fallback = hashed.generate_stored_name("c.png");
+        assert!(fallback.ends_with(".png") && fallback.len() == 36 + 4);
+    }
+
     #[tokio::test]
     async fn test_server_config_validation() {
         let config = Config::default();
@@ -118,6 +552,84 @@ mod tests {
         assert!(config.storage.chunk_size > 0);
     }
 
+    #[test]
+    fn test_thumbnail_config() {
+        use crate::config::{parse_dimensions, ThumbnailFormat};
+
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(parse_dimensions("320x240"), Some((320, 240)));
+        assert_eq!(parse_dimensions("320"), None);
+
+        config.video.thumbnail_sizes.insert("small".to_string(), "160x120".to_string());
+        let variants = config.video.thumbnail_variants();
+        assert_eq!(variants["default"], "320x240");
+        assert_eq!(variants["small"], "160x120");
+
+        config.image.thumbnail_sizes.insert("broken".to_string(), "big".to_string());
+        assert!(config.validate().is_err());
+        config.image.thumbnail_sizes.clear();
+        for name in ["default", "../x", ""] {
+            config.video.thumbnail_sizes.insert(name.to_string(), "10x10".to_string());
+            assert!(config.validate().is_err(), "{}", name);
+            config.video.thumbnail_sizes.remove(name);
+        }
+        assert!(config.validate().is_ok());
+        assert!(serde_json::from_str::<ThumbnailFormat>(r#""avif""#).is_err());
+
+        let available = [ThumbnailFormat::Jpeg, ThumbnailFormat::Webp];
+        let accept = "image/webp,image/jpeg;q=0.8";
+        assert_eq!(ThumbnailFormat::negotiate(accept, &available), Some(ThumbnailFormat::Webp));
+        assert_eq!(ThumbnailFormat::negotiate("image/webp,image/*;q=0.8", &available), Some(ThumbnailFormat::Webp));
+        assert_eq!(ThumbnailFormat::negotiate("image/*", &available), Some(ThumbnailFormat::Jpeg));
+        assert_eq!(ThumbnailFormat::negotiate("image/webp", &available), Some(ThumbnailFormat::Webp));
+        assert_eq!(ThumbnailFormat::negotiate("image/jpeg;q=0, image/*", &available), Some(ThumbnailFormat::Webp));
+        assert_eq!(ThumbnailFormat::negotiate("text/html", &available), Some(ThumbnailFormat::Jpeg));
+        assert_eq!(ThumbnailFormat::negotiate("image/webp", &[]), None);
+    }
+
+    #[test]
+    fn test_delete_protection_rules() {
+        use crate::config::DeleteProtectionConfig;
+        use chrono::Duration;
+
+        let rules = DeleteProtectionConfig {
+            larger_than: Some(1024),
+            younger_than_hours: Some(24),
+            name_contains: vec!["Release".to_string()],
+        };
+        let old = Duration::days(2);
+        assert_eq!(rules.matching_rule("app.zip", 100, old), None);
+        assert_eq!(rules.matching_rule("app.zip", 2048, old).as_deref(), Some("大于 1024 字节"));
+        assert_eq!(rules.matching_rule("app.zip", 100, Duration::hours(1)).as_deref(), Some("上传不到 24 小时"));
+        assert_eq!(rules.matching_rule("app-RELEASE.zip", 100, old).as_deref(), Some("文件名包含 Release"));
+
+        // 默认不保护任何文件
+        let none = DeleteProtectionConfig::default();
+        assert_eq!(none.matching_rule("release.zip", u64::MAX, Duration::zero()), None);
+    }
+
+    #[test]
+    fn test_i18n_translate() {
+        use crate::i18n::{translate, Locale};
+
+        assert_eq!(Locale::from_accept_language("en-US,en;q=0.9,zh;q=0.8"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("fr, zh-CN;q=0.5, en;q=0.2"), Some(Locale::ZhCn));
+        assert_eq!(Locale::from_accept_language("fr"), None);
+
+        assert_eq!(
+            translate(Locale::En, "获取文件信息失败: 未找到资源: 文件 abc"),
+            "Failed to get file info: Resource not found: file abc"
+        );
+        assert_eq!(
+            translate(Locale::En, "删除文件失败: 资源已失效: 文件 abc (a.txt) 已于 2024-01-01T00:00:00+00:00 被 未知 删除"),
+            "Failed to delete file: Resource gone: file abc (a.txt) was deleted at 2024-01-01T00:00:00+00:00 by unknown"
+        );
+        // 目录中没有的段保持原样
+        assert_eq!(translate(Locale::En, "IO错误: No such file"), "IO error: No such file");
+        assert_eq!(translate(Locale::ZhCn, "获取文件列表失败"), "获取文件列表失败");
+    }
+
     #[tokio::test]
     async fn test_api_response_structure() {
         use crate::server::ApiResponse;
@@ -132,4 +644,521 @@ mod tests {
         assert!(error_response.data.is_none());
         assert!(error_response.error.is_some());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_file_availability_window() {
+        use chrono::{Duration, Utc};
+
+        let now = Utc::now();
+        let mut record = storage::FileRecord {
+            id: "embargoed".to_string(),
+            original_name: "release-notes.pdf".to_string(),
+            stored_name: "release-notes.pdf".to_string(),
+            file_path: "/tmp/release-notes.pdf".to_string(),
+            file_size: 1024,
+            mime_type: "application/pdf".to_string(),
+            upload_time: now,
+            is_video: false,
+            thumbnail_path: None,
+            video_duration: None,
+            video_resolution: None,
+            available_from: Some(now + Duration::hours(1)),
+            available_until: Some(now + Duration::hours(2)),
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: None,
+        };
+
+        // 开放时间之前返回 403
+        let err = record.check_availability(now).unwrap_err();
+        assert_eq!(err.status_code(), 403);
+
+        // 时间窗口内允许下载
+        assert!(record.check_availability(now + Duration::minutes(90)).is_ok());
+
+        // 过期之后返回 410
+        let err = record.check_availability(now + Duration::hours(3)).unwrap_err();
+        assert_eq!(err.status_code(), 410);
+
+        record.available_from = None;
+        record.available_until = None;
+        assert!(record.check_availability(now).is_ok());
+    }
+
+    #[test]
+    fn test_geo_bounds_parse() {
+        use crate::storage::GeoBounds;
+
+        let bounds = GeoBounds::parse("120, 30, 122.5, 32").unwrap();
+        assert_eq!(bounds.min_lon, 120.0);
+        assert_eq!(bounds.max_lat, 32.0);
+
+        // 跨越 180 度经线的范围
+        assert!(GeoBounds::parse("170,-10,-170,10").is_ok());
+
+        assert!(GeoBounds::parse("1,2,3").is_err());
+        assert!(GeoBounds::parse("0,50,10,40").is_err());
+        assert!(GeoBounds::parse("0,0,200,10").is_err());
+        assert!(GeoBounds::parse("a,b,c,d").is_err());
+    }
+
+    #[test]
+    fn test_maintenance_next_run() {
+        use crate::storage::maintenance::next_run;
+        use chrono::{TimeZone, Utc};
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 2, 30, 0).unwrap();
+        assert_eq!(next_run(&now, 3), Utc.with_ymd_and_hms(2024, 3, 10, 3, 0, 0).unwrap());
+
+        // 当天的维护时刻已过, 顺延到第二天
+        assert_eq!(next_run(&now, 1), Utc.with_ymd_and_hms(2024, 3, 11, 1, 0, 0).unwrap());
+
+        let exact = Utc.with_ymd_and_hms(2024, 3, 10, 3, 0, 0).unwrap();
+        assert_eq!(next_run(&exact, 3), Utc.with_ymd_and_hms(2024, 3, 11, 3, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse_tool_version() {
+        use crate::video::toolchain::parse_tool_version;
+
+        let output = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers\nbuilt with gcc";
+        assert_eq!(parse_tool_version(output), Some("6.1.1-3ubuntu5"));
+        assert_eq!(parse_tool_version("ffprobe version n7.0 Copyright"), Some("n7.0"));
+        assert_eq!(parse_tool_version("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_checks() {
+        use crate::check::{run_checks, CheckOptions, CheckStatus};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.database.url = format!("sqlite:{}", dir.path().join("files.db").display());
+        config.storage.path = dir.path().to_path_buf();
+        config.storage.upload_dir = dir.path().join("storage");
+
+        let options = CheckOptions { min_free_space: 1, strict: false };
+        let report = run_checks(&config, &options).await;
+        let status = |name: &str| report.results.iter().find(|r| r.name == name).unwrap().status;
+        assert_eq!(status("配置"), CheckStatus::Ok);
+        assert_eq!(status("数据库"), CheckStatus::Ok);
+        assert_eq!(status("存储目录"), CheckStatus::Ok);
+        assert_eq!(status("磁盘空间"), CheckStatus::Ok);
+        assert!(dir.path().join("files.db").exists());
+
+        // 可用空间不足时失败
+        let options = CheckOptions { min_free_space: u64::MAX, strict: false };
+        let report = run_checks(&config, &options).await;
+        assert!(!report.passed(false));
+    }
+
+    #[test]
+    fn test_parse_ffmpeg_capabilities() {
+        use crate::video::toolchain::{parse_encoders, parse_hwaccels};
+
+        let encoders = "Encoders:\n V..... = Video\n ------\n V....D libx264              libx264 H.264\n A....D aac                  AAC (Advanced Audio Coding)\n";
+        let encoders = parse_encoders(encoders);
+        assert!(encoders.contains("libx264"));
+        assert!(encoders.contains("aac"));
+        assert_eq!(encoders.len(), 2);
+
+        let hwaccels = "Hardware acceleration methods:\nvdpau\ncuda\n\n";
+        assert_eq!(parse_hwaccels(hwaccels), vec!["vdpau", "cuda"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_video_toolchain_degrades_without_ffmpeg() {
+        use crate::video::{VideoProcessor, VideoToolchain};
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        // 只提供 ffprobe, ffmpeg 指向不存在的路径
+        let dir = tempfile::tempdir().unwrap();
+        let ffprobe = dir.path().join("ffprobe");
+        std::fs::write(&ffprobe, "#!/bin/sh\necho 'ffprobe version 9.9 Copyright'\n").unwrap();
+        std::fs::set_permissions(&ffprobe, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = Config::default();
+        config.video.ffmpeg_path = Some(dir.path().join("missing-ffmpeg"));
+        config.video.ffprobe_path = Some(ffprobe);
+
+        let toolchain = VideoToolchain::detect(&config.video).await;
+        assert!(toolchain.ffmpeg.is_none());
+        assert_eq!(toolchain.ffprobe.as_ref().unwrap().version, "9.9");
+
+        let plan = VideoProcessor::new(Arc::new(toolchain)).plan();
+        assert!(plan.probe);
+        assert!(!plan.thumbnail);
+        assert!(!plan.transcode);
+    }
+
+    #[test]
+    fn test_stream_buffer_size() {
+        use crate::config::StreamBufferConfig;
+        use crate::download::stream::{buffer_size, AdaptiveBuffer, StreamTracker};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let config = StreamBufferConfig {
+            min_size: 16 * 1024,
+            max_size: 1024 * 1024,
+        };
+        // Range 请求总是最小缓冲
+        assert_eq!(buffer_size(&config, 10 << 30, true, 1), 16 * 1024);
+        // 小文件不低于下限, 大文件单独下载时用到上限
+        assert_eq!(buffer_size(&config, 1000, false, 1), 16 * 1024);
+        assert_eq!(buffer_size(&config, 64 * 128 * 1024, false, 1), 128 * 1024);
+        assert_eq!(buffer_size(&config, 10 << 30, false, 1), 1024 * 1024);
+        // 并发越多每个连接的缓冲越小
+        assert_eq!(buffer_size(&config, 10 << 30, false, 4), 256 * 1024);
+        assert_eq!(buffer_size(&config, 10 << 30, false, 500), 16 * 1024);
+
+        // 客户端很快取走数据时逐步放大, 每次最多一倍, 不超过并发上限
+        let mut buffer = AdaptiveBuffer::new(&config, 16 * 1024, 1);
+        assert_eq!(buffer.adjust(16 * 1024, Duration::ZERO), 32 * 1024);
+        assert_eq!(buffer.adjust(32 * 1024, Duration::from_micros(100)), 64 * 1024);
+        for _ in 0..10 {
+            buffer.adjust(buffer.size(), Duration::from_micros(100));
+        }
+        assert_eq!(buffer.size(), 1024 * 1024);
+        let mut buffer = AdaptiveBuffer::new(&config, 16 * 1024, 4);
+        for _ in 0..10 {
+            buffer.adjust(buffer.size(), Duration::ZERO);
+        }
+        assert_eq!(buffer.size(), 256 * 1024);
+        // 慢速客户端缩小到 速度 x 100ms, 不低于下限; 速度合适时保持不变
+        assert_eq!(buffer.adjust(256 * 1024, Duration::from_millis(400)), 128 * 1024);
+        assert_eq!(buffer.adjust(128 * 1024, Duration::from_millis(100)), 128 * 1024);
+        assert_eq!(buffer.adjust(128 * 1024, Duration::from_secs(60)), 64 * 1024);
+        for _ in 0..10 {
+            buffer.adjust(buffer.size(), Duration::from_secs(60));
+        }
+        assert_eq!(buffer.size(), 16 * 1024);
+
+        let tracker = Arc::new(StreamTracker::default());
+        let first = tracker.start();
+        let second = tracker.start();
+        assert_eq!(tracker.active(), 2);
+        drop(first);
+        drop(second);
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[test]
+    fn test_cache_class() {
+        use crate::middleware::{cache_class, CacheClass};
+
+        let sha256 = "ab".repeat(32);
+        assert_eq!(cache_class(&format!("/files/by-hash/{}", sha256), "image/png"), Some(CacheClass::Immutable));
+        assert_eq!(cache_class("/api/files/abc/thumbnail", "image/webp"), Some(CacheClass::Preview));
+        assert_eq!(cache_class("/api/files/abc/image", "image/png"), Some(CacheClass::Preview));
+        assert_eq!(cache_class("/api/files/abc/archive/entries/docs/a.txt", "text/plain"), Some(CacheClass::Preview));
+        assert_eq!(cache_class("/paste/abc", "text/html; charset=utf-8"), Some(CacheClass::Preview));
+        // 压缩包条目列表本身是 JSON 列表
+        assert_eq!(cache_class("/api/files/abc/archive/entries", "application/json"), Some(CacheClass::Listing));
+        assert_eq!(cache_class("/api/files", "application/json"), Some(CacheClass::Listing));
+        assert_eq!(cache_class("/api/feed.atom", "application/atom+xml; charset=utf-8"), Some(CacheClass::Listing));
+        assert_eq!(cache_class("/api/search/download", "application/zip"), None);
+    }
+
+    #[test]
+    fn test_read_tar_entries() {
+        use crate::config::MemoryConfig;
+        use crate::download::archive::read_tar_entries;
+        use crate::memory::MemoryBudgets;
+        use std::io::Cursor;
+
+        fn tar_header(name: &str, size: usize, type_flag: u8) -> Vec<u8> {
+            let mut header = vec![0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+            header[156] = type_flag;
+            header[257..262].copy_from_slice(b"ustar");
+            header
+        }
+        fn padded(data: &[u8]) -> Vec<u8> {
+            let mut data = data.to_vec();
+            data.resize(data.len().div_ceil(512) * 512, 0);
+            data
+        }
+
+        let long_name = format!("logs/{}.log", "x".repeat(120));
+        let mut tar = tar_header("logs/", 0, b'5');
+        tar.extend(tar_header("logs/app.log", 5, b'0'));
+        tar.extend(padded(b"hello"));
+        tar.extend(tar_header("././@LongLink", long_name.len(), b'L'));
+        tar.extend(padded(long_name.as_bytes()));
+        tar.extend(tar_header("truncated", 600, b'0'));
+        tar.extend(padded(&[b'a'; 600]));
+        tar.extend([0u8; 1024]);
+
+        let budgets = MemoryBudgets::new(MemoryConfig::default());
+        let entries = read_tar_entries(&mut Cursor::new(&tar), &budgets).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.entry.path.as_str()).collect();
+        assert_eq!(paths, ["logs/", "logs/app.log", long_name.as_str()]);
+        assert!(entries[0].entry.is_dir);
+        assert_eq!(entries[1].entry.size, 5);
+        assert_eq!(&tar[entries[1].offset as usize..][..5], b"hello");
+        assert_eq!(entries[2].entry.size, 600);
+        assert_eq!(entries[2].offset, 512 * 6);
+
+        // 成员列表超出归档目录预算时拒绝
+        let small = MemoryBudgets::new(MemoryConfig {
+            archive_index: 200,
+            ..MemoryConfig::default()
+        });
+        let err = read_tar_entries(&mut Cursor::new(&tar), &small).unwrap_err();
+        assert_eq!(err.status_code(), 413);
+        assert_eq!(small.stats()["archive_index"].rejected, 1);
+    }
+
+    #[test]
+    fn test_zip_index_estimate() {
+        use crate::download::archive::zip_index_estimate;
+        use std::io::{Cursor, Write};
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for i in 0..3 {
+            zip.start_file(format!("file-{}.txt", i), options).unwrap();
+            zip.write_all(b"data").unwrap();
+        }
+        zip.set_comment("trailing comment");
+        let bytes = zip.finish().unwrap().into_inner();
+
+        // 中央目录每个成员 46 字节固定部分加 10 字节文件名
+        let estimate = zip_index_estimate(&mut Cursor::new(&bytes)).unwrap().unwrap();
+        assert_eq!(estimate, 3 * (46 + 10) + 3 * 128);
+        assert_eq!(zip_index_estimate(&mut Cursor::new(b"not a zip".to_vec())).unwrap(), None);
+    }
+
+    #[cfg(feature = "mp4-fallback")]
+    #[test]
+    fn test_mp4_fallback_info() {
+        use crate::video::mp4::{parse_moov, read_info};
+
+        fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+            let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+            data.extend(kind);
+            data.extend(body);
+            data
+        }
+
+        // mvhd 版本 0: timescale 1000, duration 12500
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&12500u32.to_be_bytes());
+
+        // 音频轨道没有尺寸, 视频轨道 1920x1080
+        let audio_tkhd = vec![0u8; 84];
+        let mut video_tkhd = vec![0u8; 84];
+        video_tkhd[76..80].copy_from_slice(&(1920u32 << 16).to_be_bytes());
+        video_tkhd[80..84].copy_from_slice(&(1080u32 << 16).to_be_bytes());
+
+        let mut moov = mp4_box(b"mvhd", &mvhd);
+        moov.extend(mp4_box(b"trak", &mp4_box(b"tkhd", &audio_tkhd)));
+        moov.extend(mp4_box(b"trak", &mp4_box(b"tkhd", &video_tkhd)));
+
+        // moov 位于 mdat 之后, 与未做 faststart 的录像文件一致
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(mp4_box(b"mdat", &[0u8; 1024]));
+        file.extend(mp4_box(b"moov", &moov));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.mp4");
+        std::fs::write(&path, file).unwrap();
+
+        let info = read_info(&path).unwrap();
+        assert_eq!(info.duration_secs, 12.5);
+        assert_eq!(info.resolution(), "1920x1080");
+
+        assert!(parse_moov(b"not an mp4").is_err());
+        std::fs::write(&path, mp4_box(b"free", b"hello")).unwrap();
+        assert!(read_info(&path).is_err());
+    }
+
+    #[test]
+    fn test_tail_last_lines() {
+        use crate::preview::tail::last_lines;
+
+        assert_eq!(last_lines(b"a\nb\nc\n", 2, false), b"b\nc\n");
+        assert_eq!(last_lines(b"a\nb\nc", 1, false), b"c");
+        assert_eq!(last_lines(b"a\nb\n", 5, false), b"a\nb\n");
+        // 从文件中间开始读取时, 不完整的第一行被丢弃
+        assert_eq!(last_lines(b"tial\nb\n", 5, true), b"b\n");
+        assert_eq!(last_lines(b"no newline", 5, true), b"no newline");
+    }
+
+    #[test]
+    fn test_viewer_registry() {
+        use crate::config::{Config, ViewerKind, ViewerMapping};
+        use crate::web::viewers::{resolve_viewer, viewer_mappings};
+
+        let builtin = viewer_mappings(&[]);
+        assert_eq!(resolve_viewer(&builtin, "video/mp4"), ViewerKind::Video);
+        assert_eq!(resolve_viewer(&builtin, "text/csv; charset=utf-8"), ViewerKind::Table);
+        assert_eq!(resolve_viewer(&builtin, "Text/Plain"), ViewerKind::Text);
+        assert_eq!(resolve_viewer(&builtin, "application/pdf"), ViewerKind::Pdf);
+        assert_eq!(resolve_viewer(&builtin, "application/octet-stream"), ViewerKind::Hex);
+
+        // 配置的映射优先
+        let configured = [ViewerMapping { pattern: "text/*".to_string(), viewer: ViewerKind::Hex }];
+        let mappings = viewer_mappings(&configured);
+        assert_eq!(resolve_viewer(&mappings, "text/csv"), ViewerKind::Hex);
+        assert_eq!(resolve_viewer(&mappings, "image/png"), ViewerKind::Image);
+
+        let patterns = [
+            ("video/*", true),
+            ("*/*", true),
+            ("application/vnd.ms-excel", true),
+            ("*/json", false),
+            ("video", false),
+        ];
+        for (pattern, valid) in patterns {
+            let mut config = Config::default();
+            config.web.viewers = vec![ViewerMapping { pattern: pattern.to_string(), viewer: ViewerKind::Text }];
+            assert_eq!(config.validate().is_ok(), valid, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        use crate::download::range::parse_range;
+
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(vec![0..=99]));
+        assert_eq!(parse_range("bytes=900-", 1000), Some(vec![900..=999]));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(vec![900..=999]));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some(vec![0..=999]));
+        assert_eq!(parse_range("bytes=0-0, 10-19", 1000), Some(vec![0..=0, 10..=19]));
+        // 超出长度的范围被丢弃, 全部超出时为空
+        assert_eq!(parse_range("bytes=0-9, 2000-", 1000), Some(vec![0..=9]));
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(vec![]));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(vec![]));
+        // 格式无效时忽略
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+        assert_eq!(parse_range(&format!("bytes={}", vec!["0-0"; 33].join(",")), 1000), None);
+    }
+
+    #[test]
+    fn test_parse_probe_output() {
+        use crate::video::metadata::parse_probe_output;
+
+        let info = parse_probe_output(
+            r#"{"programs": [], "streams": [{"width": 1920, "height": 1080}], "format": {"duration": "12.600000"}}"#,
+        )
+        .unwrap();
+        assert_eq!(info.duration(), Some(13));
+        assert_eq!(info.resolution().as_deref(), Some("1920x1080"));
+
+        // 容器没有时长时使用视频流的时长, 纯音频文件没有分辨率
+        let info = parse_probe_output(r#"{"streams": [{"duration": "3.2"}], "format": {"duration": "N/A"}}"#).unwrap();
+        assert_eq!(info.duration(), Some(3));
+        assert_eq!(info.resolution(), None);
+        let info = parse_probe_output(r#"{"streams": [], "format": {}}"#).unwrap();
+        assert_eq!((info.duration(), info.resolution()), (None, None));
+
+        assert!(parse_probe_output("not json").is_err());
+    }
+
+    #[test]
+    fn test_auth_config_validation() {
+        use crate::config::{ApiKeyConfig, KeyAccess};
+
+        let key = |name: &str, key: &str| ApiKeyConfig {
+            name: name.to_string(),
+            key: key.to_string(),
+            key_file: None,
+            key_command: None,
+            access: KeyAccess::Read,
+        };
+        let cases = [
+            (vec![], true),
+            (vec![key("ci", "a"), key("bots", "b")], true),
+            (vec![key("ci", "")], false),
+            (vec![key(" ", "a")], false),
+            (vec![key("ci", "a"), key("ci", "b")], false),
+            (vec![key("ci", "a"), key("bots", "a")], false),
+        ];
+        for (keys, valid) in cases {
+            let mut config = Config::default();
+            config.auth.keys = keys;
+            assert_eq!(config.validate().is_ok(), valid);
+            assert_eq!(config.auth.enabled(), !config.auth.keys.is_empty());
+        }
+
+        let parsed: ApiKeyConfig = serde_json::from_str(r#"{"name": "ci", "key": "k"}"#).unwrap();
+        assert_eq!(parsed.access, KeyAccess::ReadWrite);
+        let parsed: ApiKeyConfig = serde_json::from_str(r#"{"name": "ci", "key": "k", "access": "read"}"#).unwrap();
+        assert_eq!(parsed.access, KeyAccess::Read);
+        let parsed: ApiKeyConfig = serde_json::from_str(r#"{"name": "ops", "key": "k", "access": "admin"}"#).unwrap();
+        assert_eq!(parsed.access, KeyAccess::Admin);
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        use crate::config::{ApiKeyConfig, KeyAccess};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key_file = temp_dir.path().join("ci.key");
+        std::fs::write(&key_file, "from-file\n").unwrap();
+
+        let key = |key: &str, key_file: Option<&std::path::Path>, key_command: Option<&[&str]>| ApiKeyConfig {
+            name: "ci".to_string(),
+            key: key.to_string(),
+            key_file: key_file.map(|path| path.to_path_buf()),
+            key_command: key_command.map(|command| command.iter().map(|arg| arg.to_string()).collect()),
+            access: KeyAccess::ReadWrite,
+        };
+        let resolve = |api_key: ApiKeyConfig| {
+            let mut config = Config::default();
+            config.auth.keys = vec![api_key];
+            config.resolve_secrets().map(|_| config.auth.keys[0].key.clone())
+        };
+
+        // 末尾换行被去掉, 直接配置的密钥保持不变
+        assert_eq!(resolve(key("", Some(&key_file), None)).unwrap(), "from-file");
+        assert_eq!(resolve(key("inline", None, None)).unwrap(), "inline");
+
+        // 只能设置一个来源, 文件不存在时报错
+        assert!(resolve(key("inline", Some(&key_file), None)).is_err());
+        assert!(resolve(key("", Some(&key_file), Some(&["echo", "x"]))).is_err());
+        assert!(resolve(key("", Some(&temp_dir.path().join("missing")), None)).is_err());
+        assert!(resolve(key("", None, Some(&[]))).is_err());
+
+        #[cfg(unix)]
+        {
+            assert_eq!(resolve(key("", None, Some(&["echo", "from-command"]))).unwrap(), "from-command");
+            assert!(resolve(key("", None, Some(&["false"]))).is_err());
+        }
+    }
+
+    #[test]
+    fn test_mount_routes_reserved() {
+        use crate::config::MountConfig;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let validate = |route: &str| {
+            let mut config = Config::default();
+            config.storage.path = temp_dir.path().join("storage");
+            config.storage.upload_dir = temp_dir.path().join("uploads");
+            config.web.mounts.push(MountConfig {
+                route: route.to_string(),
+                path: temp_dir.path().to_path_buf(),
+                read_only: true,
+            });
+            config.validate()
+        };
+
+        // 与内置网页界面的路由重叠时启动会失败, 在验证阶段拒绝
+        assert!(validate("/ui").is_err());
+        assert!(validate("/ui/docs").is_err());
+        assert!(validate("/api").is_err());
+        assert!(validate("/ui-docs").is_ok());
+    }
+}
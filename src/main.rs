@@ -1,22 +1,141 @@
-use rust_internal_file_server::config::Config;
+use clap::{Parser, Subcommand};
+use rust_internal_file_server::check::{run_checks, CheckOptions};
+use rust_internal_file_server::config::{Config, ProfilingConfig};
+use rust_internal_file_server::seed::{seed_files, SeedOptions};
 use rust_internal_file_server::server::start_server;
+use rust_internal_file_server::storage::FileManager;
 use rust_internal_file_server::Result;
 use tracing::info;
-use tracing_subscriber;
+
+#[derive(Parser)]
+#[command(name = "file-server", version, about = "基于Rust的内网文件共享服务器")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "启动服务器 (默认)")]
+    Serve,
+    #[command(about = "部署前自检, 有检查失败时以非零状态退出")]
+    Check {
+        #[arg(long, default_value_t = 1024, help = "存储目录所在磁盘的最小可用空间 (MB)")]
+        min_free_mb: u64,
+        #[arg(long, help = "警告也视为失败")]
+        strict: bool,
+    },
+    #[command(about = "为开发环境生成测试文件, 相同种子重复执行不会重复生成")]
+    Seed {
+        #[arg(long, default_value_t = 100, help = "生成的文件数")]
+        count: usize,
+        #[arg(long, default_value_t = 1, help = "随机种子")]
+        seed: u64,
+        #[arg(long, default_value_t = 4096, help = "单个文件的最大大小 (KB)")]
+        max_size_kb: u64,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 初始化日志
-    tracing_subscriber::fmt::init();
-
-    info!("启动内网文件服务器...");
+    let cli = Cli::parse();
+    if let Some(Command::Check { min_free_mb, strict }) = cli.command {
+        tracing_subscriber::fmt::init();
+        std::process::exit(check(min_free_mb, strict).await);
+    }
+    if let Some(Command::Seed { count, seed, max_size_kb }) = cli.command {
+        tracing_subscriber::fmt::init();
+        let options = SeedOptions {
+            count,
+            seed,
+            max_size: max_size_kb.max(1) * 1024,
+        };
+        return seed_dev_data(options).await;
+    }
 
-    // 加载配置
+    // 加载配置, 日志初始化依赖其中的诊断配置
     let config = Config::load()?;
+    init_tracing(&config.profiling);
+
+    info!("启动内网文件服务器...");
     info!("配置加载完成: {}", config.server.address);
 
     // 启动服务器
     start_server(config).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// 初始化日志; 以 tokio-console 特性编译且配置启用时同时开放 tokio-console 连接
+fn init_tracing(profiling: &ProfilingConfig) {
+    #[cfg(feature = "tokio-console")]
+    if profiling.tokio_console {
+        use tracing_subscriber::prelude::*;
+
+        // 地址已在配置校验时检查过
+        let mut builder = console_subscriber::ConsoleLayer::builder();
+        if let Ok(address) = profiling.console_address.parse::<std::net::SocketAddr>() {
+            builder = builder.server_addr(address);
+        }
+        let console = builder.spawn();
+        tracing_subscriber::registry()
+            .with(console)
+            .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::EnvFilter::from_default_env()))
+            .init();
+        info!("tokio-console 监听地址: {}", profiling.console_address);
+        return;
+    }
+
+    #[cfg(not(feature = "tokio-console"))]
+    if profiling.tokio_console {
+        tracing_subscriber::fmt::init();
+        tracing::warn!("配置启用了 tokio-console, 但程序未以 tokio-console 特性编译");
+        return;
+    }
+
+    tracing_subscriber::fmt::init();
+}
+
+// 执行自检并打印报告, 返回进程退出码
+async fn check(min_free_mb: u64, strict: bool) -> i32 {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("[FAIL] 配置: {}", e);
+            return 1;
+        }
+    };
+
+    let options = CheckOptions {
+        min_free_space: min_free_mb * 1024 * 1024,
+        strict,
+    };
+    let report = run_checks(&config, &options).await;
+    print!("{}", report);
+
+    if report.passed(options.strict) {
+        println!("自检通过");
+        0
+    } else {
+        println!("自检失败");
+        1
+    }
+}
+
+// 生成测试文件并打印统计
+async fn seed_dev_data(options: SeedOptions) -> Result<()> {
+    let config = Config::load()?;
+    let file_manager = FileManager::new(&config.database.database_url(), config.storage.upload_dir.clone())
+        .await?
+        .with_naming_policy(config.storage.naming.policy);
+
+    let report = seed_files(&file_manager, &options).await?;
+    println!(
+        "生成 {} 个文件 ({} 字节), 跳过已存在的 {} 个",
+        report.created, report.bytes, report.skipped
+    );
+    for (kind, count) in &report.kinds {
+        println!("  {}: {}", kind, count);
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+// 内存预算 - 限制归档目录、图片解码和 OCR 输出等需要整体读入内存的操作, 超出时拒绝并计数
+use crate::config::MemoryConfig;
+use crate::error::{Result, ServerError};
+use crate::server::{ApiResponse, AppState};
+use axum::{extract::State, response::Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetKind {
+    // zip 中央目录和 tar 成员列表
+    ArchiveIndex,
+    // 解码后的图片像素
+    ImageDecode,
+    // OCR 命令的输出
+    OcrOutput,
+}
+
+impl BudgetKind {
+    const ALL: [BudgetKind; 3] = [BudgetKind::ArchiveIndex, BudgetKind::ImageDecode, BudgetKind::OcrOutput];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetKind::ArchiveIndex => "archive_index",
+            BudgetKind::ImageDecode => "image_decode",
+            BudgetKind::OcrOutput => "ocr_output",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStats {
+    pub limit: u64,
+    // 启动以来通过检查的最大申请量
+    pub largest_accepted: u64,
+    pub rejected: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    largest_accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct MemoryBudgets {
+    config: MemoryConfig,
+    counters: [Counters; 3],
+}
+
+impl MemoryBudgets {
+    pub fn new(config: MemoryConfig) -> Self {
+        Self {
+            config,
+            counters: Default::default(),
+        }
+    }
+
+    pub fn limit(&self, kind: BudgetKind) -> u64 {
+        match kind {
+            BudgetKind::ArchiveIndex => self.config.archive_index,
+            BudgetKind::ImageDecode => self.config.image_decode,
+            BudgetKind::OcrOutput => self.config.ocr_output,
+        }
+    }
+
+    // 检查一次操作预计占用的内存, 超出预算时返回 413
+    pub fn check(&self, kind: BudgetKind, bytes: u64) -> Result<()> {
+        let counters = &self.counters[kind.index()];
+        let limit = self.limit(kind);
+        if bytes > limit {
+            counters.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(ServerError::payload_too_large(format!(
+                "{} 需要约 {} 字节内存, 超过预算 {} 字节",
+                kind.as_str(),
+                bytes,
+                limit
+            )));
+        }
+
+        counters.largest_accepted.fetch_max(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, BudgetStats> {
+        BudgetKind::ALL
+            .iter()
+            .map(|kind| {
+                let counters = &self.counters[kind.index()];
+                let stats = BudgetStats {
+                    limit: self.limit(*kind),
+                    largest_accepted: counters.largest_accepted.load(Ordering::Relaxed),
+                    rejected: counters.rejected.load(Ordering::Relaxed),
+                };
+                (kind.as_str().to_string(), stats)
+            })
+            .collect()
+    }
+}
+
+// 各项内存预算的上限和拒绝次数
+pub async fn get_memory_budgets(State(state): State<AppState>) -> Json<ApiResponse<BTreeMap<String, BudgetStats>>> {
+    Json(ApiResponse::success(state.memory.stats()))
+}
@@ -0,0 +1,288 @@
+// 请求中间件 - API 密钥认证、外部授权、只读模式、管理接口访问控制和缓存策略
+use crate::config::{ApiKeyConfig, KeyAccess};
+use crate::error::ServerError;
+use crate::policy::{PolicyAction, PolicyInput, PolicyResource};
+use crate::server::{api_error, AppState};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tracing::warn;
+
+// 认证通过的密钥, 放在请求扩展中供后续中间件和处理函数使用
+#[derive(Debug, Clone)]
+pub struct ApiKeyIdentity {
+    pub name: String,
+    pub access: KeyAccess,
+}
+
+// 需要密钥的地址: 接口, 以及按 ID、哈希返回文件内容的地址. 配置的目录挂载另外判断
+const PROTECTED_PREFIXES: [&str; 3] = ["/api/", "/files/", "/paste/"];
+
+// 网页界面保存密钥的 Cookie. 图片、视频和下载链接由浏览器直接加载, 无法设置请求头
+pub const API_KEY_COOKIE: &str = "file_server_api_key";
+
+// 配置了 API 密钥时, 接口和文件内容必须通过 Authorization: Bearer <密钥> 或 X-API-Key 携带密钥.
+// GET/HEAD 请求也接受 Cookie 中的密钥, 修改类请求不接受, 避免跨站请求伪造
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let auth = &state.config.auth;
+    if !auth.enabled() || !is_protected(&state, request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let error = match presented_key(request.method(), request.headers()) {
+        None => ServerError::unauthorized("缺少 API 密钥"),
+        Some(presented) => match find_key(&auth.keys, &presented) {
+            Some(key) => {
+                request.extensions_mut().insert(ApiKeyIdentity { name: key.name.clone(), access: key.access });
+                return next.run(request).await;
+            }
+            None => ServerError::unauthorized("API 密钥无效"),
+        },
+    };
+
+    let mut response = api_error("请求被拒绝", error).into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+// 挂载目录的根路径和其下的所有路径都需要密钥
+fn is_protected(state: &AppState, path: &str) -> bool {
+    PROTECTED_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+        || state.config.web.mounts.iter().any(|mount| {
+            path.strip_prefix(mount.route.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+}
+
+fn presented_key<'a>(method: &Method, headers: &'a HeaderMap) -> Option<Cow<'a, [u8]>> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|key| key.trim().as_bytes());
+    let header_key = bearer.or_else(|| headers.get("x-api-key").map(HeaderValue::as_bytes));
+    if header_key.is_some() || !(method == Method::GET || method == Method::HEAD) {
+        return header_key.map(Cow::Borrowed);
+    }
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(API_KEY_COOKIE)?.strip_prefix('='))
+        .map(|value| Cow::Owned(percent_decode(value)))
+}
+
+// 网页界面用 encodeURIComponent 写入 Cookie, 这里还原为原始字节
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+// 逐个比较全部密钥, 比较时间与密钥内容无关
+fn find_key<'a>(keys: &'a [ApiKeyConfig], presented: &[u8]) -> Option<&'a ApiKeyConfig> {
+    keys.iter().fold(None, |found, key| {
+        let expected = key.key.as_bytes();
+        let same_len = expected.len() == presented.len();
+        let diff = expected.iter().zip(presented).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        if same_len && diff == 0 {
+            Some(key)
+        } else {
+            found
+        }
+    })
+}
+
+// 修改类接口和管理接口需要读写密钥. 未启用认证时请求中没有密钥信息, 直接放行
+pub async fn require_write_access(request: Request, next: Next) -> Response {
+    let read_only_key = request
+        .extensions()
+        .get::<ApiKeyIdentity>()
+        .is_some_and(|identity| identity.access == KeyAccess::Read);
+    if read_only_key {
+        return api_error(
+            "请求被拒绝",
+            ServerError::permission_denied("只读密钥不能调用修改类接口"),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+// 配置了外部授权服务时, 修改类接口需要授权服务允许
+pub async fn authorize_write(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    authorize(state, PolicyAction::Write, request, next).await
+}
+
+// 配置了外部授权服务时, 管理接口需要授权服务允许
+pub async fn authorize_admin(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    authorize(state, PolicyAction::Admin, request, next).await
+}
+
+async fn authorize(state: AppState, action: PolicyAction, request: Request, next: Next) -> Response {
+    if state.policy.is_none() {
+        return next.run(request).await;
+    }
+
+    let input = PolicyInput {
+        user: request.extensions().get::<ApiKeyIdentity>().map(|identity| identity.name.clone()),
+        action,
+        resource: PolicyResource {
+            method: request.method().to_string(),
+            path: request.uri().path().to_string(),
+        },
+    };
+    match check_policy(&state, &input).await {
+        Ok(()) => next.run(request).await,
+        Err(e) => api_error("请求被拒绝", e).into_response(),
+    }
+}
+
+// 向外部授权服务查询, 未配置授权服务时直接放行. 处理函数内需要更高权限的操作也通过这里查询
+pub(crate) async fn check_policy(state: &AppState, input: &PolicyInput) -> Result<(), ServerError> {
+    let Some(policy) = &state.policy else {
+        return Ok(());
+    };
+
+    match policy.is_allowed(input).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ServerError::permission_denied("授权服务拒绝了该请求")),
+        Err(e) if policy.fail_open() => {
+            warn!("授权服务不可用, 放行 {} {}: {}", input.resource.method, input.resource.path, e);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// 只读模式下拒绝所有修改类接口
+pub async fn reject_when_read_only(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.read_only.load(Ordering::Relaxed) {
+        return api_error(
+            "请求被拒绝",
+            ServerError::permission_denied("服务器处于只读模式"),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+// 管理接口只允许本机访问
+pub async fn require_local_client(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !client.ip().is_loopback() {
+        return api_error(
+            "请求被拒绝",
+            ServerError::permission_denied("管理接口仅允许本机访问"),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheClass {
+    Immutable,
+    Listing,
+    Preview,
+}
+
+// 按路由和响应的 MIME 类型给成功的 GET/HEAD 响应加上 Cache-Control
+pub async fn apply_cache_control(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let cacheable = request.method() == Method::GET || request.method() == Method::HEAD;
+    let path = request.uri().path().to_string();
+    let mut response = next.run(request).await;
+    if !cacheable || !(response.status().is_success() || response.status().as_u16() == 304) {
+        return response;
+    }
+
+    let mime = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    let Some(class) = cache_class(&path, mime) else {
+        return response;
+    };
+
+    let policies = &state.config.web.cache_control;
+    let policy = match class {
+        CacheClass::Immutable => &policies.immutable,
+        CacheClass::Listing => &policies.listing,
+        CacheClass::Preview => &policies.preview,
+    };
+    if policy.is_empty() {
+        return response;
+    }
+    if let Ok(value) = HeaderValue::from_str(policy) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
+
+// 先按路由判断, 其余接口按 MIME 类型: JSON 和订阅视为列表, 图片、音视频视为预览
+pub fn cache_class(path: &str, mime: &str) -> Option<CacheClass> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["files", "by-hash", _] => return Some(CacheClass::Immutable),
+        ["api", "files", _, "thumbnail" | "image" | "text"] => return Some(CacheClass::Preview),
+        ["api", "files", _, "share", "qr"] => return Some(CacheClass::Preview),
+        ["api", "files", _, "archive", "entries", _, ..] => return Some(CacheClass::Preview),
+        ["paste", _] => return Some(CacheClass::Preview),
+        _ => {}
+    }
+
+    let mime = mime.split(';').next().unwrap_or("").trim();
+    if mime == "application/json" || mime == "application/atom+xml" || mime == "text/csv" {
+        Some(CacheClass::Listing)
+    } else if mime.starts_with("image/") || mime.starts_with("video/") || mime.starts_with("audio/") {
+        Some(CacheClass::Preview)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,98 @@
+// 外部授权 - 修改类接口和管理接口的授权决策交给外部策略服务 (如 OPA), 决策结果按请求缓存
+use crate::config::PolicyConfig;
+use crate::error::{Result, ServerError};
+use mini_moka::sync::Cache;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    // 修改文件、上传等修改类接口
+    Write,
+    // 管理接口
+    Admin,
+}
+
+// 发送给授权服务的 input, 也是决策缓存的键
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct PolicyInput {
+    // 认证通过的 API 密钥名称, 未启用密钥认证时为空
+    pub user: Option<String>,
+    pub action: PolicyAction,
+    pub resource: PolicyResource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct PolicyResource {
+    pub method: String,
+    pub path: String,
+}
+
+pub struct PolicyClient {
+    config: PolicyConfig,
+    client: reqwest::Client,
+    decisions: Option<Cache<PolicyInput, bool>>,
+}
+
+impl PolicyClient {
+    pub fn new(config: PolicyConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .build()
+            .map_err(|e| ServerError::validation(format!("无法创建授权服务客户端: {}", e)))?;
+        let decisions = (config.cache_ttl > 0 && config.cache_max_entries > 0).then(|| {
+            Cache::builder()
+                .max_capacity(config.cache_max_entries)
+                .time_to_live(Duration::from_secs(config.cache_ttl))
+                .build()
+        });
+        Ok(Self { config, client, decisions })
+    }
+
+    pub fn fail_open(&self) -> bool {
+        self.config.fail_open
+    }
+
+    // 允许和拒绝的决策都会缓存, 查询失败不缓存
+    pub async fn is_allowed(&self, input: &PolicyInput) -> Result<bool> {
+        if let Some(allowed) = self.decisions.as_ref().and_then(|decisions| decisions.get(input)) {
+            return Ok(allowed);
+        }
+
+        let allowed = self.query(input).await?;
+        if let Some(decisions) = &self.decisions {
+            decisions.insert(input.clone(), allowed);
+        }
+        Ok(allowed)
+    }
+
+    async fn query(&self, input: &PolicyInput) -> Result<bool> {
+        let response = self
+            .client
+            .post(&self.config.url)
+            .json(&json!({ "input": input }))
+            .send()
+            .await
+            .map_err(|e| ServerError::service_unavailable(format!("无法连接授权服务: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ServerError::service_unavailable(format!("授权服务返回 {}", status)));
+        }
+
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| ServerError::service_unavailable(format!("授权服务的响应无效: {}", e)))?;
+        Ok(decision(&body))
+    }
+}
+
+// 接受 {"result": true} 或 {"result": {"allow": true}}. 没有 result 表示策略未定义, 按拒绝处理
+fn decision(body: &Value) -> bool {
+    match &body["result"] {
+        Value::Bool(allowed) => *allowed,
+        result => result["allow"].as_bool().unwrap_or(false),
+    }
+}
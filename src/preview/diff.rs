@@ -0,0 +1,107 @@
+// 文本对比 - 在服务端生成两个文本文件的统一格式差异
+use crate::download::canary::{self, DownloadContext};
+use crate::error::ServerError;
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+// 参与对比的单个文件的最大大小
+const MAX_DIFF_FILE_SIZE: i64 = 2 * 1024 * 1024;
+
+// 上下文行数的默认值和上限
+const DEFAULT_CONTEXT: usize = 3;
+const MAX_CONTEXT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub a: String,
+    pub b: String,
+    pub context: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffResponse {
+    pub a: String,
+    pub b: String,
+    pub identical: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    // 统一格式差异, 内容相同时为空
+    pub diff: String,
+}
+
+// 对比两个文本文件
+pub async fn diff_files(
+    Query(params): Query<DiffQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Json<ApiResponse<DiffResponse>>, ApiError> {
+    const CONTEXT: &str = "对比文件失败";
+
+    let context = params.context.unwrap_or(DEFAULT_CONTEXT);
+    if context > MAX_CONTEXT {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("上下文行数不能超过 {}", MAX_CONTEXT)),
+        ));
+    }
+
+    let a = get_downloadable_file(&state, &params.a, CONTEXT).await?;
+    let b = get_downloadable_file(&state, &params.b, CONTEXT).await?;
+    canary::check_download(&state, &a, &download).await;
+    canary::check_download(&state, &b, &download).await;
+    let old = read_text(&a).await.map_err(|e| api_error(CONTEXT, e))?;
+    let new = read_text(&b).await.map_err(|e| api_error(CONTEXT, e))?;
+
+    let response = tokio::task::spawn_blocking(move || {
+        let diff = TextDiff::from_lines(&old, &new);
+        let (mut additions, mut deletions) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => additions += 1,
+                ChangeTag::Delete => deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+
+        let unified = diff
+            .unified_diff()
+            .context_radius(context)
+            .header(&a.original_name, &b.original_name)
+            .to_string();
+
+        DiffResponse {
+            a: a.id,
+            b: b.id,
+            identical: additions == 0 && deletions == 0,
+            additions,
+            deletions,
+            diff: unified,
+        }
+    })
+    .await
+    .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?;
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+// 读取文件内容, 超过大小上限或不是 UTF-8 文本时返回验证错误
+async fn read_text(record: &FileRecord) -> crate::Result<String> {
+    if record.file_size > MAX_DIFF_FILE_SIZE {
+        return Err(ServerError::validation(format!(
+            "文件 {} 超过对比大小上限 {} 字节",
+            record.id, MAX_DIFF_FILE_SIZE
+        )));
+    }
+
+    let bytes = tokio::fs::read(&record.file_path).await.map_err(ServerError::Io)?;
+    match String::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => Ok(text),
+        _ => Err(ServerError::validation(format!("文件 {} 不是文本文件", record.id))),
+    }
+}
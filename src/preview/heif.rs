@@ -0,0 +1,134 @@
+// HEIC/HEIF 兼容 - 调用 heif-convert 生成 JPEG 副本, 供预览和下载使用, 原文件保持不变
+use crate::config::HeifConfig;
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{FileQuery, FileRecord, FileSearch};
+use axum::{extract::State, response::Json};
+use serde::Serialize;
+use std::path::{Path as FsPath, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+
+// 同时覆盖 image/heic、image/heif 及其 -sequence 变体
+const HEIF_MIME_PREFIX: &str = "image/hei";
+
+#[derive(Debug, Serialize)]
+pub struct HeifReindexResult {
+    pub scanned: usize,
+    pub converted: usize,
+    pub failed: usize,
+}
+
+pub fn is_heif(record: &FileRecord) -> bool {
+    matches!(
+        record.mime_type.as_str(),
+        "image/heic" | "image/heif" | "image/heic-sequence" | "image/heif-sequence"
+    )
+}
+
+// 图片预览使用的源文件: 启用转换时 HEIF 图片使用 JPEG 副本, 其余使用原文件
+pub async fn preview_source(state: &AppState, record: &FileRecord) -> Result<PathBuf> {
+    if state.config.image.heif.enabled && is_heif(record) {
+        return ensure_jpeg(state, record).await;
+    }
+    Ok(PathBuf::from(&record.file_path))
+}
+
+// 返回 JPEG 副本的路径, 尚未生成时先转换
+pub async fn ensure_jpeg(state: &AppState, record: &FileRecord) -> Result<PathBuf> {
+    let target = jpeg_path(state, record)?;
+    if target.exists() {
+        return Ok(target);
+    }
+
+    // heif-convert 按输出文件的扩展名选择格式
+    let temp_path = state.temp.path("heif")?.with_extension("jpg");
+    let result = async {
+        convert_to_jpeg(&state.config.image.heif, FsPath::new(&record.file_path), &temp_path).await?;
+        tokio::fs::rename(&temp_path, &target).await.map_err(ServerError::Io)
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+    Ok(target)
+}
+
+// 缓存文件名带上版本号, 替换内容后不会读到旧的副本
+fn jpeg_path(state: &AppState, record: &FileRecord) -> Result<PathBuf> {
+    let dir = state.file_manager.cache_dir("heif")?;
+    Ok(dir.join(format!("{}-v{}.jpg", record.id, record.version)))
+}
+
+pub async fn convert_to_jpeg(config: &HeifConfig, source: &FsPath, target: &FsPath) -> Result<()> {
+    let mut command = Command::new(&config.command);
+    command
+        .arg("-q")
+        .arg(config.quality.to_string())
+        .arg(source)
+        .arg(target)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(Duration::from_secs(config.timeout), command.output())
+        .await
+        .map_err(|_| ServerError::file_operation("HEIF 转换超时"))?
+        .map_err(|e| ServerError::file_operation(format!("无法启动 HEIF 转换命令: {}", e)))?;
+    if !output.status.success() {
+        return Err(ServerError::file_operation(format!(
+            "HEIF 转换失败: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    if !target.is_file() {
+        return Err(ServerError::file_operation("HEIF 转换命令没有生成输出文件"));
+    }
+    Ok(())
+}
+
+// 为所有 HEIF 图片生成 JPEG 副本, 逐张处理以免同时启动过多进程
+pub async fn reindex_heif(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<HeifReindexResult>>, ApiError> {
+    const CONTEXT: &str = "生成 HEIF 兼容副本失败";
+
+    if !state.config.image.heif.enabled {
+        return Err(api_error(CONTEXT, ServerError::validation("HEIF 转换未启用")));
+    }
+
+    let file_query = FileQuery {
+        search: FileSearch {
+            mime_type: Some(HEIF_MIME_PREFIX.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let records: Vec<FileRecord> = state
+        .files
+        .query_files(&file_query)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .filter(is_heif)
+        .collect();
+
+    let mut result = HeifReindexResult { scanned: records.len(), converted: 0, failed: 0 };
+    for record in records {
+        let existing = jpeg_path(&state, &record).map_err(|e| api_error(CONTEXT, e))?;
+        if existing.exists() {
+            continue;
+        }
+        match ensure_jpeg(&state, &record).await {
+            Ok(_) => result.converted += 1,
+            Err(e) => {
+                warn!("图片 {} 生成 JPEG 副本失败: {}", record.id, e);
+                result.failed += 1;
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(result)))
+}
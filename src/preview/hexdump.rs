@@ -0,0 +1,107 @@
+// 十六进制预览 - 按 hexdump -C 的格式显示文件中的一段字节, 排查固件和损坏的上传时不用下载整个文件
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+const DEFAULT_LENGTH: u64 = 512;
+const MAX_LENGTH: u64 = 64 * 1024;
+
+// 每行显示的字节数
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Deserialize)]
+pub struct HexdumpQuery {
+    #[serde(default)]
+    pub offset: u64,
+    pub length: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hexdump {
+    pub file_id: String,
+    pub file_size: u64,
+    pub offset: u64,
+    // 实际读取的字节数, 到达文件末尾时小于请求的长度
+    pub length: u64,
+    pub dump: String,
+}
+
+// 返回文件中 offset 开始的 length 个字节的十六进制视图
+pub async fn hexdump_file(
+    Path(file_id): Path<String>,
+    Query(params): Query<HexdumpQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Json<ApiResponse<Hexdump>>, ApiError> {
+    const CONTEXT: &str = "生成十六进制预览失败";
+
+    let length = params.length.unwrap_or(DEFAULT_LENGTH);
+    if !(1..=MAX_LENGTH).contains(&length) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("长度必须在 1-{} 之间", MAX_LENGTH)),
+        ));
+    }
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    let path = PathBuf::from(&record.file_path);
+    let offset = params.offset;
+    let (file_size, bytes) = tokio::task::spawn_blocking(move || read_range(&path, offset, length))
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(Json(ApiResponse::success(Hexdump {
+        file_id: record.id,
+        file_size,
+        offset,
+        length: bytes.len() as u64,
+        dump: format_hexdump(&bytes, offset),
+    })))
+}
+
+// 按实际的文件大小检查偏移量, 记录中的大小可能与磁盘上的文件不一致
+fn read_range(path: &std::path::Path, offset: u64, length: u64) -> Result<(u64, Vec<u8>)> {
+    let mut file = std::fs::File::open(path).map_err(ServerError::Io)?;
+    let file_size = file.metadata().map_err(ServerError::Io)?.len();
+    if offset > file_size || (offset == file_size && file_size > 0) {
+        return Err(ServerError::validation(format!("偏移量 {} 超出文件大小 {} 字节", offset, file_size)));
+    }
+
+    file.seek(SeekFrom::Start(offset)).map_err(ServerError::Io)?;
+    let mut bytes = Vec::new();
+    file.take(length).read_to_end(&mut bytes).map_err(ServerError::Io)?;
+    Ok((file_size, bytes))
+}
+
+// 每行: 8 位十六进制偏移量, 两组各 8 个字节, 以及可打印的 ASCII 字符
+pub fn format_hexdump(bytes: &[u8], offset: u64) -> String {
+    let mut dump = String::new();
+    for (index, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let _ = write!(dump, "{:08x} ", offset + (index * BYTES_PER_LINE) as u64);
+        for column in 0..BYTES_PER_LINE {
+            if column % 8 == 0 {
+                dump.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(dump, "{:02x} ", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str(" |");
+        dump.extend(line.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }));
+        dump.push_str("|\n");
+    }
+    dump
+}
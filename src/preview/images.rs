@@ -0,0 +1,332 @@
+// 图片缩放代理 - 按需缩放或裁剪图片, 结果缓存在磁盘上
+use crate::config::ThumbnailFormat;
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::memory::{BudgetKind, MemoryBudgets};
+use crate::preview::heif;
+use crate::preview::thumbnails::ThumbnailTarget;
+use crate::server::{api_error, get_downloadable_file, ApiError, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use image::codecs::{jpeg::JpegEncoder, webp::WebPEncoder};
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageReader, Limits};
+use serde::Deserialize;
+use std::path::Path as FsPath;
+
+// 输出图片的最大边长
+const MAX_DIMENSION: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    // 等比缩放并裁剪, 填满目标尺寸
+    Cover,
+    // 等比缩放, 完整放入目标尺寸
+    #[default]
+    Contain,
+    // 拉伸到目标尺寸
+    Fill,
+}
+
+impl FitMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cover => "cover",
+            Self::Contain => "contain",
+            Self::Fill => "fill",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResizeQuery {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    #[serde(default)]
+    pub fit: FitMode,
+}
+
+// 缩放图片接口
+pub async fn resize_image(
+    Path(file_id): Path<String>,
+    Query(params): Query<ResizeQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "图片缩放失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    if !record.mime_type.starts_with("image/") {
+        return Err(api_error(CONTEXT, ServerError::validation("文件不是图片")));
+    }
+
+    if params.w.is_none() && params.h.is_none() {
+        return Err(api_error(CONTEXT, ServerError::validation("宽度和高度至少需要指定一个")));
+    }
+    let in_range = |d: Option<u32>| d.is_none_or(|d| (1..=MAX_DIMENSION).contains(&d));
+    if !in_range(params.w) || !in_range(params.h) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("图片尺寸必须在 1-{} 之间", MAX_DIMENSION)),
+        ));
+    }
+
+    // JPEG 和 HEIF 源图输出 JPEG, 其他格式输出 PNG 以保留透明通道
+    let format = if record.mime_type == "image/jpeg" || heif::is_heif(&record) {
+        ImageFormat::Jpeg
+    } else {
+        ImageFormat::Png
+    };
+
+    let cache_dir = state
+        .file_manager
+        .cache_dir("images")
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let cache_path = cache_dir.join(format!(
+        "{}-v{}-{}x{}-{}.{}",
+        record.id,
+        record.version,
+        params.w.unwrap_or(0),
+        params.h.unwrap_or(0),
+        params.fit.as_str(),
+        format.extensions_str()[0],
+    ));
+
+    if !cache_path.exists() {
+        let source = heif::preview_source(&state, &record)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?;
+        let target = cache_path.clone();
+        let temp_path = state.temp.path("images").map_err(|e| api_error(CONTEXT, e))?;
+        let budgets = state.memory.clone();
+        tokio::task::spawn_blocking(move || {
+            let size = (params.w, params.h);
+            let result = render_resized(&source, &target, &temp_path, size, params.fit, format, &budgets);
+            if result.is_err() {
+                let _ = std::fs::remove_file(&temp_path);
+            }
+            result
+        })
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+    }
+
+    let bytes = tokio::fs::read(&cache_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+
+    Ok(([(header::CONTENT_TYPE, format.to_mime_type())], bytes).into_response())
+}
+
+// 格式转换输出 JPEG 时的质量
+const CONVERT_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ConvertFormat {
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            Self::Png => ImageFormat::Png,
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertQuery {
+    pub format: ConvertFormat,
+}
+
+// 图片格式转换接口, 尺寸不变
+pub async fn convert_image(
+    Path(file_id): Path<String>,
+    Query(params): Query<ConvertQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "图片格式转换失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    if !record.mime_type.starts_with("image/") {
+        return Err(api_error(CONTEXT, ServerError::validation("文件不是图片")));
+    }
+
+    let format = params.format.image_format();
+    let cache_dir = state
+        .file_manager
+        .cache_dir("images")
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let cache_path = cache_dir.join(format!(
+        "{}-v{}-convert.{}",
+        record.id,
+        record.version,
+        format.extensions_str()[0],
+    ));
+
+    if !cache_path.exists() {
+        let source = heif::preview_source(&state, &record)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?;
+        let target = cache_path.clone();
+        let temp_path = state.temp.path("images").map_err(|e| api_error(CONTEXT, e))?;
+        let budgets = state.memory.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = render_converted(&source, &target, &temp_path, format, &budgets);
+            if result.is_err() {
+                let _ = std::fs::remove_file(&temp_path);
+            }
+            result
+        })
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+    }
+
+    let bytes = tokio::fs::read(&cache_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+
+    Ok(([(header::CONTENT_TYPE, format.to_mime_type())], bytes).into_response())
+}
+
+// 解码后按目标格式重新编码, 先写临时文件再重命名
+fn render_converted(
+    source: &FsPath,
+    target: &FsPath,
+    temp_path: &FsPath,
+    format: ImageFormat,
+    budgets: &MemoryBudgets,
+) -> Result<()> {
+    let img = decode_within_budget(source, budgets)?;
+
+    let mut file = std::io::BufWriter::new(std::fs::File::create(temp_path).map_err(ServerError::Io)?);
+    let encoded = match format {
+        // JPEG 不支持透明通道
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut file, CONVERT_QUALITY)),
+        // 只有无损 WebP 编码器可用
+        ImageFormat::WebP => img.write_with_encoder(WebPEncoder::new_lossless(&mut file)),
+        _ => img.write_to(&mut file, format),
+    };
+    encoded.map_err(|e| ServerError::file_operation(format!("无法编码图片: {}", e)))?;
+    file.into_inner()
+        .map_err(|e| ServerError::Io(e.into_error()))?
+        .sync_all()
+        .map_err(ServerError::Io)?;
+
+    std::fs::rename(temp_path, target).map_err(ServerError::Io)?;
+    Ok(())
+}
+
+// 按图片头中的尺寸检查解码预算, 解码器本身也限制在预算内, 防止尺寸信息作假
+pub fn decode_within_budget(source: &FsPath, budgets: &MemoryBudgets) -> Result<DynamicImage> {
+    let decode_error = |e: image::ImageError| ServerError::validation(format!("无法解码图片: {}", e));
+
+    let mut reader = ImageReader::open(source)
+        .map_err(ServerError::Io)?
+        .with_guessed_format()
+        .map_err(ServerError::Io)?;
+    let (width, height) = reader.into_dimensions().map_err(decode_error)?;
+    // 按每像素 4 字节 (RGBA8) 估算
+    budgets.check(BudgetKind::ImageDecode, u64::from(width) * u64::from(height) * 4)?;
+
+    reader = ImageReader::open(source)
+        .map_err(ServerError::Io)?
+        .with_guessed_format()
+        .map_err(ServerError::Io)?;
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(budgets.limit(BudgetKind::ImageDecode));
+    reader.limits(limits);
+    reader.decode().map_err(decode_error)
+}
+
+// 解码、缩放并写入缓存文件, 先写临时文件再重命名, 避免并发请求读到半成品
+fn render_resized(
+    source: &FsPath,
+    target: &FsPath,
+    temp_path: &FsPath,
+    (width, height): (Option<u32>, Option<u32>),
+    fit: FitMode,
+    format: ImageFormat,
+    budgets: &MemoryBudgets,
+) -> Result<()> {
+    let img = decode_within_budget(source, budgets)?;
+
+    let (width, height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, scale_dimension(img.height(), w, img.width())),
+        (None, Some(h)) => (scale_dimension(img.width(), h, img.height()), h),
+        (None, None) => (img.width(), img.height()),
+    };
+
+    let resized = match fit {
+        FitMode::Cover => img.resize_to_fill(width, height, FilterType::CatmullRom),
+        FitMode::Contain => img.resize(width, height, FilterType::CatmullRom),
+        FitMode::Fill => img.resize_exact(width, height, FilterType::CatmullRom),
+    };
+
+    // JPEG 不支持透明通道
+    let resized = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(resized.to_rgb8()),
+        _ => resized,
+    };
+
+    resized
+        .save_with_format(temp_path, format)
+        .map_err(|e| ServerError::file_operation(format!("无法编码图片: {}", e)))?;
+    std::fs::rename(temp_path, target).map_err(ServerError::Io)?;
+
+    Ok(())
+}
+
+// 只解码一次, 生成全部缩略图. 出错时删除当前这张的临时文件, 已生成的由调用方清理
+pub fn render_thumbnails(source: &FsPath, targets: &[ThumbnailTarget], budgets: &MemoryBudgets) -> Result<()> {
+    let img = decode_within_budget(source, budgets)?;
+    for target in targets {
+        let result = write_thumbnail(&img, target);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&target.temp_path);
+        }
+        result?;
+    }
+    Ok(())
+}
+
+// 生成不超过目标尺寸的等比缩略图, 先写临时文件再重命名
+fn write_thumbnail(img: &DynamicImage, target: &ThumbnailTarget) -> Result<()> {
+    let (width, height) = target.size;
+    let thumbnail = img.thumbnail(width, height);
+
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&target.temp_path).map_err(ServerError::Io)?);
+    let encoded = match target.format {
+        ThumbnailFormat::Webp => thumbnail.write_with_encoder(WebPEncoder::new_lossless(&mut file)),
+        ThumbnailFormat::Jpeg => DynamicImage::ImageRgb8(thumbnail.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut file, target.quality)),
+    };
+    encoded.map_err(|e| ServerError::file_operation(format!("无法编码图片: {}", e)))?;
+    file.into_inner()
+        .map_err(|e| ServerError::Io(e.into_error()))?
+        .sync_all()
+        .map_err(ServerError::Io)?;
+
+    std::fs::rename(&target.temp_path, &target.target).map_err(ServerError::Io)?;
+    Ok(())
+}
+
+// 按比例计算另一条边的长度
+fn scale_dimension(other: u32, target: u32, original: u32) -> u32 {
+    ((other as u64 * target as u64) / original.max(1) as u64).max(1) as u32
+}
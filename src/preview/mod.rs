@@ -0,0 +1,11 @@
+// 内容预览模块 - 基于文件内容生成的派生视图
+pub mod diff;
+pub mod heif;
+pub mod hexdump;
+pub mod images;
+pub mod ocr;
+pub mod photos;
+pub mod similar;
+pub mod table;
+pub mod tail;
+pub mod thumbnails;
@@ -0,0 +1,137 @@
+// 图片文字识别 - 调用 tesseract 提取截图中的文字, 写入内容索引
+use crate::config::OcrConfig;
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::memory::{BudgetKind, MemoryBudgets};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileContent;
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use serde::Serialize;
+use std::path::Path as FsPath;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tracing::warn;
+
+// 内容索引中 OCR 结果的来源标记
+pub const OCR_SOURCE: &str = "ocr";
+
+// 失败时错误信息最多保留的字节数
+const STDERR_LIMIT: u64 = 64 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct OcrReindexResult {
+    pub scanned: usize,
+    pub indexed: usize,
+    pub failed: usize,
+}
+
+// 识别图片中的文字, 输出到标准输出. 输出超过 OCR 内存预算时终止命令
+pub async fn recognize_text(config: &OcrConfig, path: &FsPath, budgets: &MemoryBudgets) -> Result<String> {
+    let mut command = Command::new(&config.command);
+    command
+        .arg(path)
+        .arg("stdout")
+        .arg("-l")
+        .arg(config.languages.join("+"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| ServerError::file_operation(format!("无法启动 OCR 命令: {}", e)))?;
+    let limit = budgets.limit(BudgetKind::OcrOutput);
+    let mut stdout = child.stdout.take().expect("stdout 已设置为管道");
+    let mut stderr = child.stderr.take().expect("stderr 已设置为管道");
+
+    let run = async {
+        // 多读一个字节用来判断是否超出预算; stderr 只保留开头, 其余丢弃以免命令阻塞
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let (read, _) = tokio::join!(
+            async { (&mut stdout).take(limit + 1).read_to_end(&mut output).await },
+            async {
+                let _ = (&mut stderr).take(STDERR_LIMIT).read_to_end(&mut errors).await;
+                let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+            }
+        );
+        read.map_err(ServerError::Io)?;
+        budgets.check(BudgetKind::OcrOutput, output.len() as u64)?;
+        let status = child.wait().await.map_err(ServerError::Io)?;
+        Ok::<_, ServerError>((status, output, errors))
+    };
+
+    let (status, output, errors) = tokio::time::timeout(Duration::from_secs(config.timeout), run)
+        .await
+        .map_err(|_| ServerError::file_operation("OCR 超时"))??;
+
+    if !status.success() {
+        return Err(ServerError::file_operation(format!(
+            "OCR 命令执行失败: {}",
+            String::from_utf8_lossy(&errors).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+// 对尚未识别过的图片执行 OCR, 逐张处理以免同时启动过多进程
+pub async fn reindex_ocr(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<OcrReindexResult>>, ApiError> {
+    const CONTEXT: &str = "图片文字识别失败";
+
+    let ocr = &state.config.image.ocr;
+    if !ocr.enabled {
+        return Err(api_error(CONTEXT, ServerError::validation("OCR 未启用")));
+    }
+
+    let records = state
+        .file_manager
+        .list_images_without_content()
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let mut result = OcrReindexResult { scanned: records.len(), indexed: 0, failed: 0 };
+    for record in records {
+        match recognize_text(ocr, FsPath::new(&record.file_path), &state.memory).await {
+            Ok(text) => {
+                state
+                    .file_manager
+                    .save_file_content(&record.id, OCR_SOURCE, &text)
+                    .await
+                    .map_err(|e| api_error(CONTEXT, e))?;
+                result.indexed += 1;
+            }
+            Err(e) => {
+                warn!("图片 {} 文字识别失败: {}", record.id, e);
+                result.failed += 1;
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+// 获取文件提取出的文本
+pub async fn get_file_text(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Json<ApiResponse<FileContent>>, ApiError> {
+    const CONTEXT: &str = "获取文件文本失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    match state.file_manager.get_file_content(&record.id).await {
+        Ok(Some(content)) => Ok(Json(ApiResponse::success(content))),
+        Ok(None) => Err(api_error(CONTEXT, ServerError::not_found(format!("文件 {} 的文本", file_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
@@ -0,0 +1,215 @@
+// 照片时间线 - 从 EXIF 提取拍摄时间和坐标, 按日或按月分组浏览图片
+use crate::error::ServerError;
+use crate::preview::similar::hash_image_file;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use exif::{Exif, In, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::path::Path;
+
+// 时间线封面的缩略图尺寸
+const COVER_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineGroup {
+    #[default]
+    Day,
+    Month,
+}
+
+impl TimelineGroup {
+    // RFC 3339 时间字符串中日期部分的长度: YYYY-MM-DD 或 YYYY-MM
+    fn period_len(&self) -> i32 {
+        match self {
+            Self::Day => 10,
+            Self::Month => 7,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimelineQuery {
+    #[serde(default)]
+    pub group: TimelineGroup,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimelinePeriod {
+    pub period: String,
+    pub count: i64,
+    pub cover_id: String,
+    pub cover_url: String,
+}
+
+// 从 EXIF 中提取的照片元数据
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhotoMetadata {
+    pub capture_time: Option<DateTime<Utc>>,
+    // (纬度, 经度), 南纬和西经为负数
+    pub location: Option<(f64, f64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReindexResult {
+    pub scanned: usize,
+    pub updated: usize,
+    pub hashed: usize,
+}
+
+// 读取图片的 EXIF 元数据, 文件没有 EXIF 时返回 None
+pub fn extract_photo_metadata(path: &Path) -> Option<PhotoMetadata> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    Some(PhotoMetadata {
+        capture_time: capture_time(&exif),
+        location: location(&exif),
+    })
+}
+
+// 拍摄时间, 没有时区偏移时按 UTC 处理
+fn capture_time(exif: &Exif) -> Option<DateTime<Utc>> {
+    let field = exif
+        .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .or_else(|| exif.get_field(Tag::DateTime, In::PRIMARY))?;
+    let mut time = exif::DateTime::from_ascii(first_ascii(&field.value)?).ok()?;
+
+    if let Some(offset) = exif.get_field(Tag::OffsetTimeOriginal, In::PRIMARY) {
+        if let Some(value) = first_ascii(&offset.value) {
+            let _ = time.parse_offset(value);
+        }
+    }
+
+    let local = NaiveDate::from_ymd_opt(time.year.into(), time.month.into(), time.day.into())?
+        .and_hms_opt(time.hour.into(), time.minute.into(), time.second.into())?;
+    let offset = FixedOffset::east_opt(i32::from(time.offset.unwrap_or(0)) * 60)?;
+
+    offset
+        .from_local_datetime(&local)
+        .single()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+// GPS 坐标, 度分秒换算为十进制度数
+fn location(exif: &Exif) -> Option<(f64, f64)> {
+    let coordinate = |value_tag: Tag, ref_tag: Tag, negative: u8| -> Option<f64> {
+        let degrees = match &exif.get_field(value_tag, In::PRIMARY)?.value {
+            Value::Rational(parts) if parts.len() == 3 => {
+                parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+            }
+            _ => return None,
+        };
+        let reference = first_ascii(&exif.get_field(ref_tag, In::PRIMARY)?.value)?;
+        let sign = if reference.first() == Some(&negative) { -1.0 } else { 1.0 };
+        degrees.is_finite().then_some(sign * degrees)
+    };
+
+    let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+fn first_ascii(value: &Value) -> Option<&[u8]> {
+    match value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice),
+        _ => None,
+    }
+}
+
+// 照片时间线接口
+pub async fn get_timeline(
+    Query(params): Query<TimelineQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<TimelinePeriod>>>, ApiError> {
+    let entries = state
+        .files
+        .photo_timeline(params.group.period_len(), params.limit, params.offset)
+        .await
+        .map_err(|e| api_error("获取照片时间线失败", e))?;
+
+    let periods = entries
+        .into_iter()
+        .map(|entry| TimelinePeriod {
+            cover_url: format!(
+                "/api/files/{}/image?w={}&h={}&fit=cover",
+                entry.cover_id, COVER_SIZE, COVER_SIZE
+            ),
+            period: entry.period,
+            count: entry.count,
+            cover_id: entry.cover_id,
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::success(periods)))
+}
+
+// 为缺少拍摄时间、坐标或感知哈希的图片补充元数据
+pub async fn reindex_photo_metadata(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<ReindexResult>>, ApiError> {
+    const CONTEXT: &str = "重建照片索引失败";
+
+    let records = state
+        .files
+        .list_images_missing_photo_metadata()
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let scanned = records.len();
+
+    let extracted = tokio::task::spawn_blocking(move || {
+        records
+            .into_iter()
+            .map(|record| {
+                let path = Path::new(&record.file_path);
+                let metadata = extract_photo_metadata(path)
+                    .filter(|m| m.capture_time.is_some() || m.location.is_some());
+                let hash = match record.perceptual_hash {
+                    Some(_) => None,
+                    None => hash_image_file(path).ok(),
+                };
+                (record.id, metadata, hash)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?;
+
+    let mut updated = 0;
+    let mut hashed = 0;
+    for (id, metadata, hash) in extracted {
+        if let Some(metadata) = metadata {
+            if state
+                .files
+                .set_photo_metadata(&id, metadata.capture_time, metadata.location)
+                .await
+                .map_err(|e| api_error(CONTEXT, e))?
+            {
+                updated += 1;
+            }
+        }
+
+        if let Some(hash) = hash {
+            if state
+                .files
+                .set_perceptual_hash(&id, hash as i64)
+                .await
+                .map_err(|e| api_error(CONTEXT, e))?
+            {
+                hashed += 1;
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(ReindexResult { scanned, updated, hashed })))
+}
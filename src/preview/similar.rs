@@ -0,0 +1,149 @@
+// 相似图片 - 基于感知哈希 (pHash) 查找视觉上相近的图片
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use image::{imageops::FilterType, DynamicImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path as FsPath;
+
+// 计算哈希时缩小到的边长, 以及参与比较的低频系数的边长
+const SAMPLE_SIZE: usize = 32;
+const HASH_SIZE: usize = 8;
+
+const DEFAULT_MAX_DISTANCE: u32 = 10;
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SimilarQuery {
+    pub max_distance: Option<u32>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarFile {
+    #[serde(flatten)]
+    pub file: FileRecord,
+    // 与目标图片哈希的汉明距离, 越小越相似
+    pub distance: u32,
+}
+
+// 感知哈希: 缩小为灰度图后做二维 DCT, 取左上角 8x8 低频系数与中位数比较得到 64 位
+pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+    let gray = img
+        .resize_exact(SAMPLE_SIZE as u32, SAMPLE_SIZE as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f64> = gray.pixels().map(|p| f64::from(p[0])).collect();
+
+    let mut cosines = [[0.0f64; SAMPLE_SIZE]; HASH_SIZE];
+    for (k, row) in cosines.iter_mut().enumerate() {
+        for (n, value) in row.iter_mut().enumerate() {
+            *value = (std::f64::consts::PI * (2 * n + 1) as f64 * k as f64 / (2 * SAMPLE_SIZE) as f64).cos();
+        }
+    }
+
+    let mut coefficients = [0.0f64; HASH_SIZE * HASH_SIZE];
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            let mut sum = 0.0;
+            for y in 0..SAMPLE_SIZE {
+                for x in 0..SAMPLE_SIZE {
+                    sum += pixels[y * SAMPLE_SIZE + x] * cosines[u][x] * cosines[v][y];
+                }
+            }
+            coefficients[v * HASH_SIZE + u] = sum;
+        }
+    }
+
+    // 直流分量只反映整体亮度, 不参与中位数计算
+    let mut ac = coefficients[1..].to_vec();
+    ac.sort_by(f64::total_cmp);
+    let median = ac[ac.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c > median)
+        .fold(0u64, |hash, (i, _)| hash | (1 << i))
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// 解码图片文件并计算感知哈希
+pub fn hash_image_file(path: &FsPath) -> Result<u64> {
+    let img = image::open(path)
+        .map_err(|e| ServerError::file_operation(format!("无法解码图片: {}", e)))?;
+    Ok(perceptual_hash(&img))
+}
+
+// 相似图片接口
+pub async fn find_similar(
+    Path(file_id): Path<String>,
+    Query(params): Query<SimilarQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<SimilarFile>>>, ApiError> {
+    const CONTEXT: &str = "查找相似图片失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    if !record.mime_type.starts_with("image/") {
+        return Err(api_error(CONTEXT, ServerError::validation("文件不是图片")));
+    }
+
+    // 尚未建立索引的图片在首次查询时计算哈希
+    let target = match record.perceptual_hash {
+        Some(hash) => hash as u64,
+        None => {
+            let path = record.file_path.clone();
+            let hash = tokio::task::spawn_blocking(move || hash_image_file(FsPath::new(&path)))
+                .await
+                .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+                .map_err(|e| api_error(CONTEXT, e))?;
+            state
+                .files
+                .set_perceptual_hash(&record.id, hash as i64)
+                .await
+                .map_err(|e| api_error(CONTEXT, e))?;
+            hash
+        }
+    };
+
+    let max_distance = params.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    let mut matches: Vec<(String, u32)> = state
+        .files
+        .list_perceptual_hashes()
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .filter(|(id, _)| *id != record.id)
+        .map(|(id, hash)| (id, hamming_distance(target, hash as u64)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .collect();
+    matches.sort_by_key(|(_, distance)| *distance);
+    matches.truncate(limit);
+
+    let ids: Vec<String> = matches.iter().map(|(id, _)| id.clone()).collect();
+    let mut files: HashMap<String, FileRecord> = state
+        .files
+        .get_files_by_ids(&ids)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .map(|file| (file.id.clone(), file))
+        .collect();
+
+    let similar = matches
+        .into_iter()
+        .filter_map(|(id, distance)| files.remove(&id).map(|file| SimilarFile { file, distance }))
+        .collect();
+
+    Ok(Json(ApiResponse::success(similar)))
+}
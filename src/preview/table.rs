@@ -0,0 +1,269 @@
+// 表格预览 - 读取 CSV/TSV 文件开头的若干行, 推断列类型后以 JSON 返回, 不需要下载整个文件
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::path::Path as FsPath;
+
+const DEFAULT_ROWS: usize = 50;
+const MAX_ROWS: usize = 1000;
+
+// 最多读取文件开头的字节数, 超长的行不会让预览读完整个文件
+const MAX_PREVIEW_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct TableQuery {
+    pub rows: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Datetime,
+    String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ColumnType,
+    // 预览的行中出现过空值
+    pub nullable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TablePreview {
+    pub columns: Vec<TableColumn>,
+    pub rows: Vec<Vec<Value>>,
+    // 文件中还有更多行
+    pub truncated: bool,
+}
+
+// 表格预览接口
+pub async fn preview_table(
+    Path(file_id): Path<String>,
+    Query(params): Query<TableQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Json<ApiResponse<TablePreview>>, ApiError> {
+    const CONTEXT: &str = "预览表格失败";
+
+    let rows = params.rows.unwrap_or(DEFAULT_ROWS);
+    if !(1..=MAX_ROWS).contains(&rows) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("预览行数必须在 1-{} 之间", MAX_ROWS)),
+        ));
+    }
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    let delimiter = delimiter_for(&record).map_err(|e| api_error(CONTEXT, e))?;
+    let path = record.file_path.clone();
+    let preview = tokio::task::spawn_blocking(move || read_preview(FsPath::new(&path), delimiter, rows))
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(Json(ApiResponse::success(preview)))
+}
+
+fn delimiter_for(record: &FileRecord) -> Result<char> {
+    let extension = FsPath::new(&record.original_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match (extension.as_str(), record.mime_type.as_str()) {
+        ("tsv" | "tab", _) | (_, "text/tab-separated-values") => Ok('\t'),
+        ("csv", _) | (_, "text/csv") => Ok(','),
+        ("parquet", _) => Err(ServerError::validation("暂不支持 Parquet 文件")),
+        _ => Err(ServerError::validation("文件不是 CSV 或 TSV 表格")),
+    }
+}
+
+fn read_preview(path: &FsPath, delimiter: char, rows: usize) -> Result<TablePreview> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .map_err(ServerError::Io)?
+        .take(MAX_PREVIEW_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(ServerError::Io)?;
+    let partial = bytes.len() as u64 > MAX_PREVIEW_BYTES;
+    bytes.truncate(MAX_PREVIEW_BYTES as usize);
+
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    Ok(build_preview(text, delimiter, rows, partial))
+}
+
+// partial 表示 text 只是文件的开头, 最后一行可能不完整
+pub fn build_preview(text: &str, delimiter: char, rows: usize, partial: bool) -> TablePreview {
+    let mut records = parse_records(text, delimiter, rows + 2);
+    let complete = records.len();
+    if partial && records.len() <= rows + 1 {
+        records.pop();
+    }
+
+    let mut records = records.into_iter();
+    let header = records.next().unwrap_or_default();
+    let mut data: Vec<Vec<String>> = records.take(rows).collect();
+    let truncated = partial || complete > data.len() + 1;
+
+    // 列数以表头和数据中最长的一行为准, 缺少的单元格视为空值
+    let width = data.iter().map(Vec::len).chain([header.len()]).max().unwrap_or(0);
+    for row in &mut data {
+        row.resize(width, String::new());
+    }
+
+    let columns: Vec<TableColumn> = (0..width)
+        .map(|index| {
+            let name = header
+                .get(index)
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("column_{}", index + 1));
+            let cells = data.iter().map(|row| row[index].as_str());
+            TableColumn {
+                name,
+                kind: infer_type(cells.clone()),
+                nullable: cells.clone().any(|cell| cell.trim().is_empty()),
+            }
+        })
+        .collect();
+
+    let rows = data
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&columns)
+                .map(|(cell, column)| convert(cell, column.kind))
+                .collect()
+        })
+        .collect();
+
+    TablePreview { columns, rows, truncated }
+}
+
+// 按 RFC 4180 解析, 引号内可以包含分隔符、换行和成对的引号. 最多返回 limit 行
+pub fn parse_records(text: &str, delimiter: char, limit: usize) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if records.len() >= limit {
+            return records;
+        }
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                // 跳过空行
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if (!field.is_empty() || !record.is_empty()) && records.len() < limit {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+// 取能容纳所有非空单元格的最窄类型, 全部为空时按字符串处理
+fn infer_type<'a>(cells: impl Iterator<Item = &'a str>) -> ColumnType {
+    let candidates = [
+        ColumnType::Integer,
+        ColumnType::Float,
+        ColumnType::Boolean,
+        ColumnType::Date,
+        ColumnType::Datetime,
+    ];
+    let mut possible = candidates.to_vec();
+    let mut seen = false;
+
+    for cell in cells.filter(|cell| !cell.trim().is_empty()) {
+        seen = true;
+        possible.retain(|kind| parse(cell, *kind).is_some());
+        if possible.is_empty() {
+            return ColumnType::String;
+        }
+    }
+
+    match possible.first() {
+        Some(kind) if seen => *kind,
+        _ => ColumnType::String,
+    }
+}
+
+// 把单元格按列类型转换为 JSON 值, 空单元格为 null, 无法转换时保留原文本
+fn convert(cell: &str, kind: ColumnType) -> Value {
+    if cell.trim().is_empty() {
+        return Value::Null;
+    }
+    parse(cell, kind).unwrap_or_else(|| Value::String(cell.to_string()))
+}
+
+fn parse(cell: &str, kind: ColumnType) -> Option<Value> {
+    let trimmed = cell.trim();
+    match kind {
+        ColumnType::Integer => trimmed.parse::<i64>().ok().map(Value::from),
+        ColumnType::Float => trimmed
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(Value::from),
+        ColumnType::Boolean => match trimmed.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ColumnType::Date => NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+            .ok()
+            .map(|date| Value::from(date.to_string())),
+        ColumnType::Datetime => DateTime::parse_from_rfc3339(trimmed)
+            .map(|time| time.to_rfc3339())
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%d %H:%M:%S").map(|time| time.to_string())
+            })
+            .ok()
+            .map(Value::from),
+        ColumnType::String => None,
+    }
+}
@@ -0,0 +1,154 @@
+// 日志尾部 - 返回文本文件的最后若干行, follow 模式下继续推送之后追加的内容, 相当于网页版 tail -f
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, AppState};
+use crate::storage::FileRecord;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path as FsPath, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const DEFAULT_LINES: usize = 200;
+const MAX_LINES: usize = 10_000;
+
+// 最多从文件末尾往前读取的字节数, 行很长时返回的行数可能少于请求的行数
+const MAX_TAIL_BYTES: u64 = 1024 * 1024;
+
+// follow 模式检查文件是否变长的间隔, 以及每次最多推送的字节数
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const FOLLOW_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct TailQuery {
+    pub lines: Option<usize>,
+    #[serde(default)]
+    pub follow: bool,
+}
+
+// 返回最后若干行, follow=true 时连接保持打开, 直到客户端断开或文件被删除
+pub async fn tail_file(
+    Path(file_id): Path<String>,
+    Query(params): Query<TailQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "读取文件末尾失败";
+
+    let lines = params.lines.unwrap_or(DEFAULT_LINES);
+    if !(1..=MAX_LINES).contains(&lines) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("行数必须在 1-{} 之间", MAX_LINES)),
+        ));
+    }
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    if !is_text(&record) {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("文件 {} 不是文本文件", record.id))));
+    }
+
+    let path = PathBuf::from(&record.file_path);
+    let source = path.clone();
+    let (tail, offset) = tokio::task::spawn_blocking(move || read_tail(&source, lines))
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Internal(e.into())))?
+        .map_err(|e| api_error(CONTEXT, e))?;
+    if tail.contains(&0) {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("文件 {} 不是文本文件", record.id))));
+    }
+
+    let content_type = (header::CONTENT_TYPE, "text/plain; charset=utf-8");
+    if !params.follow {
+        return Ok(([content_type], tail).into_response());
+    }
+
+    let initial = futures::stream::once(async move { Ok::<_, std::io::Error>(tail) });
+    let appended = follow(path, offset);
+    Ok((
+        [content_type, (header::CACHE_CONTROL, "no-store")],
+        Body::from_stream(futures::StreamExt::chain(initial, appended)),
+    )
+        .into_response())
+}
+
+fn is_text(record: &FileRecord) -> bool {
+    let is_log = FsPath::new(&record.original_name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("log"));
+    is_log
+        || record.mime_type.starts_with("text/")
+        || matches!(record.mime_type.as_str(), "application/json" | "application/xml")
+}
+
+// 返回末尾若干行和读取时的文件长度, follow 从该位置继续
+fn read_tail(path: &FsPath, lines: usize) -> Result<(Vec<u8>, u64)> {
+    let mut file = std::fs::File::open(path).map_err(ServerError::Io)?;
+    let len = file.metadata().map_err(ServerError::Io)?.len();
+    let start = len.saturating_sub(MAX_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start)).map_err(ServerError::Io)?;
+    let mut bytes = Vec::new();
+    file.take(len - start).read_to_end(&mut bytes).map_err(ServerError::Io)?;
+    let len = start + bytes.len() as u64;
+
+    Ok((last_lines(&bytes, lines, start > 0).to_vec(), len))
+}
+
+// partial 表示 bytes 不是从文件开头读取的, 第一行可能不完整, 行数不够时丢弃
+pub fn last_lines(bytes: &[u8], lines: usize, partial: bool) -> &[u8] {
+    // 末尾的换行符属于最后一行
+    let body = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    let mut newlines = body.iter().enumerate().rev().filter(|(_, byte)| **byte == b'\n');
+    match newlines.nth(lines - 1) {
+        Some((index, _)) => &bytes[index + 1..],
+        None if partial => match bytes.iter().position(|byte| *byte == b'\n') {
+            Some(index) if index + 1 < bytes.len() => &bytes[index + 1..],
+            _ => bytes,
+        },
+        None => bytes,
+    }
+}
+
+// 轮询文件长度, 推送 offset 之后追加的内容. 文件变短 (被截断或轮转) 时从头开始
+fn follow(path: PathBuf, offset: u64) -> impl futures::Stream<Item = std::io::Result<Vec<u8>>> {
+    futures::stream::unfold(Some((path, offset)), |state| async move {
+        let (path, mut offset) = state?;
+        loop {
+            let len = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata.len(),
+                // 文件被删除时结束响应
+                Err(_) => return None,
+            };
+            if len < offset {
+                offset = 0;
+            }
+            if len == offset {
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+                continue;
+            }
+
+            let chunk = async {
+                let mut file = tokio::fs::File::open(&path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                let mut buffer = Vec::with_capacity(FOLLOW_CHUNK);
+                file.take((len - offset).min(FOLLOW_CHUNK as u64)).read_to_end(&mut buffer).await?;
+                Ok::<_, std::io::Error>(buffer)
+            }
+            .await;
+            return match chunk {
+                Ok(buffer) => {
+                    offset += buffer.len() as u64;
+                    Some((Ok(buffer), Some((path, offset))))
+                }
+                Err(e) => Some((Err(e), None)),
+            };
+        }
+    })
+}
@@ -0,0 +1,151 @@
+// 缩略图 - 每个命名尺寸按配置的每种格式各生成一张, 存放在 .cache/thumbnails 下
+use crate::config::{parse_dimensions, ThumbnailFormat};
+use crate::download::canary::{self, DownloadContext};
+use crate::download::range::if_none_match_matches;
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, AppState};
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// 默认尺寸 (thumbnail_size) 的名称
+pub const DEFAULT_VARIANT: &str = "default";
+
+// 一张缩略图的输出位置和参数, 先写入 temp_path 再重命名为 target
+#[derive(Debug, Clone)]
+pub struct ThumbnailTarget {
+    pub target: PathBuf,
+    pub temp_path: PathBuf,
+    pub size: (u32, u32),
+    pub format: ThumbnailFormat,
+    pub quality: u8,
+}
+
+// 默认尺寸命名为 <id>.<ext>, 其他尺寸为 <id>-<name>.<ext>
+pub fn variant_path(dir: &Path, file_id: &str, name: &str, format: ThumbnailFormat) -> PathBuf {
+    if name == DEFAULT_VARIANT {
+        dir.join(format!("{}.{}", file_id, format.extension()))
+    } else {
+        dir.join(format!("{}-{}.{}", file_id, name, format.extension()))
+    }
+}
+
+// 全部尺寸和格式的组合. 第一张是默认尺寸的首选格式, 它的路径写入记录的 thumbnail_path
+pub fn plan_targets(
+    state: &AppState,
+    file_id: &str,
+    variants: &BTreeMap<String, String>,
+    formats: &[ThumbnailFormat],
+    quality: u8,
+) -> Result<Vec<ThumbnailTarget>> {
+    let dir = state.file_manager.cache_dir("thumbnails")?;
+    let names = std::iter::once(DEFAULT_VARIANT)
+        .chain(variants.keys().map(String::as_str).filter(|name| *name != DEFAULT_VARIANT));
+    let mut unique_formats = Vec::new();
+    for format in formats {
+        if !unique_formats.contains(format) {
+            unique_formats.push(*format);
+        }
+    }
+
+    let mut targets = Vec::new();
+    for name in names {
+        let Some(size) = variants.get(name) else {
+            continue;
+        };
+        let size = parse_dimensions(size)
+            .ok_or_else(|| ServerError::validation(format!("缩略图尺寸格式无效: {}", size)))?;
+        for format in &unique_formats {
+            targets.push(ThumbnailTarget {
+                target: variant_path(&dir, file_id, name, *format),
+                temp_path: state.temp.path("thumbnails")?,
+                size,
+                format: *format,
+                quality,
+            });
+        }
+    }
+    Ok(targets)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ThumbnailQuery {
+    // 命名尺寸, 不指定时返回默认尺寸
+    pub size: Option<String>,
+}
+
+// 返回文件的缩略图, 尺寸由 size 参数指定, 格式按 Accept 从已生成的格式中选择
+pub async fn get_thumbnail(
+    UrlPath(file_id): UrlPath<String>,
+    Query(params): Query<ThumbnailQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取缩略图失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    let default_path = record
+        .thumbnail_path
+        .as_ref()
+        .ok_or_else(|| api_error(CONTEXT, ServerError::not_found(format!("文件 {} 的缩略图", file_id))))?;
+    canary::check_download(&state, &record, &download).await;
+
+    let (variants, formats) = if record.is_video {
+        (state.config.video.thumbnail_variants(), &state.config.video.thumbnail_formats)
+    } else {
+        (state.config.image.thumbnail_variants(), &state.config.image.thumbnail_formats)
+    };
+    let name = params.size.as_deref().unwrap_or(DEFAULT_VARIANT);
+    if !variants.contains_key(name) {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("未配置的缩略图尺寸: {}", name))));
+    }
+
+    // 只在实际生成的格式中选择, 例如 ffmpeg 不支持 WebP 时只有 JPEG
+    let dir = Path::new(default_path).parent().unwrap_or(Path::new(""));
+    let available: Vec<ThumbnailFormat> = formats
+        .iter()
+        .copied()
+        .filter(|format| variant_path(dir, &record.id, name, *format).is_file())
+        .collect();
+    let accept = download
+        .headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    let path = match ThumbnailFormat::negotiate(accept, &available) {
+        Some(format) => variant_path(dir, &record.id, name, format),
+        // 配置改动前生成的默认缩略图仍然可用
+        None if name == DEFAULT_VARIANT => PathBuf::from(default_path),
+        None => {
+            return Err(api_error(
+                CONTEXT,
+                ServerError::not_found(format!("文件 {} 的缩略图尺寸 {}", file_id, name)),
+            ))
+        }
+    };
+
+    // 缩略图随内容替换重新生成, 地址不变, 所以按记录版本和所选文件校验而不是长期缓存
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let etag = format!("\"{}-v{}-{}\"", record.id, record.version, file_name);
+    let validators = [(header::ETAG, etag.clone()), (header::VARY, "Accept".to_string())];
+    if download
+        .headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| if_none_match_matches(value, &etag))
+    {
+        return Ok((StatusCode::NOT_MODIFIED, validators).into_response());
+    }
+
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+    let mime_type = mime_guess::from_path(&path).first_or_octet_stream();
+
+    Ok(([(header::CONTENT_TYPE, mime_type.to_string())], validators, bytes).into_response())
+}
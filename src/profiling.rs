@@ -0,0 +1,66 @@
+// CPU 采样 - 在运行中的服务器上采样一段时间并返回火焰图, 用于排查事件循环卡顿
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, AppState};
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::time::Duration;
+
+const DEFAULT_SECONDS: u64 = 10;
+const DEFAULT_FREQUENCY: i32 = 99;
+
+#[derive(Debug, Deserialize)]
+pub struct CpuProfileQuery {
+    pub seconds: Option<u64>,
+    // 每秒采样次数
+    pub frequency: Option<i32>,
+}
+
+// 采样 seconds 秒后返回 SVG 火焰图, 没有采到样本时返回 204. 同一时间只能有一个采样
+pub async fn cpu_profile(
+    Query(params): Query<CpuProfileQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "CPU 采样失败";
+
+    let max_seconds = state.config.profiling.max_profile_seconds;
+    let seconds = params.seconds.unwrap_or(DEFAULT_SECONDS.min(max_seconds));
+    if !(1..=max_seconds).contains(&seconds) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("采样时间必须在 1-{} 秒之间", max_seconds)),
+        ));
+    }
+    let frequency = params.frequency.unwrap_or(DEFAULT_FREQUENCY);
+    if !(1..=1000).contains(&frequency) {
+        return Err(api_error(CONTEXT, ServerError::validation("采样频率必须在 1-1000 Hz 之间")));
+    }
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| {
+            api_error(CONTEXT, ServerError::conflict(format!("无法启动 CPU 采样, 可能已有采样在进行: {}", e)))
+        })?;
+    tokio::time::sleep(Duration::from_secs(seconds)).await;
+
+    let report = guard
+        .report()
+        .build()
+        .map_err(|e| api_error(CONTEXT, ServerError::file_operation(format!("生成火焰图失败: {}", e))))?;
+    // 采样期间没有任何线程占用 CPU
+    if report.data.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let mut svg = Vec::new();
+    report
+        .flamegraph(&mut svg)
+        .map_err(|e| api_error(CONTEXT, ServerError::file_operation(format!("生成火焰图失败: {}", e))))?;
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response())
+}
@@ -0,0 +1,141 @@
+// 文件搜索 - 按文件名和 MIME 类型全文搜索, 以及在服务端执行搜索后把匹配的文件打包成一个 zip 返回
+use crate::download::bundle::archive_response;
+use crate::download::canary::{self, DownloadContext};
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{FileQuery, FileRecord, FileSearch};
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path as FsPath;
+
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+const MAX_SEARCH_LIMIT: i64 = 1000;
+
+// 单次打包最多包含的文件数
+const MAX_ARCHIVE_FILES: i64 = 10_000;
+
+// 搜索参数, 为空的条件不参与过滤
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub is_video: Option<bool>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    pub uploaded_after: Option<DateTime<Utc>>,
+    pub uploaded_before: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchDownloadRequest {
+    #[serde(flatten)]
+    pub search: FileSearch,
+    // 只统计匹配的文件, 不打包
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchivePlan {
+    pub count: usize,
+    pub total_size: u64,
+    pub max_size: u64,
+    pub within_limit: bool,
+}
+
+// 搜索文件, 最新上传的在前
+pub async fn search_files(
+    Query(params): Query<SearchQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<FileRecord>>>, ApiError> {
+    const CONTEXT: &str = "搜索文件失败";
+
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("数量必须在 1-{} 之间", MAX_SEARCH_LIMIT)),
+        ));
+    }
+    let offset = params.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(api_error(CONTEXT, ServerError::validation("偏移量不能为负数")));
+    }
+    if params.min_size.zip(params.max_size).is_some_and(|(min, max)| min > max) {
+        return Err(api_error(CONTEXT, ServerError::validation("最小大小不能超过最大大小")));
+    }
+
+    let file_query = FileQuery {
+        search: FileSearch {
+            uploaded_after: params.uploaded_after,
+            uploaded_before: params.uploaded_before,
+            text: params.q,
+            is_video: params.is_video,
+            min_size: params.min_size,
+            max_size: params.max_size,
+            ..Default::default()
+        },
+        limit: Some(limit),
+        offset: Some(offset),
+        ..Default::default()
+    };
+    state
+        .files
+        .query_files(&file_query)
+        .await
+        .map(|files| Json(ApiResponse::success(files)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+// 搜索并打包下载, dry_run 时只返回匹配数量和总大小
+pub async fn download_search_results(
+    State(state): State<AppState>,
+    download: DownloadContext,
+    Json(req): Json<SearchDownloadRequest>,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "打包搜索结果失败";
+
+    // 只打包当前可下载且文件仍在磁盘上的记录
+    let now = Utc::now();
+    let records: Vec<FileRecord> = state
+        .files
+        .search_files(&req.search, MAX_ARCHIVE_FILES)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .filter(|record| record.check_availability(now).is_ok() && FsPath::new(&record.file_path).is_file())
+        .collect();
+
+    let total_size = records.iter().map(|r| r.file_size.max(0) as u64).sum::<u64>();
+    let max_size = state.config.storage.max_archive_size;
+    if req.dry_run {
+        let plan = ArchivePlan {
+            count: records.len(),
+            total_size,
+            max_size,
+            within_limit: total_size <= max_size,
+        };
+        return Ok(Json(ApiResponse::success(plan)).into_response());
+    }
+
+    if records.is_empty() {
+        return Err(api_error(CONTEXT, ServerError::not_found("匹配的文件")));
+    }
+    if total_size > max_size {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("打包大小 {} 字节超过上限 {} 字节", total_size, max_size)),
+        ));
+    }
+    for record in &records {
+        canary::check_download(&state, record, &download).await;
+    }
+
+    let filename = format!("search-{}.zip", now.format("%Y%m%d-%H%M%S"));
+    Ok(archive_response(records, &filename))
+}
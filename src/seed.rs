@@ -0,0 +1,210 @@
+// 测试数据生成 - 为开发环境批量生成各种类型和大小的文件, 同一种子生成的内容和文件 ID 完全相同
+use crate::error::{Result, ServerError};
+use crate::storage::{FileManager, FileRecord};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct SeedOptions {
+    pub count: usize,
+    // 随机种子, 相同种子重复执行时跳过已生成的文件
+    pub seed: u64,
+    // 非图片文件的最大大小 (字节)
+    pub max_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SeedReport {
+    pub created: u64,
+    // 已存在 (之前用相同种子生成过) 而跳过的文件数
+    pub skipped: u64,
+    pub bytes: u64,
+    // 按类型统计新生成的文件数, 例如 text、image、video
+    pub kinds: BTreeMap<String, u64>,
+}
+
+// 生成的文件类型, 按 text:image:video:binary = 4:3:2:1 的比例分配
+#[derive(Debug, Clone, Copy)]
+enum SeedKind {
+    Text,
+    Image,
+    Video,
+    Binary,
+}
+
+impl SeedKind {
+    fn pick(rng: &mut SeedRng) -> Self {
+        match rng.below(10) {
+            0..=3 => Self::Text,
+            4..=6 => Self::Image,
+            7..=8 => Self::Video,
+            _ => Self::Binary,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Image => "image",
+            Self::Video => "video",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+// xorshift 伪随机数, 保证同一种子在不同机器上生成相同的数据
+struct SeedRng(u64);
+
+impl SeedRng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+
+    // 对数均匀分布的大小, 小文件多、大文件少
+    fn size(&mut self, min: u64, max: u64) -> u64 {
+        let (min, max) = (min.max(1) as f64, max.max(min.max(1)) as f64);
+        let ratio = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        (min * (max / min).powf(ratio)) as u64
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len + 8);
+        while data.len() < len {
+            data.extend_from_slice(&self.next().to_le_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+}
+
+const WORDS: &[&str] = &[
+    "文件", "服务器", "内网", "共享", "视频", "图片", "预览", "下载", "上传", "存储",
+    "file", "server", "share", "video", "image", "preview", "download", "upload", "storage", "cache",
+];
+
+const RESOLUTIONS: &[(u32, u32)] = &[(640, 360), (1280, 720), (1920, 1080), (3840, 2160)];
+
+// 生成 count 个文件并写入存储目录和数据库
+pub async fn seed_files(file_manager: &FileManager, options: &SeedOptions) -> Result<SeedReport> {
+    let mut report = SeedReport::default();
+    let mut rng = SeedRng::new(options.seed);
+    let now = Utc::now();
+
+    for index in 0..options.count {
+        let kind = SeedKind::pick(&mut rng);
+        let id = Uuid::from_u64_pair(rng.next(), rng.next());
+        // 文件 ID 由种子决定, 无论是否跳过都要按相同顺序消耗随机数
+        let (original_name, mime_type, data) = generate(kind, index, &mut rng, options.max_size)?;
+        let age = Duration::seconds(rng.below(365 * 24 * 3600) as i64);
+
+        let id = id.to_string();
+        if file_manager.get_file_by_id(&id).await?.is_some() {
+            report.skipped += 1;
+            continue;
+        }
+
+        let sha256 = hex::encode(Sha256::digest(&data));
+        let stored_name = file_manager.generate_stored_name_with_hash(&original_name, Some(&sha256));
+        let file_path = file_manager.get_file_path(&stored_name);
+        tokio::fs::write(&file_path, &data).await.map_err(ServerError::Io)?;
+
+        let is_video = matches!(kind, SeedKind::Video);
+        let resolution = RESOLUTIONS[index % RESOLUTIONS.len()];
+        let record = FileRecord {
+            id,
+            original_name,
+            stored_name,
+            file_path: file_path.to_string_lossy().to_string(),
+            file_size: data.len() as i64,
+            mime_type,
+            upload_time: now - age,
+            is_video,
+            thumbnail_path: None,
+            // 视频只有元数据是真的, 内容是随机字节, 无法播放
+            video_duration: is_video.then(|| 10 + (index as i32 * 37) % 3600),
+            video_resolution: is_video.then(|| format!("{}x{}", resolution.0, resolution.1)),
+            available_from: None,
+            available_until: None,
+            version: 1,
+            capture_time: None,
+            latitude: None,
+            longitude: None,
+            perceptual_hash: None,
+            sha256: Some(sha256),
+        };
+        if let Err(e) = file_manager.save_file_record(&record).await {
+            let _ = tokio::fs::remove_file(&file_path).await;
+            return Err(e);
+        }
+
+        report.created += 1;
+        report.bytes += data.len() as u64;
+        *report.kinds.entry(kind.name().to_string()).or_default() += 1;
+    }
+    Ok(report)
+}
+
+// 返回 (文件名, MIME 类型, 内容)
+fn generate(kind: SeedKind, index: usize, rng: &mut SeedRng, max_size: u64) -> Result<(String, String, Vec<u8>)> {
+    let stem = format!(
+        "seed-{:05}-{}-{}",
+        index,
+        WORDS[rng.below(WORDS.len() as u64) as usize],
+        WORDS[rng.below(WORDS.len() as u64) as usize]
+    );
+
+    Ok(match kind {
+        SeedKind::Text => {
+            let size = rng.size(64, max_size.min(1024 * 1024)) as usize;
+            let mut text = String::with_capacity(size + 16);
+            while text.len() < size {
+                text.push_str(WORDS[rng.below(WORDS.len() as u64) as usize]);
+                text.push(if rng.below(12) == 0 { '\n' } else { ' ' });
+            }
+            let (extension, mime) = [("txt", "text/plain"), ("md", "text/markdown"), ("csv", "text/csv")]
+                [rng.below(3) as usize];
+            (format!("{}.{}", stem, extension), mime.to_string(), text.into_bytes())
+        }
+        SeedKind::Image => {
+            let width = 64 + rng.below(960) as u32;
+            let height = 64 + rng.below(960) as u32;
+            let (r, g, b) = (rng.below(256) as u32, rng.below(256) as u32, rng.below(256) as u32);
+            // 渐变色块, 不同文件的颜色和尺寸不同, 便于在界面上区分
+            let image = image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb([
+                    ((r + x * 255 / width) % 256) as u8,
+                    ((g + y * 255 / height) % 256) as u8,
+                    b as u8,
+                ])
+            });
+            let mut data = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+                .map_err(|e| ServerError::Internal(e.into()))?;
+            (format!("{}.png", stem), "image/png".to_string(), data)
+        }
+        SeedKind::Video => {
+            let size = rng.size(64 * 1024, max_size) as usize;
+            (format!("{}.mp4", stem), "video/mp4".to_string(), rng.bytes(size))
+        }
+        SeedKind::Binary => {
+            let size = rng.size(1024, max_size) as usize;
+            (format!("{}.bin", stem), "application/octet-stream".to_string(), rng.bytes(size))
+        }
+    })
+}
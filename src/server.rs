@@ -1,15 +1,36 @@
-use crate::config::Config;
+use crate::collections;
+use crate::config::{Config, KeyAccess};
+use crate::download::{self, stream::StreamTracker};
 use crate::error::ServerError;
-use crate::storage::FileManager;
+use crate::i18n;
+use crate::memory::{self, MemoryBudgets};
+use crate::middleware::{self, ApiKeyIdentity};
+use crate::policy::{PolicyAction, PolicyClient, PolicyInput, PolicyResource};
+use crate::preview;
+use crate::search;
+use crate::speedtest;
+use crate::upload::{self, proxy::UpstreamClient};
+use crate::usage::{self, UsageTracker};
+use crate::video::{self, VideoToolchain};
+use crate::web;
+use crate::storage::{
+    self, DerivedCleanupReport, FileLink, FileLock, FileManager, FileQuery, FileRecord, FileRepository, FileSummary,
+    GeoBounds, MaintenanceReport, TempCleanupReport, TempManager, TempStats, TrashedFile,
+};
 use axum::{
     Router,
-    response::Json,
-    routing::get,
-    extract::{Query, Path, State},
-    http::StatusCode,
+    body::Body,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, patch, post, put},
+    extract::{ConnectInfo, DefaultBodyLimit, Extension, Query, Path, State},
+    http::{header, HeaderMap, StatusCode},
 };
+use futures::{stream, StreamExt};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::{info, error};
@@ -18,27 +39,51 @@ type Result<T> = std::result::Result<T, ServerError>;
 
 #[derive(Clone)]
 pub struct AppState {
+    // 存储路径、缓存目录、统计和维护, 以及上传会话、流量、蜜罐、播放进度、提取文本等附属表
     pub file_manager: Arc<FileManager>,
+    // 文件记录及其锁、关联、集合、回收站的读写入口, 默认就是 file_manager, 测试时可替换为内存实现
+    pub files: Arc<dyn FileRepository>,
     pub config: Config,
+    // 运行时只读开关, 初始值来自 server.read_only
+    pub read_only: Arc<AtomicBool>,
+    // 启动时检测到的 ffmpeg/ffprobe 能力
+    pub video_toolchain: Arc<VideoToolchain>,
+    // 尚未写入数据库的流量计数
+    pub usage: Arc<UsageTracker>,
+    // 存储目录下的 .tmp, 所有临时文件都从这里分配
+    pub temp: Arc<TempManager>,
+    // 正在发送的下载, 用于调整每个连接的读缓冲
+    pub streams: Arc<StreamTracker>,
+    // 归档目录、图片解码等操作的内存预算
+    pub memory: Arc<MemoryBudgets>,
+    // 外部授权服务, 未配置 auth.policy 时为空
+    pub policy: Option<Arc<PolicyClient>>,
+    // 上传转发的目标实例, 未配置 federation.upstream 时为空
+    pub upstream: Option<Arc<UpstreamClient>>,
 }
 
 pub async fn start_server(config: Config) -> Result<()> {
     let address = config.server_address();
     
-    // 创建文件管理器
-    let file_manager = Arc::new(
-        FileManager::new(
-            &config.database.database_url(),
-            config.storage.upload_dir.clone(),
-        ).await?
-    );
-    
     // 创建应用状态
-    let state = AppState {
-        file_manager,
-        config: config.clone(),
-    };
+    let state = create_state(&config).await?;
     
+    // 低峰时段的数据库维护
+    if let Some(hour) = config.database.maintenance_hour {
+        storage::maintenance::spawn_scheduler(state.file_manager.clone(), hour);
+    }
+    usage::spawn_flusher(state.usage.clone(), state.file_manager.clone());
+    storage::temp::spawn_cleaner(state.temp.clone());
+    if config.storage.derived.cleanup_interval > 0 {
+        storage::derived::spawn_cleaner(state.file_manager.clone(), config.storage.derived.clone());
+    }
+    if config.storage.trash.enabled && config.storage.trash.max_age > 0 {
+        storage::trash::spawn_cleaner(state.file_manager.clone(), config.storage.trash.clone());
+    }
+    if config.storage.upload_sessions.max_age > 0 {
+        storage::uploads::spawn_cleaner(state.file_manager.clone(), config.storage.upload_sessions.clone());
+    }
+
     // 构建路由
     let app = create_router(state).await?;
 
@@ -49,32 +94,179 @@ pub async fn start_server(config: Config) -> Result<()> {
     // 启动服务器
     let listener = tokio::net::TcpListener::bind(&address)
         .await
-        .map_err(|e| ServerError::Io(e))?;
+        .map_err(ServerError::Io)?;
 
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .map_err(|e| ServerError::Internal(e.into()))?;
 
     Ok(())
 }
 
-async fn create_router(state: AppState) -> Result<Router> {
+// 根据配置创建文件管理器和应用状态
+pub async fn create_state(config: &Config) -> Result<AppState> {
+    let file_manager = Arc::new(
+        FileManager::new(
+            &config.database.database_url(),
+            config.storage.upload_dir.clone(),
+        ).await?
+        .with_naming_policy(config.storage.naming.policy)
+        .with_record_cache(&config.storage.record_cache)
+        .with_trash(config.storage.trash.enabled)
+    );
+
+    // 与存储目录在同一文件系统上, 临时文件可以直接重命名为正式文件
+    let temp = TempManager::new(config.storage.upload_dir.join(".tmp"), config.storage.temp.clone())?;
+    let purged = temp.purge();
+    if purged.removed_files > 0 {
+        info!("清理上次遗留的临时文件: {} 个, {} 字节", purged.removed_files, purged.removed_bytes);
+    }
+
+    Ok(AppState {
+        files: file_manager.clone(),
+        file_manager,
+        config: config.clone(),
+        read_only: Arc::new(AtomicBool::new(config.server.read_only)),
+        video_toolchain: Arc::new(VideoToolchain::detect(&config.video).await),
+        usage: Arc::new(UsageTracker::default()),
+        temp: Arc::new(temp),
+        streams: Arc::new(StreamTracker::default()),
+        memory: Arc::new(MemoryBudgets::new(config.memory.clone())),
+        policy: config.auth.policy.clone().map(PolicyClient::new).transpose()?.map(Arc::new),
+        upstream: config.federation.upstream.as_ref().map(UpstreamClient::new).transpose()?.map(Arc::new),
+    })
+}
+
+pub async fn create_router(state: AppState) -> Result<Router> {
+    // 配置了上游实例时上传转发到上游
+    let upload = if state.upstream.is_some() {
+        post(upload::proxy::forward_upload)
+    } else {
+        post(upload::handler::upload_file)
+    };
+
+    // 修改类接口, 只读模式下禁用
+    let write_routes = Router::new()
+        .route("/api/files/:file_id", delete(delete_file))
+        .route("/api/files/:file_id/restore", post(restore_file))
+        .route("/api/trash/:file_id", delete(purge_trashed_file))
+        .route("/api/files/:file_id/availability", patch(update_file_availability))
+        .route("/api/files/:file_id/lock", post(lock_file).delete(unlock_file))
+        .route("/api/files/:file_id/links", post(add_file_link))
+        .route("/api/files/:file_id/content", put(upload::replace::replace_file_content))
+        .route("/api/video/:file_id/position", post(video::playback::save_position))
+        .route("/api/video/:file_id/extract-audio", post(video::audio::extract_audio))
+        .route("/api/video/:file_id/clip", post(video::clip::create_clip))
+        .route("/api/collections", post(collections::create_collection))
+        // 单个文件的大小由 storage.max_file_size 限制, 不使用默认的请求体上限
+        .route("/api/upload", upload.layer(DefaultBodyLimit::disable()))
+        .route("/api/upload/init", post(upload::chunked::init_upload))
+        .route("/api/upload/:session_id/chunk/:index", put(upload::chunked::upload_chunk))
+        .route("/api/upload/:session_id", delete(upload::chunked::abort_upload))
+        .route("/api/upload/:session_id/complete", post(upload::chunked::complete_upload))
+        .route("/api/paste", post(upload::paste::create_paste))
+        .route("/api/screenshot", post(upload::screenshot::upload_screenshot))
+        .route(
+            "/api/collections/:collection_id",
+            put(collections::update_collection).delete(collections::delete_collection),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), middleware::authorize_write))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::reject_when_read_only,
+        ))
+        .route_layer(axum::middleware::from_fn(middleware::require_write_access));
+
+    // 管理接口, 仅允许本机访问
+    let admin_routes = Router::new()
+        .route("/api/admin/read-only", get(get_read_only).put(set_read_only))
+        .route("/api/admin/photos/reindex", post(preview::photos::reindex_photo_metadata))
+        .route("/api/admin/ocr/reindex", post(preview::ocr::reindex_ocr))
+        .route("/api/admin/heif/reindex", post(preview::heif::reindex_heif))
+        .route("/api/admin/db/maintain", post(maintain_database))
+        .route("/api/admin/video/toolchain", get(get_video_toolchain))
+        .route("/api/admin/reindex-video-metadata", post(video::metadata::reindex_video_metadata))
+        .route("/api/admin/temp", get(get_temp_stats))
+        .route("/api/admin/temp/cleanup", post(cleanup_temp))
+        .route("/api/admin/derived/cleanup", post(cleanup_derived))
+        .route("/api/admin/memory", get(memory::get_memory_budgets))
+        .route("/api/admin/canaries", get(download::canary::list_canaries))
+        .route("/api/admin/canaries/alerts", get(download::canary::list_alerts))
+        .route(
+            "/api/admin/canaries/:file_id",
+            put(download::canary::mark_canary).delete(download::canary::unmark_canary),
+        )
+        .route("/api/usage", get(usage::get_usage));
+    #[cfg(feature = "profiling")]
+    let admin_routes = admin_routes.route("/api/admin/profile/cpu", get(crate::profiling::cpu_profile));
+    let admin_routes = admin_routes
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), middleware::authorize_admin))
+        .route_layer(axum::middleware::from_fn(middleware::require_local_client))
+        .route_layer(axum::middleware::from_fn(middleware::require_write_access));
+
     let app = Router::new()
         // 健康检查和信息接口
         .route("/", get(health_check))
         .route("/health", get(health_check))
         .route("/api/info", get(server_info))
+        .route("/api/ui-config", get(web::ui_config::get_ui_config))
+        .route("/api/ui-config/logo", get(web::ui_config::get_logo))
+        .route("/api/feed.atom", get(web::feed::get_feed))
         
         // 文件管理 API
         .route("/api/files", get(list_files))
+        .route("/api/files/summary", get(list_file_summaries))
+        .route("/api/files/export", get(export_files))
+        .route("/api/files/lookup", post(lookup_files))
+        .route("/api/files/diff", get(preview::diff::diff_files))
+        .route("/api/search", get(search::search_files))
+        .route("/api/search/download", post(search::download_search_results))
         .route("/api/files/:file_id", get(get_file_info))
-        .route("/api/files/:file_id", axum::routing::delete(delete_file))
+        .route("/api/files/:file_id/image", get(preview::images::resize_image))
+        .route("/api/files/:file_id/convert", get(preview::images::convert_image))
+        .route("/api/files/:file_id/table", get(preview::table::preview_table))
+        .route("/api/files/:file_id/tail", get(preview::tail::tail_file))
+        .route("/api/files/:file_id/hexdump", get(preview::hexdump::hexdump_file))
+        .route("/api/files/:file_id/thumbnail", get(preview::thumbnails::get_thumbnail))
+        .route("/api/files/:file_id/similar", get(preview::similar::find_similar))
+        .route("/api/files/:file_id/text", get(preview::ocr::get_file_text))
+        .route("/api/files/:file_id/share/qr", get(download::share::get_share_qr))
+        .route("/api/files/:file_id/archive/entries", get(download::archive::list_entries))
+        .route("/api/files/:file_id/archive/entries/*path", get(download::archive::download_entry))
+        .route("/api/files/:file_id/lock", get(get_file_lock))
+        .route("/api/trash", get(list_trash))
         .route("/api/stats", get(get_file_stats))
+        .route("/api/speedtest/upload", post(speedtest::upload))
+        .route("/api/speedtest/download", get(speedtest::download))
+        .route("/api/photos/timeline", get(preview::photos::get_timeline))
+        .route("/api/video/:file_id/position", get(video::playback::get_position))
+        .route("/api/video/:file_id/audio", get(video::audio::get_audio))
+        .route("/api/video/:file_id/clip", get(video::clip::get_clip))
+        .route("/api/upload/:session_id", get(upload::chunked::get_upload_session))
+        .route("/api/collections", get(collections::list_collections))
+        .route("/api/collections/:collection_id", get(collections::get_collection))
         
-        // 静态文件服务 (将在后续任务中实现)
-        .route("/files/*path", get(serve_file))
+        // 文件内容, 支持 Range 请求
+        .route("/api/download/zip", post(download::bundle::download_bundle))
+        .route("/api/download/:file_id", get(download::handler::download_file))
+        .route("/files/*path", get(download::handler::serve_file))
+        .route("/files/by-hash/:sha256", get(download::by_hash::download_by_hash))
+        .route("/paste/:paste_id", get(upload::paste::view_paste))
+
+        // 内置网页界面
+        .route("/ui", get(web::static_files::redirect_to_ui))
+        .route("/ui/", get(web::static_files::serve_index))
+        .route("/ui/*path", get(web::static_files::serve_ui))
+
+        .merge(write_routes)
+        .merge(admin_routes)
+        .merge(web::mounts::mount_routes(&state.config.web.mounts))
         
         // 中间件
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::apply_cache_control))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::require_api_key))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), i18n::scope_locale))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), usage::track_usage))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
         .with_state(state);
@@ -111,6 +303,8 @@ async fn server_info() -> Json<Value> {
 struct ListFilesQuery {
     limit: Option<i32>,
     offset: Option<i32>,
+    // 坐标范围过滤: min_lon,min_lat,max_lon,max_lat
+    bbox: Option<String>,
 }
 
 // API响应结构
@@ -139,87 +333,669 @@ impl<T> ApiResponse<T> {
     }
 }
 
+// 统一的错误响应类型
+pub type ApiError = (StatusCode, Json<ApiResponse<()>>);
+
+// 将 ServerError 转换为带上下文的错误响应
+pub fn api_error(context: &str, err: ServerError) -> ApiError {
+    let status = StatusCode::from_u16(err.status_code())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if status.is_server_error() {
+        error!("{}: {}", context, err);
+    }
+    let message = i18n::translate(i18n::current_locale(), &format!("{}: {}", context, err));
+    (status, Json(ApiResponse::error(message)))
+}
+
+// 文件记录不存在时的错误响应: 已删除的文件返回 410 及删除信息, 否则返回 404
+pub async fn missing_file_error(state: &AppState, file_id: &str, context: &str) -> ApiError {
+    match state.files.get_tombstone(file_id).await {
+        Ok(Some(tombstone)) => {
+            let deleted_by = tombstone.deleted_by.as_deref().unwrap_or("未知");
+            api_error(
+                context,
+                ServerError::gone(format!(
+                    "文件 {} ({}) 已于 {} 被 {} 删除",
+                    file_id,
+                    tombstone.original_name,
+                    tombstone.deleted_at.to_rfc3339(),
+                    deleted_by
+                )),
+            )
+        }
+        Ok(None) => api_error(context, ServerError::not_found(format!("文件 {}", file_id))),
+        Err(e) => api_error(context, e),
+    }
+}
+
+// 根据 Host 和 X-Forwarded-Proto 拼出返回给客户端的绝对地址
+pub fn base_url(headers: &HeaderMap) -> String {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let host = header("host").unwrap_or("localhost");
+    let scheme = header("x-forwarded-proto").unwrap_or("http");
+    format!("{}://{}", scheme, host)
+}
+
+// 获取可下载的文件记录: 不存在时返回 404/410, 不在可用时间窗口内时返回 403/410
+pub async fn get_downloadable_file(
+    state: &AppState,
+    file_id: &str,
+    context: &str,
+) -> std::result::Result<FileRecord, ApiError> {
+    let record = match state.files.get_file_by_id(file_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err(missing_file_error(state, file_id, context).await),
+        Err(e) => return Err(api_error(context, e)),
+    };
+
+    record
+        .check_availability(Utc::now())
+        .map_err(|e| api_error(context, e))?;
+
+    Ok(record)
+}
+
 // 文件列表接口
 async fn list_files(
     Query(params): Query<ListFilesQuery>,
     State(state): State<AppState>,
-) -> std::result::Result<Json<ApiResponse<Vec<crate::storage::FileRecord>>>, (StatusCode, Json<ApiResponse<()>>)> {
-    match state.file_manager.list_files(params.limit, params.offset).await {
-        Ok(files) => Ok(Json(ApiResponse::success(files))),
-        Err(e) => {
-            error!("获取文件列表失败: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::error(format!("获取文件列表失败: {}", e)))
+) -> std::result::Result<Json<ApiResponse<Vec<FileRecord>>>, ApiError> {
+    let bounds = params
+        .bbox
+        .as_deref()
+        .map(GeoBounds::parse)
+        .transpose()
+        .map_err(|e| api_error("获取文件列表失败", e))?;
+    let file_query = FileQuery {
+        bounds,
+        limit: Some(params.limit.unwrap_or(50).into()),
+        offset: params.offset.map(Into::into),
+        ..Default::default()
+    };
+
+    state
+        .files
+        .query_files(&file_query)
+        .await
+        .map(|files| Json(ApiResponse::success(files)))
+        .map_err(|e| api_error("获取文件列表失败", e))
+}
+
+// 精简文件列表接口, 供界面无限滚动列表使用
+async fn list_file_summaries(
+    Query(params): Query<ListFilesQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Vec<FileSummary>>>, ApiError> {
+    state
+        .files
+        .list_file_summaries(params.limit, params.offset)
+        .await
+        .map(|files| Json(ApiResponse::success(files)))
+        .map_err(|e| api_error("获取文件列表失败", e))
+}
+
+// 导出查询参数, format 为 ndjson (默认) 或 csv
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+}
+
+const CSV_HEADER: &str = "id,original_name,stored_name,file_path,file_size,mime_type,upload_time,\
+is_video,thumbnail_path,video_duration,video_resolution,available_from,available_until,capture_time,latitude,longitude,sha256\n";
+
+// 导出整个文件表, 逐行流式输出
+async fn export_files(
+    Query(params): Query<ExportQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Response, ApiError> {
+    let records = state.files.stream_files();
+
+    let (content_type, filename, body) = match params.format.as_deref().unwrap_or("ndjson") {
+        "ndjson" => {
+            let lines = records.map(|record| {
+                let mut line = serde_json::to_vec(&record?)?;
+                line.push(b'\n');
+                Ok::<_, ServerError>(line)
+            });
+            ("application/x-ndjson", "files.ndjson", Body::from_stream(lines))
+        }
+        "csv" => {
+            let header = stream::once(async { Ok(CSV_HEADER.as_bytes().to_vec()) });
+            let rows = records.map(|record| record.map(|r| csv_row(&r).into_bytes()));
+            ("text/csv; charset=utf-8", "files.csv", Body::from_stream(header.chain(rows)))
+        }
+        other => {
+            return Err(api_error(
+                "导出文件列表失败",
+                ServerError::validation(format!("不支持的导出格式: {}", other)),
             ))
         }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn csv_row(record: &FileRecord) -> String {
+    let fields = [
+        record.id.clone(),
+        record.original_name.clone(),
+        record.stored_name.clone(),
+        record.file_path.clone(),
+        record.file_size.to_string(),
+        record.mime_type.clone(),
+        record.upload_time.to_rfc3339(),
+        record.is_video.to_string(),
+        record.thumbnail_path.clone().unwrap_or_default(),
+        record.video_duration.map(|d| d.to_string()).unwrap_or_default(),
+        record.video_resolution.clone().unwrap_or_default(),
+        record.available_from.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        record.available_until.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        record.capture_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        record.latitude.map(|v| v.to_string()).unwrap_or_default(),
+        record.longitude.map(|v| v.to_string()).unwrap_or_default(),
+        record.sha256.clone().unwrap_or_default(),
+    ];
+
+    let mut row = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    row.push('\n');
+    row
+}
+
+// 按 RFC 4180 转义 CSV 字段
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
 }
 
+// 单次批量查询允许的最大 ID 数量
+const MAX_LOOKUP_IDS: usize = 500;
+
+// 批量查询请求体
+#[derive(Deserialize)]
+struct LookupRequest {
+    ids: Vec<String>,
+}
+
+// 批量查询结果, missing 为未找到的 ID
+#[derive(Serialize)]
+struct LookupResponse {
+    files: Vec<FileRecord>,
+    missing: Vec<String>,
+}
+
+// 批量获取文件信息
+async fn lookup_files(
+    State(state): State<AppState>,
+    Json(req): Json<LookupRequest>,
+) -> std::result::Result<Json<ApiResponse<LookupResponse>>, ApiError> {
+    const CONTEXT: &str = "批量查询文件失败";
+
+    if req.ids.len() > MAX_LOOKUP_IDS {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("单次最多查询 {} 个文件", MAX_LOOKUP_IDS)),
+        ));
+    }
+
+    let files = state
+        .files
+        .get_files_by_ids(&req.ids)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let missing = req
+        .ids
+        .into_iter()
+        .filter(|id| !files.iter().any(|f| &f.id == id))
+        .collect();
+
+    Ok(Json(ApiResponse::success(LookupResponse { files, missing })))
+}
+
+// 文件信息: 文件记录的字段加上与其他文件的关联
+#[derive(Serialize)]
+struct FileInfo {
+    #[serde(flatten)]
+    file: FileRecord,
+    links: Vec<FileLink>,
+}
+
 // 获取单个文件信息
 async fn get_file_info(
     Path(file_id): Path<String>,
     State(state): State<AppState>,
-) -> std::result::Result<Json<ApiResponse<crate::storage::FileRecord>>, (StatusCode, Json<ApiResponse<()>>)> {
-    match state.file_manager.get_file_by_id(&file_id).await {
-        Ok(Some(file)) => Ok(Json(ApiResponse::success(file))),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ApiResponse::error(format!("文件不存在: {}", file_id)))
-        )),
-        Err(e) => {
-            error!("获取文件信息失败: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::error(format!("获取文件信息失败: {}", e)))
-            ))
+) -> std::result::Result<([(header::HeaderName, String); 1], Json<ApiResponse<FileInfo>>), ApiError> {
+    const CONTEXT: &str = "获取文件信息失败";
+
+    let file = match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    };
+    let links = state
+        .files
+        .list_links(&file_id)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(([(header::ETAG, file.etag())], Json(ApiResponse::success(FileInfo { file, links }))))
+}
+
+// 关联请求体, kind 由小写字母、数字和连字符组成
+#[derive(Deserialize)]
+struct LinkRequest {
+    target_id: String,
+    kind: String,
+}
+
+// 添加从当前文件指向另一个文件的关联
+async fn add_file_link(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    Json(req): Json<LinkRequest>,
+) -> std::result::Result<Json<ApiResponse<FileLink>>, ApiError> {
+    const CONTEXT: &str = "添加文件关联失败";
+
+    let valid_kind = !req.kind.is_empty()
+        && req.kind.len() <= 64
+        && req.kind.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_kind {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("无效的关联类型: {}", req.kind))));
+    }
+    if req.target_id == file_id {
+        return Err(api_error(CONTEXT, ServerError::validation("文件不能关联自身")));
+    }
+
+    match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    }
+    match state.files.get_file_by_id(&req.target_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err(api_error(CONTEXT, ServerError::validation(format!("文件 {} 不存在", req.target_id))))
+        }
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    }
+
+    state
+        .files
+        .add_link(&file_id, &req.target_id, &req.kind)
+        .await
+        .map(|link| Json(ApiResponse::success(link)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+// 从 If-Match 请求头或请求体的 version 字段解析期望的版本号
+pub fn expected_version(
+    config: &Config,
+    headers: &HeaderMap,
+    body_version: Option<i64>,
+) -> std::result::Result<Option<i64>, ServerError> {
+    if let Some(value) = headers.get(header::IF_MATCH) {
+        let value = value.to_str().unwrap_or_default().trim();
+        if value == "*" {
+            return Ok(None);
         }
+        let tag = value.trim_start_matches("W/").trim_matches('"');
+        return tag
+            .parse::<i64>()
+            .map(Some)
+            .map_err(|_| ServerError::precondition_failed(format!("无效的 If-Match: {}", value)));
+    }
+
+    if body_version.is_some() {
+        return Ok(body_version);
     }
+
+    if config.server.require_if_match {
+        return Err(ServerError::precondition_required("请携带 If-Match 请求头或 version 字段"));
+    }
+
+    Ok(None)
+}
+
+// 删除参数, force 用于删除受保护的文件
+#[derive(Deserialize)]
+struct DeleteQuery {
+    #[serde(default)]
+    force: bool,
 }
 
 // 删除文件
 async fn delete_file(
     Path(file_id): Path<String>,
+    Query(params): Query<DeleteQuery>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    identity: Option<Extension<ApiKeyIdentity>>,
     State(state): State<AppState>,
-) -> std::result::Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
-    match state.file_manager.delete_file(&file_id).await {
-        Ok(true) => Ok(Json(ApiResponse::success(()))),
-        Ok(false) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ApiResponse::error(format!("文件不存在: {}", file_id)))
-        )),
-        Err(e) => {
-            error!("删除文件失败: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::error(format!("删除文件失败: {}", e)))
-            ))
+    headers: HeaderMap,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "删除文件失败";
+
+    let expected = expected_version(&state.config, &headers, None)
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let record = match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    };
+    let rule = state.config.storage.delete_protection.matching_rule(
+        &record.original_name,
+        record.file_size.max(0) as u64,
+        Utc::now() - record.upload_time,
+    );
+    if let Some(rule) = rule {
+        if !params.force {
+            return Err(api_error(
+                CONTEXT,
+                ServerError::permission_denied(format!("文件受删除保护 ({}), 请由管理员使用 force=true 删除", rule)),
+            ));
         }
+        let identity = identity.map(|Extension(identity)| identity);
+        authorize_force_delete(&state, &file_id, identity.as_ref(), client)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?;
+        info!("强制删除受保护的文件 {} ({}), 来自 {}", file_id, rule, client.ip());
+    }
+
+    state
+        .files
+        .check_lock(&file_id, lock_owner(&headers))
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let deleted_by = client.ip().to_string();
+    match state.files.delete_file(&file_id, Some(&deleted_by), expected).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => Err(api_error(CONTEXT, e)),
     }
 }
 
-// 获取文件统计信息
-async fn get_file_stats(
+// 强制删除需要管理员身份: 启用密钥认证时必须使用 admin 密钥, 配置了外部授权服务时还需要它允许 admin 操作.
+// 两者都未配置时只允许本机访问
+async fn authorize_force_delete(
+    state: &AppState,
+    file_id: &str,
+    identity: Option<&ApiKeyIdentity>,
+    client: SocketAddr,
+) -> crate::error::Result<()> {
+    let auth_enabled = state.config.auth.enabled();
+    if auth_enabled && !identity.is_some_and(|identity| identity.access == KeyAccess::Admin) {
+        return Err(ServerError::permission_denied("强制删除需要管理员密钥"));
+    }
+    if state.policy.is_some() {
+        let input = PolicyInput {
+            user: identity.map(|identity| identity.name.clone()),
+            action: PolicyAction::Admin,
+            resource: PolicyResource {
+                method: "DELETE".to_string(),
+                path: format!("/api/files/{}", file_id),
+            },
+        };
+        return middleware::check_policy(state, &input).await;
+    }
+    if !auth_enabled && !client.ip().is_loopback() {
+        return Err(ServerError::permission_denied("强制删除仅允许本机访问"));
+    }
+    Ok(())
+}
+
+// 回收站中的文件, 最近删除的在前
+async fn list_trash(
+    Query(params): Query<ListFilesQuery>,
     State(state): State<AppState>,
-) -> std::result::Result<Json<ApiResponse<crate::storage::FileStats>>, (StatusCode, Json<ApiResponse<()>>)> {
-    match state.file_manager.get_file_stats().await {
-        Ok(stats) => Ok(Json(ApiResponse::success(stats))),
-        Err(e) => {
-            error!("获取统计信息失败: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::error(format!("获取统计信息失败: {}", e)))
-            ))
+) -> std::result::Result<Json<ApiResponse<Vec<TrashedFile>>>, ApiError> {
+    state
+        .files
+        .list_trash(params.limit, params.offset)
+        .await
+        .map(|files| Json(ApiResponse::success(files)))
+        .map_err(|e| api_error("获取回收站失败", e))
+}
+
+// 从回收站恢复文件, ID 和链接保持不变
+async fn restore_file(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<FileRecord>>, ApiError> {
+    const CONTEXT: &str = "恢复文件失败";
+
+    match state.files.restore_file(&file_id).await {
+        Ok(Some(record)) => Ok(Json(ApiResponse::success(record))),
+        Ok(None) => Err(api_error(CONTEXT, ServerError::not_found(format!("回收站中的文件 {}", file_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
+
+// 永久删除回收站中的文件
+async fn purge_trashed_file(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "永久删除文件失败";
+
+    match state.files.purge_trashed(&file_id).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err(api_error(CONTEXT, ServerError::not_found(format!("回收站中的文件 {}", file_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
+
+// 文件可下载时间窗口请求体, 字段为空表示不限制
+#[derive(Deserialize)]
+struct AvailabilityRequest {
+    available_from: Option<DateTime<Utc>>,
+    available_until: Option<DateTime<Utc>>,
+    version: Option<i64>,
+}
+
+// 设置文件的开放时间和过期时间
+async fn update_file_availability(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<AvailabilityRequest>,
+) -> std::result::Result<([(header::HeaderName, String); 1], Json<ApiResponse<FileRecord>>), ApiError> {
+    const CONTEXT: &str = "设置文件可用时间失败";
+
+    if let (Some(from), Some(until)) = (req.available_from, req.available_until) {
+        if from >= until {
+            return Err(api_error(
+                CONTEXT,
+                ServerError::validation("available_from 必须早于 available_until"),
+            ));
         }
     }
+
+    let expected = expected_version(&state.config, &headers, req.version)
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let updated = state
+        .files
+        .set_availability(&file_id, req.available_from, req.available_until, expected)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    if !updated {
+        return Err(missing_file_error(&state, &file_id, CONTEXT).await);
+    }
+
+    match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(file)) => Ok(([(header::ETAG, file.etag())], Json(ApiResponse::success(file)))),
+        Ok(None) => Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
 }
 
-// 文件服务接口 (占位符)
-async fn serve_file(
-    Path(_path): Path<String>,
-) -> std::result::Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
-    Err((
-        StatusCode::NOT_IMPLEMENTED,
-        Json(ApiResponse::error("文件服务功能将在后续任务中实现".to_string()))
-    ))
-}
\ No newline at end of file
+// 修改被锁定的文件时, 通过该请求头声明锁的持有者
+pub const LOCK_OWNER_HEADER: &str = "x-lock-owner";
+
+// 锁的默认有效期和最长有效期 (秒)
+const DEFAULT_LOCK_TTL: i64 = 3600;
+const MAX_LOCK_TTL: i64 = 24 * 3600;
+
+pub fn lock_owner(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(LOCK_OWNER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|owner| !owner.is_empty())
+}
+
+// 加锁请求体
+#[derive(Deserialize)]
+struct LockRequest {
+    owner: String,
+    ttl_seconds: Option<i64>,
+}
+
+// 检出文件: 加锁或由持有者续期
+async fn lock_file(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    Json(req): Json<LockRequest>,
+) -> std::result::Result<Json<ApiResponse<FileLock>>, ApiError> {
+    const CONTEXT: &str = "锁定文件失败";
+
+    let owner = req.owner.trim();
+    if owner.is_empty() {
+        return Err(api_error(CONTEXT, ServerError::validation("锁的持有者不能为空")));
+    }
+    let ttl = req.ttl_seconds.unwrap_or(DEFAULT_LOCK_TTL);
+    if !(1..=MAX_LOCK_TTL).contains(&ttl) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("锁的有效期必须在 1-{} 秒之间", MAX_LOCK_TTL)),
+        ));
+    }
+
+    match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    }
+
+    state
+        .files
+        .acquire_lock(&file_id, owner, chrono::Duration::seconds(ttl))
+        .await
+        .map(|lock| Json(ApiResponse::success(lock)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+// 查询文件当前的锁, 未锁定时 data 为 null
+async fn get_file_lock(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Option<FileLock>>>, ApiError> {
+    state
+        .files
+        .get_lock(&file_id)
+        .await
+        .map(|lock| Json(ApiResponse::success(lock)))
+        .map_err(|e| api_error("获取文件锁失败", e))
+}
+
+// 释放锁, 需要通过 X-Lock-Owner 声明持有者
+async fn unlock_file(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "解锁文件失败";
+
+    let owner = lock_owner(&headers).ok_or_else(|| {
+        api_error(CONTEXT, ServerError::validation("请通过 X-Lock-Owner 请求头指定锁的持有者"))
+    })?;
+
+    match state.files.release_lock(&file_id, owner).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err(api_error(CONTEXT, ServerError::not_found(format!("文件 {} 的锁", file_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
+
+// 只读模式开关请求体
+#[derive(Deserialize, Serialize)]
+struct ReadOnlyMode {
+    enabled: bool,
+}
+
+// 查询只读模式状态
+async fn get_read_only(State(state): State<AppState>) -> Json<ApiResponse<ReadOnlyMode>> {
+    Json(ApiResponse::success(ReadOnlyMode {
+        enabled: state.read_only.load(Ordering::Relaxed),
+    }))
+}
+
+// 运行时切换只读模式
+async fn set_read_only(
+    State(state): State<AppState>,
+    Json(req): Json<ReadOnlyMode>,
+) -> Json<ApiResponse<ReadOnlyMode>> {
+    state.read_only.store(req.enabled, Ordering::Relaxed);
+    info!("只读模式已{}", if req.enabled { "开启" } else { "关闭" });
+    Json(ApiResponse::success(req))
+}
+
+// 立即执行一次数据库维护
+async fn maintain_database(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<MaintenanceReport>>, ApiError> {
+    state
+        .file_manager
+        .maintain()
+        .await
+        .map(|report| Json(ApiResponse::success(report)))
+        .map_err(|e| api_error("数据库维护失败", e))
+}
+
+// 查询视频工具链的检测结果
+async fn get_video_toolchain(State(state): State<AppState>) -> Json<ApiResponse<VideoToolchain>> {
+    Json(ApiResponse::success(state.video_toolchain.as_ref().clone()))
+}
+
+// 临时目录的占用和清理统计
+async fn get_temp_stats(State(state): State<AppState>) -> Json<ApiResponse<TempStats>> {
+    Json(ApiResponse::success(state.temp.stats()))
+}
+
+// 立即清理过期的临时文件
+async fn cleanup_temp(State(state): State<AppState>) -> Json<ApiResponse<TempCleanupReport>> {
+    Json(ApiResponse::success(state.temp.cleanup()))
+}
+
+// 立即清理所属文件已删除的缩略图等派生文件
+async fn cleanup_derived(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<DerivedCleanupReport>>, ApiError> {
+    let min_age = std::time::Duration::from_secs(state.config.storage.derived.min_age);
+    storage::derived::cleanup_orphans(&state.file_manager, min_age)
+        .await
+        .map(|report| Json(ApiResponse::success(report)))
+        .map_err(|e| api_error("清理派生文件失败", e))
+}
+
+// 获取文件统计信息
+async fn get_file_stats(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<crate::storage::FileStats>>, ApiError> {
+    let mut stats = state
+        .file_manager
+        .get_file_stats()
+        .await
+        .map_err(|e| api_error("获取统计信息失败", e))?;
+    stats.temp_size = state.temp.usage().bytes;
+    Ok(Json(ApiResponse::success(stats)))
+}
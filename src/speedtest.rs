@@ -0,0 +1,113 @@
+// 测速接口 - 收发指定大小的生成数据, 不读写存储, 用来区分网络慢还是服务器慢
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, ApiResponse};
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+const MB: u64 = 1024 * 1024;
+
+// 单次测速的默认大小和上限 (MB)
+const DEFAULT_SIZE_MB: u64 = 10;
+const MAX_SIZE_MB: u64 = 1024;
+
+// 下载时每块的大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct SpeedTestQuery {
+    pub size_mb: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpeedTestResult {
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    // 兆比特每秒
+    pub mbps: f64,
+}
+
+// 接收并丢弃请求体, 返回服务端测得的接收速度
+pub async fn upload(body: Body) -> std::result::Result<Json<ApiResponse<SpeedTestResult>>, ApiError> {
+    const CONTEXT: &str = "上传测速失败";
+
+    let started = Instant::now();
+    let mut bytes = 0u64;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| api_error(CONTEXT, ServerError::from(e)))?;
+        bytes += chunk.len() as u64;
+        if bytes > MAX_SIZE_MB * MB {
+            return Err(api_error(
+                CONTEXT,
+                ServerError::validation(format!("测速数据不能超过 {} MB", MAX_SIZE_MB)),
+            ));
+        }
+    }
+
+    let elapsed = started.elapsed();
+    let mbps = if elapsed.as_secs_f64() > 0.0 {
+        bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1_000_000.0
+    } else {
+        0.0
+    };
+    Ok(Json(ApiResponse::success(SpeedTestResult {
+        bytes,
+        elapsed_ms: elapsed.as_millis() as u64,
+        mbps,
+    })))
+}
+
+// 返回 size_mb 兆字节的生成数据. 数据不可压缩, 避免代理压缩后测得的速度偏高
+pub async fn download(Query(params): Query<SpeedTestQuery>) -> std::result::Result<Response, ApiError> {
+    let size_mb = params.size_mb.unwrap_or(DEFAULT_SIZE_MB);
+    if !(1..=MAX_SIZE_MB).contains(&size_mb) {
+        return Err(api_error(
+            "下载测速失败",
+            ServerError::validation(format!("测速大小必须在 1-{} MB 之间", MAX_SIZE_MB)),
+        ));
+    }
+
+    let total = size_mb * MB;
+    let chunk = Bytes::from(noise(CHUNK_SIZE));
+    let chunks = stream::unfold(0u64, move |sent| {
+        let chunk = chunk.clone();
+        async move {
+            if sent >= total {
+                return None;
+            }
+            let len = (total - sent).min(CHUNK_SIZE as u64);
+            Some((Ok::<_, std::io::Error>(chunk.slice(..len as usize)), sent + len))
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_LENGTH, total.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response())
+}
+
+// xorshift 伪随机数据, 每次请求使用不同的种子
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = uuid::Uuid::new_v4().as_u64_pair().0 | 1;
+    let mut data = Vec::with_capacity(len + 8);
+    while data.len() < len {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data.truncate(len);
+    data
+}
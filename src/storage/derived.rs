@@ -0,0 +1,146 @@
+// 派生文件清理 - .cache/<kind> 下的缩略图、缩放图等以文件 ID 开头命名, 所属记录删除后一并清理
+use super::periodic::spawn_periodic;
+use super::FileManager;
+use crate::config::DerivedConfig;
+use crate::error::{Result, ServerError};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DerivedUsage {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DerivedCleanupReport {
+    // 检查过的派生条目数
+    pub scanned: u64,
+    pub removed_files: u64,
+    pub removed_bytes: u64,
+    // 按用途统计删除的内容, 例如 thumbnails、images
+    pub kinds: BTreeMap<String, DerivedUsage>,
+}
+
+// 删除所属文件记录已不存在、且修改时间早于 min_age 之前的派生条目
+pub async fn cleanup_orphans(file_manager: &FileManager, min_age: Duration) -> Result<DerivedCleanupReport> {
+    let cache_root = file_manager.get_storage_path().join(".cache");
+    let file_ids = file_manager.file_ids().await?;
+
+    tokio::task::spawn_blocking(move || remove_orphans(&cache_root, &file_ids, min_age))
+        .await
+        .map_err(|e| ServerError::Internal(e.into()))
+}
+
+fn remove_orphans(cache_root: &Path, file_ids: &HashSet<String>, min_age: Duration) -> DerivedCleanupReport {
+    let mut report = DerivedCleanupReport::default();
+    let now = SystemTime::now();
+    let Ok(kinds) = std::fs::read_dir(cache_root) else {
+        return report;
+    };
+
+    for kind in kinds.flatten() {
+        let Ok(entries) = std::fs::read_dir(kind.path()) else {
+            continue;
+        };
+        let kind_name = kind.file_name().to_string_lossy().to_string();
+
+        for entry in entries.flatten() {
+            // 不是以文件 ID 开头的条目不归这里管
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(owner) = owner_id(&name) else {
+                continue;
+            };
+            report.scanned += 1;
+            if file_ids.contains(owner) {
+                continue;
+            }
+
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age < min_age {
+                continue;
+            }
+
+            // 目录条目 (例如按文件 ID 存放的分段输出) 整个删除
+            let path = entry.path();
+            let (files, bytes) = if metadata.is_dir() {
+                let usage = dir_usage(&path);
+                if std::fs::remove_dir_all(&path).is_err() {
+                    continue;
+                }
+                usage
+            } else {
+                if std::fs::remove_file(&path).is_err() {
+                    continue;
+                }
+                (1, metadata.len())
+            };
+
+            report.removed_files += files;
+            report.removed_bytes += bytes;
+            let usage = report.kinds.entry(kind_name.clone()).or_default();
+            usage.files += files;
+            usage.bytes += bytes;
+        }
+    }
+    report
+}
+
+// 条目名开头的文件 ID, 例如 <id>.webp、<id>-v2-320x0-contain.png
+pub fn owner_id(name: &str) -> Option<&str> {
+    let id = name.get(..36)?;
+    let rest = &name[36..];
+    let separated = rest.is_empty() || rest.starts_with('.') || rest.starts_with('-');
+    (separated && Uuid::parse_str(id).is_ok()).then_some(id)
+}
+
+// 目录下全部文件的数量和总大小
+pub fn dir_usage(dir: &Path) -> (u64, u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (0, 0);
+    };
+
+    let mut usage = (0, 0);
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            let (files, bytes) = dir_usage(&entry.path());
+            usage.0 += files;
+            usage.1 += bytes;
+        } else {
+            usage.0 += 1;
+            usage.1 += metadata.len();
+        }
+    }
+    usage
+}
+
+// 启动后台清理任务, 每隔 cleanup_interval 秒清理一次孤儿派生文件
+pub fn spawn_cleaner(file_manager: Arc<FileManager>, config: DerivedConfig) -> JoinHandle<()> {
+    let min_age = Duration::from_secs(config.min_age);
+    spawn_periodic("派生文件清理", config.cleanup_interval, move || {
+        let file_manager = file_manager.clone();
+        async move {
+            let report = cleanup_orphans(&file_manager, min_age).await?;
+            if report.removed_files > 0 {
+                info!("清理孤儿派生文件: {} 个, {} 字节", report.removed_files, report.removed_bytes);
+            }
+            Ok(())
+        }
+    })
+}
@@ -1,9 +1,18 @@
+use crate::config::{NamingPolicy, RecordCacheConfig};
 use crate::error::{Result, ServerError};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use sqlx::{query, Row};
+use futures::StreamExt;
+use mini_moka::sync::Cache;
+use super::query::{FileQuery, FileSort, SortField};
+use super::uploads::SessionLocks;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{query, query_as, Row};
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,30 +28,325 @@ pub struct FileRecord {
     pub thumbnail_path: Option<String>,
     pub video_duration: Option<i32>,
     pub video_resolution: Option<String>,
+    pub available_from: Option<DateTime<Utc>>,
+    pub available_until: Option<DateTime<Utc>>,
+    pub version: i64,
+    pub capture_time: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub perceptual_hash: Option<i64>,
+    pub sha256: Option<String>,
+}
+
+impl FileRecord {
+    // 记录的 ETag, 每次修改元数据后 version 递增
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+
+    // 检查给定时间是否处于文件的可下载时间窗口内
+    pub fn check_availability(&self, now: DateTime<Utc>) -> Result<()> {
+        if let Some(from) = self.available_from {
+            if now < from {
+                return Err(ServerError::permission_denied(format!(
+                    "文件将于 {} 开放下载",
+                    from.to_rfc3339()
+                )));
+            }
+        }
+
+        if let Some(until) = self.available_until {
+            if now >= until {
+                return Err(ServerError::gone(format!(
+                    "文件已于 {} 停止提供下载",
+                    until.to_rfc3339()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// 已删除文件的墓碑记录, 用于对旧链接返回 410 Gone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileTombstone {
+    pub id: String,
+    pub original_name: String,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<String>,
+}
+
+// 回收站中的文件, 恢复前不出现在任何文件查询中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedFile {
+    #[serde(flatten)]
+    pub file: FileRecord,
+    pub trashed_at: DateTime<Utc>,
+    pub trashed_by: Option<String>,
+}
+
+// 文件列表的精简投影, 只包含界面滚动列表需要的字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSummary {
+    pub id: String,
+    pub original_name: String,
+    pub file_size: i64,
+    pub upload_time: DateTime<Utc>,
+    pub has_thumbnail: bool,
+}
+
+// 文件检出锁
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLock {
+    pub file_id: String,
+    pub owner: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+// 从文件内容中提取的文本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileContent {
+    pub file_id: String,
+    pub source: String,
+    pub content: String,
+    pub indexed_at: DateTime<Utc>,
+}
+
+// 文件之间带类型的关联, 例如 derived-from、belongs-with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileLink {
+    pub source_id: String,
+    pub target_id: String,
+    pub kind: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 有序的文件集合, 例如一门培训课程的全部视频
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub file_ids: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 某个观看者在某个视频上的播放进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackPosition {
+    pub file_id: String,
+    pub viewer: String,
+    // 单位为秒
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 分块上传会话, 已收到的块序号存放在 upload_session_chunks 表中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: String,
+    pub original_name: String,
+    pub total_size: i64,
+    pub chunk_size: i64,
+    pub received_chunks: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UploadSession {
+    pub fn chunk_count(&self) -> i64 {
+        (self.total_size + self.chunk_size - 1) / self.chunk_size
+    }
+
+    // 第 index 块的起始位置和长度, 最后一块可能较短
+    pub fn chunk_range(&self, index: i64) -> Option<(u64, u64)> {
+        if !(0..self.chunk_count()).contains(&index) {
+            return None;
+        }
+        let start = index * self.chunk_size;
+        Some((start as u64, (self.total_size - start).min(self.chunk_size) as u64))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received_chunks.len() as i64 == self.chunk_count()
+    }
+}
+
+// 按 Idempotency-Key 保存的响应, 重放同一个键时原样返回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotentResponse {
+    pub key: String,
+    pub scope: String,
+    // 序列化后的响应数据, 第一个请求还没有完成时为空
+    pub response: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 蜜罐文件, 正常情况下不应有人访问, 任何下载都会触发告警
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryFile {
+    pub file_id: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 蜜罐文件被下载时记录的客户端信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryAlert {
+    pub id: i64,
+    pub file_id: String,
+    pub client_ip: String,
+    pub method: String,
+    pub path: String,
+    pub user_agent: Option<String>,
+    // 除凭据以外的全部请求头
+    pub headers: BTreeMap<String, String>,
+    pub triggered_at: DateTime<Utc>,
+}
+
+// 某个用户某一天的流量统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub day: String,
+    pub subject: String,
+    pub uploaded_bytes: i64,
+    pub downloaded_bytes: i64,
+    pub requests: i64,
+}
+
+// 经纬度矩形范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoBounds {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl GeoBounds {
+    // 解析 "min_lon,min_lat,max_lon,max_lat", 顺序与 GeoJSON 的 bbox 一致
+    pub fn parse(value: &str) -> Result<GeoBounds> {
+        let invalid = || ServerError::validation(format!("无效的坐标范围: {}", value));
+
+        let numbers = value
+            .split(',')
+            .map(|part| part.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let [min_lon, min_lat, max_lon, max_lat] = numbers[..] else {
+            return Err(invalid());
+        };
+
+        let valid_lon = |lon: f64| (-180.0..=180.0).contains(&lon);
+        let valid_lat = |lat: f64| (-90.0..=90.0).contains(&lat);
+        if !valid_lon(min_lon) || !valid_lon(max_lon) || !valid_lat(min_lat) || !valid_lat(max_lat) || min_lat > max_lat {
+            return Err(invalid());
+        }
+
+        Ok(GeoBounds { min_lon, min_lat, max_lon, max_lat })
+    }
+}
+
+// 文件搜索条件, 为空的条件不参与过滤
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileSearch {
+    // 文件名包含该字符串, 不区分大小写
+    pub name: Option<String>,
+    // MIME 类型前缀, 例如 image/ 或 application/zip
+    pub mime_type: Option<String>,
+    pub uploaded_after: Option<DateTime<Utc>>,
+    pub uploaded_before: Option<DateTime<Utc>>,
+    // 全文搜索, 以空白分隔的每个词都要出现在文件名或 MIME 类型中, 不区分大小写
+    pub text: Option<String>,
+    pub is_video: Option<bool>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+}
+
+// 照片时间线中的一组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoTimelineEntry {
+    pub period: String,
+    pub count: i64,
+    pub cover_id: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct FileManager {
     pool: SqlitePool,
     storage_path: PathBuf,
+    naming: NamingPolicy,
+    // get_file_by_id 的结果缓存, 未启用时为 None. 只缓存存在的记录, 每次修改记录时移除对应条目
+    records: Option<Cache<String, FileRecord>>,
+    // 删除时移入回收站而不是直接删除
+    trash: bool,
+    // 分块上传会话的锁, 见 uploads::SessionLocks
+    upload_locks: SessionLocks,
 }
 
 impl FileManager {
     pub async fn new(database_url: &str, storage_path: PathBuf) -> Result<Self> {
+        // 内存数据库每个连接都是独立的库, 只能使用单个连接
+        let max_connections = if database_url.contains(":memory:") { 1 } else { 20 };
+
+        // 数据库文件不存在时自动创建
+        let options = SqliteConnectOptions::from_str(database_url)
+            .map_err(ServerError::Database)?
+            .create_if_missing(true);
+
         let pool = SqlitePoolOptions::new()
-            .max_connections(20)
-            .connect(database_url)
+            .max_connections(max_connections)
+            .connect_with(options)
             .await
-            .map_err(|e| ServerError::Database(e))?;
+            .map_err(ServerError::Database)?;
 
-        let manager = Self { pool, storage_path };
+        let manager = Self {
+            pool,
+            storage_path,
+            naming: NamingPolicy::default(),
+            records: None,
+            trash: false,
+            upload_locks: SessionLocks::default(),
+        };
         manager.init().await?;
         Ok(manager)
     }
 
+    // 设置新文件的命名方式, 默认为 uuid
+    pub fn with_naming_policy(mut self, naming: NamingPolicy) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    // 启用回收站, 默认直接删除
+    pub fn with_trash(mut self, enabled: bool) -> Self {
+        self.trash = enabled;
+        self
+    }
+
+    // 启用文件记录缓存, ttl 或 max_entries 为 0 时不缓存
+    pub fn with_record_cache(mut self, config: &RecordCacheConfig) -> Self {
+        self.records = (config.ttl > 0 && config.max_entries > 0).then(|| {
+            Cache::builder()
+                .max_capacity(config.max_entries)
+                .time_to_live(std::time::Duration::from_secs(config.ttl))
+                .build()
+        });
+        self
+    }
+
+    fn invalidate_record(&self, file_id: &str) {
+        if let Some(records) = &self.records {
+            records.invalidate(&file_id.to_string());
+        }
+    }
+
     pub async fn init(&self) -> Result<()> {
         std::fs::create_dir_all(&self.storage_path)
-            .map_err(|e| ServerError::Io(e))?;
+            .map_err(ServerError::Io)?;
 
         let create_files_table = r#"
             CREATE TABLE IF NOT EXISTS files (
@@ -56,38 +360,346 @@ impl FileManager {
                 is_video BOOLEAN NOT NULL DEFAULT FALSE,
                 thumbnail_path TEXT,
                 video_duration INTEGER,
-                video_resolution TEXT
+                video_resolution TEXT,
+                available_from TEXT,
+                available_until TEXT,
+                version INTEGER NOT NULL DEFAULT 1,
+                capture_time TEXT,
+                latitude REAL,
+                longitude REAL,
+                perceptual_hash INTEGER,
+                sha256 TEXT
             )
         "#;
 
         query(create_files_table)
             .execute(&self.pool)
             .await
-            .map_err(|e| ServerError::Database(e))?;
+            .map_err(ServerError::Database)?;
+
+        let create_tombstones_table = r#"
+            CREATE TABLE IF NOT EXISTS file_tombstones (
+                id TEXT PRIMARY KEY,
+                original_name TEXT NOT NULL,
+                deleted_at TEXT NOT NULL,
+                deleted_by TEXT
+            )
+        "#;
+
+        query(create_tombstones_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 回收站, 列与 files 表相同, 另加移入回收站的时间和操作者
+        let create_trash_table = r#"
+            CREATE TABLE IF NOT EXISTS trashed_files (
+                id TEXT PRIMARY KEY,
+                original_name TEXT NOT NULL,
+                stored_name TEXT NOT NULL,
+                file_path TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                mime_type TEXT NOT NULL,
+                upload_time TEXT NOT NULL,
+                is_video BOOLEAN NOT NULL DEFAULT FALSE,
+                thumbnail_path TEXT,
+                video_duration INTEGER,
+                video_resolution TEXT,
+                available_from TEXT,
+                available_until TEXT,
+                version INTEGER NOT NULL DEFAULT 1,
+                capture_time TEXT,
+                latitude REAL,
+                longitude REAL,
+                perceptual_hash INTEGER,
+                sha256 TEXT,
+                trashed_at TEXT NOT NULL,
+                trashed_by TEXT
+            )
+        "#;
+
+        for sql in [
+            create_trash_table,
+            "CREATE INDEX IF NOT EXISTS idx_trashed_at ON trashed_files(trashed_at)",
+            "CREATE INDEX IF NOT EXISTS idx_trashed_file_path ON trashed_files(file_path)",
+        ] {
+            query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        // 文件检出锁, 过期后自动失效
+        let create_locks_table = r#"
+            CREATE TABLE IF NOT EXISTS file_locks (
+                file_id TEXT PRIMARY KEY,
+                owner TEXT NOT NULL,
+                acquired_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )
+        "#;
+
+        query(create_locks_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 从文件内容中提取的文本, 例如图片的 OCR 结果, 供内容搜索使用
+        let create_contents_table = r#"
+            CREATE TABLE IF NOT EXISTS file_contents (
+                file_id TEXT PRIMARY KEY,
+                source TEXT NOT NULL,
+                content TEXT NOT NULL,
+                indexed_at TEXT NOT NULL
+            )
+        "#;
+
+        query(create_contents_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 文件之间的关联, 有方向: source 指向 target
+        let create_links_table = r#"
+            CREATE TABLE IF NOT EXISTS file_links (
+                source_id TEXT NOT NULL,
+                target_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (source_id, target_id, kind)
+            )
+        "#;
+
+        query(create_links_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        query("CREATE INDEX IF NOT EXISTS idx_file_links_target ON file_links(target_id)")
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 文件集合及其中按顺序排列的文件
+        let create_collections_table = r#"
+            CREATE TABLE IF NOT EXISTS collections (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+        "#;
+        let create_collection_items_table = r#"
+            CREATE TABLE IF NOT EXISTS collection_items (
+                collection_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                file_id TEXT NOT NULL,
+                PRIMARY KEY (collection_id, position)
+            )
+        "#;
+
+        for sql in [create_collections_table, create_collection_items_table] {
+            query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        // 视频播放进度, 每个观看者每个视频一条
+        let create_positions_table = r#"
+            CREATE TABLE IF NOT EXISTS playback_positions (
+                file_id TEXT NOT NULL,
+                viewer TEXT NOT NULL,
+                position REAL NOT NULL,
+                duration REAL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (file_id, viewer)
+            )
+        "#;
+
+        query(create_positions_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 按天汇总的上传/下载流量, day 为 UTC 日期
+        let create_usage_table = r#"
+            CREATE TABLE IF NOT EXISTS usage_daily (
+                day TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                uploaded_bytes INTEGER NOT NULL DEFAULT 0,
+                downloaded_bytes INTEGER NOT NULL DEFAULT 0,
+                requests INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, subject)
+            )
+        "#;
+
+        query(create_usage_table)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 分块上传会话及其已收到的块
+        let create_upload_sessions_table = r#"
+            CREATE TABLE IF NOT EXISTS upload_sessions (
+                id TEXT PRIMARY KEY,
+                original_name TEXT NOT NULL,
+                total_size INTEGER NOT NULL,
+                chunk_size INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+        "#;
+        let create_upload_chunks_table = r#"
+            CREATE TABLE IF NOT EXISTS upload_session_chunks (
+                session_id TEXT NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                PRIMARY KEY (session_id, chunk_index)
+            )
+        "#;
+
+        // 按 Idempotency-Key 保存的响应, scope 是请求针对的对象, 例如上传会话 ID
+        let create_idempotency_table = r#"
+            CREATE TABLE IF NOT EXISTS idempotency_keys (
+                key TEXT PRIMARY KEY,
+                scope TEXT NOT NULL,
+                response TEXT,
+                created_at TEXT NOT NULL
+            )
+        "#;
+
+        for sql in [create_upload_sessions_table, create_upload_chunks_table, create_idempotency_table] {
+            query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        // 蜜罐文件及其触发的告警, 文件删除后告警仍然保留
+        let create_canaries_table = r#"
+            CREATE TABLE IF NOT EXISTS canary_files (
+                file_id TEXT PRIMARY KEY,
+                note TEXT,
+                created_at TEXT NOT NULL
+            )
+        "#;
+        let create_canary_alerts_table = r#"
+            CREATE TABLE IF NOT EXISTS canary_alerts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_id TEXT NOT NULL,
+                client_ip TEXT NOT NULL,
+                method TEXT NOT NULL,
+                path TEXT NOT NULL,
+                user_agent TEXT,
+                headers TEXT NOT NULL,
+                triggered_at TEXT NOT NULL
+            )
+        "#;
+
+        for sql in [create_canaries_table, create_canary_alerts_table] {
+            query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        // 兼容旧版本数据库: 补齐后续新增的列
+        self.ensure_column("files", "available_from", "TEXT").await?;
+        self.ensure_column("files", "available_until", "TEXT").await?;
+        self.ensure_column("files", "version", "INTEGER NOT NULL DEFAULT 1").await?;
+        self.ensure_column("files", "capture_time", "TEXT").await?;
+        self.ensure_column("files", "latitude", "REAL").await?;
+        self.ensure_column("files", "longitude", "REAL").await?;
+        self.ensure_column("files", "perceptual_hash", "INTEGER").await?;
+        self.ensure_column("files", "sha256", "TEXT").await?;
 
         let create_index = r#"
             CREATE INDEX IF NOT EXISTS idx_upload_time ON files(upload_time DESC);
             CREATE INDEX IF NOT EXISTS idx_is_video ON files(is_video);
             CREATE INDEX IF NOT EXISTS idx_file_size ON files(file_size DESC);
+            CREATE INDEX IF NOT EXISTS idx_capture_time ON files(capture_time DESC);
+            CREATE INDEX IF NOT EXISTS idx_location ON files(latitude, longitude);
+            CREATE INDEX IF NOT EXISTS idx_sha256 ON files(sha256);
+            CREATE INDEX IF NOT EXISTS idx_file_path ON files(file_path);
         "#;
 
         query(create_index)
             .execute(&self.pool)
             .await
-            .map_err(|e| ServerError::Database(e))?;
+            .map_err(ServerError::Database)?;
+
+        // 文件名和 MIME 类型的全文索引. trigram 分词支持任意位置的子串, 中文文件名也能搜索
+        let create_fts_table = r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
+                file_id UNINDEXED,
+                original_name,
+                mime_type,
+                tokenize = 'trigram'
+            )
+        "#;
+        // 补齐建立索引之前已有的记录
+        let backfill_fts = r#"
+            INSERT INTO files_fts (file_id, original_name, mime_type)
+            SELECT id, original_name, mime_type FROM files
+            WHERE id NOT IN (SELECT file_id FROM files_fts)
+        "#;
+
+        for sql in [create_fts_table, backfill_fts] {
+            query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        Ok(())
+    }
+
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let rows = query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        let exists = rows
+            .iter()
+            .any(|row| row.get::<String, _>("name") == column);
+
+        if !exists {
+            query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
 
         Ok(())
     }
 
     pub async fn save_file_record(&self, record: &FileRecord) -> Result<()> {
-        let sql = r#"
+        self.insert_record(record, false).await.map(|_| ())
+    }
+
+    // 保存与已有记录共用存储文件的记录. 已经没有内容相同的记录使用该文件时 (被并发删除或替换) 不写入, 返回 false.
+    // 判断和写入在同一条语句中完成, 删除和替换内容时的引用计数要么看到这条记录, 要么先于它完成
+    pub async fn save_shared_record(&self, record: &FileRecord) -> Result<bool> {
+        self.insert_record(record, true).await
+    }
+
+    async fn insert_record(&self, record: &FileRecord, shared: bool) -> Result<bool> {
+        let sql = format!(
+            r#"
             INSERT INTO files (
                 id, original_name, stored_name, file_path, file_size, mime_type, 
-                upload_time, is_video, thumbnail_path, video_duration, video_resolution
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#;
+                upload_time, is_video, thumbnail_path, video_duration, video_resolution,
+                available_from, available_until, version, capture_time, latitude, longitude,
+                perceptual_hash, sha256
+            ) SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? {}
+        "#,
+            if shared { "WHERE EXISTS (SELECT 1 FROM files WHERE file_path = ? AND sha256 = ?)" } else { "" }
+        );
 
-        query(sql)
+        // 记录和全文索引在同一事务中写入
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        let mut insert = query(&sql)
             .bind(&record.id)
             .bind(&record.original_name)
             .bind(&record.stored_name)
@@ -99,147 +711,1522 @@ impl FileManager {
             .bind(&record.thumbnail_path)
             .bind(record.video_duration)
             .bind(&record.video_resolution)
-            .execute(&self.pool)
+            .bind(record.available_from.map(|t| t.to_rfc3339()))
+            .bind(record.available_until.map(|t| t.to_rfc3339()))
+            .bind(record.version)
+            .bind(record.capture_time.map(|t| t.to_rfc3339()))
+            .bind(record.latitude)
+            .bind(record.longitude)
+            .bind(record.perceptual_hash)
+            .bind(&record.sha256);
+        if shared {
+            insert = insert.bind(&record.file_path).bind(&record.sha256);
+        }
+        let inserted = insert
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?
+            .rows_affected();
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        query("INSERT INTO files_fts (file_id, original_name, mime_type) VALUES (?, ?, ?)")
+            .bind(&record.id)
+            .bind(&record.original_name)
+            .bind(&record.mime_type)
+            .execute(&mut *tx)
             .await
-            .map_err(|e| ServerError::Database(e))?;
+            .map_err(ServerError::Database)?;
 
-        Ok(())
+        tx.commit().await.map_err(ServerError::Database)?;
+        self.invalidate_record(&record.id);
+
+        Ok(true)
     }
 
+    // 读取文件记录, 启用缓存时优先使用缓存
     pub async fn get_file_by_id(&self, file_id: &str) -> Result<Option<FileRecord>> {
-        let sql = "SELECT * FROM files WHERE id = ?";
-        
-        let row = query(sql)
+        let Some(records) = &self.records else {
+            return self.load_file_by_id(file_id).await;
+        };
+        if let Some(record) = records.get(&file_id.to_string()) {
+            return Ok(Some(record));
+        }
+
+        let record = self.load_file_by_id(file_id).await?;
+        if let Some(record) = &record {
+            records.insert(file_id.to_string(), record.clone());
+        }
+        Ok(record)
+    }
+
+    // 直接查询数据库, 修改记录前的版本检查使用这里的结果
+    async fn load_file_by_id(&self, file_id: &str) -> Result<Option<FileRecord>> {
+        query_as("SELECT * FROM files WHERE id = ?")
             .bind(file_id)
             .fetch_optional(&self.pool)
             .await
-            .map_err(|e| ServerError::Database(e))?;
-
-        if let Some(row) = row {
-            let upload_time_str: String = row.get("upload_time");
-            let upload_time = DateTime::parse_from_rfc3339(&upload_time_str)
-                .map_err(|e| ServerError::Internal(e.into()))?
-                .with_timezone(&Utc);
-
-            Ok(Some(FileRecord {
-                id: row.get("id"),
-                original_name: row.get("original_name"),
-                stored_name: row.get("stored_name"),
-                file_path: row.get("file_path"),
-                file_size: row.get("file_size"),
-                mime_type: row.get("mime_type"),
-                upload_time,
-                is_video: row.get("is_video"),
-                thumbnail_path: row.get("thumbnail_path"),
-                video_duration: row.get("video_duration"),
-                video_resolution: row.get("video_resolution"),
-            }))
-        } else {
-            Ok(None)
-        }
+            .map_err(ServerError::Database)
     }
 
-    pub async fn list_files(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<FileRecord>> {
-        let limit = limit.unwrap_or(50);
-        let offset = offset.unwrap_or(0);
-        
-        let sql = "SELECT * FROM files ORDER BY upload_time DESC LIMIT ? OFFSET ?";
-        
-        let rows = query(sql)
-            .bind(limit)
-            .bind(offset)
+    // 按查询条件读取文件记录
+    pub async fn query_files(&self, file_query: &FileQuery) -> Result<Vec<FileRecord>> {
+        file_query
+            .build("*")
+            .build_query_as()
             .fetch_all(&self.pool)
             .await
-            .map_err(|e| ServerError::Database(e))?;
-
-        let mut files = Vec::new();
-        for row in rows {
-            let upload_time_str: String = row.get("upload_time");
-            let upload_time = DateTime::parse_from_rfc3339(&upload_time_str)
-                .map_err(|e| ServerError::Internal(e.into()))?
-                .with_timezone(&Utc);
-
-            files.push(FileRecord {
-                id: row.get("id"),
-                original_name: row.get("original_name"),
-                stored_name: row.get("stored_name"),
-                file_path: row.get("file_path"),
-                file_size: row.get("file_size"),
-                mime_type: row.get("mime_type"),
-                upload_time,
-                is_video: row.get("is_video"),
-                thumbnail_path: row.get("thumbnail_path"),
-                video_duration: row.get("video_duration"),
-                video_resolution: row.get("video_resolution"),
-            });
-        }
-
-        Ok(files)
-    }
-
-    pub async fn delete_file(&self, file_id: &str) -> Result<bool> {
-        if let Some(record) = self.get_file_by_id(file_id).await? {
-            let file_path = Path::new(&record.file_path);
-            if file_path.exists() {
-                std::fs::remove_file(file_path)
-                    .map_err(|e| ServerError::Io(e))?;
-            }
+            .map_err(ServerError::Database)
+    }
 
-            if let Some(thumbnail) = &record.thumbnail_path {
-                let thumb_path = Path::new(thumbnail);
-                if thumb_path.exists() {
-                    let _ = std::fs::remove_file(thumb_path);
-                }
-            }
+    pub async fn get_files_by_ids(&self, file_ids: &[String]) -> Result<Vec<FileRecord>> {
+        if file_ids.is_empty() {
+            return Ok(Vec::new());
+        }
 
-            let sql = "DELETE FROM files WHERE id = ?";
-            query(sql)
-                .bind(file_id)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| ServerError::Database(e))?;
+        self.query_files(&FileQuery {
+            ids: Some(file_ids.to_vec()),
+            ..Default::default()
+        })
+        .await
+    }
 
-            Ok(true)
-        } else {
-            Ok(false)
-        }
+    // 内容相同的全部文件记录, 最早上传的在前
+    pub async fn find_files_by_sha256(&self, sha256: &str) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            sha256: Some(sha256.to_string()),
+            sort: FileSort::ascending(SortField::UploadTime),
+            ..Default::default()
+        })
+        .await
     }
 
-    pub async fn get_file_stats(&self) -> Result<FileStats> {
-        let sql = r#"
-            SELECT 
-                COUNT(*) as total_files,
-                SUM(file_size) as total_size,
+    // 全部文件 ID, 包括回收站中的文件, 用于判断派生文件是否还有所属的记录
+    pub async fn file_ids(&self) -> Result<HashSet<String>> {
+        let rows = query("SELECT id FROM files UNION ALL SELECT id FROM trashed_files")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(rows.iter().map(|row| row.get::<String, _>("id")).collect())
+    }
+
+    pub async fn list_files(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            limit: Some(limit.unwrap_or(50).into()),
+            offset: offset.map(Into::into),
+            ..Default::default()
+        })
+        .await
+    }
+
+    // 按条件搜索文件, 最新上传的在前
+    pub async fn search_files(&self, search: &FileSearch, limit: i64) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            search: search.clone(),
+            limit: Some(limit),
+            ..Default::default()
+        })
+        .await
+    }
+
+    // 逐行流式读取整张文件表, 不在内存中构建完整列表
+    pub fn stream_files(&self) -> ReceiverStream<Result<FileRecord>> {
+        let pool = self.pool.clone();
+        let (tx, rx) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            let file_query = FileQuery::default();
+            let mut builder = file_query.build("*");
+            let mut rows = builder.build_query_as::<FileRecord>().fetch(&pool);
+
+            while let Some(row) = rows.next().await {
+                let record = row.map_err(ServerError::Database);
+                if tx.send(record).await.is_err() {
+                    // 客户端已断开
+                    break;
+                }
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+
+    pub async fn list_file_summaries(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<FileSummary>> {
+        let file_query = FileQuery {
+            limit: Some(limit.unwrap_or(50).into()),
+            offset: offset.map(Into::into),
+            ..Default::default()
+        };
+
+        file_query
+            .build("id, original_name, file_size, upload_time, thumbnail_path IS NOT NULL AS has_thumbnail")
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // expected_version 不为空时, 只有版本号一致才会删除. 启用回收站时移入回收站, 否则直接删除
+    pub async fn delete_file(
+        &self,
+        file_id: &str,
+        deleted_by: Option<&str>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        let record = match self.load_file_by_id(file_id).await? {
+            Some(record) => record,
+            None => return Ok(false),
+        };
+        check_version(&record, expected_version)?;
+
+        if self.trash {
+            self.move_to_trash(&record, deleted_by).await?;
+        } else {
+            self.remove_record(&record, deleted_by).await?;
+        }
+        Ok(true)
+    }
+
+    // 记录移入回收站, 提取的文本、集合和链接保留到永久删除时. 没有其他记录使用的文件移入 .trash
+    async fn move_to_trash(&self, record: &FileRecord, deleted_by: Option<&str>) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+
+        let sql = format!(
+            "INSERT INTO trashed_files ({0}, trashed_at, trashed_by) SELECT {0}, ?, ? FROM files WHERE id = ? AND version = ?",
+            FILE_COLUMNS
+        );
+        let result = query(&sql)
+            .bind(Utc::now().to_rfc3339())
+            .bind(deleted_by)
+            .bind(&record.id)
+            .bind(record.version)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() == 0 {
+            return Err(ServerError::precondition_failed("文件已被其他请求修改"));
+        }
+
+        // 提取的文本等附属数据保留到永久删除, 集合和关联涉及其他文件, 移入回收站时就移除
+        for sql in [
+            "DELETE FROM files WHERE id = ?",
+            "DELETE FROM file_locks WHERE file_id = ?",
+            "DELETE FROM collection_items WHERE file_id = ?",
+            "DELETE FROM file_links WHERE source_id = ?1 OR target_id = ?1",
+            "DELETE FROM files_fts WHERE file_id = ?",
+        ] {
+            query(sql)
+                .bind(&record.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+        write_tombstone(&mut tx, record, deleted_by).await?;
+
+        let live: i64 = query("SELECT COUNT(*) FROM files WHERE file_path = ?")
+            .bind(&record.file_path)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?
+            .get(0);
+        let source = Path::new(&record.file_path);
+        let moved = if live == 0 && source.exists() {
+            // 同一文件的其他回收站记录一起指向新位置
+            let target = self.trash_dir()?.join(&record.id);
+            query("UPDATE trashed_files SET file_path = ? WHERE file_path = ?")
+                .bind(target.to_string_lossy().to_string())
+                .bind(&record.file_path)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+            std::fs::rename(source, &target).map_err(ServerError::Io)?;
+            Some(target)
+        } else {
+            None
+        };
+
+        if let Err(e) = tx.commit().await {
+            if let Some(target) = &moved {
+                let _ = std::fs::rename(target, source);
+            }
+            return Err(ServerError::Database(e));
+        }
+        self.invalidate_record(&record.id);
+        Ok(())
+    }
+
+    async fn remove_record(&self, record: &FileRecord, deleted_by: Option<&str>) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+
+        let sql = "DELETE FROM files WHERE id = ? AND version = ?";
+        let result = query(sql)
+            .bind(&record.id)
+            .bind(record.version)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() == 0 {
+            return Err(ServerError::precondition_failed("文件已被其他请求修改"));
+        }
+
+        write_tombstone(&mut tx, record, deleted_by).await?;
+        delete_dependents(&mut tx, &record.id).await?;
+        let references = count_references(&mut tx, &record.file_path).await?;
+
+        tx.commit().await.map_err(ServerError::Database)?;
+        self.invalidate_record(&record.id);
+
+        // 记录删除成功后再清理磁盘文件
+        self.remove_stored_files(record, references)
+    }
+
+    // 去重后多条记录可能指向同一个文件, 最后一条引用删除后才删除文件
+    fn remove_stored_files(&self, record: &FileRecord, references: i64) -> Result<()> {
+        let file_path = Path::new(&record.file_path);
+        if references == 0 && file_path.exists() {
+            std::fs::remove_file(file_path)
+                .map_err(ServerError::Io)?;
+        }
+
+        if let Some(thumbnail) = &record.thumbnail_path {
+            let thumb_path = Path::new(thumbnail);
+            if thumb_path.exists() {
+                let _ = std::fs::remove_file(thumb_path);
+            }
+        }
+        self.remove_cached(&record.id);
+        Ok(())
+    }
+
+    // 回收站目录, 位于存储目录下的 .trash
+    fn trash_dir(&self) -> Result<PathBuf> {
+        let dir = self.storage_path.join(".trash");
+        std::fs::create_dir_all(&dir).map_err(ServerError::Io)?;
+        Ok(dir)
+    }
+
+    pub async fn get_trashed(&self, file_id: &str) -> Result<Option<TrashedFile>> {
+        query_as("SELECT * FROM trashed_files WHERE id = ?")
+            .bind(file_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 回收站中的文件, 最近删除的在前
+    pub async fn list_trash(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<TrashedFile>> {
+        query_as("SELECT * FROM trashed_files ORDER BY trashed_at DESC, id DESC LIMIT ? OFFSET ?")
+            .bind(limit.unwrap_or(50))
+            .bind(offset.unwrap_or(0))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 在 before 之前移入回收站的文件 ID
+    pub async fn list_expired_trash(&self, before: DateTime<Utc>) -> Result<Vec<String>> {
+        let rows = query("SELECT id FROM trashed_files WHERE trashed_at < ?")
+            .bind(before.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(rows.iter().map(|row| row.get::<String, _>("id")).collect())
+    }
+
+    // 从回收站恢复, 不在回收站中时返回 None. 文件已移入 .trash 时移回存储目录, 原来的名字被占用时换一个新名字
+    pub async fn restore_file(&self, file_id: &str) -> Result<Option<FileRecord>> {
+        let Some(trashed) = self.get_trashed(file_id).await? else {
+            return Ok(None);
+        };
+        let record = trashed.file;
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+
+        let source = PathBuf::from(&record.file_path);
+        let moved = if source.starts_with(self.storage_path.join(".trash")) {
+            let mut stored_name = record.stored_name.clone();
+            if self.get_file_path(&stored_name).exists() {
+                stored_name = self.generate_stored_name_with_hash(&record.original_name, record.sha256.as_deref());
+            }
+            let target = self.get_file_path(&stored_name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(ServerError::Io)?;
+            }
+            query("UPDATE trashed_files SET stored_name = ?, file_path = ? WHERE file_path = ?")
+                .bind(&stored_name)
+                .bind(target.to_string_lossy().to_string())
+                .bind(&record.file_path)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+            Some(target)
+        } else {
+            None
+        };
+
+        let sql = format!("INSERT INTO files ({0}) SELECT {0} FROM trashed_files WHERE id = ?", FILE_COLUMNS);
+        for sql in [
+            sql.as_str(),
+            "DELETE FROM trashed_files WHERE id = ?",
+            "DELETE FROM file_tombstones WHERE id = ?",
+        ] {
+            query(sql)
+                .bind(file_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+        query("INSERT INTO files_fts (file_id, original_name, mime_type) VALUES (?, ?, ?)")
+            .bind(file_id)
+            .bind(&record.original_name)
+            .bind(&record.mime_type)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+
+        // 文件最后移动, 之前的步骤失败时事务回滚即可
+        if let Some(target) = &moved {
+            if let Err(e) = std::fs::rename(&source, target) {
+                let _ = std::fs::remove_file(target);
+                return Err(ServerError::Io(e));
+            }
+        }
+        if let Err(e) = tx.commit().await {
+            if let Some(target) = &moved {
+                let _ = std::fs::rename(target, &source);
+            }
+            return Err(ServerError::Database(e));
+        }
+        self.invalidate_record(file_id);
+
+        self.get_file_by_id(file_id).await
+    }
+
+    // 永久删除回收站中的文件, 不在回收站中时返回 false
+    pub async fn purge_trashed(&self, file_id: &str) -> Result<bool> {
+        let Some(trashed) = self.get_trashed(file_id).await? else {
+            return Ok(false);
+        };
+        let record = trashed.file;
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        let result = query("DELETE FROM trashed_files WHERE id = ?")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        delete_dependents(&mut tx, file_id).await?;
+        let references = count_references(&mut tx, &record.file_path).await?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        self.remove_stored_files(&record, references)?;
+        Ok(true)
+    }
+
+    // 用 temp_path 替换文件内容, ID 和其他元数据保持不变, 版本号递增.
+    // 从内容派生的字段 (缩略图、视频信息、EXIF、感知哈希、提取的文本) 被清空, 由后续的重建任务重新生成.
+    // 覆盖原文件前用硬链接保留旧内容, 重命名或提交失败时换回, 文件和记录始终一致
+    pub async fn replace_content(
+        &self,
+        file_id: &str,
+        temp_path: &Path,
+        file_size: i64,
+        sha256: &str,
+        expected_version: Option<i64>,
+    ) -> Result<Option<FileRecord>> {
+        let record = match self.load_file_by_id(file_id).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        check_version(&record, expected_version)?;
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+
+        // 文件与其他记录共用时不能覆盖, 新内容保存为新文件
+        let shared = count_references(&mut tx, &record.file_path).await? - 1;
+        let (stored_name, file_path) = if shared > 0 {
+            let stored_name = self.generate_stored_name_with_hash(&record.original_name, Some(sha256));
+            let file_path = self.get_file_path(&stored_name).to_string_lossy().to_string();
+            (stored_name, file_path)
+        } else {
+            (record.stored_name.clone(), record.file_path.clone())
+        };
+
+        let sql = r#"
+            UPDATE files SET file_size = ?, sha256 = ?, version = version + 1,
+                             stored_name = ?, file_path = ?,
+                             thumbnail_path = NULL, video_duration = NULL, video_resolution = NULL,
+                             capture_time = NULL, latitude = NULL, longitude = NULL, perceptual_hash = NULL
+            WHERE id = ? AND version = ?
+        "#;
+        let backup = if shared > 0 {
+            None
+        } else {
+            let backup = PathBuf::from(format!("{}.replaced", file_path));
+            let _ = std::fs::remove_file(&backup);
+            match std::fs::hard_link(&file_path, &backup) {
+                Ok(()) => Some(backup),
+                // 原文件已经丢失, 没有需要保留的内容
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(ServerError::Io(e)),
+            }
+        };
+        let replaced = async {
+            let result = query(sql)
+                .bind(file_size)
+                .bind(sha256)
+                .bind(&stored_name)
+                .bind(&file_path)
+                .bind(file_id)
+                .bind(record.version)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+            if result.rows_affected() == 0 {
+                return Err(ServerError::precondition_failed("文件已被其他请求修改"));
+            }
+
+            query("DELETE FROM file_contents WHERE file_id = ?")
+                .bind(file_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+
+            std::fs::rename(temp_path, &file_path).map_err(ServerError::Io)?;
+            tx.commit().await.map_err(ServerError::Database)
+        }
+        .await;
+        if let Err(e) = replaced {
+            // 共用时删除为新内容占用的文件名, 否则换回旧内容
+            if shared > 0 {
+                let _ = std::fs::remove_file(&file_path);
+            } else if let Some(backup) = &backup {
+                let _ = std::fs::rename(backup, &file_path);
+            }
+            return Err(e);
+        }
+        if let Some(backup) = &backup {
+            let _ = std::fs::remove_file(backup);
+        }
+        self.invalidate_record(file_id);
+
+        // 清理旧内容的派生文件
+        if let Some(thumbnail) = &record.thumbnail_path {
+            let _ = std::fs::remove_file(thumbnail);
+        }
+        self.remove_cached(&record.id);
+
+        self.get_file_by_id(file_id).await
+    }
+
+    // 删除缓存目录中属于该文件的条目, 缓存文件名以文件 ID 开头
+    fn remove_cached(&self, file_id: &str) {
+        let cache_root = self.storage_path.join(".cache");
+        let Ok(kinds) = std::fs::read_dir(&cache_root) else {
+            return;
+        };
+
+        for kind in kinds.flatten() {
+            let Ok(entries) = std::fs::read_dir(kind.path()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with(file_id) {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    pub async fn get_tombstone(&self, file_id: &str) -> Result<Option<FileTombstone>> {
+        query_as("SELECT * FROM file_tombstones WHERE id = ?")
+            .bind(file_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn set_availability(
+        &self,
+        file_id: &str,
+        available_from: Option<DateTime<Utc>>,
+        available_until: Option<DateTime<Utc>>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        let sql = r#"
+            UPDATE files SET available_from = ?, available_until = ?, version = version + 1
+            WHERE id = ? AND (? IS NULL OR version = ?)
+        "#;
+
+        let result = query(sql)
+            .bind(available_from.map(|t| t.to_rfc3339()))
+            .bind(available_until.map(|t| t.to_rfc3339()))
+            .bind(file_id)
+            .bind(expected_version)
+            .bind(expected_version)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        if result.rows_affected() > 0 {
+            self.invalidate_record(file_id);
+            return Ok(true);
+        }
+
+        // 没有更新任何行: 区分记录不存在和版本过期
+        match self.load_file_by_id(file_id).await? {
+            Some(record) => {
+                check_version(&record, expected_version)?;
+                Err(ServerError::precondition_failed("文件已被其他请求修改"))
+            }
+            None => Ok(false),
+        }
+    }
+
+    // 拍摄时间和坐标来自 EXIF, 属于提取出的元数据, 不递增版本号.
+    // 传入 None 的字段保留原值
+    pub async fn set_photo_metadata(
+        &self,
+        file_id: &str,
+        capture_time: Option<DateTime<Utc>>,
+        location: Option<(f64, f64)>,
+    ) -> Result<bool> {
+        let sql = r#"
+            UPDATE files SET capture_time = COALESCE(?, capture_time),
+                             latitude = COALESCE(?, latitude),
+                             longitude = COALESCE(?, longitude)
+            WHERE id = ?
+        "#;
+
+        let result = query(sql)
+            .bind(capture_time.map(|t| t.to_rfc3339()))
+            .bind(location.map(|(lat, _)| lat))
+            .bind(location.map(|(_, lon)| lon))
+            .bind(file_id)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        self.invalidate_record(file_id);
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_images_missing_photo_metadata(&self) -> Result<Vec<FileRecord>> {
+        let sql = r#"
+            SELECT * FROM files
+            WHERE mime_type LIKE 'image/%'
+              AND (capture_time IS NULL OR latitude IS NULL OR perceptual_hash IS NULL)
+        "#;
+
+        query_as(sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn set_perceptual_hash(&self, file_id: &str, hash: i64) -> Result<bool> {
+        let sql = "UPDATE files SET perceptual_hash = ? WHERE id = ?";
+
+        let result = query(sql)
+            .bind(hash)
+            .bind(file_id)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        self.invalidate_record(file_id);
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 只在版本号未变时写入, 内容已被替换的文件不会用上旧内容的缩略图
+    pub async fn set_thumbnail_path(&self, file_id: &str, version: i64, thumbnail_path: &str) -> Result<bool> {
+        let sql = "UPDATE files SET thumbnail_path = ? WHERE id = ? AND version = ?";
+
+        let result = query(sql)
+            .bind(thumbnail_path)
+            .bind(file_id)
+            .bind(version)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        self.invalidate_record(file_id);
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 同 set_thumbnail_path, 只在版本号未变时写入
+    pub async fn set_video_metadata(
+        &self,
+        file_id: &str,
+        version: i64,
+        duration: Option<i32>,
+        resolution: Option<&str>,
+    ) -> Result<bool> {
+        let sql = "UPDATE files SET video_duration = ?, video_resolution = ? WHERE id = ? AND version = ?";
+
+        let result = query(sql)
+            .bind(duration)
+            .bind(resolution)
+            .bind(file_id)
+            .bind(version)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        self.invalidate_record(file_id);
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 所有已计算感知哈希的文件, 返回 (id, hash)
+    pub async fn list_perceptual_hashes(&self) -> Result<Vec<(String, i64)>> {
+        let sql = "SELECT id, perceptual_hash FROM files WHERE perceptual_hash IS NOT NULL";
+
+        let rows = query(sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("id"), row.get("perceptual_hash")))
+            .collect())
+    }
+
+    // 加锁或续期: 无锁、锁已过期或由同一持有者持有时成功, 否则返回冲突
+    pub async fn acquire_lock(&self, file_id: &str, owner: &str, ttl: Duration) -> Result<FileLock> {
+        let now = Utc::now();
+        let lock = FileLock {
+            file_id: file_id.to_string(),
+            owner: owner.to_string(),
+            acquired_at: now,
+            expires_at: now + ttl,
+        };
+
+        let sql = r#"
+            INSERT INTO file_locks (file_id, owner, acquired_at, expires_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(file_id) DO UPDATE SET
+                owner = excluded.owner,
+                acquired_at = excluded.acquired_at,
+                expires_at = excluded.expires_at
+            WHERE file_locks.owner = excluded.owner OR file_locks.expires_at <= ?
+        "#;
+
+        let result = query(sql)
+            .bind(&lock.file_id)
+            .bind(&lock.owner)
+            .bind(lock.acquired_at.to_rfc3339())
+            .bind(lock.expires_at.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        if result.rows_affected() == 0 {
+            self.check_lock(file_id, Some(owner)).await?;
+        }
+
+        Ok(lock)
+    }
+
+    // 释放锁, 锁由其他人持有时返回冲突; 没有有效的锁时返回 false
+    pub async fn release_lock(&self, file_id: &str, owner: &str) -> Result<bool> {
+        self.check_lock(file_id, Some(owner)).await?;
+
+        let sql = "DELETE FROM file_locks WHERE file_id = ? AND expires_at > ?";
+        let result = query(sql)
+            .bind(file_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 当前有效的锁
+    pub async fn get_lock(&self, file_id: &str) -> Result<Option<FileLock>> {
+        let sql = "SELECT * FROM file_locks WHERE file_id = ? AND expires_at > ?";
+
+        query_as(sql)
+            .bind(file_id)
+            .bind(Utc::now().to_rfc3339())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 修改文件前检查锁: 文件被其他人锁定时返回冲突
+    pub async fn check_lock(&self, file_id: &str, owner: Option<&str>) -> Result<()> {
+        match self.get_lock(file_id).await? {
+            Some(lock) if Some(lock.owner.as_str()) != owner => Err(ServerError::conflict(format!(
+                "文件已被 {} 锁定至 {}",
+                lock.owner,
+                lock.expires_at.to_rfc3339()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // 保存提取出的文本, source 标明来源, 例如 ocr
+    pub async fn save_file_content(&self, file_id: &str, source: &str, content: &str) -> Result<()> {
+        let sql = r#"
+            INSERT OR REPLACE INTO file_contents (file_id, source, content, indexed_at)
+            VALUES (?, ?, ?, ?)
+        "#;
+
+        query(sql)
+            .bind(file_id)
+            .bind(source)
+            .bind(content)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn get_file_content(&self, file_id: &str) -> Result<Option<FileContent>> {
+        query_as("SELECT * FROM file_contents WHERE file_id = ?")
+            .bind(file_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn list_images_without_content(&self) -> Result<Vec<FileRecord>> {
+        let sql = r#"
+            SELECT * FROM files
+            WHERE mime_type LIKE 'image/%'
+              AND id NOT IN (SELECT file_id FROM file_contents)
+        "#;
+
+        query_as(sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 查询坐标落在矩形范围内的文件
+    pub async fn list_files_in_bounds(
+        &self,
+        bounds: &GeoBounds,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            bounds: Some(*bounds),
+            limit: Some(limit.unwrap_or(50).into()),
+            offset: offset.map(Into::into),
+            ..Default::default()
+        })
+        .await
+    }
+
+    // 按拍摄日期分组统计图片, 没有拍摄时间的图片按上传时间归组.
+    // 时间均以 RFC 3339 存储, 取前 period_len 个字符即为日 (10) 或月 (7).
+    // 每组的封面取组内最新的一张
+    pub async fn photo_timeline(
+        &self,
+        period_len: i32,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<PhotoTimelineEntry>> {
+        let limit = limit.unwrap_or(50);
+        let offset = offset.unwrap_or(0);
+
+        let sql = r#"
+            SELECT substr(COALESCE(capture_time, upload_time), 1, ?) AS period,
+                   COUNT(*) AS count,
+                   id AS cover_id,
+                   MAX(COALESCE(capture_time, upload_time)) AS latest
+            FROM files WHERE mime_type LIKE 'image/%'
+            GROUP BY period ORDER BY period DESC LIMIT ? OFFSET ?
+        "#;
+
+        query_as(sql)
+            .bind(period_len)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn get_file_stats(&self) -> Result<FileStats> {
+        let sql = r#"
+            SELECT 
+                COUNT(*) as total_files,
+                SUM(file_size) as total_size,
                 COUNT(CASE WHEN is_video = 1 THEN 1 END) as video_count
             FROM files
         "#;
 
-        let row = query(sql)
+        let row = query(sql)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        let sizes = r#"
+            SELECT
+                (SELECT SUM(file_size) FROM (
+                    SELECT MAX(file_size) AS file_size FROM (
+                        SELECT file_path, file_size FROM files
+                        UNION ALL
+                        SELECT file_path, file_size FROM trashed_files
+                    ) GROUP BY file_path
+                )) AS physical_size,
+                (SELECT SUM(file_size) FROM (
+                    SELECT MAX(file_size) AS file_size FROM files WHERE sha256 IS NOT NULL GROUP BY sha256
+                    UNION ALL
+                    SELECT file_size FROM files WHERE sha256 IS NULL
+                )) AS unique_content_size,
+                (SELECT COUNT(*) FROM trashed_files) AS trash_files,
+                (SELECT SUM(file_size) FROM trashed_files) AS trash_size
+        "#;
+        let size_row = query(sizes)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        let total_size = row.get::<Option<i64>, _>("total_size").unwrap_or(0) as u64;
+        let physical_size = size_row.get::<Option<i64>, _>("physical_size").unwrap_or(0) as u64;
+        let unique_content_size = size_row.get::<Option<i64>, _>("unique_content_size").unwrap_or(0) as u64;
+        let trash_size = size_row.get::<Option<i64>, _>("trash_size").unwrap_or(0) as u64;
+
+        let cache_root = self.storage_path.join(".cache");
+        let (_, derived_size) = tokio::task::spawn_blocking(move || super::derived::dir_usage(&cache_root))
+            .await
+            .map_err(|e| ServerError::Internal(e.into()))?;
+
+        Ok(FileStats {
+            total_files: row.get::<i64, _>("total_files") as u64,
+            total_size,
+            video_count: row.get::<i64, _>("video_count") as u64,
+            physical_size,
+            dedup_savings: (total_size + trash_size).saturating_sub(physical_size),
+            unique_content_size: unique_content_size.min(physical_size),
+            backends: BTreeMap::from([("local".to_string(), physical_size)]),
+            trash_files: size_row.get::<i64, _>("trash_files") as u64,
+            trash_size,
+            derived_size,
+            temp_size: 0,
+        })
+    }
+
+    // 重复添加同一关联时保留原来的记录
+    pub async fn add_link(&self, source_id: &str, target_id: &str, kind: &str) -> Result<FileLink> {
+        let sql = r#"
+            INSERT OR IGNORE INTO file_links (source_id, target_id, kind, created_at)
+            VALUES (?, ?, ?, ?)
+        "#;
+
+        query(sql)
+            .bind(source_id)
+            .bind(target_id)
+            .bind(kind)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        query_as("SELECT * FROM file_links WHERE source_id = ? AND target_id = ? AND kind = ?")
+            .bind(source_id)
+            .bind(target_id)
+            .bind(kind)
             .fetch_one(&self.pool)
             .await
-            .map_err(|e| ServerError::Database(e))?;
+            .map_err(ServerError::Database)
+    }
 
-        Ok(FileStats {
-            total_files: row.get::<i64, _>("total_files") as u64,
-            total_size: row.get::<Option<i64>, _>("total_size").unwrap_or(0) as u64,
-            video_count: row.get::<i64, _>("video_count") as u64,
+    // 文件作为任一端的全部关联
+    pub async fn list_links(&self, file_id: &str) -> Result<Vec<FileLink>> {
+        let sql = "SELECT * FROM file_links WHERE source_id = ?1 OR target_id = ?1 ORDER BY created_at";
+
+        query_as(sql)
+            .bind(file_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn create_collection(&self, collection: &Collection) -> Result<()> {
+        let sql = r#"
+            INSERT INTO collections (id, name, description, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+        "#;
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        query(sql)
+            .bind(&collection.id)
+            .bind(&collection.name)
+            .bind(&collection.description)
+            .bind(collection.created_at.to_rfc3339())
+            .bind(collection.updated_at.to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        write_collection_items(&mut tx, &collection.id, &collection.file_ids).await?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    // 整体替换名称、描述和文件列表, 集合不存在时返回 false
+    pub async fn update_collection(&self, collection: &Collection) -> Result<bool> {
+        let sql = "UPDATE collections SET name = ?, description = ?, updated_at = ? WHERE id = ?";
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        let result = query(sql)
+            .bind(&collection.name)
+            .bind(&collection.description)
+            .bind(collection.updated_at.to_rfc3339())
+            .bind(&collection.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        write_collection_items(&mut tx, &collection.id, &collection.file_ids).await?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        Ok(true)
+    }
+
+    pub async fn get_collection(&self, collection_id: &str) -> Result<Option<Collection>> {
+        let collection: Option<Collection> = query_as("SELECT * FROM collections WHERE id = ?")
+            .bind(collection_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        let Some(mut collection) = collection else {
+            return Ok(None);
+        };
+
+        collection.file_ids = query("SELECT file_id FROM collection_items WHERE collection_id = ? ORDER BY position")
+            .bind(collection_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?
+            .iter()
+            .map(|item| item.get("file_id"))
+            .collect();
+
+        Ok(Some(collection))
+    }
+
+    pub async fn list_collections(&self) -> Result<Vec<Collection>> {
+        let mut collections: Vec<Collection> = query_as("SELECT * FROM collections ORDER BY updated_at DESC")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        let items = query("SELECT collection_id, file_id FROM collection_items ORDER BY collection_id, position")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        for collection in &mut collections {
+            collection.file_ids = items
+                .iter()
+                .filter(|item| item.get::<String, _>("collection_id") == collection.id)
+                .map(|item| item.get("file_id"))
+                .collect();
+        }
+        Ok(collections)
+    }
+
+    pub async fn delete_collection(&self, collection_id: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        query("DELETE FROM collection_items WHERE collection_id = ?")
+            .bind(collection_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        let result = query("DELETE FROM collections WHERE id = ?")
+            .bind(collection_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 保存播放进度, 覆盖该观看者之前的记录
+    pub async fn save_playback_position(&self, position: &PlaybackPosition) -> Result<()> {
+        let sql = r#"
+            INSERT OR REPLACE INTO playback_positions (file_id, viewer, position, duration, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+        "#;
+
+        query(sql)
+            .bind(&position.file_id)
+            .bind(&position.viewer)
+            .bind(position.position)
+            .bind(position.duration)
+            .bind(position.updated_at.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn get_playback_position(&self, file_id: &str, viewer: &str) -> Result<Option<PlaybackPosition>> {
+        let sql = "SELECT * FROM playback_positions WHERE file_id = ? AND viewer = ?";
+
+        query_as(sql)
+            .bind(file_id)
+            .bind(viewer)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 创建会话并为其预留 total_size 字节. 各会话尚未收到的字节数之和加上新会话超过 capacity 时拒绝,
+    // 检查和插入在同一条语句中完成, 并发创建的会话不会一起超额
+    pub async fn create_upload_session(&self, session: &UploadSession, capacity: u64) -> Result<()> {
+        let sql = r#"
+            INSERT INTO upload_sessions (id, original_name, total_size, chunk_size, created_at, updated_at)
+            SELECT ?, ?, ?, ?, ?, ?
+            WHERE (
+                SELECT COALESCE(SUM(s.total_size - MIN(s.total_size, s.chunk_size * (
+                    SELECT COUNT(*) FROM upload_session_chunks c WHERE c.session_id = s.id
+                ))), 0)
+                FROM upload_sessions s
+            ) + ? <= ?
+        "#;
+
+        let result = query(sql)
+            .bind(&session.id)
+            .bind(&session.original_name)
+            .bind(session.total_size)
+            .bind(session.chunk_size)
+            .bind(session.created_at.to_rfc3339())
+            .bind(session.updated_at.to_rfc3339())
+            .bind(session.total_size)
+            .bind(capacity.min(i64::MAX as u64) as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() == 0 {
+            return Err(ServerError::insufficient_storage(format!(
+                "可用空间不足, 无法为 {} 字节的上传预留空间",
+                session.total_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    // 在 before 之后没有收到新分块的会话 ID
+    pub async fn list_expired_upload_sessions(&self, before: DateTime<Utc>) -> Result<Vec<String>> {
+        let rows = query("SELECT id FROM upload_sessions WHERE updated_at < ?")
+            .bind(before.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(rows.iter().map(|row| row.get::<String, _>("id")).collect())
+    }
+
+    pub async fn get_upload_session(&self, session_id: &str) -> Result<Option<UploadSession>> {
+        let session: Option<UploadSession> = query_as("SELECT * FROM upload_sessions WHERE id = ?")
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+
+        session.received_chunks =
+            query("SELECT chunk_index FROM upload_session_chunks WHERE session_id = ? ORDER BY chunk_index")
+                .bind(session_id)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(ServerError::Database)?
+                .iter()
+                .map(|row| row.get("chunk_index"))
+                .collect();
+
+        Ok(Some(session))
+    }
+
+    // 记录已收到的块, 重复上传同一块时只更新会话的修改时间
+    pub async fn record_upload_chunk(&self, session_id: &str, chunk_index: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        query("INSERT OR IGNORE INTO upload_session_chunks (session_id, chunk_index) VALUES (?, ?)")
+            .bind(session_id)
+            .bind(chunk_index)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        query("UPDATE upload_sessions SET updated_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    // 删除会话, 已经被删除 (例如并发的 complete 请求) 时返回 false
+    pub async fn delete_upload_session(&self, session_id: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        query("DELETE FROM upload_session_chunks WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        let result = query("DELETE FROM upload_sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(ServerError::Database)?;
+        tx.commit().await.map_err(ServerError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 占用一个 Idempotency-Key. 成功占用时返回 None, 键已被占用时返回之前保存的内容.
+    // 插入和冲突检查在同一条语句中完成, 并发的重试中只有一个会继续处理
+    pub async fn claim_idempotency_key(&self, key: &str, scope: &str) -> Result<Option<IdempotentResponse>> {
+        let result = query("INSERT OR IGNORE INTO idempotency_keys (key, scope, created_at) VALUES (?, ?, ?)")
+            .bind(key)
+            .bind(scope)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        if result.rows_affected() > 0 {
+            return Ok(None);
+        }
+
+        let saved = query_as("SELECT * FROM idempotency_keys WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+        // 查询前被释放时按仍在处理返回, 客户端稍后重试
+        Ok(Some(saved.unwrap_or_else(|| IdempotentResponse {
+            key: key.to_string(),
+            scope: scope.to_string(),
+            response: None,
+            created_at: Utc::now(),
+        })))
+    }
+
+    // 保存占用的键对应的响应
+    pub async fn save_idempotent_response(&self, key: &str, response: &str) -> Result<()> {
+        query("UPDATE idempotency_keys SET response = ? WHERE key = ?")
+            .bind(response)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    // 请求失败时释放占用的键, 重试可以重新处理. 已保存响应的键不受影响
+    pub async fn release_idempotency_key(&self, key: &str) -> Result<()> {
+        query("DELETE FROM idempotency_keys WHERE key = ? AND response IS NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(())
+    }
+
+    // 删除 before 之前占用的键, 返回删除的数量
+    pub async fn purge_idempotent_responses(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = query("DELETE FROM idempotency_keys WHERE created_at < ?")
+            .bind(before.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 标记为蜜罐文件, 已标记时更新备注
+    pub async fn mark_canary(&self, file_id: &str, note: Option<&str>) -> Result<CanaryFile> {
+        let sql = r#"
+            INSERT INTO canary_files (file_id, note, created_at) VALUES (?, ?, ?)
+            ON CONFLICT(file_id) DO UPDATE SET note = excluded.note
+            RETURNING *
+        "#;
+
+        query_as(sql)
+            .bind(file_id)
+            .bind(note)
+            .bind(Utc::now().to_rfc3339())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 取消标记, 不是蜜罐文件时返回 false
+    pub async fn unmark_canary(&self, file_id: &str) -> Result<bool> {
+        let result = query("DELETE FROM canary_files WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_canary(&self, file_id: &str) -> Result<Option<CanaryFile>> {
+        query_as("SELECT * FROM canary_files WHERE file_id = ?")
+            .bind(file_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    pub async fn list_canaries(&self) -> Result<Vec<CanaryFile>> {
+        query_as("SELECT * FROM canary_files ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 保存告警, 返回分配的 ID
+    pub async fn record_canary_alert(&self, alert: &CanaryAlert) -> Result<i64> {
+        let sql = r#"
+            INSERT INTO canary_alerts (file_id, client_ip, method, path, user_agent, headers, triggered_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        let headers = serde_json::to_string(&alert.headers).map_err(|e| ServerError::Internal(e.into()))?;
+        let result = query(sql)
+            .bind(&alert.file_id)
+            .bind(&alert.client_ip)
+            .bind(&alert.method)
+            .bind(&alert.path)
+            .bind(&alert.user_agent)
+            .bind(headers)
+            .bind(alert.triggered_at.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    // 最近的告警, 最新的在前
+    pub async fn list_canary_alerts(&self, file_id: Option<&str>, limit: i64) -> Result<Vec<CanaryAlert>> {
+        let sql = "SELECT * FROM canary_alerts WHERE ?1 IS NULL OR file_id = ?1 ORDER BY id DESC LIMIT ?2";
+
+        query_as(sql)
+            .bind(file_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 把内存中累计的流量合并到按天汇总的表中
+    pub async fn record_usage(&self, entries: &[UsageEntry]) -> Result<()> {
+        let sql = r#"
+            INSERT INTO usage_daily (day, subject, uploaded_bytes, downloaded_bytes, requests)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(day, subject) DO UPDATE SET
+                uploaded_bytes = uploaded_bytes + excluded.uploaded_bytes,
+                downloaded_bytes = downloaded_bytes + excluded.downloaded_bytes,
+                requests = requests + excluded.requests
+        "#;
+
+        let mut tx = self.pool.begin().await.map_err(ServerError::Database)?;
+        for entry in entries {
+            query(sql)
+                .bind(&entry.day)
+                .bind(&entry.subject)
+                .bind(entry.uploaded_bytes)
+                .bind(entry.downloaded_bytes)
+                .bind(entry.requests)
+                .execute(&mut *tx)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+        tx.commit().await.map_err(ServerError::Database)?;
+        Ok(())
+    }
+
+    // 查询流量统计, 日期范围包含两端
+    pub async fn list_usage(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+        subject: Option<&str>,
+    ) -> Result<Vec<UsageEntry>> {
+        let sql = r#"
+            SELECT * FROM usage_daily
+            WHERE (? IS NULL OR day >= ?)
+              AND (? IS NULL OR day <= ?)
+              AND (? IS NULL OR subject = ?)
+            ORDER BY day DESC, subject
+        "#;
+
+        query_as(sql)
+            .bind(from)
+            .bind(from)
+            .bind(to)
+            .bind(to)
+            .bind(subject)
+            .bind(subject)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ServerError::Database)
+    }
+
+    // 数据库维护: WAL 检查点、回收空闲页、更新查询统计信息.
+    // 数据库尚未启用增量回收时执行一次完整 VACUUM 并切换为增量模式
+    pub async fn maintain(&self) -> Result<MaintenanceReport> {
+        let started = std::time::Instant::now();
+        let (size_before, free_before) = self.database_size().await?;
+
+        query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        let auto_vacuum: i64 = query("PRAGMA auto_vacuum")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(ServerError::Database)?
+            .get(0);
+
+        // auto_vacuum: 0 = NONE, 1 = FULL, 2 = INCREMENTAL
+        let full_vacuum = auto_vacuum != 2;
+        if full_vacuum {
+            query("PRAGMA auto_vacuum = INCREMENTAL")
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+            query("VACUUM")
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        } else {
+            query("PRAGMA incremental_vacuum")
+                .execute(&self.pool)
+                .await
+                .map_err(ServerError::Database)?;
+        }
+
+        // 合并全文索引的分段
+        query("INSERT INTO files_fts (files_fts) VALUES ('optimize')")
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        query("ANALYZE")
+            .execute(&self.pool)
+            .await
+            .map_err(ServerError::Database)?;
+
+        let (size_after, free_after) = self.database_size().await?;
+
+        Ok(MaintenanceReport {
+            size_before,
+            size_after,
+            free_before,
+            free_after,
+            full_vacuum,
+            duration_ms: started.elapsed().as_millis() as u64,
         })
     }
 
+    // 数据库大小和空闲页占用的字节数
+    async fn database_size(&self) -> Result<(u64, u64)> {
+        let mut values = Vec::with_capacity(3);
+        for pragma in ["PRAGMA page_count", "PRAGMA page_size", "PRAGMA freelist_count"] {
+            let value: i64 = query(pragma)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(ServerError::Database)?
+                .get(0);
+            values.push(value.max(0) as u64);
+        }
+
+        let (page_count, page_size, freelist_count) = (values[0], values[1], values[2]);
+        Ok((page_count * page_size, freelist_count * page_size))
+    }
+
     pub fn generate_stored_name(&self, original_name: &str) -> String {
+        self.generate_stored_name_with_hash(original_name, None)
+    }
+
+    // 按命名方式生成存储名, 可能包含子目录. 子目录会被创建;
+    // 保留原名和按哈希命名时会先创建空文件占住名字, 避免并发保存时互相覆盖
+    pub fn generate_stored_name_with_hash(&self, original_name: &str, sha256: Option<&str>) -> String {
         let extension = Path::new(original_name)
             .extension()
             .and_then(|ext| ext.to_str())
             .unwrap_or("");
-        
-        let uuid = Uuid::new_v4();
-        if extension.is_empty() {
-            uuid.to_string()
-        } else {
-            format!("{}.{}", uuid, extension)
+        let with_extension = |stem: &str| {
+            if extension.is_empty() {
+                stem.to_string()
+            } else {
+                format!("{}.{}", stem, extension)
+            }
+        };
+
+        match (self.naming, sha256) {
+            (NamingPolicy::DatePrefixed, _) => {
+                let dir = Utc::now().format("%Y/%m").to_string();
+                let _ = std::fs::create_dir_all(self.storage_path.join(&dir));
+                format!("{}/{}", dir, with_extension(&Uuid::new_v4().to_string()))
+            }
+            (NamingPolicy::OriginalName, _) => {
+                let name = sanitize_file_name(original_name);
+                let stem = Path::new(&name)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or("file")
+                    .to_string();
+                self.reserve_name(&stem, &with_extension)
+            }
+            (NamingPolicy::Hash, Some(sha256)) => self.reserve_name(sha256, &with_extension),
+            (NamingPolicy::Uuid, _) | (NamingPolicy::Hash, None) => with_extension(&Uuid::new_v4().to_string()),
         }
     }
 
+    // 依次尝试 stem、stem-1、stem-2 ..., 返回第一个能独占创建的名字
+    fn reserve_name(&self, stem: &str, with_extension: &dyn Fn(&str) -> String) -> String {
+        for attempt in 0..1000 {
+            let name = if attempt == 0 {
+                with_extension(stem)
+            } else {
+                with_extension(&format!("{}-{}", stem, attempt))
+            };
+            let created = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.storage_path.join(&name));
+            match created {
+                Ok(_) => return name,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                // 目录不可写等错误留给随后的写入报告
+                Err(_) => return name,
+            }
+        }
+        with_extension(&Uuid::new_v4().to_string())
+    }
+
     pub fn get_storage_path(&self) -> &Path {
         &self.storage_path
     }
@@ -247,11 +2234,169 @@ impl FileManager {
     pub fn get_file_path(&self, stored_name: &str) -> PathBuf {
         self.storage_path.join(stored_name)
     }
+
+    pub fn upload_locks(&self) -> &SessionLocks {
+        &self.upload_locks
+    }
+
+    // 上传会话的拼接文件
+    pub fn upload_part_path(&self, session_id: &str) -> Result<PathBuf> {
+        Ok(self.upload_session_dir()?.join(format!("{}.part", session_id)))
+    }
+
+    // 分块上传过程中拼接文件所在的目录, 位于存储目录下的 .uploads
+    pub fn upload_session_dir(&self) -> Result<PathBuf> {
+        let dir = self.storage_path.join(".uploads");
+        std::fs::create_dir_all(&dir).map_err(ServerError::Io)?;
+        Ok(dir)
+    }
+
+    // 派生内容的缓存目录, 位于存储目录下的 .cache/<kind>
+    pub fn cache_dir(&self, kind: &str) -> Result<PathBuf> {
+        let dir = self.storage_path.join(".cache").join(kind);
+        std::fs::create_dir_all(&dir).map_err(ServerError::Io)?;
+        Ok(dir)
+    }
+}
+
+// 去掉路径分隔符、控制字符和开头的点, 保证原文件名可以安全地作为存储目录下的文件名
+fn sanitize_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "file".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+pub(super) fn check_version(record: &FileRecord, expected_version: Option<i64>) -> Result<()> {
+    match expected_version {
+        Some(expected) if expected != record.version => Err(ServerError::precondition_failed(format!(
+            "版本不匹配: 当前版本为 {}, 请求版本为 {}",
+            record.version, expected
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// files 表的全部列, 记录在 files 和 trashed_files 之间移动时使用. files 表新增列时需同步加入
+const FILE_COLUMNS: &str = "id, original_name, stored_name, file_path, file_size, mime_type, upload_time, is_video, \
+    thumbnail_path, video_duration, video_resolution, available_from, available_until, version, capture_time, \
+    latitude, longitude, perceptual_hash, sha256";
+
+async fn write_tombstone(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    record: &FileRecord,
+    deleted_by: Option<&str>,
+) -> Result<()> {
+    let sql = r#"
+        INSERT OR REPLACE INTO file_tombstones (id, original_name, deleted_at, deleted_by)
+        VALUES (?, ?, ?, ?)
+    "#;
+    query(sql)
+        .bind(&record.id)
+        .bind(&record.original_name)
+        .bind(Utc::now().to_rfc3339())
+        .bind(deleted_by)
+        .execute(&mut **tx)
+        .await
+        .map_err(ServerError::Database)?;
+    Ok(())
+}
+
+// 永久删除文件时一并删除的关联数据
+async fn delete_dependents(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, file_id: &str) -> Result<()> {
+    for sql in [
+        "DELETE FROM file_contents WHERE file_id = ?",
+        "DELETE FROM file_locks WHERE file_id = ?",
+        "DELETE FROM canary_files WHERE file_id = ?",
+        "DELETE FROM playback_positions WHERE file_id = ?",
+        "DELETE FROM collection_items WHERE file_id = ?",
+        "DELETE FROM file_links WHERE source_id = ?1 OR target_id = ?1",
+        "DELETE FROM files_fts WHERE file_id = ?",
+    ] {
+        query(sql)
+            .bind(file_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(ServerError::Database)?;
+    }
+    Ok(())
+}
+
+// 指向该文件的记录数, 包括回收站中的记录
+async fn count_references(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, file_path: &str) -> Result<i64> {
+    let sql = r#"
+        SELECT (SELECT COUNT(*) FROM files WHERE file_path = ?1)
+             + (SELECT COUNT(*) FROM trashed_files WHERE file_path = ?1)
+    "#;
+    Ok(query(sql)
+        .bind(file_path)
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(ServerError::Database)?
+        .get(0))
+}
+
+// 用新的文件列表替换集合中的全部条目, position 即顺序
+async fn write_collection_items(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    collection_id: &str,
+    file_ids: &[String],
+) -> Result<()> {
+    query("DELETE FROM collection_items WHERE collection_id = ?")
+        .bind(collection_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(ServerError::Database)?;
+
+    for (position, file_id) in file_ids.iter().enumerate() {
+        query("INSERT INTO collection_items (collection_id, position, file_id) VALUES (?, ?, ?)")
+            .bind(collection_id)
+            .bind(position as i64)
+            .bind(file_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(ServerError::Database)?;
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Serialize)]
 pub struct FileStats {
     pub total_files: u64,
+    // 各记录大小之和 (逻辑大小)
     pub total_size: u64,
     pub video_count: u64,
-}
\ No newline at end of file
+    // 按存储路径去重后实际占用的大小, 包括回收站中的文件, 多条记录共用同一存储文件时只计一次
+    pub physical_size: u64,
+    // total_size 加 trash_size 与 physical_size 之差, 即共用存储文件节省的空间
+    pub dedup_savings: u64,
+    // 按内容哈希去重后的大小, 与 physical_size 之差是重复内容仍可节省的空间
+    pub unique_content_size: u64,
+    // 各存储后端占用的大小, 目前只有本地存储
+    pub backends: BTreeMap<String, u64>,
+    // 回收站中的记录数和大小, 不计入 total_files 和 total_size
+    pub trash_files: u64,
+    pub trash_size: u64,
+    // .cache 下缩略图、缩放图等派生文件的大小
+    pub derived_size: u64,
+    // .tmp 下临时文件的大小, 由服务器层填写
+    pub temp_size: u64,
+}
+
+// 数据库维护结果, 大小均为字节
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub free_before: u64,
+    pub free_after: u64,
+    pub full_vacuum: bool,
+    pub duration_ms: u64,
+}
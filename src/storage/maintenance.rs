@@ -0,0 +1,48 @@
+// 数据库定时维护 - 每天在配置的低峰时刻执行一次
+use super::FileManager;
+use chrono::{DateTime, Local, TimeZone};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+// 计算 now 之后下一个整点 hour 的时刻, 夏令时跳过的时刻顺延到下一天
+pub fn next_run<Tz: TimeZone>(now: &DateTime<Tz>, hour: u32) -> DateTime<Tz> {
+    let timezone = now.timezone();
+    let hour = hour.min(23);
+    let mut date = now.date_naive();
+
+    loop {
+        let candidate = date
+            .and_hms_opt(hour, 0, 0)
+            .and_then(|time| timezone.from_local_datetime(&time).earliest());
+        if let Some(candidate) = candidate {
+            if candidate > *now {
+                return candidate;
+            }
+        }
+        date = match date.succ_opt() {
+            Some(next) => next,
+            None => return now.clone(),
+        };
+    }
+}
+
+// 启动后台维护任务, 按本地时间调度
+pub fn spawn_scheduler(file_manager: Arc<FileManager>, hour: u32) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let now = Local::now();
+            let next = next_run(&now, hour);
+            info!("下次数据库维护时间: {}", next);
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+            match file_manager.maintain().await {
+                Ok(report) => info!(
+                    "数据库维护完成: {} -> {} 字节, 耗时 {} ms",
+                    report.size_before, report.size_after, report.duration_ms
+                ),
+                Err(e) => error!("数据库维护失败: {}", e),
+            }
+        }
+    })
+}
@@ -1,7 +1,24 @@
 // 存储模块 - 文件系统操作和元数据管理
 
+pub mod derived;
 pub mod file_manager;
+pub mod maintenance;
 pub mod metadata;
+pub mod periodic;
+pub mod query;
+pub mod repository;
+mod rows;
+pub mod temp;
+pub mod trash;
+pub mod uploads;
 
-pub use file_manager::{FileManager, FileRecord, FileStats};
-pub use metadata::FileMetadata;
\ No newline at end of file
+pub use file_manager::{
+    CanaryAlert, CanaryFile, Collection, FileContent, FileLink, FileLock, FileManager, FileRecord, FileSearch,
+    FileStats, FileSummary, FileTombstone, GeoBounds, IdempotentResponse, MaintenanceReport, PhotoTimelineEntry,
+    PlaybackPosition, TrashedFile, UploadSession, UsageEntry,
+};
+pub use derived::DerivedCleanupReport;
+pub use metadata::FileMetadata;
+pub use query::{FileQuery, FileSort, SortField};
+pub use repository::{FileRepository, MemoryFileRepository};
+pub use temp::{TempCleanupReport, TempManager, TempStats};
\ No newline at end of file
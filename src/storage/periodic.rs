@@ -0,0 +1,24 @@
+// 后台周期任务 - 各个清理任务共用的定时循环
+use crate::error::Result;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+// 每隔 interval 秒执行一次 task, 启动后先等待一个间隔. 单次失败只记录警告, 下一轮再试
+pub fn spawn_periodic<F, Fut>(name: &'static str, interval: u64, mut task: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(interval.max(1));
+        loop {
+            tokio::time::sleep(interval).await;
+
+            if let Err(e) = task().await {
+                warn!("{}任务失败: {}", name, e);
+            }
+        }
+    })
+}
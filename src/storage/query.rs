@@ -0,0 +1,248 @@
+// 文件表的查询构建 - 过滤条件、排序和分页统一在这里拼接成 SQL, 各查询方法不再各写一份
+use super::file_manager::{FileRecord, FileSearch, GeoBounds};
+use serde::Deserialize;
+use sqlx::{QueryBuilder, Sqlite};
+
+// trigram 分词时能使用全文索引的最短词长
+const MIN_INDEXED_TERM_CHARS: usize = 3;
+
+// 可排序的字段, 同值时再按 id 排序保证分页稳定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    UploadTime,
+    Name,
+    Size,
+}
+
+impl SortField {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::UploadTime => "upload_time",
+            Self::Name => "original_name",
+            Self::Size => "file_size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct FileSort {
+    #[serde(default)]
+    pub field: SortField,
+    #[serde(default = "default_descending")]
+    pub descending: bool,
+}
+
+// 默认最新上传的在前
+impl Default for FileSort {
+    fn default() -> Self {
+        Self {
+            field: SortField::UploadTime,
+            descending: true,
+        }
+    }
+}
+
+impl FileSort {
+    pub fn ascending(field: SortField) -> Self {
+        Self { field, descending: false }
+    }
+
+    pub fn descending(field: SortField) -> Self {
+        Self { field, descending: true }
+    }
+}
+
+fn default_descending() -> bool {
+    true
+}
+
+// 文件查询, 为空的条件不参与过滤, limit 为空时不分页
+#[derive(Debug, Clone, Default)]
+pub struct FileQuery {
+    pub search: FileSearch,
+    pub ids: Option<Vec<String>>,
+    pub sha256: Option<String>,
+    pub bounds: Option<GeoBounds>,
+    pub sort: FileSort,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl FileQuery {
+    // 拼接 SELECT <columns> FROM files WHERE ... ORDER BY ... LIMIT ... OFFSET ...
+    pub fn build(&self, columns: &str) -> QueryBuilder<'_, Sqlite> {
+        let mut builder = QueryBuilder::new(format!("SELECT {} FROM files WHERE 1 = 1", columns));
+        self.push_filters(&mut builder);
+
+        let direction = if self.sort.descending { "DESC" } else { "ASC" };
+        builder.push(format!(" ORDER BY {} {}, id {}", self.sort.field.column(), direction, direction));
+
+        if let Some(limit) = self.limit {
+            builder.push(" LIMIT ").push_bind(limit);
+            builder.push(" OFFSET ").push_bind(self.offset.unwrap_or(0));
+        }
+        builder
+    }
+
+    fn push_filters<'a>(&'a self, builder: &mut QueryBuilder<'a, Sqlite>) {
+        let search = &self.search;
+        if let Some(name) = &search.name {
+            builder
+                .push(" AND original_name LIKE '%' || ")
+                .push_bind(escape_like(name))
+                .push(" || '%' ESCAPE '\\'");
+        }
+        if let Some(mime_type) = &search.mime_type {
+            builder
+                .push(" AND mime_type LIKE ")
+                .push_bind(escape_like(mime_type))
+                .push(" || '%' ESCAPE '\\'");
+        }
+        if let Some(after) = search.uploaded_after {
+            builder.push(" AND upload_time >= ").push_bind(after.to_rfc3339());
+        }
+        if let Some(before) = search.uploaded_before {
+            builder.push(" AND upload_time < ").push_bind(before.to_rfc3339());
+        }
+        if let Some(is_video) = search.is_video {
+            builder.push(" AND is_video = ").push_bind(is_video);
+        }
+        if let Some(min_size) = search.min_size {
+            builder.push(" AND file_size >= ").push_bind(min_size);
+        }
+        if let Some(max_size) = search.max_size {
+            builder.push(" AND file_size <= ").push_bind(max_size);
+        }
+
+        // trigram 索引只能匹配至少 3 个字符的词, 更短的词直接按 LIKE 过滤
+        let (indexed, short): (Vec<&str>, Vec<&str>) = search_terms(search.text.as_deref())
+            .partition(|term| term.chars().count() >= MIN_INDEXED_TERM_CHARS);
+        if !indexed.is_empty() {
+            let expression = indexed
+                .iter()
+                .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            builder
+                .push(" AND id IN (SELECT file_id FROM files_fts WHERE files_fts MATCH ")
+                .push_bind(expression)
+                .push(")");
+        }
+        for term in short {
+            let pattern = escape_like(term);
+            builder
+                .push(" AND (original_name LIKE '%' || ")
+                .push_bind(pattern.clone())
+                .push(" || '%' ESCAPE '\\' OR mime_type LIKE '%' || ")
+                .push_bind(pattern)
+                .push(" || '%' ESCAPE '\\')");
+        }
+
+        if let Some(ids) = &self.ids {
+            // 空列表不匹配任何记录
+            builder.push(" AND id IN (");
+            let mut separated = builder.separated(", ");
+            separated.push("NULL");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            builder.push(")");
+        }
+        if let Some(sha256) = &self.sha256 {
+            builder.push(" AND sha256 = ").push_bind(sha256);
+        }
+
+        // 范围跨越 180 度经线时 min_lon 大于 max_lon
+        if let Some(bounds) = &self.bounds {
+            builder
+                .push(" AND latitude BETWEEN ")
+                .push_bind(bounds.min_lat)
+                .push(" AND ")
+                .push_bind(bounds.max_lat);
+            if bounds.min_lon <= bounds.max_lon {
+                builder
+                    .push(" AND longitude BETWEEN ")
+                    .push_bind(bounds.min_lon)
+                    .push(" AND ")
+                    .push_bind(bounds.max_lon);
+            } else {
+                builder
+                    .push(" AND (longitude >= ")
+                    .push_bind(bounds.min_lon)
+                    .push(" OR longitude <= ")
+                    .push_bind(bounds.max_lon)
+                    .push(")");
+            }
+        }
+    }
+}
+
+// 在内存中执行查询, 结果与数据库查询一致, 供不使用数据库的实现使用
+impl FileQuery {
+    pub fn apply(&self, records: impl IntoIterator<Item = FileRecord>) -> Vec<FileRecord> {
+        let mut records: Vec<FileRecord> = records.into_iter().filter(|record| self.matches(record)).collect();
+        records.sort_by(|a, b| {
+            let ordering = match self.sort.field {
+                SortField::UploadTime => a.upload_time.cmp(&b.upload_time),
+                SortField::Name => a.original_name.cmp(&b.original_name),
+                SortField::Size => a.file_size.cmp(&b.file_size),
+            }
+            .then_with(|| a.id.cmp(&b.id));
+            if self.sort.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        let offset = self.offset.unwrap_or(0).max(0) as usize;
+        let limit = self.limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+        records.into_iter().skip(offset).take(limit).collect()
+    }
+
+    // LIKE 对 ASCII 字母不区分大小写, 这里同样按 ASCII 小写比较
+    pub fn matches(&self, record: &FileRecord) -> bool {
+        let search = &self.search;
+        let name = record.original_name.to_ascii_lowercase();
+        let mime_type = record.mime_type.to_ascii_lowercase();
+
+        search.name.as_ref().is_none_or(|part| name.contains(&part.to_ascii_lowercase()))
+            && search
+                .mime_type
+                .as_ref()
+                .is_none_or(|prefix| mime_type.starts_with(&prefix.to_ascii_lowercase()))
+            && search.uploaded_after.is_none_or(|after| record.upload_time >= after)
+            && search.uploaded_before.is_none_or(|before| record.upload_time < before)
+            && search.is_video.is_none_or(|is_video| record.is_video == is_video)
+            && search.min_size.is_none_or(|min_size| record.file_size >= min_size)
+            && search.max_size.is_none_or(|max_size| record.file_size <= max_size)
+            && search_terms(search.text.as_deref()).all(|term| {
+                let term = term.to_lowercase();
+                record.original_name.to_lowercase().contains(&term) || mime_type.contains(&term)
+            })
+            && self.ids.as_ref().is_none_or(|ids| ids.contains(&record.id))
+            && self.sha256.as_ref().is_none_or(|sha256| record.sha256.as_ref() == Some(sha256))
+            && self.bounds.is_none_or(|bounds| {
+                record.latitude.zip(record.longitude).is_some_and(|(lat, lon)| {
+                    let in_lon = if bounds.min_lon <= bounds.max_lon {
+                        (bounds.min_lon..=bounds.max_lon).contains(&lon)
+                    } else {
+                        lon >= bounds.min_lon || lon <= bounds.max_lon
+                    };
+                    (bounds.min_lat..=bounds.max_lat).contains(&lat) && in_lon
+                })
+            })
+    }
+}
+
+// 全文搜索的词, 以空白分隔
+fn search_terms(text: Option<&str>) -> impl Iterator<Item = &str> {
+    text.unwrap_or_default().split_whitespace()
+}
+
+// 转义 LIKE 模式中的通配符, 配合 ESCAPE '\' 使用
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
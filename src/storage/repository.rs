@@ -0,0 +1,731 @@
+// 文件记录仓库 - 处理器通过该 trait 读写文件记录, 不直接依赖 SQLite.
+// FileManager 是正式实现, MemoryFileRepository 把记录放在内存中, 供测试和实验性后端使用
+use super::file_manager::{
+    check_version, Collection, FileLink, FileLock, FileManager, FileRecord, FileSearch, FileSummary, FileTombstone,
+    PhotoTimelineEntry, TrashedFile,
+};
+use super::query::{FileQuery, FileSort, SortField};
+use crate::error::{Result, ServerError};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait FileRepository: Send + Sync {
+    async fn save_file_record(&self, record: &FileRecord) -> Result<()>;
+
+    // 保存与已有记录共用存储文件的记录. 已经没有内容相同的记录使用该文件时不写入, 返回 false
+    async fn save_shared_record(&self, record: &FileRecord) -> Result<bool>;
+
+    async fn get_file_by_id(&self, file_id: &str) -> Result<Option<FileRecord>>;
+
+    async fn query_files(&self, file_query: &FileQuery) -> Result<Vec<FileRecord>>;
+
+    // expected_version 不为空时, 只有版本号一致才会删除
+    async fn delete_file(&self, file_id: &str, deleted_by: Option<&str>, expected_version: Option<i64>)
+        -> Result<bool>;
+
+    async fn get_tombstone(&self, file_id: &str) -> Result<Option<FileTombstone>>;
+
+    async fn set_availability(
+        &self,
+        file_id: &str,
+        available_from: Option<DateTime<Utc>>,
+        available_until: Option<DateTime<Utc>>,
+        expected_version: Option<i64>,
+    ) -> Result<bool>;
+
+    // 用 temp_path 替换文件内容, 文件不存在时返回 None
+    async fn replace_content(
+        &self,
+        file_id: &str,
+        temp_path: &Path,
+        file_size: i64,
+        sha256: &str,
+        expected_version: Option<i64>,
+    ) -> Result<Option<FileRecord>>;
+
+    // 逐条读取全部文件记录, 用于导出
+    fn stream_files(&self) -> ReceiverStream<Result<FileRecord>>;
+
+    async fn list_trash(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<TrashedFile>>;
+
+    async fn restore_file(&self, file_id: &str) -> Result<Option<FileRecord>>;
+
+    async fn purge_trashed(&self, file_id: &str) -> Result<bool>;
+
+    async fn acquire_lock(&self, file_id: &str, owner: &str, ttl: Duration) -> Result<FileLock>;
+
+    async fn release_lock(&self, file_id: &str, owner: &str) -> Result<bool>;
+
+    async fn get_lock(&self, file_id: &str) -> Result<Option<FileLock>>;
+
+    async fn add_link(&self, source_id: &str, target_id: &str, kind: &str) -> Result<FileLink>;
+
+    async fn list_links(&self, file_id: &str) -> Result<Vec<FileLink>>;
+
+    async fn create_collection(&self, collection: &Collection) -> Result<()>;
+
+    async fn update_collection(&self, collection: &Collection) -> Result<bool>;
+
+    async fn get_collection(&self, collection_id: &str) -> Result<Option<Collection>>;
+
+    async fn list_collections(&self) -> Result<Vec<Collection>>;
+
+    async fn delete_collection(&self, collection_id: &str) -> Result<bool>;
+
+    // 以下是从文件内容提取出的元数据, 写入时不递增版本号.
+    // set_photo_metadata 中为 None 的字段保留原值
+    async fn set_photo_metadata(
+        &self,
+        file_id: &str,
+        capture_time: Option<DateTime<Utc>>,
+        location: Option<(f64, f64)>,
+    ) -> Result<bool>;
+
+    async fn set_perceptual_hash(&self, file_id: &str, hash: i64) -> Result<bool>;
+
+    // 只在版本号未变时写入
+    async fn set_thumbnail_path(&self, file_id: &str, version: i64, thumbnail_path: &str) -> Result<bool>;
+
+    // 只在版本号未变时写入
+    async fn set_video_metadata(
+        &self,
+        file_id: &str,
+        version: i64,
+        duration: Option<i32>,
+        resolution: Option<&str>,
+    ) -> Result<bool>;
+
+    // 修改文件前检查锁: 文件被其他人锁定时返回冲突
+    async fn check_lock(&self, file_id: &str, owner: Option<&str>) -> Result<()> {
+        match self.get_lock(file_id).await? {
+            Some(lock) if Some(lock.owner.as_str()) != owner => Err(ServerError::conflict(format!(
+                "文件已被 {} 锁定至 {}",
+                lock.owner,
+                lock.expires_at.to_rfc3339()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn search_files(&self, search: &FileSearch, limit: i64) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            search: search.clone(),
+            limit: Some(limit),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn list_file_summaries(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<FileSummary>> {
+        let records = self
+            .query_files(&FileQuery {
+                limit: Some(limit.unwrap_or(50).into()),
+                offset: offset.map(Into::into),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| FileSummary {
+                has_thumbnail: record.thumbnail_path.is_some(),
+                id: record.id,
+                original_name: record.original_name,
+                file_size: record.file_size,
+                upload_time: record.upload_time,
+            })
+            .collect())
+    }
+
+    async fn list_perceptual_hashes(&self) -> Result<Vec<(String, i64)>> {
+        let records = self.query_files(&FileQuery::default()).await?;
+        Ok(records
+            .into_iter()
+            .filter_map(|record| record.perceptual_hash.map(|hash| (record.id, hash)))
+            .collect())
+    }
+
+    async fn list_images_missing_photo_metadata(&self) -> Result<Vec<FileRecord>> {
+        let records = list_images(self).await?;
+        Ok(records
+            .into_iter()
+            .filter(|record| {
+                record.capture_time.is_none() || record.latitude.is_none() || record.perceptual_hash.is_none()
+            })
+            .collect())
+    }
+
+    // 按拍摄日期分组统计图片, 没有拍摄时间的按上传时间归组, 与数据库实现一样取 RFC 3339 的前 period_len 个字符
+    async fn photo_timeline(
+        &self,
+        period_len: i32,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<PhotoTimelineEntry>> {
+        let mut groups: Vec<(String, PhotoTimelineEntry)> = Vec::new();
+        for record in list_images(self).await? {
+            let time = record.capture_time.unwrap_or(record.upload_time).to_rfc3339();
+            let period: String = time.chars().take(period_len.max(0) as usize).collect();
+            match groups.iter_mut().find(|(_, entry)| entry.period == period) {
+                Some((latest, entry)) => {
+                    entry.count += 1;
+                    if time > *latest {
+                        *latest = time;
+                        entry.cover_id = record.id;
+                    }
+                }
+                None => groups.push((
+                    time,
+                    PhotoTimelineEntry {
+                        period,
+                        count: 1,
+                        cover_id: record.id,
+                    },
+                )),
+            }
+        }
+
+        groups.sort_by(|(_, a), (_, b)| b.period.cmp(&a.period));
+        let offset = offset.unwrap_or(0).max(0) as usize;
+        let limit = limit.unwrap_or(50).max(0) as usize;
+        Ok(groups.into_iter().skip(offset).take(limit).map(|(_, entry)| entry).collect())
+    }
+
+    async fn get_files_by_ids(&self, file_ids: &[String]) -> Result<Vec<FileRecord>> {
+        if file_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.query_files(&FileQuery {
+            ids: Some(file_ids.to_vec()),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn find_files_by_sha256(&self, sha256: &str) -> Result<Vec<FileRecord>> {
+        self.query_files(&FileQuery {
+            sha256: Some(sha256.to_string()),
+            sort: FileSort::ascending(SortField::UploadTime),
+            ..Default::default()
+        })
+        .await
+    }
+}
+
+// 全部图片记录
+async fn list_images<R: FileRepository + ?Sized>(files: &R) -> Result<Vec<FileRecord>> {
+    files
+        .query_files(&FileQuery {
+            search: FileSearch {
+                mime_type: Some("image/".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await
+}
+
+#[async_trait]
+impl FileRepository for FileManager {
+    async fn save_file_record(&self, record: &FileRecord) -> Result<()> {
+        FileManager::save_file_record(self, record).await
+    }
+
+    async fn save_shared_record(&self, record: &FileRecord) -> Result<bool> {
+        FileManager::save_shared_record(self, record).await
+    }
+
+    async fn get_file_by_id(&self, file_id: &str) -> Result<Option<FileRecord>> {
+        FileManager::get_file_by_id(self, file_id).await
+    }
+
+    async fn query_files(&self, file_query: &FileQuery) -> Result<Vec<FileRecord>> {
+        FileManager::query_files(self, file_query).await
+    }
+
+    async fn delete_file(
+        &self,
+        file_id: &str,
+        deleted_by: Option<&str>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        FileManager::delete_file(self, file_id, deleted_by, expected_version).await
+    }
+
+    async fn get_tombstone(&self, file_id: &str) -> Result<Option<FileTombstone>> {
+        FileManager::get_tombstone(self, file_id).await
+    }
+
+    async fn set_availability(
+        &self,
+        file_id: &str,
+        available_from: Option<DateTime<Utc>>,
+        available_until: Option<DateTime<Utc>>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        FileManager::set_availability(self, file_id, available_from, available_until, expected_version).await
+    }
+
+    async fn replace_content(
+        &self,
+        file_id: &str,
+        temp_path: &Path,
+        file_size: i64,
+        sha256: &str,
+        expected_version: Option<i64>,
+    ) -> Result<Option<FileRecord>> {
+        FileManager::replace_content(self, file_id, temp_path, file_size, sha256, expected_version).await
+    }
+
+    fn stream_files(&self) -> ReceiverStream<Result<FileRecord>> {
+        FileManager::stream_files(self)
+    }
+
+    async fn list_trash(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<TrashedFile>> {
+        FileManager::list_trash(self, limit, offset).await
+    }
+
+    async fn restore_file(&self, file_id: &str) -> Result<Option<FileRecord>> {
+        FileManager::restore_file(self, file_id).await
+    }
+
+    async fn purge_trashed(&self, file_id: &str) -> Result<bool> {
+        FileManager::purge_trashed(self, file_id).await
+    }
+
+    async fn acquire_lock(&self, file_id: &str, owner: &str, ttl: Duration) -> Result<FileLock> {
+        FileManager::acquire_lock(self, file_id, owner, ttl).await
+    }
+
+    async fn release_lock(&self, file_id: &str, owner: &str) -> Result<bool> {
+        FileManager::release_lock(self, file_id, owner).await
+    }
+
+    async fn get_lock(&self, file_id: &str) -> Result<Option<FileLock>> {
+        FileManager::get_lock(self, file_id).await
+    }
+
+    async fn add_link(&self, source_id: &str, target_id: &str, kind: &str) -> Result<FileLink> {
+        FileManager::add_link(self, source_id, target_id, kind).await
+    }
+
+    async fn list_links(&self, file_id: &str) -> Result<Vec<FileLink>> {
+        FileManager::list_links(self, file_id).await
+    }
+
+    async fn create_collection(&self, collection: &Collection) -> Result<()> {
+        FileManager::create_collection(self, collection).await
+    }
+
+    async fn update_collection(&self, collection: &Collection) -> Result<bool> {
+        FileManager::update_collection(self, collection).await
+    }
+
+    async fn get_collection(&self, collection_id: &str) -> Result<Option<Collection>> {
+        FileManager::get_collection(self, collection_id).await
+    }
+
+    async fn list_collections(&self) -> Result<Vec<Collection>> {
+        FileManager::list_collections(self).await
+    }
+
+    async fn delete_collection(&self, collection_id: &str) -> Result<bool> {
+        FileManager::delete_collection(self, collection_id).await
+    }
+
+    async fn set_photo_metadata(
+        &self,
+        file_id: &str,
+        capture_time: Option<DateTime<Utc>>,
+        location: Option<(f64, f64)>,
+    ) -> Result<bool> {
+        FileManager::set_photo_metadata(self, file_id, capture_time, location).await
+    }
+
+    async fn set_perceptual_hash(&self, file_id: &str, hash: i64) -> Result<bool> {
+        FileManager::set_perceptual_hash(self, file_id, hash).await
+    }
+
+    async fn set_thumbnail_path(&self, file_id: &str, version: i64, thumbnail_path: &str) -> Result<bool> {
+        FileManager::set_thumbnail_path(self, file_id, version, thumbnail_path).await
+    }
+
+    async fn set_video_metadata(
+        &self,
+        file_id: &str,
+        version: i64,
+        duration: Option<i32>,
+        resolution: Option<&str>,
+    ) -> Result<bool> {
+        FileManager::set_video_metadata(self, file_id, version, duration, resolution).await
+    }
+
+    async fn check_lock(&self, file_id: &str, owner: Option<&str>) -> Result<()> {
+        FileManager::check_lock(self, file_id, owner).await
+    }
+
+    async fn search_files(&self, search: &FileSearch, limit: i64) -> Result<Vec<FileRecord>> {
+        FileManager::search_files(self, search, limit).await
+    }
+
+    async fn list_file_summaries(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<FileSummary>> {
+        FileManager::list_file_summaries(self, limit, offset).await
+    }
+
+    async fn list_perceptual_hashes(&self) -> Result<Vec<(String, i64)>> {
+        FileManager::list_perceptual_hashes(self).await
+    }
+
+    async fn list_images_missing_photo_metadata(&self) -> Result<Vec<FileRecord>> {
+        FileManager::list_images_missing_photo_metadata(self).await
+    }
+
+    async fn photo_timeline(
+        &self,
+        period_len: i32,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<PhotoTimelineEntry>> {
+        FileManager::photo_timeline(self, period_len, limit, offset).await
+    }
+
+    async fn get_files_by_ids(&self, file_ids: &[String]) -> Result<Vec<FileRecord>> {
+        FileManager::get_files_by_ids(self, file_ids).await
+    }
+
+    async fn find_files_by_sha256(&self, sha256: &str) -> Result<Vec<FileRecord>> {
+        FileManager::find_files_by_sha256(self, sha256).await
+    }
+}
+
+// 内存中的文件记录仓库. 只保存记录本身, 删除时不清理磁盘上的文件, 也没有回收站
+#[derive(Debug, Default)]
+pub struct MemoryFileRepository {
+    records: RwLock<HashMap<String, FileRecord>>,
+    tombstones: RwLock<HashMap<String, FileTombstone>>,
+    locks: RwLock<HashMap<String, FileLock>>,
+    links: RwLock<Vec<FileLink>>,
+    collections: RwLock<HashMap<String, Collection>>,
+}
+
+impl MemoryFileRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FileRepository for MemoryFileRepository {
+    // 与数据库实现一致, ID 已存在时返回错误
+    async fn save_file_record(&self, record: &FileRecord) -> Result<()> {
+        let mut records = self.records.write().unwrap();
+        if records.contains_key(&record.id) {
+            return Err(ServerError::conflict(format!("文件记录已存在: {}", record.id)));
+        }
+        records.insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn save_shared_record(&self, record: &FileRecord) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        if records.contains_key(&record.id) {
+            return Err(ServerError::conflict(format!("文件记录已存在: {}", record.id)));
+        }
+        let shared = records
+            .values()
+            .any(|existing| existing.file_path == record.file_path && existing.sha256 == record.sha256);
+        if shared {
+            records.insert(record.id.clone(), record.clone());
+        }
+        Ok(shared)
+    }
+
+    async fn get_file_by_id(&self, file_id: &str) -> Result<Option<FileRecord>> {
+        Ok(self.records.read().unwrap().get(file_id).cloned())
+    }
+
+    async fn query_files(&self, file_query: &FileQuery) -> Result<Vec<FileRecord>> {
+        let records = self.records.read().unwrap();
+        Ok(file_query.apply(records.values().cloned()))
+    }
+
+    async fn delete_file(
+        &self,
+        file_id: &str,
+        deleted_by: Option<&str>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get(file_id) else {
+            return Ok(false);
+        };
+        check_version(record, expected_version)?;
+
+        let record = records.remove(file_id).expect("记录存在");
+        self.locks.write().unwrap().remove(file_id);
+        self.links
+            .write()
+            .unwrap()
+            .retain(|link| link.source_id != file_id && link.target_id != file_id);
+        for collection in self.collections.write().unwrap().values_mut() {
+            collection.file_ids.retain(|id| id != file_id);
+        }
+        self.tombstones.write().unwrap().insert(
+            file_id.to_string(),
+            FileTombstone {
+                id: record.id,
+                original_name: record.original_name,
+                deleted_at: Utc::now(),
+                deleted_by: deleted_by.map(str::to_string),
+            },
+        );
+        Ok(true)
+    }
+
+    async fn get_tombstone(&self, file_id: &str) -> Result<Option<FileTombstone>> {
+        Ok(self.tombstones.read().unwrap().get(file_id).cloned())
+    }
+
+    async fn set_availability(
+        &self,
+        file_id: &str,
+        available_from: Option<DateTime<Utc>>,
+        available_until: Option<DateTime<Utc>>,
+        expected_version: Option<i64>,
+    ) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get_mut(file_id) else {
+            return Ok(false);
+        };
+        check_version(record, expected_version)?;
+
+        record.available_from = available_from;
+        record.available_until = available_until;
+        record.version += 1;
+        Ok(true)
+    }
+
+    // 文件与其他记录共用时新内容保存为同目录下的新文件, 否则直接覆盖
+    async fn replace_content(
+        &self,
+        file_id: &str,
+        temp_path: &Path,
+        file_size: i64,
+        sha256: &str,
+        expected_version: Option<i64>,
+    ) -> Result<Option<FileRecord>> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get(file_id) else {
+            return Ok(None);
+        };
+        check_version(record, expected_version)?;
+
+        let shared = records
+            .values()
+            .any(|other| other.id != file_id && other.file_path == record.file_path);
+        let (stored_name, file_path) = if shared {
+            let extension = Path::new(&record.stored_name)
+                .extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_default();
+            let stored_name = format!("{}{}", Uuid::new_v4(), extension);
+            let file_path = Path::new(&record.file_path).with_file_name(&stored_name);
+            (stored_name, file_path.to_string_lossy().to_string())
+        } else {
+            (record.stored_name.clone(), record.file_path.clone())
+        };
+        std::fs::rename(temp_path, &file_path).map_err(ServerError::Io)?;
+
+        let record = records.get_mut(file_id).expect("记录存在");
+        record.file_size = file_size;
+        record.sha256 = Some(sha256.to_string());
+        record.version += 1;
+        record.stored_name = stored_name;
+        record.file_path = file_path;
+        record.thumbnail_path = None;
+        record.video_duration = None;
+        record.video_resolution = None;
+        record.capture_time = None;
+        record.latitude = None;
+        record.longitude = None;
+        record.perceptual_hash = None;
+        Ok(Some(record.clone()))
+    }
+
+    // 通道容量等于记录数, 发送不会阻塞
+    fn stream_files(&self) -> ReceiverStream<Result<FileRecord>> {
+        let records = FileQuery::default().apply(self.records.read().unwrap().values().cloned());
+        let (tx, rx) = mpsc::channel(records.len().max(1));
+        for record in records {
+            let _ = tx.try_send(Ok(record));
+        }
+        ReceiverStream::new(rx)
+    }
+
+    async fn list_trash(&self, _limit: Option<i32>, _offset: Option<i32>) -> Result<Vec<TrashedFile>> {
+        Ok(Vec::new())
+    }
+
+    async fn restore_file(&self, _file_id: &str) -> Result<Option<FileRecord>> {
+        Ok(None)
+    }
+
+    async fn purge_trashed(&self, _file_id: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    // 与数据库实现一致: 无锁、锁已过期或由同一持有者持有时成功
+    async fn acquire_lock(&self, file_id: &str, owner: &str, ttl: Duration) -> Result<FileLock> {
+        self.check_lock(file_id, Some(owner)).await?;
+
+        let now = Utc::now();
+        let lock = FileLock {
+            file_id: file_id.to_string(),
+            owner: owner.to_string(),
+            acquired_at: now,
+            expires_at: now + ttl,
+        };
+        self.locks.write().unwrap().insert(file_id.to_string(), lock.clone());
+        Ok(lock)
+    }
+
+    async fn release_lock(&self, file_id: &str, owner: &str) -> Result<bool> {
+        self.check_lock(file_id, Some(owner)).await?;
+
+        let mut locks = self.locks.write().unwrap();
+        match locks.remove(file_id) {
+            Some(lock) => Ok(lock.expires_at > Utc::now()),
+            None => Ok(false),
+        }
+    }
+
+    async fn get_lock(&self, file_id: &str) -> Result<Option<FileLock>> {
+        let locks = self.locks.read().unwrap();
+        Ok(locks.get(file_id).filter(|lock| lock.expires_at > Utc::now()).cloned())
+    }
+
+    // 重复添加同一关联时保留原来的记录
+    async fn add_link(&self, source_id: &str, target_id: &str, kind: &str) -> Result<FileLink> {
+        let mut links = self.links.write().unwrap();
+        let existing = links
+            .iter()
+            .find(|link| link.source_id == source_id && link.target_id == target_id && link.kind == kind);
+        if let Some(link) = existing {
+            return Ok(link.clone());
+        }
+
+        let link = FileLink {
+            source_id: source_id.to_string(),
+            target_id: target_id.to_string(),
+            kind: kind.to_string(),
+            created_at: Utc::now(),
+        };
+        links.push(link.clone());
+        Ok(link)
+    }
+
+    async fn list_links(&self, file_id: &str) -> Result<Vec<FileLink>> {
+        let links = self.links.read().unwrap();
+        Ok(links
+            .iter()
+            .filter(|link| link.source_id == file_id || link.target_id == file_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn create_collection(&self, collection: &Collection) -> Result<()> {
+        let mut collections = self.collections.write().unwrap();
+        if collections.contains_key(&collection.id) {
+            return Err(ServerError::conflict(format!("集合已存在: {}", collection.id)));
+        }
+        collections.insert(collection.id.clone(), collection.clone());
+        Ok(())
+    }
+
+    // 创建时间保持不变, 与数据库实现一致
+    async fn update_collection(&self, collection: &Collection) -> Result<bool> {
+        let mut collections = self.collections.write().unwrap();
+        let Some(existing) = collections.get_mut(&collection.id) else {
+            return Ok(false);
+        };
+        *existing = Collection {
+            created_at: existing.created_at,
+            ..collection.clone()
+        };
+        Ok(true)
+    }
+
+    async fn get_collection(&self, collection_id: &str) -> Result<Option<Collection>> {
+        Ok(self.collections.read().unwrap().get(collection_id).cloned())
+    }
+
+    // 最近更新的在前
+    async fn list_collections(&self) -> Result<Vec<Collection>> {
+        let mut collections: Vec<Collection> = self.collections.read().unwrap().values().cloned().collect();
+        collections.sort_by_key(|collection| std::cmp::Reverse(collection.updated_at));
+        Ok(collections)
+    }
+
+    async fn delete_collection(&self, collection_id: &str) -> Result<bool> {
+        Ok(self.collections.write().unwrap().remove(collection_id).is_some())
+    }
+
+    async fn set_photo_metadata(
+        &self,
+        file_id: &str,
+        capture_time: Option<DateTime<Utc>>,
+        location: Option<(f64, f64)>,
+    ) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get_mut(file_id) else {
+            return Ok(false);
+        };
+        record.capture_time = capture_time.or(record.capture_time);
+        if let Some((latitude, longitude)) = location {
+            record.latitude = Some(latitude);
+            record.longitude = Some(longitude);
+        }
+        Ok(true)
+    }
+
+    async fn set_perceptual_hash(&self, file_id: &str, hash: i64) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get_mut(file_id) else {
+            return Ok(false);
+        };
+        record.perceptual_hash = Some(hash);
+        Ok(true)
+    }
+
+    async fn set_thumbnail_path(&self, file_id: &str, version: i64, thumbnail_path: &str) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get_mut(file_id).filter(|record| record.version == version) else {
+            return Ok(false);
+        };
+        record.thumbnail_path = Some(thumbnail_path.to_string());
+        Ok(true)
+    }
+
+    async fn set_video_metadata(
+        &self,
+        file_id: &str,
+        version: i64,
+        duration: Option<i32>,
+        resolution: Option<&str>,
+    ) -> Result<bool> {
+        let mut records = self.records.write().unwrap();
+        let Some(record) = records.get_mut(file_id).filter(|record| record.version == version) else {
+            return Ok(false);
+        };
+        record.video_duration = duration;
+        record.video_resolution = resolution.map(str::to_string);
+        Ok(true)
+    }
+}
@@ -0,0 +1,222 @@
+// 数据库行到记录类型的映射. 时间列以 RFC 3339 文本存储, 解析失败时作为列解码错误返回
+use super::file_manager::{
+    CanaryAlert, CanaryFile, Collection, FileContent, FileLink, FileLock, FileRecord, FileSummary, FileTombstone,
+    IdempotentResponse, PhotoTimelineEntry, PlaybackPosition, TrashedFile, UploadSession, UsageEntry,
+};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row};
+
+fn time_column(row: &SqliteRow, column: &str) -> sqlx::Result<DateTime<Utc>> {
+    let value: String = row.try_get(column)?;
+    parse_time(column, &value)
+}
+
+fn optional_time_column(row: &SqliteRow, column: &str) -> sqlx::Result<Option<DateTime<Utc>>> {
+    let value: Option<String> = row.try_get(column)?;
+    value.map(|value| parse_time(column, &value)).transpose()
+}
+
+fn parse_time(column: &str, value: &str) -> sqlx::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| sqlx::Error::ColumnDecode {
+            index: column.to_string(),
+            source: Box::new(e),
+        })
+}
+
+impl FromRow<'_, SqliteRow> for FileRecord {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            original_name: row.try_get("original_name")?,
+            stored_name: row.try_get("stored_name")?,
+            file_path: row.try_get("file_path")?,
+            file_size: row.try_get("file_size")?,
+            mime_type: row.try_get("mime_type")?,
+            upload_time: time_column(row, "upload_time")?,
+            is_video: row.try_get("is_video")?,
+            thumbnail_path: row.try_get("thumbnail_path")?,
+            video_duration: row.try_get("video_duration")?,
+            video_resolution: row.try_get("video_resolution")?,
+            available_from: optional_time_column(row, "available_from")?,
+            available_until: optional_time_column(row, "available_until")?,
+            version: row.try_get("version")?,
+            capture_time: optional_time_column(row, "capture_time")?,
+            latitude: row.try_get("latitude")?,
+            longitude: row.try_get("longitude")?,
+            perceptual_hash: row.try_get("perceptual_hash")?,
+            sha256: row.try_get("sha256")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for TrashedFile {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            file: FileRecord::from_row(row)?,
+            trashed_at: time_column(row, "trashed_at")?,
+            trashed_by: row.try_get("trashed_by")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for FileSummary {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            original_name: row.try_get("original_name")?,
+            file_size: row.try_get("file_size")?,
+            upload_time: time_column(row, "upload_time")?,
+            has_thumbnail: row.try_get("has_thumbnail")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for FileTombstone {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            original_name: row.try_get("original_name")?,
+            deleted_at: time_column(row, "deleted_at")?,
+            deleted_by: row.try_get("deleted_by")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for FileLock {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            file_id: row.try_get("file_id")?,
+            owner: row.try_get("owner")?,
+            acquired_at: time_column(row, "acquired_at")?,
+            expires_at: time_column(row, "expires_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for FileContent {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            file_id: row.try_get("file_id")?,
+            source: row.try_get("source")?,
+            content: row.try_get("content")?,
+            indexed_at: time_column(row, "indexed_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for FileLink {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            source_id: row.try_get("source_id")?,
+            target_id: row.try_get("target_id")?,
+            kind: row.try_get("kind")?,
+            created_at: time_column(row, "created_at")?,
+        })
+    }
+}
+
+// 集合的文件列表存放在 collection_items 表中, 由调用方另行填入
+impl FromRow<'_, SqliteRow> for Collection {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            file_ids: Vec::new(),
+            created_at: time_column(row, "created_at")?,
+            updated_at: time_column(row, "updated_at")?,
+        })
+    }
+}
+
+// 已收到的块存放在 upload_session_chunks 表中, 由调用方另行填入
+impl FromRow<'_, SqliteRow> for UploadSession {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            original_name: row.try_get("original_name")?,
+            total_size: row.try_get("total_size")?,
+            chunk_size: row.try_get("chunk_size")?,
+            received_chunks: Vec::new(),
+            created_at: time_column(row, "created_at")?,
+            updated_at: time_column(row, "updated_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for PlaybackPosition {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            file_id: row.try_get("file_id")?,
+            viewer: row.try_get("viewer")?,
+            position: row.try_get("position")?,
+            duration: row.try_get("duration")?,
+            updated_at: time_column(row, "updated_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for PhotoTimelineEntry {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            period: row.try_get("period")?,
+            count: row.try_get("count")?,
+            cover_id: row.try_get("cover_id")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for UsageEntry {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            day: row.try_get("day")?,
+            subject: row.try_get("subject")?,
+            uploaded_bytes: row.try_get("uploaded_bytes")?,
+            downloaded_bytes: row.try_get("downloaded_bytes")?,
+            requests: row.try_get("requests")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for IdempotentResponse {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            key: row.try_get("key")?,
+            scope: row.try_get("scope")?,
+            response: row.try_get("response")?,
+            created_at: time_column(row, "created_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for CanaryFile {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            file_id: row.try_get("file_id")?,
+            note: row.try_get("note")?,
+            created_at: time_column(row, "created_at")?,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for CanaryAlert {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let headers: String = row.try_get("headers")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            file_id: row.try_get("file_id")?,
+            client_ip: row.try_get("client_ip")?,
+            method: row.try_get("method")?,
+            path: row.try_get("path")?,
+            user_agent: row.try_get("user_agent")?,
+            headers: serde_json::from_str(&headers).map_err(|e| sqlx::Error::ColumnDecode {
+                index: "headers".to_string(),
+                source: Box::new(e),
+            })?,
+            triggered_at: time_column(row, "triggered_at")?,
+        })
+    }
+}
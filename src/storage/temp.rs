@@ -0,0 +1,191 @@
+// 临时文件管理 - 上传暂存等临时文件统一放在存储目录下的 .tmp/<kind>, 限制总大小并定期清理过期条目
+use crate::config::TempConfig;
+use super::periodic::spawn_periodic;
+use crate::error::{Result, ServerError};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TempUsage {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TempStats {
+    pub root: PathBuf,
+    pub max_size: u64,
+    pub files: u64,
+    pub bytes: u64,
+    // 按用途统计, 例如 upload、images
+    pub kinds: BTreeMap<String, TempUsage>,
+    // 启动以来清理掉的文件数和字节数
+    pub removed_files: u64,
+    pub removed_bytes: u64,
+    // 因超出大小上限被拒绝的次数
+    pub rejected: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TempCleanupReport {
+    pub removed_files: u64,
+    pub removed_bytes: u64,
+}
+
+pub struct TempManager {
+    root: PathBuf,
+    config: TempConfig,
+    removed_files: AtomicU64,
+    removed_bytes: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl TempManager {
+    pub fn new(root: PathBuf, config: TempConfig) -> Result<Self> {
+        std::fs::create_dir_all(&root).map_err(ServerError::Io)?;
+        Ok(Self {
+            root,
+            config,
+            removed_files: AtomicU64::new(0),
+            removed_bytes: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // 分配一个新的临时文件路径, 文件由调用方创建, 用完后重命名或删除
+    pub fn path(&self, kind: &str) -> Result<PathBuf> {
+        let usage = self.usage();
+        if usage.bytes >= self.config.max_size {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(ServerError::insufficient_storage(format!(
+                "临时目录已用 {} 字节, 超过上限 {} 字节",
+                usage.bytes, self.config.max_size
+            )));
+        }
+
+        let dir = self.root.join(kind);
+        std::fs::create_dir_all(&dir).map_err(ServerError::Io)?;
+        Ok(dir.join(uuid::Uuid::new_v4().to_string()))
+    }
+
+    // 当前占用的总大小
+    pub fn usage(&self) -> TempUsage {
+        self.kind_usage().into_values().fold(TempUsage::default(), |total, usage| TempUsage {
+            files: total.files + usage.files,
+            bytes: total.bytes + usage.bytes,
+        })
+    }
+
+    pub fn stats(&self) -> TempStats {
+        let kinds = self.kind_usage();
+        let usage = self.usage();
+        TempStats {
+            root: self.root.clone(),
+            max_size: self.config.max_size,
+            files: usage.files,
+            bytes: usage.bytes,
+            kinds,
+            removed_files: self.removed_files.load(Ordering::Relaxed),
+            removed_bytes: self.removed_bytes.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    // 删除修改时间早于 max_age 秒之前的临时文件
+    pub fn cleanup(&self) -> TempCleanupReport {
+        self.remove_older_than(Duration::from_secs(self.config.max_age))
+    }
+
+    // 启动时还没有任何请求在使用临时文件, 剩下的都是上次异常退出的遗留
+    pub fn purge(&self) -> TempCleanupReport {
+        self.remove_older_than(Duration::ZERO)
+    }
+
+    fn remove_older_than(&self, max_age: Duration) -> TempCleanupReport {
+        let mut report = TempCleanupReport::default();
+        let now = SystemTime::now();
+
+        for (path, metadata) in self.entries() {
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age < max_age {
+                continue;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                report.removed_files += 1;
+                report.removed_bytes += metadata.len();
+            }
+        }
+
+        self.removed_files.fetch_add(report.removed_files, Ordering::Relaxed);
+        self.removed_bytes.fetch_add(report.removed_bytes, Ordering::Relaxed);
+        report
+    }
+
+    fn kind_usage(&self) -> BTreeMap<String, TempUsage> {
+        let mut kinds: BTreeMap<String, TempUsage> = BTreeMap::new();
+        for (path, metadata) in self.entries() {
+            let kind = path
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let usage = kinds.entry(kind).or_default();
+            usage.files += 1;
+            usage.bytes += metadata.len();
+        }
+        kinds
+    }
+
+    // .tmp/<kind>/<name> 下的全部文件
+    fn entries(&self) -> Vec<(PathBuf, std::fs::Metadata)> {
+        let Ok(kinds) = std::fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+
+        let mut entries = Vec::new();
+        for kind in kinds.flatten() {
+            let Ok(files) = std::fs::read_dir(kind.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                if let Ok(metadata) = file.metadata() {
+                    if metadata.is_file() {
+                        entries.push((file.path(), metadata));
+                    }
+                }
+            }
+        }
+        entries
+    }
+}
+
+// 启动后台清理任务, 每隔 cleanup_interval 秒清理一次过期临时文件
+pub fn spawn_cleaner(temp: Arc<TempManager>) -> JoinHandle<()> {
+    let interval = temp.config.cleanup_interval;
+    spawn_periodic("临时文件清理", interval, move || {
+        let cleaner = temp.clone();
+        async move {
+            let report = tokio::task::spawn_blocking(move || cleaner.cleanup())
+                .await
+                .map_err(|e| ServerError::Internal(e.into()))?;
+            if report.removed_files > 0 {
+                info!("清理过期临时文件: {} 个, {} 字节", report.removed_files, report.removed_bytes);
+            }
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,38 @@
+// 回收站自动清除 - 在回收站中超过 max_age 的文件被永久删除
+use super::periodic::spawn_periodic;
+use super::FileManager;
+use crate::config::TrashConfig;
+use crate::error::Result;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+// 永久删除移入回收站超过 max_age 秒的文件, 返回删除的数量
+pub async fn purge_expired(file_manager: &FileManager, max_age: u64) -> Result<u64> {
+    let before = Utc::now() - Duration::seconds(max_age.min(i64::MAX as u64) as i64);
+    let mut purged = 0;
+    for file_id in file_manager.list_expired_trash(before).await? {
+        // 单个文件失败不影响其余文件, 下一轮再试
+        match file_manager.purge_trashed(&file_id).await {
+            Ok(true) => purged += 1,
+            Ok(false) => {}
+            Err(e) => warn!("永久删除回收站中的文件 {} 失败: {}", file_id, e),
+        }
+    }
+    Ok(purged)
+}
+
+// 启动后台清除任务, 每隔 cleanup_interval 秒检查一次
+pub fn spawn_cleaner(file_manager: Arc<FileManager>, config: TrashConfig) -> JoinHandle<()> {
+    spawn_periodic("回收站清除", config.cleanup_interval, move || {
+        let file_manager = file_manager.clone();
+        async move {
+            let purged = purge_expired(&file_manager, config.max_age).await?;
+            if purged > 0 {
+                info!("清除回收站中过期的文件: {} 个", purged);
+            }
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,96 @@
+// 上传会话自动清理 - 超过 max_age 没有收到新分块的会话连同拼接文件一起删除, 过期的 Idempotency-Key 同时清除
+use super::periodic::spawn_periodic;
+use super::FileManager;
+use crate::config::UploadSessionConfig;
+use crate::error::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+// 每个会话一把读写锁. 分块写入拼接文件时持有读锁, 各块仍可并发写入;
+// 完成、取消和过期清理持有写锁, 删除会话时不会有分块正写到一半, 也不会在删除后重新创建拼接文件.
+// FileManager 的各个副本共用同一张表
+#[derive(Debug, Clone, Default)]
+pub struct SessionLocks {
+    locks: Arc<Mutex<HashMap<String, Weak<RwLock<()>>>>>,
+}
+
+impl SessionLocks {
+    pub async fn read(&self, session_id: &str) -> OwnedRwLockReadGuard<()> {
+        self.lock(session_id).read_owned().await
+    }
+
+    pub async fn write(&self, session_id: &str) -> OwnedRwLockWriteGuard<()> {
+        self.lock(session_id).write_owned().await
+    }
+
+    // 没有人持有的锁随时丢弃, 表中只保留正在使用的会话
+    fn lock(&self, session_id: &str) -> Arc<RwLock<()>> {
+        let mut locks = self.locks.lock().unwrap();
+        if let Some(lock) = locks.get(session_id).and_then(Weak::upgrade) {
+            return lock;
+        }
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(RwLock::new(()));
+        locks.insert(session_id.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+}
+
+// 删除会话及其拼接文件, 会话不存在时返回 false
+pub async fn remove_session(file_manager: &FileManager, session_id: &str) -> Result<bool> {
+    remove_session_before(file_manager, session_id, None).await
+}
+
+// 指定 before 时, 只在会话此后没有收到新分块时删除, 列出过期会话之后到达的分块不会被清理掉
+async fn remove_session_before(
+    file_manager: &FileManager,
+    session_id: &str,
+    before: Option<DateTime<Utc>>,
+) -> Result<bool> {
+    let _guard = file_manager.upload_locks().write(session_id).await;
+    if let Some(before) = before {
+        match file_manager.get_upload_session(session_id).await? {
+            Some(session) if session.updated_at < before => {}
+            _ => return Ok(false),
+        }
+    }
+    let removed = file_manager.delete_upload_session(session_id).await?;
+    if removed {
+        let _ = tokio::fs::remove_file(file_manager.upload_part_path(session_id)?).await;
+    }
+    Ok(removed)
+}
+
+// 删除过期的会话和 Idempotency-Key, 返回删除的会话数量
+pub async fn purge_expired(file_manager: &FileManager, max_age: u64) -> Result<u64> {
+    let before = Utc::now() - Duration::seconds(max_age.min(i64::MAX as u64) as i64);
+    let mut purged = 0;
+    for session_id in file_manager.list_expired_upload_sessions(before).await? {
+        // 单个会话失败不影响其余会话, 下一轮再试
+        match remove_session_before(file_manager, &session_id, Some(before)).await {
+            Ok(true) => purged += 1,
+            Ok(false) => {}
+            Err(e) => warn!("删除过期的上传会话 {} 失败: {}", session_id, e),
+        }
+    }
+    file_manager.purge_idempotent_responses(before).await?;
+    Ok(purged)
+}
+
+// 启动后台清理任务, 每隔 cleanup_interval 秒检查一次
+pub fn spawn_cleaner(file_manager: Arc<FileManager>, config: UploadSessionConfig) -> JoinHandle<()> {
+    spawn_periodic("上传会话清理", config.cleanup_interval, move || {
+        let file_manager = file_manager.clone();
+        async move {
+            let purged = purge_expired(&file_manager, config.max_age).await?;
+            if purged > 0 {
+                info!("删除过期的上传会话: {} 个", purged);
+            }
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,100 @@
+// 测试辅助模块 - 在随机端口上启动完整的服务器, 使用临时存储目录和内存数据库
+use crate::config::Config;
+use crate::error::{Result, ServerError};
+use crate::server::{create_router, create_state, AppState};
+use crate::storage::{FileManager, FileRepository};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+pub struct TestServer {
+    addr: SocketAddr,
+    state: AppState,
+    storage_dir: PathBuf,
+    handle: JoinHandle<()>,
+}
+
+impl TestServer {
+    // 使用默认配置启动测试服务器
+    pub async fn start() -> Result<Self> {
+        Self::start_with_config(Config::default()).await
+    }
+
+    // 使用自定义配置启动测试服务器, 存储目录、数据库和监听地址会被覆盖
+    pub async fn start_with_config(config: Config) -> Result<Self> {
+        Self::start_inner(config, None).await
+    }
+
+    // 使用指定的文件记录仓库启动测试服务器, 处理器读写的文件记录都来自该仓库.
+    // 附属表仍使用内存中的 SQLite 数据库
+    pub async fn start_with_repository(files: Arc<dyn FileRepository>) -> Result<Self> {
+        Self::start_inner(Config::default(), Some(files)).await
+    }
+
+    async fn start_inner(mut config: Config, files: Option<Arc<dyn FileRepository>>) -> Result<Self> {
+        let storage_dir = std::env::temp_dir().join(format!("file-server-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&storage_dir).map_err(ServerError::Io)?;
+
+        config.server.address = "127.0.0.1".to_string();
+        config.server.port = 0;
+        config.database.url = "sqlite::memory:".to_string();
+        config.storage.path = storage_dir.clone();
+        config.storage.upload_dir = storage_dir.clone();
+
+        let mut state = create_state(&config).await?;
+        if let Some(files) = files {
+            state.files = files;
+        }
+        let app = create_router(state.clone()).await?;
+
+        let listener = tokio::net::TcpListener::bind(config.server_address())
+            .await
+            .map_err(ServerError::Io)?;
+        let addr = listener.local_addr().map_err(ServerError::Io)?;
+
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await;
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            storage_dir,
+            handle,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // 拼接完整的请求地址, 例如 server.url("/api/files")
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn file_manager(&self) -> &FileManager {
+        &self.state.file_manager
+    }
+
+    pub fn files(&self) -> &dyn FileRepository {
+        self.state.files.as_ref()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.state.config
+    }
+
+    pub fn storage_dir(&self) -> &Path {
+        &self.storage_dir
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+        let _ = std::fs::remove_dir_all(&self.storage_dir);
+    }
+}
@@ -0,0 +1,301 @@
+// 分块上传 - 大文件按 storage.chunk_size 分块上传, 断线后查询会话即可只补传缺少的块.
+// 各块写入 .uploads 下拼接文件的对应位置, 全部收到后计算哈希并移入存储目录
+use crate::check::available_space;
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{uploads, FileRecord, UploadSession};
+use crate::upload::handler::{detect_mime_type, is_video, original_name};
+use crate::upload::dedup::{self, store_file};
+use crate::upload::idempotency;
+use crate::upload::processing::{DedupOutcome, UploadResponse};
+use crate::upload::writer::{verify_checksum, write_body_to_temp};
+use crate::video::processor::spawn_processing;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::HeaderMap,
+    response::Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{Read, SeekFrom};
+use tokio::io::AsyncSeekExt;
+
+// 计算哈希时每次读取的字节数
+const HASH_BUFFER: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct InitUploadRequest {
+    pub file_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadSessionResponse {
+    #[serde(flatten)]
+    pub session: UploadSession,
+    pub chunk_count: i64,
+    // 尚未收到的块, 续传时只需上传这些块
+    pub missing_chunks: Vec<i64>,
+}
+
+impl From<UploadSession> for UploadSessionResponse {
+    fn from(session: UploadSession) -> Self {
+        let missing_chunks = (0..session.chunk_count())
+            .filter(|index| session.received_chunks.binary_search(index).is_err())
+            .collect();
+        Self { chunk_count: session.chunk_count(), missing_chunks, session }
+    }
+}
+
+// 创建上传会话, 块大小取 storage.chunk_size. 为声明的大小预留磁盘空间, 空间不足时返回 507
+pub async fn init_upload(
+    State(state): State<AppState>,
+    Json(request): Json<InitUploadRequest>,
+) -> std::result::Result<Json<ApiResponse<UploadSessionResponse>>, ApiError> {
+    const CONTEXT: &str = "创建上传会话失败";
+
+    let original_name = original_name(&request.file_name).map_err(|e| api_error(CONTEXT, e))?;
+    let max_size = state.config.storage.max_file_size;
+    if request.size == 0 || request.size > max_size {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("文件大小必须在 1-{} 字节之间", max_size)),
+        ));
+    }
+
+    let now = Utc::now();
+    let session = UploadSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        original_name,
+        total_size: request.size as i64,
+        chunk_size: state.config.storage.chunk_size as i64,
+        received_chunks: Vec::new(),
+        created_at: now,
+        updated_at: now,
+    };
+    let sessions = &state.config.storage.upload_sessions;
+    let capacity = available_space(&state.config.storage.upload_dir)
+        .unwrap_or(u64::MAX)
+        .min(sessions.max_reserved.unwrap_or(u64::MAX));
+    state
+        .file_manager
+        .create_upload_session(&session, capacity)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(Json(ApiResponse::success(session.into())))
+}
+
+// 查询会话进度, 客户端断线重连后据此续传
+pub async fn get_upload_session(
+    Path(session_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<UploadSessionResponse>>, ApiError> {
+    const CONTEXT: &str = "获取上传会话失败";
+
+    let session = load_session(&state, &session_id).await.map_err(|e| api_error(CONTEXT, e))?;
+    Ok(Json(ApiResponse::success(session.into())))
+}
+
+// 上传第 n 块 (从 0 开始), 请求体长度必须与该块的长度一致. 重复上传同一块会覆盖之前的内容
+pub async fn upload_chunk(
+    Path((session_id, index)): Path<(String, i64)>,
+    State(state): State<AppState>,
+    body: Body,
+) -> std::result::Result<Json<ApiResponse<UploadSessionResponse>>, ApiError> {
+    const CONTEXT: &str = "上传分块失败";
+
+    let session = load_session(&state, &session_id).await.map_err(|e| api_error(CONTEXT, e))?;
+    let (start, length) = session.chunk_range(index).ok_or_else(|| {
+        api_error(
+            CONTEXT,
+            ServerError::validation(format!("分块序号必须在 0-{} 之间", session.chunk_count() - 1)),
+        )
+    })?;
+
+    write_chunk(&state, &session.id, index, body, start, length)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let session = load_session(&state, &session_id).await.map_err(|e| api_error(CONTEXT, e))?;
+    Ok(Json(ApiResponse::success(session.into())))
+}
+
+// 取消上传, 删除会话和已收到的内容并释放预留的空间
+pub async fn abort_upload(
+    Path(session_id): Path<String>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<()>>, ApiError> {
+    const CONTEXT: &str = "取消上传失败";
+
+    match uploads::remove_session(&state.file_manager, &session_id).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err(api_error(CONTEXT, ServerError::not_found(format!("上传会话 {}", session_id)))),
+        Err(e) => Err(api_error(CONTEXT, e)),
+    }
+}
+
+// 全部块收到后创建文件记录, 会话随之删除.
+// 带 X-Content-SHA256 时先校验内容, 不一致时保留会话, 客户端可以重传分块后再次完成.
+// 带 Idempotency-Key 时, 会话删除后的重试仍然返回第一次的结果
+pub async fn complete_upload(
+    Path(session_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<Json<ApiResponse<Value>>, ApiError> {
+    const CONTEXT: &str = "完成分块上传失败";
+
+    let key = idempotency::key(&headers);
+    if let Some(key) = key {
+        let saved = idempotency::claim(&state, key, &session_id)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?;
+        if let Some(response) = saved {
+            return Ok(Json(ApiResponse::success(response)));
+        }
+    }
+
+    let result = finish_session(&state, &session_id, &headers).await;
+    let response = idempotency::finish(&state, key, result)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    Ok(Json(ApiResponse::success(response)))
+}
+
+async fn finish_session(state: &AppState, session_id: &str, headers: &HeaderMap) -> Result<UploadResponse> {
+    // 持有写锁直到会话删除, 此后到达的分块找不到会话
+    let guard = state.file_manager.upload_locks().write(session_id).await;
+    let session = load_session(state, session_id).await?;
+    if !session.is_complete() {
+        let missing = UploadSessionResponse::from(session).missing_chunks;
+        return Err(ServerError::precondition_failed(format!("还有 {} 个分块没有上传", missing.len())));
+    }
+
+    let path = state.file_manager.upload_part_path(&session.id)?;
+    let source = path.clone();
+    let sha256 = tokio::task::spawn_blocking(move || hash_file(&source))
+        .await
+        .map_err(|e| ServerError::Internal(e.into()))??;
+    verify_checksum(headers, &sha256)?;
+
+    // 先删除会话, 并发的 complete 请求中只有一个会继续
+    if !state.file_manager.delete_upload_session(&session.id).await? {
+        return Err(ServerError::not_found(format!("上传会话 {}", session.id)));
+    }
+    drop(guard);
+
+    let (record, dedup) = match save_record(state, &session, &path, sha256).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(e);
+        }
+    };
+    if record.is_video {
+        spawn_processing(state, &record);
+    }
+    Ok(UploadResponse::new(state, record).with_dedup(dedup))
+}
+
+async fn load_session(state: &AppState, session_id: &str) -> Result<UploadSession> {
+    state
+        .file_manager
+        .get_upload_session(session_id)
+        .await?
+        .ok_or_else(|| ServerError::not_found(format!("上传会话 {}", session_id)))
+}
+
+// 先完整接收到临时文件, 长度正确后再写入拼接文件中 [start, start + length) 的位置,
+// 重传的块中途断开时不会破坏之前已经收到的内容. 各块可以并发上传.
+// 写入拼接文件时持有会话的读锁, 并确认会话仍然存在, 与完成和取消互斥
+async fn write_chunk(
+    state: &AppState,
+    session_id: &str,
+    index: i64,
+    body: Body,
+    start: u64,
+    length: u64,
+) -> Result<()> {
+    let written = write_body_to_temp(&state.temp, body, length).await?;
+    let result = async {
+        if written.size != length {
+            return Err(ServerError::validation(format!(
+                "分块长度应为 {} 字节, 实际收到 {} 字节",
+                length, written.size
+            )));
+        }
+
+        let _guard = state.file_manager.upload_locks().read(session_id).await;
+        load_session(state, session_id).await?;
+        let path = state.file_manager.upload_part_path(session_id)?;
+        let mut source = tokio::fs::File::open(&written.path).await.map_err(ServerError::Io)?;
+        let mut target = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .await
+            .map_err(ServerError::Io)?;
+        target.seek(SeekFrom::Start(start)).await.map_err(ServerError::Io)?;
+        tokio::io::copy(&mut source, &mut target).await.map_err(ServerError::Io)?;
+        target.sync_all().await.map_err(ServerError::Io)?;
+        state.file_manager.record_upload_chunk(session_id, index).await
+    }
+    .await;
+
+    let _ = tokio::fs::remove_file(&written.path).await;
+    result
+}
+
+async fn save_record(
+    state: &AppState,
+    session: &UploadSession,
+    path: &std::path::Path,
+    sha256: String,
+) -> Result<(FileRecord, DedupOutcome)> {
+    let original_name = &session.original_name;
+    let stored = store_file(state, path, original_name, session.total_size as u64, &sha256).await?;
+
+    let mime_type = detect_mime_type(original_name, None);
+    let mut record = FileRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        original_name: original_name.clone(),
+        stored_name: stored.stored_name.clone(),
+        file_path: stored.file_path.to_string_lossy().to_string(),
+        file_size: session.total_size,
+        is_video: is_video(state, original_name, &mime_type),
+        mime_type,
+        upload_time: Utc::now(),
+        thumbnail_path: None,
+        video_duration: None,
+        video_resolution: None,
+        available_from: None,
+        available_until: None,
+        version: 1,
+        capture_time: None,
+        latitude: None,
+        longitude: None,
+        perceptual_hash: None,
+        sha256: Some(sha256),
+    };
+
+    let dedup = dedup::save_record(state, stored, &mut record).await?;
+    Ok((record, dedup))
+}
+
+fn hash_file(path: &std::path::Path) -> Result<String> {
+    let mut file = std::fs::File::open(path).map_err(ServerError::Io)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; HASH_BUFFER];
+    loop {
+        let read = file.read(&mut buffer).map_err(ServerError::Io)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
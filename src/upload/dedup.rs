@@ -0,0 +1,89 @@
+// 内容去重 - 上传内容与已有文件完全相同时只保存一份, 多条记录指向同一个文件.
+// 删除记录时只有最后一条引用被删除后才删除文件
+use crate::error::{Result, ServerError};
+use crate::server::AppState;
+use crate::storage::FileRecord;
+use crate::upload::processing::DedupOutcome;
+use std::path::{Path, PathBuf};
+
+// 文件在存储目录中的位置
+#[derive(Debug)]
+pub struct StoredFile {
+    pub stored_name: String,
+    pub file_path: PathBuf,
+    pub dedup: DedupOutcome,
+    // 复用已有文件时保留的临时文件, 记录写入后才删除
+    pending: Option<PathBuf>,
+}
+
+impl StoredFile {
+    // 本次上传的内容所在位置, 复用已有文件时为保留的临时文件, 不受已有文件被并发删除的影响
+    pub fn content_path(&self) -> &Path {
+        self.pending.as_deref().unwrap_or(&self.file_path)
+    }
+}
+
+// 写入 store_file 之后的记录. 复用的已有文件在写入前已被其他请求删除或替换时, 把保留的临时文件保存为新文件,
+// 记录改为指向新文件. 返回最终的去重结果, 写入失败时删除本次新保存的文件, 临时文件由调用方清理
+pub async fn save_record(state: &AppState, mut stored: StoredFile, record: &mut FileRecord) -> Result<DedupOutcome> {
+    if let Some(temp_path) = stored.pending.take() {
+        if state.files.save_shared_record(record).await? {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Ok(DedupOutcome::Duplicate);
+        }
+
+        let sha256 = record.sha256.clone().unwrap_or_default();
+        stored = move_into_storage(state, &temp_path, &record.original_name, &sha256, DedupOutcome::Unique).await?;
+        record.stored_name = stored.stored_name.clone();
+        record.file_path = stored.file_path.to_string_lossy().to_string();
+    }
+
+    if let Err(e) = state.files.save_file_record(record).await {
+        let _ = tokio::fs::remove_file(&stored.file_path).await;
+        return Err(e);
+    }
+    Ok(stored.dedup)
+}
+
+// 把临时文件移入存储目录. 启用去重且已有大小和 SHA-256 都相同的文件时复用已有文件, 临时文件保留到 save_record
+pub async fn store_file(
+    state: &AppState,
+    temp_path: &Path,
+    original_name: &str,
+    size: u64,
+    sha256: &str,
+) -> Result<StoredFile> {
+    let deduplicate = state.config.storage.deduplicate;
+    if deduplicate {
+        let existing = state
+            .files
+            .find_files_by_sha256(sha256)
+            .await?
+            .into_iter()
+            .find(|record| record.file_size == size as i64 && Path::new(&record.file_path).is_file());
+        if let Some(existing) = existing {
+            return Ok(StoredFile {
+                stored_name: existing.stored_name,
+                file_path: PathBuf::from(existing.file_path),
+                dedup: DedupOutcome::Duplicate,
+                pending: Some(temp_path.to_path_buf()),
+            });
+        }
+    }
+
+    let dedup = if deduplicate { DedupOutcome::Unique } else { DedupOutcome::NotChecked };
+    move_into_storage(state, temp_path, original_name, sha256, dedup).await
+}
+
+async fn move_into_storage(
+    state: &AppState,
+    temp_path: &Path,
+    original_name: &str,
+    sha256: &str,
+    dedup: DedupOutcome,
+) -> Result<StoredFile> {
+    let stored_name = state.file_manager.generate_stored_name_with_hash(original_name, Some(sha256));
+    let file_path = state.file_manager.get_file_path(&stored_name);
+    tokio::fs::rename(temp_path, &file_path).await.map_err(ServerError::Io)?;
+    Ok(StoredFile { stored_name, file_path, dedup, pending: None })
+}
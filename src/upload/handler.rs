@@ -1,15 +1,219 @@
-// 上传处理器占位符
-use crate::error::Result;
+// 文件上传 - 接收 multipart/form-data 表单, 文件字段边接收边写入磁盘, 完成后创建文件记录
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use crate::upload::dedup::{self, store_file};
+use crate::upload::idempotency;
+use crate::upload::processing::{DedupOutcome, UploadResponse};
+use crate::upload::writer::{verify_checksum, write_stream_to_temp, WrittenFile};
+use crate::video::processor::spawn_processing;
+use axum::{
+    extract::{Multipart, State},
+    http::HeaderMap,
+    response::Json,
+};
+use chrono::Utc;
+use futures::TryStreamExt;
+use serde_json::Value;
+use std::path::Path as FsPath;
+use std::time::Instant;
+use tracing::{debug, info};
 
-pub struct UploadHandler;
+// 上传文件, 表单中带文件名的字段即为文件内容, 每次请求上传一个文件.
+// 带 X-Content-SHA256 时校验收到的内容, 带 Idempotency-Key 时重试返回第一次的结果
+pub async fn upload_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> std::result::Result<Json<ApiResponse<Value>>, ApiError> {
+    const CONTEXT: &str = "上传文件失败";
 
-impl UploadHandler {
-    pub fn new() -> Self {
-        Self
+    let key = idempotency::key(&headers);
+    if let Some(key) = key {
+        let saved = idempotency::claim(&state, key, idempotency::UPLOAD_SCOPE)
+            .await
+            .map_err(|e| api_error(CONTEXT, e))?;
+        if let Some(response) = saved {
+            return Ok(Json(ApiResponse::success(response)));
+        }
     }
 
-    pub async fn handle_upload(&self) -> Result<()> {
-        // TODO: 实现文件上传逻辑
-        Ok(())
+    let result = receive_upload(&state, &headers, multipart).await;
+    let response = idempotency::finish(&state, key, result)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    Ok(Json(ApiResponse::success(response)))
+}
+
+async fn receive_upload(state: &AppState, headers: &HeaderMap, mut multipart: Multipart) -> Result<UploadResponse> {
+    // 文件先写入临时目录, 读完整个表单后才创建记录, 表单出错时只需删除临时文件
+    let mut uploaded: Option<UploadedField> = None;
+    let limits = &state.config.storage.multipart;
+    let result = async {
+        let mut parts = 0;
+        while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+            parts += 1;
+            if parts > limits.max_parts {
+                return Err(ServerError::validation(format!("表单字段数量超过上限 {}", limits.max_parts)));
+            }
+
+            // 没有文件名的普通字段读完后丢弃, 但不能超过 max_field_size
+            let Some(file_name) = field.file_name().map(str::to_string) else {
+                let name = field.name().unwrap_or_default().to_string();
+                let mut size = 0;
+                while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                    size += chunk.len() as u64;
+                    if size > limits.max_field_size {
+                        return Err(ServerError::validation(format!(
+                            "表单字段 {} 超过 {} 字节",
+                            name, limits.max_field_size
+                        )));
+                    }
+                }
+                continue;
+            };
+            if uploaded.is_some() {
+                return Err(ServerError::validation("每次请求只能上传一个文件"));
+            }
+            let original_name = original_name(&file_name)?;
+            let content_type = field.content_type().map(str::to_string);
+            let mut progress = UploadProgress::new(&original_name, limits.progress_interval);
+            let stream = field.map_err(multipart_error).inspect_ok(|chunk| progress.record(chunk.len()));
+            let written = write_stream_to_temp(&state.temp, stream, state.config.storage.max_file_size).await?;
+            progress.finish();
+            uploaded = Some(UploadedField { original_name, content_type, written });
+        }
+        let uploaded = uploaded.as_ref().ok_or_else(|| ServerError::validation("表单中没有文件字段"))?;
+        verify_checksum(headers, &uploaded.written.sha256)?;
+        save_record(state, uploaded).await
+    }
+    .await;
+
+    match result {
+        Ok((record, dedup)) => {
+            if record.is_video {
+                spawn_processing(state, &record);
+            }
+            Ok(UploadResponse::new(state, record).with_dedup(dedup))
+        }
+        Err(e) => {
+            if let Some(uploaded) = uploaded {
+                let _ = tokio::fs::remove_file(&uploaded.written.path).await;
+            }
+            Err(e)
+        }
+    }
+}
+
+// 接收文件字段时的进度, 每收到 interval 字节记录一次累计大小和平均速率
+struct UploadProgress<'a> {
+    name: &'a str,
+    interval: u64,
+    started: Instant,
+    bytes: u64,
+    next_report: u64,
+}
+
+impl<'a> UploadProgress<'a> {
+    fn new(name: &'a str, interval: u64) -> Self {
+        Self { name, interval, started: Instant::now(), bytes: 0, next_report: interval }
+    }
+
+    fn record(&mut self, len: usize) {
+        self.bytes += len as u64;
+        if self.bytes >= self.next_report {
+            self.next_report = self.bytes - self.bytes % self.interval + self.interval;
+            debug!("正在接收上传 {}: {} 字节, {:.1} MB/s", self.name, self.bytes, self.throughput());
+        }
     }
-}
\ No newline at end of file
+
+    fn finish(&self) {
+        info!(
+            "接收上传 {} 完成: {} 字节, 用时 {:.1} 秒, {:.1} MB/s",
+            self.name,
+            self.bytes,
+            self.started.elapsed().as_secs_f64(),
+            self.throughput()
+        );
+    }
+
+    fn throughput(&self) -> f64 {
+        self.bytes as f64 / 1024.0 / 1024.0 / self.started.elapsed().as_secs_f64().max(0.001)
+    }
+}
+
+struct UploadedField {
+    original_name: String,
+    content_type: Option<String>,
+    written: WrittenFile,
+}
+
+async fn save_record(state: &AppState, uploaded: &UploadedField) -> Result<(FileRecord, DedupOutcome)> {
+    let UploadedField { original_name, content_type, written } = uploaded;
+    let stored = store_file(state, &written.path, original_name, written.size, &written.sha256).await?;
+
+    let mime_type = detect_mime_type(original_name, content_type.as_deref());
+    let is_video = is_video(state, original_name, &mime_type);
+    let mut record = FileRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        original_name: original_name.clone(),
+        stored_name: stored.stored_name.clone(),
+        file_path: stored.file_path.to_string_lossy().to_string(),
+        file_size: written.size as i64,
+        mime_type,
+        upload_time: Utc::now(),
+        is_video,
+        thumbnail_path: None,
+        video_duration: None,
+        video_resolution: None,
+        available_from: None,
+        available_until: None,
+        version: 1,
+        capture_time: None,
+        latitude: None,
+        longitude: None,
+        perceptual_hash: None,
+        sha256: Some(written.sha256.clone()),
+    };
+
+    let dedup = dedup::save_record(state, stored, &mut record).await?;
+    Ok((record, dedup))
+}
+
+// 部分客户端会带上完整的本地路径, 只保留最后一段
+pub fn original_name(file_name: &str) -> Result<String> {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() {
+        return Err(ServerError::validation("上传的文件名不能为空"));
+    }
+    Ok(name.to_string())
+}
+
+// 优先按扩展名判断, 无法判断时使用表单字段声明的类型
+pub fn detect_mime_type(original_name: &str, content_type: Option<&str>) -> String {
+    match mime_guess::from_path(original_name).first() {
+        Some(mime) => mime.to_string(),
+        None => content_type
+            .filter(|value| value.parse::<mime::Mime>().is_ok())
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM.as_ref())
+            .to_string(),
+    }
+}
+
+pub fn is_video(state: &AppState, original_name: &str, mime_type: &str) -> bool {
+    let extension = FsPath::new(original_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    mime_type.starts_with("video/")
+        || state
+            .config
+            .video
+            .supported_formats
+            .iter()
+            .any(|format| format.eq_ignore_ascii_case(&extension))
+}
+
+fn multipart_error(e: axum::extract::multipart::MultipartError) -> ServerError {
+    ServerError::validation(format!("上传表单格式无效: {}", e.body_text()))
+}
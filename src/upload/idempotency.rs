@@ -0,0 +1,53 @@
+// 上传的 Idempotency-Key - 客户端超时重试时携带同一个键, 返回第一次成功的结果而不是再创建一条记录.
+// 键在处理前占用, 第一个请求还在处理时到达的重试返回 409, 失败的请求释放键以便重试
+use crate::error::{Result, ServerError};
+use crate::server::AppState;
+use axum::http::HeaderMap;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::warn;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+// POST /api/upload 使用的 scope, 分块上传以会话 ID 作为 scope
+pub const UPLOAD_SCOPE: &str = "upload";
+
+pub fn key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+// 占用键. 返回 Some 时是之前保存的响应, 直接返回给客户端
+pub async fn claim(state: &AppState, key: &str, scope: &str) -> Result<Option<Value>> {
+    let Some(saved) = state.file_manager.claim_idempotency_key(key, scope).await? else {
+        return Ok(None);
+    };
+    if saved.scope != scope {
+        return Err(ServerError::conflict("Idempotency-Key 已用于其他请求"));
+    }
+    let response = saved
+        .response
+        .ok_or_else(|| ServerError::conflict("相同 Idempotency-Key 的请求正在处理"))?;
+    Ok(Some(serde_json::from_str(&response)?))
+}
+
+// 保存成功的响应, 失败时释放键
+pub async fn finish<T: Serialize>(state: &AppState, key: Option<&str>, result: Result<T>) -> Result<Value> {
+    let result = result.and_then(|response| Ok(serde_json::to_value(response)?));
+    let Some(key) = key else {
+        return result;
+    };
+
+    let saved = match &result {
+        Ok(response) => state.file_manager.save_idempotent_response(key, &response.to_string()).await,
+        Err(_) => state.file_manager.release_idempotency_key(key).await,
+    };
+    // 请求本身已经完成, 这里失败只影响重放
+    if let Err(e) = saved {
+        warn!("更新 Idempotency-Key {} 失败: {}", key, e);
+    }
+    result
+}
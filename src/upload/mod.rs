@@ -1,4 +1,11 @@
-// 文件上传模块占位符
+// 文件上传模块
+pub mod chunked;
+pub mod dedup;
 pub mod handler;
-
-pub use handler::UploadHandler;
\ No newline at end of file
+pub mod idempotency;
+pub mod paste;
+pub mod processing;
+pub mod proxy;
+pub mod replace;
+pub mod screenshot;
+pub mod writer;
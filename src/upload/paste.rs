@@ -0,0 +1,158 @@
+// 文本片段 - 直接提交一段文本保存为小文件, 并提供带语法标记的查看页面
+use crate::download::canary::{self, DownloadContext};
+use crate::error::ServerError;
+use crate::server::{api_error, base_url, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use crate::upload::dedup::{self, store_file};
+use crate::upload::writer::write_body_to_temp;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+// 单个片段的最大大小
+const MAX_PASTE_SIZE: u64 = 1024 * 1024;
+
+// 最长保留时间 (秒)
+const MAX_PASTE_TTL: i64 = 365 * 24 * 3600;
+
+#[derive(Debug, Deserialize)]
+pub struct PasteQuery {
+    // 语法提示, 例如 rust、json, 作为文件扩展名保存
+    pub syntax: Option<String>,
+    // 多少秒后过期, 为空时不过期
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasteResponse {
+    #[serde(flatten)]
+    pub file: FileRecord,
+    pub view_url: String,
+}
+
+// 保存文本片段
+pub async fn create_paste(
+    Query(params): Query<PasteQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> std::result::Result<Json<ApiResponse<PasteResponse>>, ApiError> {
+    const CONTEXT: &str = "保存文本片段失败";
+
+    let syntax = params.syntax.unwrap_or_else(|| "txt".to_string()).to_ascii_lowercase();
+    let valid_syntax = !syntax.is_empty()
+        && syntax.len() <= 20
+        && syntax.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '+' || c == '-');
+    if !valid_syntax {
+        return Err(api_error(CONTEXT, ServerError::validation(format!("无效的语法提示: {}", syntax))));
+    }
+    if params.expires_in.is_some_and(|ttl| !(1..=MAX_PASTE_TTL).contains(&ttl)) {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::validation(format!("过期时间必须在 1-{} 秒之间", MAX_PASTE_TTL)),
+        ));
+    }
+
+    let written = write_body_to_temp(&state.temp, body, MAX_PASTE_SIZE)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let result = save_paste(&state, &written.path, written.size, written.sha256, &syntax, params.expires_in).await;
+    let record = match result {
+        Ok(record) => record,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&written.path).await;
+            return Err(api_error(CONTEXT, e));
+        }
+    };
+
+    let view_url = format!("{}/paste/{}", base_url(&headers), record.id);
+    Ok(Json(ApiResponse::success(PasteResponse { file: record, view_url })))
+}
+
+async fn save_paste(
+    state: &AppState,
+    temp_path: &std::path::Path,
+    size: u64,
+    sha256: String,
+    syntax: &str,
+    expires_in: Option<i64>,
+) -> crate::Result<FileRecord> {
+    let text = tokio::fs::read(temp_path).await.map_err(ServerError::Io)?;
+    if text.is_empty() {
+        return Err(ServerError::validation("文本片段不能为空"));
+    }
+    if std::str::from_utf8(&text).is_err() {
+        return Err(ServerError::validation("文本片段必须是 UTF-8 文本"));
+    }
+
+    let now = Utc::now();
+    let original_name = format!("paste-{}.{}", now.format("%Y%m%d-%H%M%S"), syntax);
+    let stored = store_file(state, temp_path, &original_name, size, &sha256).await?;
+
+    let mut record = FileRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        original_name,
+        stored_name: stored.stored_name.clone(),
+        file_path: stored.file_path.to_string_lossy().to_string(),
+        file_size: size as i64,
+        mime_type: "text/plain".to_string(),
+        upload_time: now,
+        is_video: false,
+        thumbnail_path: None,
+        video_duration: None,
+        video_resolution: None,
+        available_from: None,
+        available_until: expires_in.map(|ttl| now + Duration::seconds(ttl)),
+        version: 1,
+        capture_time: None,
+        latitude: None,
+        longitude: None,
+        perceptual_hash: None,
+        sha256: Some(sha256),
+    };
+
+    dedup::save_record(state, stored, &mut record).await?;
+    Ok(record)
+}
+
+// 查看文本片段. 代码块带 language-* 类名, 供前端的高亮脚本着色
+pub async fn view_paste(
+    Path(paste_id): Path<String>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "查看文本片段失败";
+
+    let record = get_downloadable_file(&state, &paste_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    if !record.mime_type.starts_with("text/") || record.file_size as u64 > MAX_PASTE_SIZE {
+        return Err(api_error(CONTEXT, ServerError::not_found(format!("文本片段 {}", paste_id))));
+    }
+    let bytes = tokio::fs::read(&record.file_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let syntax = record.original_name.rsplit('.').next().unwrap_or("txt");
+
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{name}</h1>\n<pre><code class=\"language-{syntax}\">{text}</code></pre>\n</body>\n</html>\n",
+        title = escape_html(&state.config.web.branding.title),
+        name = escape_html(&record.original_name),
+        syntax = escape_html(syntax),
+        text = escape_html(&text),
+    );
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,114 @@
+// 上传处理结果 - 告诉客户端文件通过了哪些检查、哪些派生任务还在排队
+use crate::server::AppState;
+use crate::storage::FileRecord;
+use crate::video::VideoProcessor;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    // 等待后台任务或管理接口的重建任务处理
+    Pending,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingJob {
+    pub name: &'static str,
+    pub status: JobStatus,
+    // 跳过原因, 例如 ffmpeg_missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+impl ProcessingJob {
+    fn pending(name: &'static str) -> Self {
+        Self { name, status: JobStatus::Pending, reason: None }
+    }
+
+    fn skipped(name: &'static str, reason: &'static str) -> Self {
+        Self { name, status: JobStatus::Skipped, reason: Some(reason) }
+    }
+
+    fn when(name: &'static str, available: bool, reason: &'static str) -> Self {
+        if available {
+            Self::pending(name)
+        } else {
+            Self::skipped(name, reason)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    NotConfigured,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupOutcome {
+    // 未启用去重
+    NotChecked,
+    Unique,
+    // 与已有文件内容相同, 记录指向已有文件
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingReport {
+    // 已通过的上传策略
+    pub policies: Vec<&'static str>,
+    pub jobs: Vec<ProcessingJob>,
+    pub scan: ScanStatus,
+    pub dedup: DedupOutcome,
+}
+
+impl ProcessingReport {
+    pub fn for_record(state: &AppState, record: &FileRecord) -> Self {
+        let mut jobs = Vec::new();
+
+        if record.mime_type.starts_with("image/") {
+            jobs.push(ProcessingJob::pending("photo_metadata"));
+            jobs.push(ProcessingJob::pending("perceptual_hash"));
+            jobs.push(ProcessingJob::when("ocr", state.config.image.ocr.enabled, "ocr_disabled"));
+        }
+
+        if record.is_video {
+            let plan = VideoProcessor::new(state.video_toolchain.clone()).plan();
+            jobs.push(ProcessingJob::when(
+                "video_metadata",
+                plan.probe || plan.probe_fallback,
+                "ffprobe_missing",
+            ));
+            jobs.push(ProcessingJob::when("thumbnail", plan.thumbnail, "ffmpeg_missing"));
+        }
+
+        Self {
+            policies: vec!["max_file_size"],
+            jobs,
+            scan: ScanStatus::NotConfigured,
+            dedup: DedupOutcome::NotChecked,
+        }
+    }
+}
+
+// 上传类接口的响应: 文件记录的字段加上 processing
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadResponse {
+    #[serde(flatten)]
+    pub file: FileRecord,
+    pub processing: ProcessingReport,
+}
+
+impl UploadResponse {
+    pub fn new(state: &AppState, file: FileRecord) -> Self {
+        let processing = ProcessingReport::for_record(state, &file);
+        Self { file, processing }
+    }
+
+    pub fn with_dedup(mut self, dedup: DedupOutcome) -> Self {
+        self.processing.dedup = dedup;
+        self
+    }
+}
@@ -0,0 +1,93 @@
+// 上传转发 - 配置了上游实例时, /api/upload 的请求体不落地, 边接收边转发到上游, 返回上游创建的记录.
+// 分片上传、粘贴和截图仍保存在本实例
+use crate::config::UpstreamConfig;
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, AppState};
+use crate::upload::idempotency::IDEMPOTENCY_KEY_HEADER;
+use crate::upload::writer::CHECKSUM_HEADER;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// 转发请求时保留的请求头, 上游据此解析表单、校验内容、重放重试和选择错误信息的语言
+const FORWARDED_HEADERS: [header::HeaderName; 5] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::ACCEPT_LANGUAGE,
+    header::HeaderName::from_static(CHECKSUM_HEADER),
+    header::HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+];
+
+pub struct UpstreamClient {
+    upload_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl UpstreamClient {
+    pub fn new(config: &UpstreamConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .build()
+            .map_err(|e| ServerError::validation(format!("无法创建上游实例客户端: {}", e)))?;
+        Ok(Self {
+            upload_url: format!("{}/api/upload", config.url.trim_end_matches('/')),
+            api_key: (!config.api_key.is_empty()).then(|| config.api_key.clone()),
+            client,
+        })
+    }
+}
+
+// 上游的状态码和响应体原样返回, 上游返回的错误也由客户端直接看到
+pub async fn forward_upload(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+) -> Response {
+    const CONTEXT: &str = "转发上传失败";
+
+    let Some(upstream) = &state.upstream else {
+        return api_error(CONTEXT, ServerError::not_found("上游实例")).into_response();
+    };
+
+    let (parts, body) = request.into_parts();
+    let mut forwarded = upstream.client.post(&upstream.upload_url);
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = parts.headers.get(&name) {
+            forwarded = forwarded.header(name, value);
+        }
+    }
+    let forwarded_for = match parts.headers.get("x-forwarded-for").and_then(|value| value.to_str().ok()) {
+        Some(chain) => format!("{}, {}", chain, client.ip()),
+        None => client.ip().to_string(),
+    };
+    forwarded = forwarded.header("x-forwarded-for", forwarded_for);
+    if let Some(api_key) = &upstream.api_key {
+        forwarded = forwarded.bearer_auth(api_key);
+    }
+
+    let response = match forwarded
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            let error = ServerError::service_unavailable(format!("无法连接上游实例: {}", e));
+            return api_error(CONTEXT, error).into_response();
+        }
+    };
+
+    let status = response.status();
+    let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+    let mut proxied = (status, Body::from_stream(response.bytes_stream())).into_response();
+    if let Some(content_type) = content_type {
+        proxied.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    proxied
+}
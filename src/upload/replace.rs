@@ -0,0 +1,66 @@
+// 替换文件内容 - 保持文件 ID 和链接不变, 只更新字节内容
+use crate::error::ServerError;
+use crate::server::{api_error, expected_version, lock_owner, missing_file_error, ApiError, ApiResponse, AppState};
+use crate::upload::processing::UploadResponse;
+use crate::upload::writer::write_body_to_temp;
+use crate::video::processor::spawn_processing;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    response::Json,
+};
+
+// 用请求体替换文件内容, 支持 If-Match 和文件锁
+pub async fn replace_file_content(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> std::result::Result<([(header::HeaderName, String); 1], Json<ApiResponse<UploadResponse>>), ApiError> {
+    const CONTEXT: &str = "替换文件内容失败";
+
+    let expected = expected_version(&state.config, &headers, None)
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    match state.files.get_file_by_id(&file_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(missing_file_error(&state, &file_id, CONTEXT).await),
+        Err(e) => return Err(api_error(CONTEXT, e)),
+    }
+    state
+        .files
+        .check_lock(&file_id, lock_owner(&headers))
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    // 临时目录与目标文件位于同一文件系统, 保证重命名是原子操作
+    let written = write_body_to_temp(&state.temp, body, state.config.storage.max_file_size)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let result = state
+        .files
+        .replace_content(&file_id, &written.path, written.size as i64, &written.sha256, expected)
+        .await;
+
+    match result {
+        Ok(Some(record)) => {
+            // 旧内容的视频信息和缩略图已清空, 按新内容重新生成
+            if record.is_video {
+                spawn_processing(&state, &record);
+            }
+            let etag = record.etag();
+            let response = UploadResponse::new(&state, record);
+            Ok(([(header::ETAG, etag)], Json(ApiResponse::success(response))))
+        }
+        Ok(None) => {
+            let _ = tokio::fs::remove_file(&written.path).await;
+            Err(api_error(CONTEXT, ServerError::not_found(format!("文件 {}", file_id))))
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&written.path).await;
+            Err(api_error(CONTEXT, e))
+        }
+    }
+}
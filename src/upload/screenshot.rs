@@ -0,0 +1,131 @@
+// 截图上传 - 请求体即图片, 按时间命名并同步生成缩略图, 一次请求返回查看地址
+use crate::error::ServerError;
+use crate::preview::images::render_thumbnails;
+use crate::preview::thumbnails;
+use crate::server::{api_error, base_url, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use crate::upload::dedup::{self, store_file};
+use crate::upload::processing::DedupOutcome;
+use crate::upload::writer::{write_body_to_temp, WrittenFile};
+use axum::{
+    body::Body,
+    extract::State,
+    http::HeaderMap,
+    response::Json,
+};
+use chrono::Utc;
+use image::ImageFormat;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct ScreenshotResponse {
+    #[serde(flatten)]
+    pub file: FileRecord,
+    pub view_url: String,
+    pub thumbnail_url: String,
+}
+
+// 保存截图
+pub async fn upload_screenshot(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> std::result::Result<Json<ApiResponse<ScreenshotResponse>>, ApiError> {
+    const CONTEXT: &str = "保存截图失败";
+
+    let written = write_body_to_temp(&state.temp, body, state.config.storage.max_file_size)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let mut created = Vec::new();
+    let result = save_screenshot(&state, &written, &mut created).await;
+    let record = match result {
+        Ok(record) => record,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&written.path).await;
+            for path in created {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(api_error(CONTEXT, e));
+        }
+    };
+
+    let base = base_url(&headers);
+    let response = ScreenshotResponse {
+        view_url: format!("{}/files/by-hash/{}", base, written.sha256),
+        thumbnail_url: format!("{}/api/files/{}/thumbnail", base, record.id),
+        file: record,
+    };
+    Ok(Json(ApiResponse::success(response)))
+}
+
+// created 记录已经写入存储目录的文件, 出错时由调用方删除
+async fn save_screenshot(
+    state: &AppState,
+    written: &WrittenFile,
+    created: &mut Vec<PathBuf>,
+) -> crate::Result<FileRecord> {
+    let path = written.path.clone();
+    let format = tokio::task::spawn_blocking(move || {
+        image::ImageReader::open(&path)
+            .and_then(|reader| reader.with_guessed_format())
+            .ok()
+            .and_then(|reader| reader.format())
+    })
+    .await
+    .map_err(|e| ServerError::Internal(e.into()))?;
+    let format = format
+        .filter(|format| matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif))
+        .ok_or_else(|| ServerError::validation("请求体不是 PNG、JPEG、WebP 或 GIF 图片"))?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = Utc::now();
+    let original_name = format!("screenshot-{}.{}", now.format("%Y%m%d-%H%M%S"), format.extensions_str()[0]);
+    let stored = store_file(state, &written.path, &original_name, written.size, &written.sha256).await?;
+    // 复用的已有文件不属于本次请求, 出错时不能删除
+    if stored.dedup != DedupOutcome::Duplicate {
+        created.push(stored.file_path.clone());
+    }
+
+    // 同步生成全部尺寸和格式的缩略图, 返回时缩略图已经可用
+    let image_config = &state.config.image;
+    let targets = thumbnails::plan_targets(
+        state,
+        &id,
+        &image_config.thumbnail_variants(),
+        &image_config.thumbnail_formats,
+        image_config.thumbnail_quality,
+    )?;
+    created.extend(targets.iter().map(|target| target.target.clone()));
+    let thumbnail_path = targets[0].target.clone();
+    let source = stored.content_path().to_path_buf();
+    let budgets = state.memory.clone();
+    tokio::task::spawn_blocking(move || render_thumbnails(&source, &targets, &budgets))
+        .await
+        .map_err(|e| ServerError::Internal(e.into()))??;
+
+    let mut record = FileRecord {
+        id,
+        original_name,
+        stored_name: stored.stored_name.clone(),
+        file_path: stored.file_path.to_string_lossy().to_string(),
+        file_size: written.size as i64,
+        mime_type: format.to_mime_type().to_string(),
+        upload_time: now,
+        is_video: false,
+        thumbnail_path: Some(thumbnail_path.to_string_lossy().to_string()),
+        video_duration: None,
+        video_resolution: None,
+        available_from: None,
+        available_until: None,
+        version: 1,
+        capture_time: None,
+        latitude: None,
+        longitude: None,
+        perceptual_hash: None,
+        sha256: Some(written.sha256.clone()),
+    };
+    dedup::save_record(state, stored, &mut record).await?;
+    Ok(record)
+}
@@ -0,0 +1,78 @@
+// 流式写入 - 把请求体边接收边写入临时文件, 同时计算大小和 SHA-256
+use crate::error::{Result, ServerError};
+use crate::storage::TempManager;
+use axum::body::{Body, Bytes};
+use axum::http::HeaderMap;
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+// 客户端声明的内容 SHA-256 (十六进制), 用于发现传输过程中损坏的上传
+pub const CHECKSUM_HEADER: &str = "x-content-sha256";
+
+#[derive(Debug)]
+pub struct WrittenFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+// 写入临时目录下新分配的文件, 超过 max_size 或出错时删除临时文件
+pub async fn write_body_to_temp(temp: &TempManager, body: Body, max_size: u64) -> Result<WrittenFile> {
+    write_stream_to_temp(temp, body.into_data_stream().map(|chunk| chunk.map_err(ServerError::from)), max_size).await
+}
+
+// 同 write_body_to_temp, 数据来自任意字节流, 例如 multipart 表单中的一个字段
+pub async fn write_stream_to_temp(
+    temp: &TempManager,
+    stream: impl Stream<Item = Result<Bytes>> + Unpin,
+    max_size: u64,
+) -> Result<WrittenFile> {
+    let path = temp.path("upload")?;
+
+    match write_stream(&path, stream, max_size).await {
+        Ok((size, sha256)) => Ok(WrittenFile { path, size, sha256 }),
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(e)
+        }
+    }
+}
+
+async fn write_stream(
+    path: &Path,
+    mut stream: impl Stream<Item = Result<Bytes>> + Unpin,
+    max_size: u64,
+) -> Result<(u64, String)> {
+    let mut file = tokio::fs::File::create(path).await.map_err(ServerError::Io)?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(ServerError::validation(format!("文件大小超过上限 {} 字节", max_size)));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await.map_err(ServerError::Io)?;
+    }
+
+    // 重命名前确保数据落盘
+    file.sync_all().await.map_err(ServerError::Io)?;
+
+    Ok((size, hex::encode(hasher.finalize())))
+}
+
+// 请求带有 X-Content-SHA256 时与收到内容的哈希比较, 不一致时拒绝, 不创建记录
+pub fn verify_checksum(headers: &HeaderMap, sha256: &str) -> Result<()> {
+    let Some(expected) = headers.get(CHECKSUM_HEADER).and_then(|value| value.to_str().ok()) else {
+        return Ok(());
+    };
+    let expected = expected.trim();
+    if !expected.eq_ignore_ascii_case(sha256) {
+        return Err(ServerError::validation(format!("SHA-256 不一致, 声明为 {}, 实际为 {}", expected, sha256)));
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// 流量统计 - 按用户累计上传/下载字节数, 定期按天汇总写入数据库
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{FileManager, UsageEntry};
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Query, Request, State},
+    middleware::Next,
+    response::{Json, Response},
+};
+use chrono::{NaiveDate, Utc};
+use http_body::{Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+// 内存中的计数写入数据库的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// 尚未写入数据库的计数, 键为 (日期, 用户)
+#[derive(Default)]
+pub struct UsageTracker {
+    pending: Mutex<HashMap<(String, String), UsageEntry>>,
+}
+
+impl UsageTracker {
+    pub fn add(&self, subject: &str, uploaded: u64, downloaded: u64, requests: i64) {
+        let day = Utc::now().format("%Y-%m-%d").to_string();
+        let mut pending = self.pending.lock().unwrap();
+        let entry = pending
+            .entry((day.clone(), subject.to_string()))
+            .or_insert_with(|| UsageEntry {
+                day,
+                subject: subject.to_string(),
+                ..UsageEntry::default()
+            });
+        entry.uploaded_bytes += uploaded as i64;
+        entry.downloaded_bytes += downloaded as i64;
+        entry.requests += requests;
+    }
+
+    // 写入失败时把计数放回去, 下次再试
+    pub async fn flush(&self, file_manager: &FileManager) -> crate::Result<()> {
+        let entries: Vec<UsageEntry> = std::mem::take(&mut *self.pending.lock().unwrap())
+            .into_values()
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(e) = file_manager.record_usage(&entries).await {
+            for entry in entries {
+                self.add(&entry.subject, entry.uploaded_bytes as u64, entry.downloaded_bytes as u64, entry.requests);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+pub fn spawn_flusher(tracker: Arc<UsageTracker>, file_manager: Arc<FileManager>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = tracker.flush(&file_manager).await {
+                error!("写入流量统计失败: {}", e);
+            }
+        }
+    })
+}
+
+// 统计对象, 目前按客户端 IP 区分
+fn usage_subject(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(client)| client.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// 统计请求体和响应体实际传输的字节数, 请求在请求体被丢弃时计入
+pub async fn track_usage(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let subject = usage_subject(&request);
+
+    let request = request.map(|body| {
+        Body::new(CountingBody::new(body, state.usage.clone(), subject.clone(), Direction::Upload))
+    });
+    let response = next.run(request).await;
+
+    response.map(|body| Body::new(CountingBody::new(body, state.usage.clone(), subject, Direction::Download)))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Upload,
+    Download,
+}
+
+struct CountingBody {
+    inner: Body,
+    tracker: Arc<UsageTracker>,
+    subject: String,
+    direction: Direction,
+    bytes: u64,
+}
+
+impl CountingBody {
+    fn new(inner: Body, tracker: Arc<UsageTracker>, subject: String, direction: Direction) -> Self {
+        Self { inner, tracker, subject, direction, bytes: 0 }
+    }
+}
+
+impl http_body::Body for CountingBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, axum::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    // 保留原始长度, 响应仍然带 Content-Length
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        match self.direction {
+            Direction::Upload => self.tracker.add(&self.subject, self.bytes, 0, 1),
+            Direction::Download => self.tracker.add(&self.subject, 0, self.bytes, 0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    // 起止日期 (UTC), 格式 YYYY-MM-DD, 包含两端
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub uploaded_bytes: i64,
+    pub downloaded_bytes: i64,
+    pub requests: i64,
+    pub entries: Vec<UsageEntry>,
+}
+
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Query(params): Query<UsageQuery>,
+) -> std::result::Result<Json<ApiResponse<UsageReport>>, ApiError> {
+    const CONTEXT: &str = "查询流量统计失败";
+
+    for date in [&params.from, &params.to].into_iter().flatten() {
+        if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(api_error(CONTEXT, ServerError::validation(format!("无效的日期: {}", date))));
+        }
+    }
+
+    // 先写入尚未落盘的计数, 保证结果包含最近的请求
+    state
+        .usage
+        .flush(&state.file_manager)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let entries = state
+        .file_manager
+        .list_usage(params.from.as_deref(), params.to.as_deref(), params.subject.as_deref())
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok(Json(ApiResponse::success(UsageReport {
+        uploaded_bytes: entries.iter().map(|entry| entry.uploaded_bytes).sum(),
+        downloaded_bytes: entries.iter().map(|entry| entry.downloaded_bytes).sum(),
+        requests: entries.iter().map(|entry| entry.requests).sum(),
+        entries,
+    })))
+}
@@ -0,0 +1,199 @@
+// 音频提取 - 用 ffmpeg 从视频中取出音轨, 结果作为派生文件缓存, 也可以不落盘直接边转边发
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use crate::video::ffmpeg;
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path as FsPath, PathBuf};
+use tokio::process::Command;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Mp3,
+    #[default]
+    M4a,
+}
+
+impl AudioFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::M4a => "m4a",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::M4a => "audio/mp4",
+        }
+    }
+
+    fn encoder(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "libmp3lame",
+            Self::M4a => "aac",
+        }
+    }
+
+    // 输出到管道时无法回写文件头, m4a 改用分片 MP4
+    fn output_args(&self, piped: bool) -> &'static [&'static str] {
+        match (self, piped) {
+            (Self::Mp3, _) => &["-q:a", "4", "-f", "mp3"],
+            (Self::M4a, false) => &["-b:a", "128k", "-movflags", "+faststart", "-f", "ipod"],
+            (Self::M4a, true) => &["-b:a", "128k", "-movflags", "frag_keyframe+empty_moov", "-f", "mp4"],
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AudioQuery {
+    #[serde(default)]
+    pub format: AudioFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AudioExtraction {
+    pub file_id: String,
+    pub format: AudioFormat,
+    pub size: u64,
+    // 下载提取结果的地址
+    pub url: String,
+}
+
+// 提取音轨并缓存, 同一版本的视频只提取一次
+pub async fn extract_audio(
+    Path(file_id): Path<String>,
+    Query(params): Query<AudioQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<AudioExtraction>>, ApiError> {
+    const CONTEXT: &str = "提取音频失败";
+
+    let record = get_video(&state, &file_id, CONTEXT).await?;
+    let ffmpeg = ffmpeg_for(&state, params.format).map_err(|e| api_error(CONTEXT, e))?;
+    let cache_path = cache_path(&state, &record, params.format).map_err(|e| api_error(CONTEXT, e))?;
+
+    if !cache_path.exists() {
+        let temp_path = state.temp.path("audio").map_err(|e| api_error(CONTEXT, e))?;
+        let result = async {
+            let output = temp_path.to_string_lossy();
+            ffmpeg::run("ffmpeg", command(&ffmpeg, FsPath::new(&record.file_path), &output, params.format)).await?;
+            tokio::fs::rename(&temp_path, &cache_path).await.map_err(ServerError::Io)
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(api_error(CONTEXT, e));
+        }
+    }
+
+    let size = tokio::fs::metadata(&cache_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?
+        .len();
+    Ok(Json(ApiResponse::success(AudioExtraction {
+        url: format!("/api/video/{}/audio?format={}", record.id, params.format.extension()),
+        file_id: record.id,
+        format: params.format,
+        size,
+    })))
+}
+
+// 下载音轨: 已提取过时返回缓存文件 (支持 Range), 否则边转换边发送, 不写入缓存
+pub async fn get_audio(
+    Path(file_id): Path<String>,
+    Query(params): Query<AudioQuery>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+    request: Request,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取音频失败";
+
+    let record = get_video(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    let format = params.format;
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        base_name(&record.original_name).replace('"', "_"),
+        format.extension()
+    );
+
+    let cache_path = cache_path(&state, &record, format).map_err(|e| api_error(CONTEXT, e))?;
+    if cache_path.exists() {
+        let mime = format.mime_type().parse().unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        let Ok(mut response) = ServeFile::new_with_mime(&cache_path, &mime).oneshot(request).await;
+        if let Ok(value) = disposition.parse() {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+        }
+        return Ok(response.map(Body::new));
+    }
+
+    let ffmpeg = ffmpeg_for(&state, format).map_err(|e| api_error(CONTEXT, e))?;
+    let output = ffmpeg::stream("ffmpeg", command(&ffmpeg, FsPath::new(&record.file_path), "pipe:1", format))
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.mime_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(output),
+    )
+        .into_response())
+}
+
+async fn get_video(state: &AppState, file_id: &str, context: &str) -> std::result::Result<FileRecord, ApiError> {
+    let record = get_downloadable_file(state, file_id, context).await?;
+    if !record.is_video {
+        return Err(api_error(context, ServerError::validation("文件不是视频")));
+    }
+    Ok(record)
+}
+
+// 找到 ffmpeg 并确认支持目标格式的编码器
+fn ffmpeg_for(state: &AppState, format: AudioFormat) -> Result<PathBuf> {
+    let toolchain = &state.video_toolchain;
+    let ffmpeg = toolchain
+        .ffmpeg
+        .as_ref()
+        .ok_or_else(|| ServerError::video_processing("未找到 ffmpeg"))?;
+    if !toolchain.has_encoder(format.encoder()) {
+        return Err(ServerError::video_processing(format!("ffmpeg 不支持编码器 {}", format.encoder())));
+    }
+    Ok(ffmpeg.path.clone())
+}
+
+// 缓存文件名带上版本号, 替换内容后不会读到旧的音轨
+fn cache_path(state: &AppState, record: &FileRecord, format: AudioFormat) -> Result<PathBuf> {
+    let dir = state.file_manager.cache_dir("audio")?;
+    Ok(dir.join(format!("{}-v{}.{}", record.id, record.version, format.extension())))
+}
+
+fn base_name(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+// 只取第一条音轨, 没有音轨时 ffmpeg 报错退出
+fn command(ffmpeg: &FsPath, source: &FsPath, output: &str, format: AudioFormat) -> Command {
+    let mut command = Command::new(ffmpeg);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-i"])
+        .arg(source)
+        .args(["-vn", "-map", "0:a:0", "-c:a", format.encoder()])
+        .args(format.output_args(output == "pipe:1"))
+        .arg(output);
+    command
+}
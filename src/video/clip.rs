@@ -0,0 +1,226 @@
+// 视频片段截取 - 按起止时间从视频中截出一段, 结果作为派生文件缓存.
+// 起点正好落在关键帧上时直接复制码流, 否则重新编码, 保证片段从指定时间开始播放
+use crate::download::canary::{self, DownloadContext};
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, get_downloadable_file, ApiError, ApiResponse, AppState};
+use crate::storage::FileRecord;
+use crate::video::ffmpeg;
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::header,
+    response::{Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path as FsPath, PathBuf};
+use tokio::process::Command;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+// 起点与关键帧相差不超过该值 (秒) 时视为对齐
+const KEYFRAME_TOLERANCE: f64 = 0.05;
+
+// 重新编码需要的编码器
+const VIDEO_ENCODER: &str = "libx264";
+const AUDIO_ENCODER: &str = "aac";
+
+// 起止时间, 单位为秒
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ClipRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl ClipRange {
+    fn validate(&self, duration: Option<i32>) -> Result<()> {
+        if !self.start.is_finite() || !self.end.is_finite() || self.start < 0.0 || self.end <= self.start {
+            return Err(ServerError::validation("片段起止时间无效, 需要 0 <= start < end"));
+        }
+        if let Some(duration) = duration.filter(|d| *d > 0) {
+            if self.start >= f64::from(duration) {
+                return Err(ServerError::validation(format!("片段起点超出视频时长 {} 秒", duration)));
+            }
+        }
+        Ok(())
+    }
+
+    // 缓存文件名中使用毫秒, 避免浮点数的格式差异
+    fn cache_key(&self) -> String {
+        format!("{}-{}", (self.start * 1000.0).round() as u64, (self.end * 1000.0).round() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipMode {
+    // 直接复制码流
+    Copy,
+    Reencode,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClipResult {
+    pub file_id: String,
+    pub start: f64,
+    pub end: f64,
+    // 缓存命中时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ClipMode>,
+    pub size: u64,
+    // 下载片段的地址
+    pub url: String,
+}
+
+// 截取片段并缓存, 同一版本的视频和同样的起止时间只截取一次
+pub async fn create_clip(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    Json(range): Json<ClipRange>,
+) -> std::result::Result<Json<ApiResponse<ClipResult>>, ApiError> {
+    const CONTEXT: &str = "截取视频片段失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    if !record.is_video {
+        return Err(api_error(CONTEXT, ServerError::validation("文件不是视频")));
+    }
+    range
+        .validate(record.video_duration)
+        .map_err(|e| api_error(CONTEXT, e))?;
+    let cache_path = cache_path(&state, &record, &range).map_err(|e| api_error(CONTEXT, e))?;
+
+    let mut mode = None;
+    if !cache_path.exists() {
+        let temp_path = state.temp.path("clips").map_err(|e| api_error(CONTEXT, e))?;
+        let result = async {
+            let clip_mode = render_clip(&state, &record, &range, &temp_path).await?;
+            tokio::fs::rename(&temp_path, &cache_path).await.map_err(ServerError::Io)?;
+            Ok::<_, ServerError>(clip_mode)
+        }
+        .await;
+        match result {
+            Ok(clip_mode) => mode = Some(clip_mode),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(api_error(CONTEXT, e));
+            }
+        }
+    }
+
+    let size = tokio::fs::metadata(&cache_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?
+        .len();
+    Ok(Json(ApiResponse::success(ClipResult {
+        url: format!("/api/video/{}/clip?start={}&end={}", record.id, range.start, range.end),
+        file_id: record.id,
+        start: range.start,
+        end: range.end,
+        mode,
+        size,
+    })))
+}
+
+// 下载已截取的片段, 尚未截取时返回 404
+pub async fn get_clip(
+    Path(file_id): Path<String>,
+    Query(range): Query<ClipRange>,
+    State(state): State<AppState>,
+    download: DownloadContext,
+    request: Request,
+) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取视频片段失败";
+
+    let record = get_downloadable_file(&state, &file_id, CONTEXT).await?;
+    canary::check_download(&state, &record, &download).await;
+    let cache_path = cache_path(&state, &record, &range).map_err(|e| api_error(CONTEXT, e))?;
+    if !cache_path.exists() {
+        return Err(api_error(
+            CONTEXT,
+            ServerError::not_found(format!("文件 {} 的片段 {}-{}", file_id, range.start, range.end)),
+        ));
+    }
+
+    let mime = "video/mp4".parse().unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    let Ok(mut response) = ServeFile::new_with_mime(&cache_path, &mime).oneshot(request).await;
+    let stem = record
+        .original_name
+        .rsplit_once('.')
+        .map_or(record.original_name.as_str(), |(stem, _)| stem);
+    let disposition = format!("attachment; filename=\"{}-clip.mp4\"", stem.replace('"', "_"));
+    if let Ok(value) = disposition.parse() {
+        response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(response.map(Body::new))
+}
+
+// 缓存文件名带上版本号, 替换内容后不会读到旧的片段
+fn cache_path(state: &AppState, record: &FileRecord, range: &ClipRange) -> Result<PathBuf> {
+    let dir = state.file_manager.cache_dir("clips")?;
+    Ok(dir.join(format!("{}-v{}-{}.mp4", record.id, record.version, range.cache_key())))
+}
+
+async fn render_clip(state: &AppState, record: &FileRecord, range: &ClipRange, target: &FsPath) -> Result<ClipMode> {
+    let toolchain = &state.video_toolchain;
+    let ffmpeg = toolchain
+        .ffmpeg
+        .as_ref()
+        .ok_or_else(|| ServerError::video_processing("未找到 ffmpeg"))?;
+    let source = FsPath::new(&record.file_path);
+
+    // 复制码流时只能从关键帧开始, 终点不需要对齐
+    let aligned = match &toolchain.ffprobe {
+        _ if range.start == 0.0 => true,
+        Some(ffprobe) => starts_on_keyframe(&ffprobe.path, source, range.start).await?,
+        None => false,
+    };
+    let mode = if aligned { ClipMode::Copy } else { ClipMode::Reencode };
+    if mode == ClipMode::Reencode {
+        for encoder in [VIDEO_ENCODER, AUDIO_ENCODER] {
+            if !toolchain.has_encoder(encoder) {
+                return Err(ServerError::video_processing(format!("ffmpeg 不支持编码器 {}", encoder)));
+            }
+        }
+    }
+
+    let mut command = Command::new(&ffmpeg.path);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-ss"])
+        .arg(range.start.to_string())
+        .arg("-i")
+        .arg(source)
+        .arg("-t")
+        .arg((range.end - range.start).to_string());
+    match mode {
+        ClipMode::Copy => command.args(["-map", "0", "-c", "copy", "-avoid_negative_ts", "make_zero"]),
+        ClipMode::Reencode => command.args([
+            "-map", "0:v:0", "-map", "0:a:0?", "-c:v", VIDEO_ENCODER, "-preset", "veryfast", "-crf", "23", "-c:a",
+            AUDIO_ENCODER,
+        ]),
+    };
+    command.args(["-movflags", "+faststart", "-f", "mp4"]).arg(target);
+
+    ffmpeg::run("ffmpeg", command).await?;
+    Ok(mode)
+}
+
+// 用 ffprobe 列出起点附近的关键帧时间
+async fn starts_on_keyframe(ffprobe: &FsPath, source: &FsPath, start: f64) -> Result<bool> {
+    let mut command = Command::new(ffprobe);
+    command
+        .args(["-v", "error", "-select_streams", "v:0", "-skip_frame", "nokey"])
+        .args(["-show_entries", "frame=pts_time", "-of", "csv=p=0", "-read_intervals"])
+        .arg(format!("{}%{}", (start - 1.0).max(0.0), start + 1.0))
+        .arg(source);
+    let output = ffmpeg::run("ffprobe", command).await?;
+
+    let output = String::from_utf8_lossy(&output);
+    let aligned = parse_keyframes(&output).any(|keyframe| (keyframe - start).abs() <= KEYFRAME_TOLERANCE);
+    Ok(aligned)
+}
+
+// 每行一个时间, 无法解析的行 (例如 N/A) 跳过
+pub fn parse_keyframes(output: &str) -> impl Iterator<Item = f64> + '_ {
+    output
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').parse::<f64>().ok())
+}
@@ -0,0 +1,82 @@
+// ffmpeg/ffprobe 进程 - 统一处理标准输入输出、错误信息收集和提前终止
+use crate::error::{Result, ServerError};
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+
+// 失败时错误信息最多保留的字节数
+const STDERR_LIMIT: u64 = 64 * 1024;
+
+// 直接转发时每次读取的字节数
+const STREAM_CHUNK: usize = 64 * 1024;
+
+// 运行命令直到结束, 返回标准输出. 失败时错误信息中带上 stderr 的开头部分
+pub async fn run(name: &str, mut command: Command) -> Result<Vec<u8>> {
+    let mut child = spawn(name, &mut command)?;
+    let mut stdout = child.stdout.take().expect("stdout 已设置为管道");
+    let mut stderr = child.stderr.take().expect("stderr 已设置为管道");
+
+    // 两个管道同时读取, stderr 只保留开头, 其余丢弃以免命令阻塞
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+    let (read, _) = tokio::join!(stdout.read_to_end(&mut output), async {
+        let _ = (&mut stderr).take(STDERR_LIMIT).read_to_end(&mut errors).await;
+        let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+    });
+    read.map_err(ServerError::Io)?;
+
+    let status = child.wait().await.map_err(ServerError::Io)?;
+    if !status.success() {
+        return Err(ServerError::video_processing(format!(
+            "{} 执行失败: {}",
+            name,
+            String::from_utf8_lossy(&errors).trim()
+        )));
+    }
+    Ok(output)
+}
+
+// 启动命令并把标准输出转成响应体. 子进程随流一起释放, 客户端断开时命令被终止
+pub fn stream(
+    name: &str,
+    mut command: Command,
+) -> Result<impl futures::Stream<Item = std::io::Result<Vec<u8>>>> {
+    let mut child = spawn(name, &mut command)?;
+    let stdout = child.stdout.take().expect("stdout 已设置为管道");
+    // 转发过程中不读取 stderr, 丢弃以免写满管道后阻塞
+    if let Some(mut stderr) = child.stderr.take() {
+        tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+        });
+    }
+
+    Ok(futures::stream::unfold(Some((stdout, child)), |state| async move {
+        let (mut stdout, mut child) = state?;
+        let mut buffer = vec![0; STREAM_CHUNK];
+        match stdout.read(&mut buffer).await {
+            Ok(0) => {
+                // 输出结束后检查退出状态, 失败时让响应体以错误结束, 客户端不会把截断的内容当成完整文件
+                match child.wait().await {
+                    Ok(status) if status.success() => None,
+                    Ok(status) => Some((Err(std::io::Error::other(format!("exited with {}", status))), None)),
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(buffer), Some((stdout, child))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    }))
+}
+
+fn spawn(name: &str, command: &mut Command) -> Result<Child> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| ServerError::video_processing(format!("无法启动 {}: {}", name, e)))
+}
@@ -0,0 +1,141 @@
+// 视频元数据 - 用 ffprobe 读取时长和分辨率, 写入文件记录的 video_duration 和 video_resolution
+use crate::error::{Result, ServerError};
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::storage::{FileQuery, FileRecord};
+use crate::video::ffmpeg;
+use crate::video::processor::VideoProcessor;
+use axum::{extract::State, response::Json};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VideoInfo {
+    pub duration_secs: Option<f64>,
+    // 第一条视频流的画面尺寸, 纯音频文件没有
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl VideoInfo {
+    // 与 video_duration 一致, 按整秒四舍五入
+    pub fn duration(&self) -> Option<i32> {
+        self.duration_secs
+            .filter(|secs| secs.is_finite() && *secs >= 0.0)
+            .map(|secs| secs.round().min(i32::MAX as f64) as i32)
+    }
+
+    // 与 video_resolution 一致, 例如 1920x1080
+    pub fn resolution(&self) -> Option<String> {
+        self.dimensions.map(|(width, height)| format!("{}x{}", width, height))
+    }
+}
+
+#[cfg(feature = "mp4-fallback")]
+impl From<crate::video::mp4::Mp4Info> for VideoInfo {
+    fn from(info: crate::video::mp4::Mp4Info) -> Self {
+        let dimensions = (info.width > 0 && info.height > 0).then_some((info.width, info.height));
+        Self { duration_secs: Some(info.duration_secs), dimensions }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    width: Option<u32>,
+    height: Option<u32>,
+    duration: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+// 只读取第一条视频流和容器的时长
+pub async fn probe(ffprobe: &Path, source: &Path) -> Result<VideoInfo> {
+    let mut command = Command::new(ffprobe);
+    command
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,duration:format=duration", "-of", "json"])
+        .arg(source);
+    let output = ffmpeg::run("ffprobe", command).await?;
+    parse_probe_output(&String::from_utf8_lossy(&output))
+}
+
+// 容器时长优先, 部分格式只在视频流上有时长. ffprobe 对未知的值输出 N/A
+pub fn parse_probe_output(output: &str) -> Result<VideoInfo> {
+    let output: ProbeOutput = serde_json::from_str(output)
+        .map_err(|e| ServerError::video_processing(format!("无法解析 ffprobe 输出: {}", e)))?;
+    let stream = output.streams.first();
+    let parse = |value: Option<&String>| value.and_then(|value| value.parse::<f64>().ok());
+
+    let duration_secs = parse(output.format.as_ref().and_then(|format| format.duration.as_ref()))
+        .or_else(|| parse(stream.and_then(|stream| stream.duration.as_ref())));
+    let dimensions = stream
+        .and_then(|stream| Some((stream.width?, stream.height?)))
+        .filter(|(width, height)| *width > 0 && *height > 0);
+    Ok(VideoInfo { duration_secs, dimensions })
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoMetadataReindexResult {
+    pub scanned: usize,
+    pub updated: usize,
+    pub failed: usize,
+}
+
+// 为缺少时长或分辨率的视频补读元数据, 逐个处理以免同时启动过多进程
+pub async fn reindex_video_metadata(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<VideoMetadataReindexResult>>, ApiError> {
+    const CONTEXT: &str = "重建视频元数据失败";
+
+    let processor = VideoProcessor::new(state.video_toolchain.clone());
+    let plan = processor.plan();
+    if !plan.probe && !plan.probe_fallback {
+        return Err(api_error(CONTEXT, ServerError::video_processing("未找到 ffprobe")));
+    }
+
+    let records: Vec<FileRecord> = state
+        .files
+        .query_files(&FileQuery::default())
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?
+        .into_iter()
+        .filter(|record| record.is_video && (record.video_duration.is_none() || record.video_resolution.is_none()))
+        .collect();
+
+    let mut result = VideoMetadataReindexResult { scanned: records.len(), updated: 0, failed: 0 };
+    for record in records {
+        let stored = match processor.read_info(Path::new(&record.file_path)).await {
+            Ok(info) => store(&state, &record, &info).await,
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(true) => result.updated += 1,
+            // 处理期间被删除或替换的文件不计入
+            Ok(false) => {}
+            Err(e) => {
+                warn!("视频 {} 读取元数据失败: {}", record.id, e);
+                result.failed += 1;
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+// 写入读取到的元数据, 记录已被删除或内容已被替换时返回 false
+pub async fn store(state: &AppState, record: &FileRecord, info: &VideoInfo) -> Result<bool> {
+    state
+        .files
+        .set_video_metadata(&record.id, record.version, info.duration(), info.resolution().as_deref())
+        .await
+}
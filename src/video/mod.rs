@@ -1,4 +1,13 @@
-// 视频处理模块占位符
+// 视频处理模块
+pub mod audio;
+pub mod clip;
+pub mod ffmpeg;
+pub mod metadata;
+#[cfg(feature = "mp4-fallback")]
+pub mod mp4;
+pub mod playback;
 pub mod processor;
+pub mod toolchain;
 
-pub use processor::VideoProcessor;
\ No newline at end of file
+pub use processor::VideoProcessor;
+pub use toolchain::VideoToolchain;
@@ -0,0 +1,138 @@
+// MP4 元数据解析 - 没有 ffprobe 时的纯 Rust 回退方案, 只读取 moov 中的时长和画面尺寸
+use crate::error::{Result, ServerError};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// moov 盒子的最大读取长度, 超过时视为异常文件
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Mp4Info {
+    pub duration_secs: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Mp4Info {
+    // 与 FileRecord 的 video_resolution 格式一致
+    pub fn resolution(&self) -> String {
+        format!("{}x{}", self.width, self.height)
+    }
+}
+
+pub fn read_info(path: &Path) -> Result<Mp4Info> {
+    let mut file = File::open(path).map_err(ServerError::Io)?;
+    let file_size = file.metadata().map_err(ServerError::Io)?.len();
+
+    // moov 可能位于文件开头或末尾, 逐个跳过顶层盒子
+    let mut offset = 0;
+    while offset < file_size {
+        file.seek(SeekFrom::Start(offset)).map_err(ServerError::Io)?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8]).map_err(ServerError::Io)?;
+
+        let mut size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..16]).map_err(ServerError::Io)?;
+            size = u64::from_be_bytes(header[8..16].try_into().unwrap());
+            header_len = 16;
+        } else if size == 0 {
+            size = file_size - offset;
+        }
+        if size < header_len {
+            return Err(invalid("盒子长度无效"));
+        }
+
+        if &header[4..8] == b"moov" {
+            let body_len = size - header_len;
+            if body_len > MAX_MOOV_SIZE {
+                return Err(invalid("moov 过大"));
+            }
+            let mut moov = vec![0u8; body_len as usize];
+            file.read_exact(&mut moov).map_err(ServerError::Io)?;
+            return parse_moov(&moov);
+        }
+
+        offset += size;
+    }
+
+    Err(invalid("未找到 moov"))
+}
+
+pub fn parse_moov(moov: &[u8]) -> Result<Mp4Info> {
+    let mut duration_secs = None;
+    let mut dimensions = None;
+
+    for (kind, body) in boxes(moov) {
+        match kind {
+            b"mvhd" => duration_secs = parse_mvhd(body),
+            b"trak" if dimensions.is_none() => {
+                // 第一条有画面尺寸的轨道即视频轨道
+                dimensions = boxes(body)
+                    .find(|(kind, _)| *kind == b"tkhd")
+                    .and_then(|(_, tkhd)| parse_tkhd(tkhd))
+                    .filter(|(width, height)| *width > 0 && *height > 0);
+            }
+            _ => {}
+        }
+    }
+
+    let duration_secs = duration_secs.ok_or_else(|| invalid("缺少 mvhd"))?;
+    let (width, height) = dimensions.ok_or_else(|| invalid("没有视频轨道"))?;
+    Ok(Mp4Info { duration_secs, width, height })
+}
+
+// 遍历一段数据中的子盒子, 返回 (类型, 内容)
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8; 4], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.len() < 8 {
+            return None;
+        }
+        let size = u32::from_be_bytes(rest[0..4].try_into().ok()?) as usize;
+        let kind: &[u8; 4] = rest[4..8].try_into().ok()?;
+        let (header_len, size) = match size {
+            0 => (8, rest.len()),
+            1 => (16, u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?) as usize),
+            n => (8, n),
+        };
+        if size < header_len || size > rest.len() {
+            return None;
+        }
+        let body = &rest[header_len..size];
+        rest = &rest[size..];
+        Some((kind, body))
+    })
+}
+
+fn parse_mvhd(body: &[u8]) -> Option<f64> {
+    let version = *body.first()?;
+    let (timescale, duration) = if version == 1 {
+        (read_u32(body, 20)?, read_u64(body, 24)?)
+    } else {
+        (read_u32(body, 12)?, read_u32(body, 16)? as u64)
+    };
+    (timescale > 0).then(|| duration as f64 / timescale as f64)
+}
+
+// 宽高为 16.16 定点数, 位于矩阵之后
+fn parse_tkhd(body: &[u8]) -> Option<(u32, u32)> {
+    let version = *body.first()?;
+    let offset = if version == 1 { 88 } else { 76 };
+    Some((read_u32(body, offset)? >> 16, read_u32(body, offset + 4)? >> 16))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+fn invalid(reason: &str) -> ServerError {
+    ServerError::video_processing(format!("无法解析 MP4: {}", reason))
+}
@@ -0,0 +1,89 @@
+// 播放进度 - 按观看者保存视频的播放位置, 换设备后可以从上次的位置继续
+use crate::error::ServerError;
+use crate::server::{api_error, missing_file_error, ApiError, ApiResponse, AppState};
+use crate::storage::PlaybackPosition;
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct PositionQuery {
+    pub viewer: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PositionRequest {
+    pub viewer: String,
+    // 单位为秒
+    pub position: f64,
+    pub duration: Option<f64>,
+}
+
+// 观看者标识由播放器提供, 同一个标识在不同设备上共享进度
+fn validate_viewer(viewer: &str) -> crate::Result<&str> {
+    let viewer = viewer.trim();
+    if viewer.is_empty() {
+        return Err(ServerError::validation("观看者标识不能为空"));
+    }
+    Ok(viewer)
+}
+
+async fn ensure_video(state: &AppState, file_id: &str, context: &str) -> Result<(), ApiError> {
+    match state.files.get_file_by_id(file_id).await {
+        Ok(Some(record)) if record.is_video => Ok(()),
+        Ok(Some(_)) => Err(api_error(context, ServerError::validation("文件不是视频"))),
+        Ok(None) => Err(missing_file_error(state, file_id, context).await),
+        Err(e) => Err(api_error(context, e)),
+    }
+}
+
+pub async fn save_position(
+    Path(file_id): Path<String>,
+    State(state): State<AppState>,
+    Json(req): Json<PositionRequest>,
+) -> std::result::Result<Json<ApiResponse<PlaybackPosition>>, ApiError> {
+    const CONTEXT: &str = "保存播放进度失败";
+
+    let viewer = validate_viewer(&req.viewer).map_err(|e| api_error(CONTEXT, e))?;
+    let valid = |value: f64| value.is_finite() && value >= 0.0;
+    if !valid(req.position) || !req.duration.is_none_or(valid) {
+        return Err(api_error(CONTEXT, ServerError::validation("播放位置必须是非负数")));
+    }
+    ensure_video(&state, &file_id, CONTEXT).await?;
+
+    let position = PlaybackPosition {
+        file_id,
+        viewer: viewer.to_string(),
+        position: req.position,
+        duration: req.duration,
+        updated_at: Utc::now(),
+    };
+    state
+        .file_manager
+        .save_playback_position(&position)
+        .await
+        .map(|_| Json(ApiResponse::success(position)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
+
+// 没有保存过进度时 data 为 null
+pub async fn get_position(
+    Path(file_id): Path<String>,
+    Query(params): Query<PositionQuery>,
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ApiResponse<Option<PlaybackPosition>>>, ApiError> {
+    const CONTEXT: &str = "获取播放进度失败";
+
+    let viewer = validate_viewer(&params.viewer).map_err(|e| api_error(CONTEXT, e))?;
+    ensure_video(&state, &file_id, CONTEXT).await?;
+
+    state
+        .file_manager
+        .get_playback_position(&file_id, viewer)
+        .await
+        .map(|position| Json(ApiResponse::success(position)))
+        .map_err(|e| api_error(CONTEXT, e))
+}
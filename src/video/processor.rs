@@ -1,15 +1,203 @@
-// 视频处理器占位符
-use crate::error::Result;
+// 视频处理器 - 根据工具链的能力决定执行哪些处理步骤, 上传后在后台截取缩略图
+use crate::config::ThumbnailFormat;
+use crate::error::{Result, ServerError};
+use crate::preview::thumbnails::{self, ThumbnailTarget};
+use crate::server::AppState;
+use crate::storage::FileRecord;
+use crate::video::ffmpeg;
+use crate::video::metadata::{self, VideoInfo};
+use crate::video::toolchain::VideoToolchain;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
 
-pub struct VideoProcessor;
+// 一个视频文件实际会执行的处理步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProcessingPlan {
+    pub probe: bool,
+    // 没有 ffprobe 时使用内置的 MP4 解析器读取时长和分辨率
+    pub probe_fallback: bool,
+    pub thumbnail: bool,
+    pub transcode: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessedVideo {
+    pub plan: ProcessingPlan,
+    // 读取到的时长和分辨率
+    pub info: Option<VideoInfo>,
+    // 成功生成的缩略图, 顺序与传入的目标一致
+    pub thumbnails: Vec<PathBuf>,
+}
+
+pub struct VideoProcessor {
+    toolchain: Arc<VideoToolchain>,
+}
 
 impl VideoProcessor {
-    pub fn new() -> Self {
-        Self
+    pub fn new(toolchain: Arc<VideoToolchain>) -> Self {
+        Self { toolchain }
+    }
+
+    // 缺少 ffmpeg 时跳过缩略图和转码, 只要有 ffprobe 仍然读取元数据
+    pub fn plan(&self) -> ProcessingPlan {
+        ProcessingPlan {
+            probe: self.toolchain.can_probe(),
+            probe_fallback: cfg!(feature = "mp4-fallback") && !self.toolchain.can_probe(),
+            thumbnail: self.toolchain.can_transcode(),
+            transcode: self.toolchain.can_transcode(),
+        }
+    }
+
+    // 配置中 ffmpeg 能编码的格式, 保持配置顺序. 都不支持时使用 JPEG (ffmpeg 内置 mjpeg 编码器)
+    pub fn thumbnail_formats(&self, formats: &[ThumbnailFormat]) -> Vec<ThumbnailFormat> {
+        let encodable: Vec<ThumbnailFormat> = formats
+            .iter()
+            .copied()
+            .filter(|format| match format {
+                ThumbnailFormat::Jpeg => true,
+                ThumbnailFormat::Webp => self.toolchain.has_encoder("libwebp"),
+            })
+            .collect();
+        if encodable.is_empty() {
+            vec![ThumbnailFormat::Jpeg]
+        } else {
+            encodable
+        }
     }
 
-    pub async fn process_video(&self) -> Result<()> {
-        // TODO: 实现视频处理逻辑
-        Ok(())
+    // 单个步骤失败只记录日志, 不影响其他步骤
+    pub async fn process_video(&self, path: &Path, thumbnails: &[ThumbnailTarget]) -> Result<ProcessedVideo> {
+        let plan = self.plan();
+        if !plan.probe && !plan.probe_fallback {
+            warn!("未找到 ffprobe, 跳过视频元数据读取: {:?}", path);
+        }
+        if !plan.transcode {
+            warn!("未找到 ffmpeg, 跳过缩略图和转码: {:?}", path);
+        }
+
+        let mut processed = ProcessedVideo { plan, info: None, thumbnails: Vec::new() };
+        if plan.probe || plan.probe_fallback {
+            match self.read_info(path).await {
+                Ok(info) => processed.info = Some(info),
+                Err(e) => warn!("无法读取视频信息 {:?}: {}", path, e),
+            }
+        }
+
+        for thumbnail in thumbnails.iter().filter(|_| plan.thumbnail) {
+            match self.generate_thumbnail(path, thumbnail).await {
+                Ok(()) => processed.thumbnails.push(thumbnail.target.clone()),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&thumbnail.temp_path).await;
+                    warn!("无法生成视频缩略图 {:?}: {}", path, e);
+                }
+            }
+        }
+
+        debug!("视频处理计划 {:?}: {:?}", path, plan);
+        Ok(processed)
+    }
+
+    // 优先使用 ffprobe, 没有时使用内置的 MP4 解析器
+    pub async fn read_info(&self, path: &Path) -> Result<VideoInfo> {
+        if let Some(ffprobe) = &self.toolchain.ffprobe {
+            return metadata::probe(&ffprobe.path, path).await;
+        }
+
+        #[cfg(feature = "mp4-fallback")]
+        {
+            let source = path.to_path_buf();
+            let info = tokio::task::spawn_blocking(move || crate::video::mp4::read_info(&source))
+                .await
+                .map_err(|e| ServerError::Internal(e.into()))??;
+            Ok(info.into())
+        }
+        #[cfg(not(feature = "mp4-fallback"))]
+        Err(ServerError::video_processing("未找到 ffprobe"))
+    }
+
+    // 用 thumbnail 滤镜从开头的若干帧中挑一张有代表性的画面 (避开黑屏的片头), 等比缩小到不超过指定尺寸
+    pub async fn generate_thumbnail(&self, source: &Path, thumbnail: &ThumbnailTarget) -> Result<()> {
+        let ffmpeg = self
+            .toolchain
+            .ffmpeg
+            .as_ref()
+            .ok_or_else(|| ServerError::video_processing("未找到 ffmpeg"))?;
+        let (width, height) = thumbnail.size;
+
+        let mut command = Command::new(&ffmpeg.path);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y", "-i"])
+            .arg(source)
+            .arg("-vf")
+            .arg(format!(
+                "thumbnail,scale={}:{}:force_original_aspect_ratio=decrease",
+                width, height
+            ))
+            .args(["-frames:v", "1", "-an"])
+            .args(encoder_args(thumbnail.format, thumbnail.quality))
+            .args(["-f", "image2"])
+            .arg(&thumbnail.temp_path);
+        ffmpeg::run("ffmpeg", command).await?;
+
+        tokio::fs::rename(&thumbnail.temp_path, &thumbnail.target)
+            .await
+            .map_err(ServerError::Io)
+    }
+}
+
+// 质量 1-100 换算为各编码器的参数, mjpeg 的 -q:v 取值 2-31, 越小越好
+fn encoder_args(format: ThumbnailFormat, quality: u8) -> Vec<String> {
+    let quality = quality.clamp(1, 100) as u32;
+    match format {
+        ThumbnailFormat::Webp => vec!["-c:v".into(), "libwebp".into(), "-quality".into(), quality.to_string()],
+        ThumbnailFormat::Jpeg => {
+            let scale = 2 + (100 - quality) * 29 / 99;
+            vec!["-c:v".into(), "mjpeg".into(), "-q:v".into(), scale.to_string()]
+        }
+    }
+}
+
+// 上传完成后在后台处理视频, 读取到的元数据和生成的缩略图写入文件记录
+pub fn spawn_processing(state: &AppState, record: &FileRecord) {
+    let state = state.clone();
+    let record = record.clone();
+    tokio::spawn(async move {
+        if let Err(e) = process_record(&state, &record).await {
+            warn!("视频后台处理失败 {}: {}", record.id, e);
+        }
+    });
+}
+
+async fn process_record(state: &AppState, record: &FileRecord) -> Result<()> {
+    let processor = VideoProcessor::new(state.video_toolchain.clone());
+    let config = &state.config.video;
+    let targets = thumbnails::plan_targets(
+        state,
+        &record.id,
+        &config.thumbnail_variants(),
+        &processor.thumbnail_formats(&config.thumbnail_formats),
+        config.thumbnail_quality,
+    )?;
+
+    let processed = processor.process_video(Path::new(&record.file_path), &targets).await?;
+    if let Some(info) = &processed.info {
+        metadata::store(state, record, info).await?;
+    }
+    // 记录中保存默认尺寸首选格式的路径, 它生成失败时用第一张成功的
+    let Some(path) = processed.thumbnails.first() else {
+        return Ok(());
+    };
+    // 处理期间文件被删除或内容被替换时, 缩略图已经过时
+    let path_str = path.to_string_lossy().to_string();
+    if state.files.set_thumbnail_path(&record.id, record.version, &path_str).await? {
+        info!("已生成视频缩略图 {}: {} 张", record.id, processed.thumbnails.len());
+    } else {
+        for path in &processed.thumbnails {
+            let _ = tokio::fs::remove_file(path).await;
+        }
     }
-}
\ No newline at end of file
+    Ok(())
+}
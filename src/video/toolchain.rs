@@ -0,0 +1,162 @@
+// 视频工具链 - 查找 ffmpeg/ffprobe 并检测可用的编码器和硬件加速
+use crate::config::VideoConfig;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+// 可执行文件旁的附带工具目录
+const SIDECAR_DIR: &str = "tools";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolSource {
+    // video.ffmpeg_path / video.ffprobe_path 配置的路径
+    Config,
+    // PATH 环境变量
+    Path,
+    // 服务器可执行文件旁的 tools 目录
+    Sidecar,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    pub path: PathBuf,
+    pub version: String,
+    pub source: ToolSource,
+}
+
+// 启动时检测一次, 之后共享使用
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VideoToolchain {
+    pub ffmpeg: Option<ToolInfo>,
+    pub ffprobe: Option<ToolInfo>,
+    pub encoders: BTreeSet<String>,
+    pub hwaccels: Vec<String>,
+}
+
+impl VideoToolchain {
+    pub async fn detect(config: &VideoConfig) -> Self {
+        let ffmpeg = detect_tool("ffmpeg", config.ffmpeg_path.as_deref()).await;
+        let ffprobe = detect_tool("ffprobe", config.ffprobe_path.as_deref()).await;
+
+        let mut toolchain = Self {
+            ffmpeg,
+            ffprobe,
+            ..Self::default()
+        };
+
+        if let Some(ffmpeg) = &toolchain.ffmpeg {
+            if let Some(output) = run(&ffmpeg.path, &["-hide_banner", "-encoders"]).await {
+                toolchain.encoders = parse_encoders(&output);
+            }
+            if let Some(output) = run(&ffmpeg.path, &["-hide_banner", "-hwaccels"]).await {
+                toolchain.hwaccels = parse_hwaccels(&output);
+            }
+        }
+
+        toolchain.log_summary();
+        toolchain
+    }
+
+    // 读取视频元数据只需要 ffprobe
+    pub fn can_probe(&self) -> bool {
+        self.ffprobe.is_some()
+    }
+
+    // 截取缩略图和转码需要 ffmpeg
+    pub fn can_transcode(&self) -> bool {
+        self.ffmpeg.is_some()
+    }
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    fn log_summary(&self) {
+        for (name, tool) in [("ffmpeg", &self.ffmpeg), ("ffprobe", &self.ffprobe)] {
+            match tool {
+                Some(tool) => info!("{} {} ({:?})", name, tool.version, tool.path),
+                None => warn!("未找到 {}, 相关视频处理将被跳过", name),
+            }
+        }
+        if self.can_transcode() {
+            info!(
+                "可用编码器 {} 个, 硬件加速: {}",
+                self.encoders.len(),
+                if self.hwaccels.is_empty() { "无".to_string() } else { self.hwaccels.join(", ") }
+            );
+        }
+    }
+}
+
+// 按 配置路径、PATH、附带目录 的顺序查找, 找到后读取版本号确认可以执行
+async fn detect_tool(name: &str, configured: Option<&Path>) -> Option<ToolInfo> {
+    let (path, source) = locate(name, configured)?;
+    let output = run(&path, &["-version"]).await?;
+    let version = parse_tool_version(&output).unwrap_or("unknown").to_string();
+    Some(ToolInfo { path, version, source })
+}
+
+pub fn locate(name: &str, configured: Option<&Path>) -> Option<(PathBuf, ToolSource)> {
+    if let Some(path) = configured {
+        // 配置了路径却找不到时不再回退, 避免悄悄使用另一个版本
+        return path.is_file().then(|| (path.to_path_buf(), ToolSource::Config));
+    }
+
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    if let Some(paths) = std::env::var_os("PATH") {
+        if let Some(path) = std::env::split_paths(&paths)
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+        {
+            return Some((path, ToolSource::Path));
+        }
+    }
+
+    let sidecar = std::env::current_exe()
+        .ok()?
+        .parent()?
+        .join(SIDECAR_DIR)
+        .join(&file_name);
+    sidecar.is_file().then_some((sidecar, ToolSource::Sidecar))
+}
+
+async fn run(program: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// 从 "ffmpeg version 6.1.1 Copyright ..." 中取出版本号
+pub fn parse_tool_version(output: &str) -> Option<&str> {
+    let mut words = output.lines().next()?.split_whitespace();
+    words.find(|word| *word == "version")?;
+    words.next()
+}
+
+// 解析 ffmpeg -encoders 的输出, 列表位于 "------" 分隔行之后, 每行为 " V....D libx264  说明"
+pub fn parse_encoders(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("------"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+// 解析 ffmpeg -hwaccels 的输出, 标题行之后每行一个方法
+pub fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
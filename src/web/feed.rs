@@ -0,0 +1,103 @@
+// Atom 订阅 - 最近上传的文件, 可按文件名和 MIME 类型过滤
+use crate::server::{api_error, base_url, ApiError, AppState};
+use crate::storage::{FileRecord, FileSearch};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+// 条目数量的默认值和上限
+const DEFAULT_FEED_ENTRIES: i64 = 50;
+const MAX_FEED_ENTRIES: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub async fn get_feed(
+    Query(params): Query<FeedQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let search = FileSearch {
+        name: params.name,
+        mime_type: params.mime_type,
+        ..FileSearch::default()
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_FEED_ENTRIES).clamp(1, MAX_FEED_ENTRIES);
+
+    let now = Utc::now();
+    let records: Vec<FileRecord> = state
+        .files
+        .search_files(&search, limit)
+        .await
+        .map_err(|e| api_error("生成订阅失败", e))?
+        .into_iter()
+        .filter(|record| record.check_availability(now).is_ok())
+        .collect();
+
+    let base = base_url(&headers);
+    let feed = render_feed(&state.config.web.branding.title, &base, &records);
+    Ok(([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], feed).into_response())
+}
+
+fn render_feed(title: &str, base: &str, records: &[FileRecord]) -> String {
+    let updated = records
+        .iter()
+        .map(|record| record.upload_time)
+        .max()
+        .unwrap_or_else(Utc::now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}/api/feed.atom</id>\n", escape_xml(base)));
+    xml.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}/api/feed.atom\"/>\n",
+        escape_xml(base)
+    ));
+
+    for record in records {
+        // 有内容哈希时链接到永久下载地址, 否则链接到文件信息
+        let link = match &record.sha256 {
+            Some(sha256) => format!("{}/files/by-hash/{}", base, sha256),
+            None => format!("{}/api/files/{}", base, record.id),
+        };
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>urn:uuid:{}</id>\n", escape_xml(&record.id)));
+        xml.push_str(&format!("    <title>{}</title>\n", escape_xml(&record.original_name)));
+        xml.push_str(&format!("    <updated>{}</updated>\n", record.upload_time.to_rfc3339()));
+        xml.push_str(&format!(
+            "    <link href=\"{}\" type=\"{}\" length=\"{}\"/>\n",
+            escape_xml(&link),
+            escape_xml(&record.mime_type),
+            record.file_size
+        ));
+        xml.push_str(&format!(
+            "    <summary>{}, {} bytes</summary>\n",
+            escape_xml(&record.mime_type),
+            record.file_size
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -1,4 +1,6 @@
-// Web界面模块占位符
+// Web界面模块
+pub mod feed;
+pub mod mounts;
 pub mod static_files;
-
-pub use static_files::StaticFileHandler;
\ No newline at end of file
+pub mod ui_config;
+pub mod viewers;
@@ -0,0 +1,311 @@
+// 目录挂载 - 把配置的主机目录按原样提供浏览和下载, 内容不导入数据库
+use crate::config::MountConfig;
+use crate::error::ServerError;
+use crate::i18n;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::{Path as FsPath, PathBuf};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+const CONTEXT: &str = "访问挂载目录失败";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MountQuery {
+    #[serde(default)]
+    pub sort: SortKey,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MountEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MountListing {
+    // 挂载路由下的相对路径, 根目录为空
+    pub path: String,
+    pub entries: Vec<MountEntry>,
+}
+
+// 为每个挂载注册根目录和子路径两条路由
+pub fn mount_routes(mounts: &[MountConfig]) -> Router<AppState> {
+    let mut router = Router::new();
+    for (index, mount) in mounts.iter().enumerate() {
+        router = router
+            .route(
+                &mount.route,
+                get(move |State(state): State<AppState>, request: Request| {
+                    serve_mount(state, index, String::new(), request)
+                }),
+            )
+            .route(
+                &format!("{}/", mount.route),
+                get(move |State(state): State<AppState>, request: Request| {
+                    serve_mount(state, index, String::new(), request)
+                }),
+            )
+            .route(
+                &format!("{}/*path", mount.route),
+                get(move |State(state): State<AppState>, Path(path): Path<String>, request: Request| {
+                    serve_mount(state, index, path, request)
+                }),
+            );
+    }
+    router
+}
+
+async fn serve_mount(
+    state: AppState,
+    index: usize,
+    relative: String,
+    request: Request,
+) -> std::result::Result<Response, ApiError> {
+    let mount = &state.config.web.mounts[index];
+    let path = resolve(&mount.path, &relative)
+        .await
+        .map_err(|e| api_error(CONTEXT, e))?;
+
+    let metadata = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+    if metadata.is_dir() {
+        let Query(query) = Query::<MountQuery>::try_from_uri(request.uri())
+            .map_err(|e| api_error(CONTEXT, ServerError::validation(e.body_text())))?;
+        let mut entries = list_dir(&path).await.map_err(|e| api_error(CONTEXT, e))?;
+        sort_entries(&mut entries, query.sort, query.order);
+        let listing = MountListing {
+            path: relative.trim_matches('/').to_string(),
+            entries,
+        };
+
+        // 浏览器访问时返回目录索引页, 其余情况返回 JSON
+        if wants_html(request.headers()) {
+            let html = render_index(&state.config.web.branding.title, &mount.route, &listing, &query);
+            return Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response());
+        }
+        return Ok(Json(ApiResponse::success(listing)).into_response());
+    }
+
+    // ServeFile 负责 Content-Type、Range 和条件请求
+    let Ok(response) = ServeFile::new(&path).oneshot(request).await;
+    Ok(response.map(Body::new))
+}
+
+// 把请求路径解析为挂载目录内的实际路径. 隐藏文件和 .. 一律视为不存在,
+// 解析符号链接后仍须位于挂载目录内, 防止通过链接访问挂载范围之外的文件
+async fn resolve(root: &FsPath, relative: &str) -> crate::Result<PathBuf> {
+    let not_found = || ServerError::not_found(format!("挂载路径 /{}", relative.trim_matches('/')));
+
+    let mut path = root.to_path_buf();
+    for segment in relative.split('/').filter(|segment| !segment.is_empty()) {
+        if segment.starts_with('.') || segment.contains('\\') || segment.contains('\0') {
+            return Err(not_found());
+        }
+        path.push(segment);
+    }
+
+    let root = tokio::fs::canonicalize(root).await.map_err(ServerError::Io)?;
+    let path = tokio::fs::canonicalize(&path).await.map_err(|_| not_found())?;
+    if !path.starts_with(&root) {
+        return Err(not_found());
+    }
+    Ok(path)
+}
+
+// 列出目录内容, 不包含隐藏文件
+async fn list_dir(dir: &FsPath) -> crate::Result<Vec<MountEntry>> {
+    let mut entries = Vec::new();
+    let mut reader = tokio::fs::read_dir(dir).await.map_err(ServerError::Io)?;
+    while let Some(entry) = reader.next_entry().await.map_err(ServerError::Io)? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        // 符号链接按指向的目标显示, 失效的链接跳过
+        let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+            continue;
+        };
+        entries.push(MountEntry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        });
+    }
+    Ok(entries)
+}
+
+// 目录始终在前, 同类按指定字段排序, 字段相同时按名称
+pub fn sort_entries(entries: &mut [MountEntry], key: SortKey, order: SortOrder) {
+    entries.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        b.is_dir.cmp(&a.is_dir).then(ordering)
+    });
+}
+
+fn wants_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+// 渲染目录索引页: 面包屑导航、可点击排序的表头和按类型区分的图标
+fn render_index(title: &str, route: &str, listing: &MountListing, query: &MountQuery) -> String {
+    let locale = i18n::current_locale();
+    let segments: Vec<&str> = listing.path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    // 各级目录的链接都是绝对路径, 目录地址有无结尾斜杠都能正确跳转
+    let mut href = route.to_string();
+    let mut breadcrumbs = format!("<a href=\"{}/\">{}</a>", escape_html(&href), escape_html(route));
+    for segment in &segments {
+        href.push('/');
+        href.push_str(&encode_segment(segment));
+        breadcrumbs.push_str(&format!(" / <a href=\"{}/\">{}</a>", escape_html(&href), escape_html(segment)));
+    }
+    let base = href;
+
+    let mut rows = String::new();
+    if !segments.is_empty() {
+        let parent = base.rsplit_once('/').map(|(parent, _)| parent).unwrap_or(route);
+        rows.push_str(&format!(
+            "<tr><td>&#x2B06;&#xFE0F; <a href=\"{}/\">{}</a></td><td></td><td></td></tr>\n",
+            escape_html(parent),
+            escape_html(&i18n::translate(locale, "上级目录"))
+        ));
+    }
+    for entry in &listing.entries {
+        let href = format!("{}/{}{}", base, encode_segment(&entry.name), if entry.is_dir { "/" } else { "" });
+        rows.push_str(&format!(
+            "<tr><td>{} <a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            icon(entry),
+            escape_html(&href),
+            escape_html(&entry.name),
+            if entry.is_dir { String::new() } else { format_size(entry.size) },
+            entry
+                .modified
+                .map(|modified| modified.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default(),
+        ));
+    }
+
+    // 点击当前排序列切换升降序, 点击其他列按升序
+    let column = |key: SortKey, name: &str, label: &str| {
+        let (order, arrow) = match (query.sort == key, query.order) {
+            (true, SortOrder::Asc) => ("desc", " &#x25B2;"),
+            (true, SortOrder::Desc) => ("asc", " &#x25BC;"),
+            (false, _) => ("asc", ""),
+        };
+        format!(
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a>{}</th>",
+            name,
+            order,
+            escape_html(&i18n::translate(locale, label)),
+            arrow
+        )
+    };
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title} - {path}</title>\n</head>\n<body>\n<nav>{breadcrumbs}</nav>\n<table>\n<thead><tr>{name}{size}{modified}</tr></thead>\n<tbody>\n{rows}</tbody>\n</table>\n</body>\n</html>\n",
+        title = escape_html(title),
+        path = escape_html(&format!("{}/{}", route, listing.path)),
+        breadcrumbs = breadcrumbs,
+        name = column(SortKey::Name, "name", "名称"),
+        size = column(SortKey::Size, "size", "大小"),
+        modified = column(SortKey::Modified, "modified", "修改时间"),
+        rows = rows,
+    )
+}
+
+fn icon(entry: &MountEntry) -> &'static str {
+    if entry.is_dir {
+        return "&#x1F4C1;";
+    }
+    let mime = mime_guess::from_path(&entry.name).first_or_octet_stream();
+    match (mime.type_().as_str(), mime.subtype().as_str()) {
+        ("image", _) => "&#x1F5BC;&#xFE0F;",
+        ("video", _) => "&#x1F3AC;",
+        ("audio", _) => "&#x1F3B5;",
+        ("text", _) | ("application", "json" | "xml" | "pdf") => "&#x1F4C4;",
+        ("application", "zip" | "gzip" | "x-tar" | "x-7z-compressed" | "vnd.rar") => "&#x1F4E6;",
+        _ => "&#x1F4CE;",
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// 百分号编码路径中的一段, 保留 RFC 3986 的非保留字符
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
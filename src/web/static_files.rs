@@ -1,15 +1,64 @@
-// 静态文件处理器占位符
-use crate::error::Result;
+// 内置网页界面 - 编译时嵌入 ui 目录, 在 /ui 下提供文件列表、拖放上传和视频播放
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError};
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use include_dir::{include_dir, Dir};
+use sha2::{Digest, Sha256};
 
-pub struct StaticFileHandler;
+static UI_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/ui");
 
-impl StaticFileHandler {
-    pub fn new() -> Self {
-        Self
-    }
+const INDEX: &str = "index.html";
+
+// 界面中的相对地址以 /ui/ 为基准
+pub async fn redirect_to_ui() -> Redirect {
+    Redirect::permanent("/ui/")
+}
+
+pub async fn serve_index(headers: HeaderMap) -> std::result::Result<Response, ApiError> {
+    serve_asset(INDEX, &headers)
+}
+
+pub async fn serve_ui(Path(path): Path<String>, headers: HeaderMap) -> std::result::Result<Response, ApiError> {
+    serve_asset(&path, &headers)
+}
 
-    pub async fn serve_static(&self) -> Result<()> {
-        // TODO: 实现静态文件服务
-        Ok(())
+// 没有扩展名的路径视为界面内的页面, 返回 index.html 由前端处理
+fn serve_asset(path: &str, headers: &HeaderMap) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取界面文件失败";
+
+    let path = path.trim_start_matches('/');
+    let path = if path.is_empty() { INDEX } else { path };
+    let file = match UI_DIR.get_file(path) {
+        Some(file) => file,
+        None if !path.rsplit('/').next().unwrap_or_default().contains('.') => {
+            UI_DIR.get_file(INDEX).expect("ui 目录中缺少 index.html")
+        }
+        None => return Err(api_error(CONTEXT, ServerError::not_found(format!("界面文件 {}", path)))),
+    };
+
+    // 内容随程序一起发布, 用内容哈希作为 ETag, 升级后浏览器自动取新版本
+    let contents = file.contents();
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(contents))[..16]);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim().trim_start_matches("W/") == etag));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
     }
-}
\ No newline at end of file
+
+    let mime_type = mime_guess::from_path(file.path()).first_or_octet_stream();
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime_type.to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        contents,
+    )
+        .into_response())
+}
@@ -0,0 +1,59 @@
+// 前端界面配置接口 - 品牌信息、查看器映射等
+use crate::config::ViewerMapping;
+use crate::error::ServerError;
+use crate::server::{api_error, ApiError, ApiResponse, AppState};
+use crate::web::viewers::viewer_mappings;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+
+#[derive(Debug, Serialize)]
+pub struct UiConfig {
+    pub title: String,
+    pub logo_url: Option<String>,
+    pub accent_color: String,
+    pub footer_text: Option<String>,
+    pub default_language: String,
+    pub read_only: bool,
+    // 按顺序匹配文件的 MIME 类型, 第一个匹配的映射决定查看器
+    pub viewers: Vec<ViewerMapping>,
+}
+
+// 获取界面配置
+pub async fn get_ui_config(State(state): State<AppState>) -> Json<ApiResponse<UiConfig>> {
+    let branding = &state.config.web.branding;
+
+    Json(ApiResponse::success(UiConfig {
+        title: branding.title.clone(),
+        logo_url: branding.logo_path.as_ref().map(|_| "/api/ui-config/logo".to_string()),
+        accent_color: branding.accent_color.clone(),
+        footer_text: branding.footer_text.clone(),
+        default_language: branding.default_language.clone(),
+        read_only: state.read_only.load(Ordering::Relaxed),
+        viewers: viewer_mappings(&state.config.web.viewers),
+    }))
+}
+
+// 返回配置的 Logo 图片
+pub async fn get_logo(State(state): State<AppState>) -> std::result::Result<Response, ApiError> {
+    const CONTEXT: &str = "获取 Logo 失败";
+
+    let logo_path = state
+        .config
+        .web
+        .branding
+        .logo_path
+        .as_ref()
+        .ok_or_else(|| api_error(CONTEXT, ServerError::not_found("Logo 未配置")))?;
+
+    let bytes = tokio::fs::read(logo_path)
+        .await
+        .map_err(|e| api_error(CONTEXT, ServerError::Io(e)))?;
+    let mime_type = mime_guess::from_path(logo_path).first_or_octet_stream();
+
+    Ok(([(header::CONTENT_TYPE, mime_type.to_string())], bytes).into_response())
+}
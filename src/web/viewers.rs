@@ -0,0 +1,42 @@
+// 查看器注册表 - 按 MIME 类型决定前端用哪种查看器展示文件, 管理员配置的映射优先于内置映射
+use crate::config::{ViewerKind, ViewerMapping};
+
+// 内置映射, 按顺序匹配, 最后的 */* 保证总能找到查看器
+const BUILTIN_VIEWERS: &[(&str, ViewerKind)] = &[
+    ("video/*", ViewerKind::Video),
+    ("image/*", ViewerKind::Image),
+    ("application/pdf", ViewerKind::Pdf),
+    ("text/csv", ViewerKind::Table),
+    ("text/tab-separated-values", ViewerKind::Table),
+    ("text/*", ViewerKind::Text),
+    ("application/json", ViewerKind::Text),
+    ("application/xml", ViewerKind::Text),
+    ("*/*", ViewerKind::Hex),
+];
+
+// 生效的映射: 配置中的映射在前, 内置映射在后
+pub fn viewer_mappings(configured: &[ViewerMapping]) -> Vec<ViewerMapping> {
+    configured
+        .iter()
+        .cloned()
+        .chain(BUILTIN_VIEWERS.iter().map(|(pattern, viewer)| ViewerMapping {
+            pattern: pattern.to_string(),
+            viewer: *viewer,
+        }))
+        .collect()
+}
+
+// 返回第一个匹配的查看器, 忽略 MIME 类型中的参数部分
+pub fn resolve_viewer(mappings: &[ViewerMapping], mime_type: &str) -> ViewerKind {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let (kind, subtype) = essence.split_once('/').unwrap_or((essence.as_str(), ""));
+    mappings
+        .iter()
+        .find(|mapping| match mapping.pattern.to_ascii_lowercase().split_once('/') {
+            Some(("*", "*")) => true,
+            Some((pattern_kind, "*")) => pattern_kind == kind,
+            Some((pattern_kind, pattern_subtype)) => pattern_kind == kind && pattern_subtype == subtype,
+            None => false,
+        })
+        .map_or(ViewerKind::Hex, |mapping| mapping.viewer)
+}
@@ -0,0 +1,3622 @@
+use chrono::{Duration, Utc};
+use rust_internal_file_server::storage::{FileRecord, MemoryFileRepository};
+use rust_internal_file_server::testing::TestServer;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+// 直接写入存储目录并登记记录, 模拟一次已完成的上传
+async fn seed_file(server: &TestServer, name: &str, content: &[u8]) -> FileRecord {
+    let stored_name = server.file_manager().generate_stored_name(name);
+    let file_path = server.file_manager().get_file_path(&stored_name);
+    std::fs::write(&file_path, content).unwrap();
+
+    let mime_type = mime_guess::from_path(name).first_or_octet_stream().to_string();
+    let record = FileRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        original_name: name.to_string(),
+        stored_name,
+        file_path: file_path.to_string_lossy().to_string(),
+        file_size: content.len() as i64,
+        is_video: mime_type.starts_with("video/"),
+        mime_type,
+        upload_time: Utc::now(),
+        thumbnail_path: None,
+        video_duration: None,
+        video_resolution: None,
+        available_from: None,
+        available_until: None,
+        version: 1,
+        capture_time: None,
+        latitude: None,
+        longitude: None,
+        perceptual_hash: None,
+        sha256: None,
+    };
+    server.files().save_file_record(&record).await.unwrap();
+    record
+}
+
+// 在 dir 下写一个代替 ffmpeg 的脚本: 报告 encoders 中的编码器, 转换时执行 output_cmd, $output 为最后一个参数
+#[cfg(unix)]
+fn fake_ffmpeg(dir: &std::path::Path, encoders: &[&str], output_cmd: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join("fake-ffmpeg");
+    std::fs::write(
+        &script,
+        format!(
+            r#"#!/bin/sh
+case "$*" in
+    -version) echo "ffmpeg version 9.9 Copyright"; exit 0 ;;
+    "-hide_banner -encoders") printf ' ------\n{}\n'; exit 0 ;;
+    "-hide_banner -hwaccels") echo "Hardware acceleration methods:"; exit 0 ;;
+esac
+for output; do :; done
+{}
+"#,
+            encoders.join("\\n"),
+            output_cmd
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[tokio::test]
+async fn test_health_endpoint() {
+    let server = TestServer::start().await.unwrap();
+
+    let body: Value = reqwest::get(server.url("/health")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_ui_config() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.web.branding.title = "构建产物库".to_string();
+    config.web.branding.footer_text = Some("平台组".to_string());
+    config.web.viewers = vec![rust_internal_file_server::config::ViewerMapping {
+        pattern: "application/x-ndjson".to_string(),
+        viewer: rust_internal_file_server::config::ViewerKind::Table,
+    }];
+    let server = TestServer::start_with_config(config).await.unwrap();
+
+    let body: Value = reqwest::get(server.url("/api/ui-config")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["title"], "构建产物库");
+    assert_eq!(body["data"]["footer_text"], "平台组");
+    assert_eq!(body["data"]["default_language"], "zh-CN");
+    assert!(body["data"]["logo_url"].is_null());
+    let viewers = body["data"]["viewers"].as_array().unwrap();
+    assert_eq!(viewers[0], json!({ "pattern": "application/x-ndjson", "viewer": "table" }));
+    assert_eq!(viewers.last().unwrap(), &json!({ "pattern": "*/*", "viewer": "hex" }));
+
+    let response = reqwest::get(server.url("/api/ui-config/logo")).await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_file_lifecycle() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 通过上传接口创建文件
+    let boundary = "lifecycle-boundary";
+    let response = client
+        .post(server.url("/api/upload"))
+        .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+        .body(multipart_body(boundary, &[("file", Some("report.txt"), b"hello world")]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+
+    // 列表和详情
+    let body: Value = client.get(server.url("/api/files")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    let body: Value = client.get(server.url("/api/files/summary")).send().await.unwrap().json().await.unwrap();
+    let summary = &body["data"][0];
+    assert_eq!(summary["original_name"], "report.txt");
+    assert_eq!(summary["has_thumbnail"], false);
+    assert!(summary.get("file_path").is_none());
+
+    let info_url = server.url(&format!("/api/files/{}", id));
+    let body: Value = client.get(&info_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["original_name"], "report.txt");
+
+    let body: Value = client.get(server.url("/api/stats")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["total_size"], 11);
+
+    // 下载得到上传的内容
+    let response = client.get(server.url(&format!("/api/download/{}", id))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(&response.bytes().await.unwrap()[..], b"hello world");
+
+    // 设置可用时间窗口
+    let until = Utc::now() + Duration::days(1);
+    let response = client
+        .patch(server.url(&format!("/api/files/{}/availability", id)))
+        .json(&json!({ "available_until": until }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 删除后文件从磁盘移除, 旧链接返回 410
+    let file_path = server.files().get_file_by_id(&id).await.unwrap().unwrap().file_path;
+    assert!(std::path::Path::new(&file_path).exists());
+    let response = client.delete(&info_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(!std::path::Path::new(&file_path).exists());
+
+    let response = client.get(&info_url).send().await.unwrap();
+    assert_eq!(response.status(), 410);
+    let response = client.get(server.url(&format!("/api/download/{}", id))).send().await.unwrap();
+    assert_eq!(response.status(), 410);
+}
+
+#[tokio::test]
+async fn test_memory_repository_server() {
+    let server = TestServer::start_with_repository(Arc::new(MemoryFileRepository::new())).await.unwrap();
+    let client = reqwest::Client::new();
+    let mut record = seed_file(&server, "notes.txt", b"in memory").await;
+
+    // 记录只存在于内存仓库中, 数据库里没有
+    assert!(server.file_manager().get_file_by_id(&record.id).await.unwrap().is_none());
+
+    let body: Value = client.get(server.url("/api/files")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"][0]["id"], record.id.as_str());
+
+    // 按哈希下载读取的也是内存中的记录
+    let first_id = record.id.clone();
+    record.id = uuid::Uuid::new_v4().to_string();
+    record.sha256 = Some("ab".repeat(32));
+    server.files().save_file_record(&record).await.unwrap();
+    let response = client
+        .get(server.url(&format!("/files/by-hash/{}", "ab".repeat(32))))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"in memory");
+
+    // 搜索、关联、锁和内容替换也只读写内存仓库
+    let body: Value = client.get(server.url("/api/search?name=notes")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    let links_url = server.url(&format!("/api/files/{}/links", record.id));
+    let response = client
+        .post(&links_url)
+        .json(&json!({ "target_id": first_id, "kind": "derived-from" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+    let body: Value = client.get(&info_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["links"][0]["target_id"], first_id.as_str());
+
+    let lock_url = server.url(&format!("/api/files/{}/lock", record.id));
+    let content_url = server.url(&format!("/api/files/{}/content", record.id));
+    let response = client.post(&lock_url).json(&json!({ "owner": "alice" })).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.put(&content_url).body("replaced").send().await.unwrap();
+    assert_eq!(response.status(), 409);
+    let response = client.put(&content_url).header("X-Lock-Owner", "alice").body("replaced").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.delete(&lock_url).header("X-Lock-Owner", "alice").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 两条记录原本共用一个文件, 替换后另一条记录的内容不变
+    let body = client.get(server.url(&format!("/files/{}", record.id))).send().await.unwrap().bytes().await.unwrap();
+    assert_eq!(body.as_ref(), b"replaced");
+    let body = client.get(server.url(&format!("/files/{}", first_id))).send().await.unwrap().bytes().await.unwrap();
+    assert_eq!(body.as_ref(), b"in memory");
+
+    let response = client.delete(&info_url).header("If-Match", "\"2\"").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&info_url).send().await.unwrap();
+    assert_eq!(response.status(), 410);
+}
+
+#[tokio::test]
+async fn test_export_files() {
+    let server = TestServer::start().await.unwrap();
+    seed_file(&server, "a.txt", b"a").await;
+    seed_file(&server, "b, \"quoted\".txt", b"bb").await;
+
+    let response = reqwest::get(server.url("/api/files/export")).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let lines: Vec<Value> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+
+    let body = reqwest::get(server.url("/api/files/export?format=csv")).await.unwrap().text().await.unwrap();
+    assert!(body.starts_with("id,original_name,"));
+    assert!(body.contains("\"b, \"\"quoted\"\".txt\""));
+    assert_eq!(body.lines().count(), 3);
+
+    let response = reqwest::get(server.url("/api/files/export?format=xml")).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_lookup_files() {
+    let server = TestServer::start().await.unwrap();
+    let a = seed_file(&server, "a.txt", b"a").await;
+    let b = seed_file(&server, "b.txt", b"b").await;
+
+    let body: Value = reqwest::Client::new()
+        .post(server.url("/api/files/lookup"))
+        .json(&json!({ "ids": [a.id, b.id, "nope"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["files"].as_array().unwrap().len(), 2);
+    assert_eq!(body["data"]["missing"], json!(["nope"]));
+}
+
+#[tokio::test]
+async fn test_if_match_rejects_stale_writes() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "notes.txt", b"v1").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+    let availability_url = server.url(&format!("/api/files/{}/availability", record.id));
+
+    let response = client.get(&info_url).send().await.unwrap();
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, "\"1\"");
+
+    // 第一次修改成功, 版本递增
+    let response = client
+        .patch(&availability_url)
+        .header("If-Match", &etag)
+        .json(&json!({ "available_until": Utc::now() + Duration::days(1) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+
+    // 使用旧 ETag 的修改和删除被拒绝
+    let response = client
+        .patch(&availability_url)
+        .header("If-Match", &etag)
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+
+    let response = client.delete(&info_url).header("If-Match", &etag).send().await.unwrap();
+    assert_eq!(response.status(), 412);
+
+    let response = client.delete(&info_url).header("If-Match", "\"2\"").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_require_if_match() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.server.require_if_match = true;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let record = seed_file(&server, "notes.txt", b"v1").await;
+
+    let response = reqwest::Client::new()
+        .delete(server.url(&format!("/api/files/{}", record.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 428);
+}
+
+#[tokio::test]
+async fn test_read_only_mode() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.server.read_only = true;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "snapshot.txt", b"data").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+
+    // 读取正常, 修改被拒绝
+    assert_eq!(client.get(&info_url).send().await.unwrap().status(), 200);
+    assert_eq!(client.delete(&info_url).send().await.unwrap().status(), 403);
+
+    // 本机可以在运行时关闭只读模式
+    let response = client
+        .put(server.url("/api/admin/read-only"))
+        .json(&json!({ "enabled": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(client.delete(&info_url).send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_api_key_auth() {
+    use rust_internal_file_server::config::{ApiKeyConfig, KeyAccess};
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.auth.keys = vec![
+        ApiKeyConfig {
+            name: "ops".to_string(),
+            key: "rw-secret".to_string(),
+            key_file: None,
+            key_command: None,
+            access: KeyAccess::ReadWrite,
+        },
+        ApiKeyConfig {
+            name: "dashboard".to_string(),
+            key: "ro-secret".to_string(),
+            key_file: None,
+            key_command: None,
+            access: KeyAccess::Read,
+        },
+    ];
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "report.txt", b"data").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+
+    // 缺少或错误的密钥返回 401, 响应格式与其他错误一致
+    let response = client.get(&info_url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], false);
+    assert!(body["data"].is_null());
+    assert!(body["error"].as_str().unwrap().contains("缺少 API 密钥"));
+    let response = client.get(&info_url).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    // 两种携带方式都可以, 只读密钥不能修改
+    let response = client.get(&info_url).bearer_auth("ro-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&info_url).header("x-api-key", "rw-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.delete(&info_url).bearer_auth("ro-secret").send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let response = client
+        .get(server.url("/api/admin/read-only"))
+        .bearer_auth("ro-secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    // 健康检查和网页界面不需要密钥, 文件内容需要
+    assert_eq!(client.get(server.url("/health")).send().await.unwrap().status(), 200);
+    assert_eq!(client.get(server.url("/ui/")).send().await.unwrap().status(), 200);
+    let content_url = server.url(&format!("/files/{}/report.txt", record.id));
+    assert_eq!(client.get(&content_url).send().await.unwrap().status(), 401);
+    let response = client
+        .get(server.url(&format!("/files/by-hash/{}", "0".repeat(64))))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client.get(&content_url).bearer_auth("ro-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 浏览器直接加载的内容可以用 Cookie 携带密钥, 修改类请求不接受 Cookie
+    let cookie = "theme=dark; file_server_api_key=rw%2Dsecret";
+    let response = client.get(&content_url).header("cookie", cookie).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let thumbnail_url = server.url(&format!("/api/files/{}/thumbnail", record.id));
+    let response = client.get(&thumbnail_url).header("cookie", cookie).send().await.unwrap();
+    assert_ne!(response.status(), 401);
+    let response = client.delete(&info_url).header("cookie", cookie).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = client.delete(&info_url).bearer_auth("rw-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_external_policy() {
+    use axum::{routing::post, Json, Router};
+    use rust_internal_file_server::config::{ApiKeyConfig, KeyAccess, PolicyConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 模拟的授权服务: ops 可以做任何操作, 其他密钥不能调用管理接口
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+    let policy = Router::new().route(
+        "/v1/data/files/allow",
+        post(move |Json(body): Json<Value>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let input = &body["input"];
+            let allow = input["user"] == "ops" || input["action"] != "admin";
+            async move { Json(json!({ "result": { "allow": allow } })) }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let policy_url = format!("http://{}/v1/data/files/allow", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, policy).await.unwrap() });
+
+    let key = |name: &str| ApiKeyConfig {
+        name: name.to_string(),
+        key: format!("{}-secret", name),
+        key_file: None,
+        key_command: None,
+        access: KeyAccess::ReadWrite,
+    };
+    let policy_config = |url: &str, fail_open: bool| PolicyConfig {
+        url: url.to_string(),
+        timeout: 2,
+        cache_ttl: 60,
+        cache_max_entries: 100,
+        fail_open,
+    };
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.auth.keys = vec![key("ops"), key("dashboard")];
+    config.auth.policy = Some(policy_config(&policy_url, false));
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let admin_url = server.url("/api/admin/read-only");
+
+    // 查询接口不经过授权服务
+    let response = client.get(server.url("/api/files")).bearer_auth("dashboard-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(queries.load(Ordering::SeqCst), 0);
+
+    let response = client.get(&admin_url).bearer_auth("dashboard-secret").send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("授权服务拒绝了该请求"));
+    let response = client.get(&admin_url).bearer_auth("ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 相同的请求使用缓存的决策
+    let response = client.get(&admin_url).bearer_auth("ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+    let record = seed_file(&server, "report.txt", b"data").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+    let response = client.delete(&info_url).bearer_auth("dashboard-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 强制删除受保护的文件需要授权服务允许 admin 操作, admin 密钥本身不够
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.delete_protection.name_contains = vec!["release".to_string()];
+    config.auth.keys = vec![key("ops"), key("dashboard")];
+    for key in &mut config.auth.keys {
+        key.access = KeyAccess::Admin;
+    }
+    config.auth.policy = Some(policy_config(&policy_url, false));
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let protected = seed_file(&server, "app-release.zip", b"ga").await;
+    let force_url = server.url(&format!("/api/files/{}?force=true", protected.id));
+    let response = client.delete(&force_url).bearer_auth("dashboard-secret").send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("授权服务拒绝了该请求"));
+    let response = client.delete(&force_url).bearer_auth("ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 授权服务不可用时默认拒绝, fail_open 时放行
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_url = format!("http://{}/", closed.local_addr().unwrap());
+    drop(closed);
+    for (fail_open, status) in [(false, 503), (true, 200)] {
+        let mut config = rust_internal_file_server::config::Config::default();
+        config.auth.policy = Some(policy_config(&closed_url, fail_open));
+        let server = TestServer::start_with_config(config).await.unwrap();
+        let response = client.get(server.url("/api/admin/read-only")).send().await.unwrap();
+        assert_eq!(response.status(), status);
+    }
+}
+
+#[tokio::test]
+async fn test_image_resize() {
+    let server = TestServer::start().await.unwrap();
+    let mut png = Vec::new();
+    image::RgbaImage::new(400, 200)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let record = seed_file(&server, "diagram.png", &png).await;
+    let url = |query: &str| server.url(&format!("/api/files/{}/image?{}", record.id, query));
+
+    // 只指定宽度时等比缩放
+    let response = reqwest::get(url("w=100")).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let resized = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!((resized.width(), resized.height()), (100, 50));
+
+    let response = reqwest::get(url("w=80&h=80&fit=cover")).await.unwrap();
+    let resized = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!((resized.width(), resized.height()), (80, 80));
+
+    assert_eq!(reqwest::get(url("")).await.unwrap().status(), 400);
+    assert_eq!(reqwest::get(url("w=100000")).await.unwrap().status(), 400);
+
+    let text = seed_file(&server, "notes.txt", b"text").await;
+    let response = reqwest::get(server.url(&format!("/api/files/{}/image?w=10", text.id))).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_image_convert() {
+    let server = TestServer::start().await.unwrap();
+    let mut png = Vec::new();
+    image::RgbaImage::new(40, 20)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let record = seed_file(&server, "diagram.png", &png).await;
+    let url = |query: &str| server.url(&format!("/api/files/{}/convert?{}", record.id, query));
+
+    for (format, mime_type, image_format) in [
+        ("jpeg", "image/jpeg", image::ImageFormat::Jpeg),
+        ("webp", "image/webp", image::ImageFormat::WebP),
+    ] {
+        let response = reqwest::get(url(&format!("format={}", format))).await.unwrap();
+        assert_eq!(response.headers()["content-type"], mime_type);
+        let bytes = response.bytes().await.unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image_format);
+        let converted = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((converted.width(), converted.height()), (40, 20));
+    }
+
+    assert_eq!(reqwest::get(url("format=heic")).await.unwrap().status(), 400);
+    assert_eq!(reqwest::get(url("")).await.unwrap().status(), 400);
+
+    // 无法解码的图片
+    let broken = seed_file(&server, "broken.jpg", b"not an image").await;
+    let response = reqwest::get(server.url(&format!("/api/files/{}/convert?format=png", broken.id))).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+// 手工拼出 multipart/form-data 请求体, parts 为 (字段名, 文件名, 内容)
+fn multipart_body(boundary: &str, parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, file_name, content) in parts {
+        body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", boundary, name).as_bytes());
+        if let Some(file_name) = file_name {
+            body.extend_from_slice(format!("; filename=\"{}\"", file_name).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+#[tokio::test]
+async fn test_multipart_upload() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.max_file_size = 4 * 1024 * 1024;
+    config.storage.multipart.max_parts = 3;
+    config.storage.multipart.max_field_size = 16;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let boundary = "upload-boundary";
+    let upload = |body: Vec<u8>| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(body)
+            .send()
+    };
+
+    // 超过默认请求体上限 (2MB) 但未超过 max_file_size
+    let content = vec![b'x'; 3 * 1024 * 1024];
+    let body = multipart_body(
+        boundary,
+        &[("note", None, b"ignored"), ("file", Some("C:\\Users\\me\\report.txt"), &content)],
+    );
+    let response = upload(body).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["original_name"], "report.txt");
+    assert_eq!(data["mime_type"], "text/plain");
+    assert_eq!(data["file_size"], content.len() as u64);
+    assert_eq!(data["is_video"], false);
+    assert_eq!(data["processing"]["policies"], json!(["max_file_size"]));
+
+    let id = data["id"].as_str().unwrap();
+    let record = server.files().get_file_by_id(id).await.unwrap().unwrap();
+    assert_eq!(std::fs::read(&record.file_path).unwrap(), content);
+    let response = reqwest::get(server.url(&format!("/api/files/{}", id))).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = upload(multipart_body(boundary, &[("file", Some("clip.mkv"), b"video")]))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["is_video"], true);
+
+    let too_large = vec![0u8; 4 * 1024 * 1024 + 1];
+    let response = upload(multipart_body(boundary, &[("file", Some("big.bin"), &too_large)])).await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = upload(multipart_body(boundary, &[("note", None, b"no file")])).await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let two_files = multipart_body(boundary, &[("a", Some("a.txt"), b"a"), ("b", Some("b.txt"), b"b")]);
+    assert_eq!(upload(two_files).await.unwrap().status(), 400);
+
+    // 字段数量和普通字段的大小受 storage.multipart 限制
+    let note: (&str, Option<&str>, &[u8]) = ("note", None, b"ok");
+    let parts = [note, note, note, ("file", Some("a.txt"), b"a")];
+    assert_eq!(upload(multipart_body(boundary, &parts)).await.unwrap().status(), 400);
+    let long_note = [("note", None, &[b'x'; 17][..]), ("file", Some("a.txt"), b"a")];
+    assert_eq!(upload(multipart_body(boundary, &long_note)).await.unwrap().status(), 400);
+    let parts = [note, note, ("file", Some("a.txt"), &b"a"[..])];
+    assert_eq!(upload(multipart_body(boundary, &parts)).await.unwrap().status(), 200);
+
+    // 声明的 SHA-256 与收到的内容不一致时拒绝
+    let upload_with_checksum = |sha256: String| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .header("X-Content-SHA256", sha256)
+            .body(multipart_body(boundary, &[("file", Some("firmware.bin"), b"firmware")]))
+            .send()
+    };
+    assert_eq!(upload_with_checksum("00".repeat(32)).await.unwrap().status(), 400);
+    let sha256 = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b"firmware"));
+    assert_eq!(upload_with_checksum(sha256.to_uppercase()).await.unwrap().status(), 200);
+
+    // 带 Idempotency-Key 的重试返回第一次的记录; 失败的请求不占用键
+    let upload_with_key = |key: &'static str, parts: &[(&str, Option<&str>, &[u8])]| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .header("Idempotency-Key", key)
+            .body(multipart_body(boundary, parts))
+            .send()
+    };
+    let artifact: &[(&str, Option<&str>, &[u8])] = &[("file", Some("artifact.zip"), b"artifact")];
+    assert_eq!(upload_with_key("ci-run-7", &[note]).await.unwrap().status(), 400);
+    let first: Value = upload_with_key("ci-run-7", artifact).await.unwrap().json().await.unwrap();
+    let replay: Value = upload_with_key("ci-run-7", artifact).await.unwrap().json().await.unwrap();
+    assert_eq!(replay["data"]["id"], first["data"]["id"]);
+
+    // 第一个请求还在处理时, 重试返回 409
+    assert!(server.file_manager().claim_idempotency_key("ci-run-8", "upload").await.unwrap().is_none());
+    assert_eq!(upload_with_key("ci-run-8", artifact).await.unwrap().status(), 409);
+
+    // 失败的上传不留下记录
+    let listed: Value = reqwest::get(server.url("/api/files")).await.unwrap().json().await.unwrap();
+    assert_eq!(listed["data"].as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_upload_forwarded_to_upstream() {
+    use rust_internal_file_server::config::{ApiKeyConfig, Config, KeyAccess, UpstreamConfig};
+
+    let mut config = Config::default();
+    config.auth.keys = vec![ApiKeyConfig {
+        name: "branch-office".to_string(),
+        key: "upstream-secret".to_string(),
+        key_file: None,
+        key_command: None,
+        access: KeyAccess::ReadWrite,
+    }];
+    let upstream = TestServer::start_with_config(config).await.unwrap();
+    let upstream_config = |url: String| UpstreamConfig {
+        url,
+        api_key: "upstream-secret".to_string(),
+        api_key_file: None,
+        api_key_command: None,
+        connect_timeout: 2,
+    };
+    let mut config = Config::default();
+    config.federation.upstream = Some(upstream_config(upstream.url("/")));
+    let front = TestServer::start_with_config(config).await.unwrap();
+
+    let client = reqwest::Client::new();
+    let boundary = "federation-boundary";
+    let upload = |server: &TestServer, parts: &[(&str, Option<&str>, &[u8])]| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(multipart_body(boundary, parts))
+            .send()
+    };
+
+    // 内容保存在上游, 返回上游创建的记录
+    let response = upload(&front, &[("file", Some("report.txt"), b"from the branch")]).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let id = body["data"]["id"].as_str().unwrap();
+    assert_eq!(body["data"]["original_name"], "report.txt");
+    let content = client
+        .get(upstream.url(&format!("/api/download/{}", id)))
+        .bearer_auth("upstream-secret")
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(&content[..], b"from the branch");
+    assert!(front.files().get_file_by_id(id).await.unwrap().is_none());
+
+    // 上游返回的错误原样返回
+    let response = upload(&front, &[("note", None, b"no file")]).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("表单中没有文件字段"));
+
+    // 声明的 SHA-256 由上游校验
+    let response = client
+        .post(front.url("/api/upload"))
+        .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+        .header("X-Content-SHA256", "00".repeat(32))
+        .body(multipart_body(boundary, &[("file", Some("report.txt"), b"corrupted")]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // 上游不可用
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_url = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    let mut config = Config::default();
+    config.federation.upstream = Some(upstream_config(closed_url));
+    let front = TestServer::start_with_config(config).await.unwrap();
+    let response = upload(&front, &[("file", Some("report.txt"), b"lost")]).await.unwrap();
+    assert_eq!(response.status(), 503);
+}
+
+#[tokio::test]
+async fn test_upload_deduplication() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let boundary = "dedup-boundary";
+    let upload = |name: &str, content: &[u8]| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(multipart_body(boundary, &[("file", Some(name), content)]))
+            .send()
+    };
+    let download = |id: String| {
+        let client = client.clone();
+        let url = server.url(&format!("/api/download/{}", id));
+        async move { client.get(url).send().await.unwrap().bytes().await.unwrap().to_vec() }
+    };
+
+    let first: Value = upload("first.txt", b"same bytes").await.unwrap().json().await.unwrap();
+    let second: Value = upload("second.txt", b"same bytes").await.unwrap().json().await.unwrap();
+    let third: Value = upload("third.txt", b"same bytes").await.unwrap().json().await.unwrap();
+    let (first, second, third) = (&first["data"], &second["data"], &third["data"]);
+    assert_eq!(first["processing"]["dedup"], "unique");
+    assert_eq!(second["processing"]["dedup"], "duplicate");
+    assert_eq!(first["sha256"], second["sha256"]);
+    assert_eq!(first["file_path"], second["file_path"]);
+    assert_eq!(second["original_name"], "second.txt");
+
+    // 其他记录仍在引用时删除记录不删除文件
+    let first_id = first["id"].as_str().unwrap().to_string();
+    let response = client.delete(server.url(&format!("/api/files/{}", first_id))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let second_id = second["id"].as_str().unwrap().to_string();
+    assert_eq!(download(second_id.clone()).await, b"same bytes");
+
+    // 替换共用文件的内容时另存为新文件, 其他记录的内容不变
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", second_id)))
+        .body("new bytes")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let third_id = third["id"].as_str().unwrap().to_string();
+    assert_eq!(download(second_id.clone()).await, b"new bytes");
+    assert_eq!(download(third_id.clone()).await, b"same bytes");
+
+    // 最后一条引用删除后文件被删除
+    let shared_path = third["file_path"].as_str().unwrap().to_string();
+    let response = client.delete(server.url(&format!("/api/files/{}", third_id))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(!std::path::Path::new(&shared_path).exists());
+
+    // 关闭去重后每次上传都保存副本
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.deduplicate = false;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let upload = |name: &str| {
+        client
+            .post(server.url("/api/upload"))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(multipart_body(boundary, &[("file", Some(name), b"same bytes")]))
+            .send()
+    };
+    let first: Value = upload("first.txt").await.unwrap().json().await.unwrap();
+    let second: Value = upload("second.txt").await.unwrap().json().await.unwrap();
+    assert_eq!(second["data"]["processing"]["dedup"], "not_checked");
+    assert_ne!(first["data"]["file_path"], second["data"]["file_path"]);
+}
+
+#[tokio::test]
+async fn test_trash_restore() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "notes.txt", b"keep me").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+
+    // 删除后文件移入回收站, 旧链接返回 410
+    let response = client.delete(&info_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(!std::path::Path::new(&record.file_path).exists());
+    assert_eq!(client.get(&info_url).send().await.unwrap().status(), 410);
+
+    let body: Value = client.get(server.url("/api/trash")).send().await.unwrap().json().await.unwrap();
+    let trashed = &body["data"][0];
+    assert_eq!(trashed["id"], record.id.as_str());
+    assert!(trashed["trashed_at"].is_string());
+    assert!(trashed["file_path"].as_str().unwrap().contains(".trash"));
+
+    // 恢复后 ID 不变, 内容完整
+    let restore_url = server.url(&format!("/api/files/{}/restore", record.id));
+    let response = client.post(&restore_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(std::path::Path::new(&record.file_path).exists());
+    let content = client
+        .get(server.url(&format!("/api/download/{}", record.id)))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(&content[..], b"keep me");
+    assert_eq!(client.post(&restore_url).send().await.unwrap().status(), 404);
+
+    // 永久删除后文件不能再恢复
+    client.delete(&info_url).send().await.unwrap();
+    let body: Value = client.get(server.url("/api/trash")).send().await.unwrap().json().await.unwrap();
+    let trashed_path = body["data"][0]["file_path"].as_str().unwrap().to_string();
+    let purge_url = server.url(&format!("/api/trash/{}", record.id));
+    assert_eq!(client.delete(&purge_url).send().await.unwrap().status(), 200);
+    assert!(!std::path::Path::new(&trashed_path).exists());
+    assert_eq!(client.delete(&purge_url).send().await.unwrap().status(), 404);
+    assert_eq!(client.post(&restore_url).send().await.unwrap().status(), 404);
+
+    // 过期的文件被自动清除
+    let record = seed_file(&server, "old.txt", b"old").await;
+    client.delete(server.url(&format!("/api/files/{}", record.id))).send().await.unwrap();
+    let purged = rust_internal_file_server::storage::trash::purge_expired(server.file_manager(), 3600).await.unwrap();
+    assert_eq!(purged, 0);
+    let purged = rust_internal_file_server::storage::trash::purge_expired(server.file_manager(), 0).await.unwrap();
+    assert_eq!(purged, 1);
+    let body: Value = client.get(server.url("/api/trash")).send().await.unwrap().json().await.unwrap();
+    assert!(body["data"].as_array().unwrap().is_empty());
+
+    // 关闭回收站后直接删除
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.trash.enabled = false;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let record = seed_file(&server, "gone.txt", b"gone").await;
+    client.delete(server.url(&format!("/api/files/{}", record.id))).send().await.unwrap();
+    let body: Value = client.get(server.url("/api/trash")).send().await.unwrap().json().await.unwrap();
+    assert!(body["data"].as_array().unwrap().is_empty());
+    let response = client.post(server.url(&format!("/api/files/{}/restore", record.id))).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_table_preview() {
+    let server = TestServer::start().await.unwrap();
+    let csv = "id,price,active,day,note\r\n1,2.5,true,2024-01-02,\"hello, world\"\r\n2,3,false,2024-02-03,\"multi\nline \"\"quoted\"\"\"\r\n3,,TRUE,2024-03-04,\r\n";
+    let record = seed_file(&server, "sales.csv", csv.as_bytes()).await;
+    let url = |id: &str, query: &str| server.url(&format!("/api/files/{}/table?{}", id, query));
+
+    let body: Value = reqwest::get(url(&record.id, "")).await.unwrap().json().await.unwrap();
+    let data = &body["data"];
+    let types: Vec<&str> = data["columns"].as_array().unwrap().iter().map(|c| c["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["integer", "float", "boolean", "date", "string"]);
+    assert_eq!(data["columns"][0]["name"], "id");
+    assert_eq!(data["columns"][1]["nullable"], true);
+    assert_eq!(data["columns"][0]["nullable"], false);
+    assert_eq!(data["rows"].as_array().unwrap().len(), 3);
+    assert_eq!(data["rows"][0], serde_json::json!([1, 2.5, true, "2024-01-02", "hello, world"]));
+    assert_eq!(data["rows"][1][4], "multi\nline \"quoted\"");
+    assert_eq!(data["rows"][2][1], Value::Null);
+    assert_eq!(data["rows"][2][4], Value::Null);
+    assert_eq!(data["truncated"], false);
+
+    let body: Value = reqwest::get(url(&record.id, "rows=2")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["rows"].as_array().unwrap().len(), 2);
+    assert_eq!(body["data"]["truncated"], true);
+
+    let tsv = seed_file(&server, "scores.tsv", b"name\tscore\nalice\t9.5\nbob\t7\n").await;
+    let body: Value = reqwest::get(url(&tsv.id, "")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["columns"][1]["type"], "float");
+    assert_eq!(body["data"]["rows"][1], serde_json::json!(["bob", 7.0]));
+
+    assert_eq!(reqwest::get(url(&record.id, "rows=0")).await.unwrap().status(), 400);
+    let parquet = seed_file(&server, "events.parquet", b"PAR1").await;
+    assert_eq!(reqwest::get(url(&parquet.id, "")).await.unwrap().status(), 400);
+    let text = seed_file(&server, "notes.txt", b"a,b\n1,2\n").await;
+    assert_eq!(reqwest::get(url(&text.id, "")).await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn test_chunked_upload() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.chunk_size = 4;
+    config.storage.max_file_size = 64;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let content = b"resumable upload";
+
+    assert_eq!(
+        client
+            .post(server.url("/api/upload/init"))
+            .json(&json!({ "file_name": "huge.iso", "size": 65 }))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        400
+    );
+
+    let body: Value = client
+        .post(server.url("/api/upload/init"))
+        .json(&json!({ "file_name": "notes.txt", "size": content.len() }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let session_id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["chunk_size"], 4);
+    assert_eq!(body["data"]["chunk_count"], 4);
+    let chunk_url = |index: i64| server.url(&format!("/api/upload/{}/chunk/{}", session_id, index));
+    let put_chunk = |index: i64, data: &[u8]| client.put(chunk_url(index)).body(data.to_vec()).send();
+
+    // 乱序上传, 其中一块长度不对
+    assert_eq!(put_chunk(3, &content[12..]).await.unwrap().status(), 200);
+    assert_eq!(put_chunk(1, &content[4..8]).await.unwrap().status(), 200);
+    assert_eq!(put_chunk(0, &content[..3]).await.unwrap().status(), 400);
+    assert_eq!(put_chunk(4, b"more").await.unwrap().status(), 400);
+
+    // 未完成时不能结束上传
+    let complete_url = server.url(&format!("/api/upload/{}/complete", session_id));
+    assert_eq!(client.post(&complete_url).send().await.unwrap().status(), 412);
+
+    // 断线后查询进度, 只补传缺少的块
+    let body: Value = reqwest::get(server.url(&format!("/api/upload/{}", session_id)))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["received_chunks"], json!([1, 3]));
+    assert_eq!(body["data"]["missing_chunks"], json!([0, 2]));
+    assert_eq!(put_chunk(0, &content[..4]).await.unwrap().status(), 200);
+    assert_eq!(put_chunk(2, &content[8..12]).await.unwrap().status(), 200);
+
+    let body: Value = client.post(&complete_url).send().await.unwrap().json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["original_name"], "notes.txt");
+    assert_eq!(data["mime_type"], "text/plain");
+    assert_eq!(data["file_size"], content.len());
+    let record = server.files().get_file_by_id(data["id"].as_str().unwrap()).await.unwrap().unwrap();
+    assert_eq!(std::fs::read(&record.file_path).unwrap(), content);
+
+    // 会话已经结束
+    assert_eq!(client.post(&complete_url).send().await.unwrap().status(), 404);
+    assert_eq!(put_chunk(0, &content[..4]).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn test_chunked_upload_sessions() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.chunk_size = 4;
+    config.storage.upload_sessions.max_reserved = Some(20);
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let init = |size: usize| {
+        client
+            .post(server.url("/api/upload/init"))
+            .json(&json!({ "file_name": "build.tar", "size": size }))
+            .send()
+    };
+    let put_chunk = |session_id: &str, index: i64, data: &'static [u8]| {
+        client
+            .put(server.url(&format!("/api/upload/{}/chunk/{}", session_id, index)))
+            .body(data)
+            .send()
+    };
+
+    // 创建会话时预留声明的大小, 超出预算的会话被拒绝; 收到的块不再占用预留
+    let body: Value = init(16).await.unwrap().json().await.unwrap();
+    let aborted = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(init(8).await.unwrap().status(), 507);
+    assert_eq!(put_chunk(&aborted, 0, b"abcd").await.unwrap().status(), 200);
+    let body: Value = init(8).await.unwrap().json().await.unwrap();
+    let reserved = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(init(1).await.unwrap().status(), 507);
+
+    // 取消上传删除会话和拼接文件, 并释放预留的空间
+    let part = server.storage_dir().join(".uploads").join(format!("{}.part", aborted));
+    assert!(part.exists());
+    let abort_url = server.url(&format!("/api/upload/{}", aborted));
+    assert_eq!(client.delete(&abort_url).send().await.unwrap().status(), 200);
+    assert!(!part.exists());
+    assert_eq!(client.delete(&abort_url).send().await.unwrap().status(), 404);
+    assert_eq!(put_chunk(&aborted, 1, b"efgh").await.unwrap().status(), 404);
+    assert_eq!(client.delete(server.url(&format!("/api/upload/{}", reserved))).send().await.unwrap().status(), 200);
+
+    // 声明的 SHA-256 不一致时保留会话, 可以再次完成
+    let body: Value = init(8).await.unwrap().json().await.unwrap();
+    let session_id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(put_chunk(&session_id, 0, b"abcd").await.unwrap().status(), 200);
+    assert_eq!(put_chunk(&session_id, 1, b"efgh").await.unwrap().status(), 200);
+    let complete_url = server.url(&format!("/api/upload/{}/complete", session_id));
+    let complete = |sha256: String| {
+        client
+            .post(&complete_url)
+            .header("X-Content-SHA256", sha256)
+            .header("Idempotency-Key", "ci-build-42")
+            .send()
+    };
+    assert_eq!(complete("00".repeat(32)).await.unwrap().status(), 400);
+    let sha256 = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b"abcdefgh"));
+    let first: Value = complete(sha256.clone()).await.unwrap().json().await.unwrap();
+    assert_eq!(first["data"]["sha256"], sha256.as_str());
+
+    // 会话删除后重放同一个键仍返回第一次的结果, 不会再创建记录; 键不能用于其他会话
+    let replay: Value = complete(sha256.clone()).await.unwrap().json().await.unwrap();
+    assert_eq!(replay["data"], first["data"]);
+    let body: Value = client.get(server.url("/api/files")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    let other_url = server.url(&format!("/api/upload/{}/complete", aborted));
+    let response = client.post(&other_url).header("Idempotency-Key", "ci-build-42").send().await.unwrap();
+    assert_eq!(response.status(), 409);
+
+    // 过期的会话被清理
+    let body: Value = init(4).await.unwrap().json().await.unwrap();
+    let expired = body["data"]["id"].as_str().unwrap().to_string();
+    let purged = rust_internal_file_server::storage::uploads::purge_expired(server.file_manager(), 0)
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    let response = client.get(server.url(&format!("/api/upload/{}", expired))).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_chunked_upload_races() {
+    use sha2::Digest;
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.chunk_size = 4;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let init = || async {
+        let body: Value = client
+            .post(server.url("/api/upload/init"))
+            .json(&json!({ "file_name": "build.tar", "size": 8 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["data"]["id"].as_str().unwrap().to_string()
+    };
+    let put_chunk = |session_id: &str, index: i64, data: &'static [u8]| {
+        client
+            .put(server.url(&format!("/api/upload/{}/chunk/{}", session_id, index)))
+            .body(data)
+            .send()
+    };
+
+    for _ in 0..20 {
+        // 分块要么在取消前写完, 要么找不到会话; 取消后不会留下拼接文件
+        let session_id = init().await;
+        let abort = client.delete(server.url(&format!("/api/upload/{}", session_id))).send();
+        let (chunk, abort) = tokio::join!(put_chunk(&session_id, 0, b"abcd"), abort);
+        assert_eq!(abort.unwrap().status(), 200);
+        assert!(matches!(chunk.unwrap().status().as_u16(), 200 | 404));
+        assert!(!server.storage_dir().join(".uploads").join(format!("{}.part", session_id)).exists());
+
+        // 完成时重传的分块不会混进已经校验过的内容
+        let session_id = init().await;
+        assert_eq!(put_chunk(&session_id, 0, b"abcd").await.unwrap().status(), 200);
+        assert_eq!(put_chunk(&session_id, 1, b"efgh").await.unwrap().status(), 200);
+        let complete = client.post(server.url(&format!("/api/upload/{}/complete", session_id))).send();
+        let (chunk, complete) = tokio::join!(put_chunk(&session_id, 1, b"EFGH"), complete);
+        assert!(matches!(chunk.unwrap().status().as_u16(), 200 | 404));
+        let body: Value = complete.unwrap().json().await.unwrap();
+        let record = server.files().get_file_by_id(body["data"]["id"].as_str().unwrap()).await.unwrap().unwrap();
+        let content = std::fs::read(&record.file_path).unwrap();
+        assert_eq!(hex::encode(sha2::Sha256::digest(&content)), record.sha256.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_tail_follow() {
+    let server = TestServer::start().await.unwrap();
+    let record = seed_file(&server, "job.log", b"line 1\nline 2\nline 3\n").await;
+    let url = |query: &str| server.url(&format!("/api/files/{}/tail?{}", record.id, query));
+
+    let response = reqwest::get(url("lines=2")).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(response.text().await.unwrap(), "line 2\nline 3\n");
+    assert_eq!(reqwest::get(url("")).await.unwrap().text().await.unwrap(), "line 1\nline 2\nline 3\n");
+    assert_eq!(reqwest::get(url("lines=0")).await.unwrap().status(), 400);
+
+    // follow 模式先返回末尾, 再推送之后追加的内容
+    let mut response = reqwest::get(url("lines=1&follow=true")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.chunk().await.unwrap().unwrap(), "line 3\n");
+    let mut file = std::fs::OpenOptions::new().append(true).open(&record.file_path).unwrap();
+    std::io::Write::write_all(&mut file, b"line 4\n").unwrap();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk()).await.unwrap();
+    assert_eq!(chunk.unwrap().unwrap(), "line 4\n");
+
+    // 删除文件后响应结束
+    std::fs::remove_file(&record.file_path).unwrap();
+    let end = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk()).await.unwrap();
+    assert!(end.unwrap().is_none());
+
+    let binary = seed_file(&server, "firmware.bin", &[0, 1, 2]).await;
+    let response = reqwest::get(server.url(&format!("/api/files/{}/tail", binary.id))).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_hexdump_preview() {
+    let server = TestServer::start().await.unwrap();
+    let content: Vec<u8> = b"Hello, firmware!".iter().copied().chain(0u8..40).collect();
+    let record = seed_file(&server, "firmware.bin", &content).await;
+    let url = |query: &str| server.url(&format!("/api/files/{}/hexdump?{}", record.id, query));
+
+    let body: Value = reqwest::get(url("length=20")).await.unwrap().json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["file_size"], content.len() as u64);
+    assert_eq!(data["length"], 20);
+    let lines: Vec<&str> = data["dump"].as_str().unwrap().lines().collect();
+    assert_eq!(lines[0], "00000000  48 65 6c 6c 6f 2c 20 66  69 72 6d 77 61 72 65 21  |Hello, firmware!|");
+    assert_eq!(lines[1], "00000010  00 01 02 03                                       |....|");
+
+    // 读到文件末尾为止
+    let body: Value = reqwest::get(url("offset=50")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["length"], 6);
+    assert!(body["data"]["dump"].as_str().unwrap().starts_with("00000032  22 23 24 25 26 27"));
+
+    assert_eq!(reqwest::get(url("offset=56")).await.unwrap().status(), 400);
+    assert_eq!(reqwest::get(url("length=0")).await.unwrap().status(), 400);
+    assert_eq!(reqwest::get(url("length=65537")).await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn test_range_downloads() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "报告 v1.txt", b"0123456789").await;
+    let url = server.url(&format!("/api/download/{}", record.id));
+    let get = |range: Option<&str>, if_range: Option<&str>| {
+        let mut request = client.get(&url);
+        if let Some(range) = range {
+            request = request.header("Range", range);
+        }
+        if let Some(if_range) = if_range {
+            request = request.header("If-Range", if_range);
+        }
+        request.send()
+    };
+
+    let response = get(None, None).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert_eq!(response.headers()["content-length"], "10");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"__ v1.txt\"; filename*=UTF-8''%E6%8A%A5%E5%91%8A%20v1.txt"
+    );
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(response.text().await.unwrap(), "0123456789");
+
+    for (range, content_range, body) in [
+        ("bytes=2-4", "bytes 2-4/10", "234"),
+        ("bytes=7-", "bytes 7-9/10", "789"),
+        ("bytes=-3", "bytes 7-9/10", "789"),
+        ("bytes=8-100", "bytes 8-9/10", "89"),
+    ] {
+        let response = get(Some(range), None).await.unwrap();
+        assert_eq!(response.status(), 206, "{}", range);
+        assert_eq!(response.headers()["content-range"], content_range);
+        assert_eq!(response.text().await.unwrap(), body);
+    }
+
+    // 多段范围返回 multipart/byteranges
+    let response = get(Some("bytes=0-1, 5-6"), None).await.unwrap();
+    assert_eq!(response.status(), 206);
+    let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+    let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+    let length: usize = response.headers()["content-length"].to_str().unwrap().parse().unwrap();
+    let body = response.text().await.unwrap();
+    assert_eq!(body.len(), length);
+    assert_eq!(
+        body,
+        format!(
+            "--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 5-6/10\r\n\r\n56\r\n--{b}--\r\n",
+            b = boundary
+        )
+    );
+
+    let response = get(Some("bytes=10-"), None).await.unwrap();
+    assert_eq!(response.status(), 416);
+    assert_eq!(response.headers()["content-range"], "bytes */10");
+    // 无法识别的 Range 被忽略
+    assert_eq!(get(Some("lines=1-2"), None).await.unwrap().status(), 200);
+
+    // If-Range 不匹配时返回完整内容
+    assert_eq!(get(Some("bytes=0-0"), Some(&etag)).await.unwrap().status(), 206);
+    assert_eq!(get(Some("bytes=0-0"), Some("\"stale\"")).await.unwrap().status(), 200);
+    let response = client.get(&url).header("If-None-Match", &etag).send().await.unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = reqwest::get(server.url(&format!("/files/{}/report.txt", record.id))).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-disposition"].to_str().unwrap().starts_with("inline;"));
+    assert_eq!(response.text().await.unwrap(), "0123456789");
+
+    assert_eq!(reqwest::get(server.url("/api/download/missing")).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn test_download_zip() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let first = seed_file(&server, "notes.txt", b"first").await;
+    let second = seed_file(&server, "notes.txt", b"second").await;
+    let video = seed_file(&server, "clip.mp4", &[7u8; 200_000]).await;
+    let url = server.url("/api/download/zip");
+
+    let response = client
+        .post(&url)
+        .json(&json!({ "file_ids": [first.id, second.id, video.id, first.id] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/zip");
+    let bytes = response.bytes().await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes.to_vec())).unwrap();
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    assert_eq!(names.len(), 3);
+    assert!(names.contains(&format!("{}-notes.txt", second.id)));
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("notes.txt").unwrap(), &mut content).unwrap();
+    assert_eq!(content, "first");
+    let mut content = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("clip.mp4").unwrap(), &mut content).unwrap();
+    assert_eq!(content, vec![7u8; 200_000]);
+
+    // 按集合打包
+    let body: Value = client
+        .post(server.url("/api/collections"))
+        .json(&json!({ "name": "Docs", "file_ids": [second.id] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let collection_id = body["data"]["id"].as_str().unwrap();
+    let response = client.post(&url).json(&json!({ "collection_id": collection_id })).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let bytes = response.bytes().await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes.to_vec())).unwrap();
+    assert_eq!(archive.len(), 1);
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("notes.txt").unwrap(), &mut content).unwrap();
+    assert_eq!(content, "second");
+
+    // 打包蜜罐文件同样产生告警
+    client.put(server.url(&format!("/api/admin/canaries/{}", video.id))).send().await.unwrap();
+    let response = client.post(&url).json(&json!({ "file_ids": [video.id] })).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = client
+        .get(server.url(&format!("/api/admin/canaries/alerts?file_id={}", video.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"][0]["path"], "/api/download/zip");
+
+    // 参数错误和不存在的文件
+    let response = client.post(&url).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .post(&url)
+        .json(&json!({ "file_ids": [first.id], "collection_id": collection_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.post(&url).json(&json!({ "file_ids": [first.id, "missing"] })).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let response = client.post(&url).json(&json!({ "collection_id": "missing" })).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_embedded_ui() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client.get(server.url("/ui")).send().await.unwrap();
+    assert_eq!(response.status(), 308);
+    assert_eq!(response.headers()["location"], "/ui/");
+
+    let response = client.get(server.url("/ui/")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+    let etag = response.headers()["etag"].clone();
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<video"));
+    assert!(html.contains("/ui/app.js"));
+
+    let response = client
+        .get(server.url("/ui/"))
+        .header("if-none-match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = client.get(server.url("/ui/app.js")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().contains("javascript"));
+    assert!(response.text().await.unwrap().contains("/api/upload"));
+
+    // 界面内的页面路径返回 index.html, 不存在的静态文件返回 404
+    let response = client.get(server.url("/ui/files/recent")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("<video"));
+    let response = client.get(server.url("/ui/missing.css")).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_canary_files() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let canary = seed_file(&server, "passwords.xlsx", b"bait").await;
+    let normal = seed_file(&server, "readme.txt", b"hello").await;
+
+    let body: Value = client
+        .put(server.url(&format!("/api/admin/canaries/{}", canary.id)))
+        .json(&json!({ "note": "finance share" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["file_id"], canary.id);
+    assert_eq!(body["data"]["note"], "finance share");
+
+    let body: Value = client
+        .get(server.url("/api/admin/canaries"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    // 下载照常完成, 只有蜜罐文件产生告警, 凭据类请求头不保存
+    let response = client
+        .get(server.url(&format!("/api/download/{}", canary.id)))
+        .header("user-agent", "snooper/1.0")
+        .header("cookie", "session=secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"bait");
+    let response = client
+        .get(server.url(&format!("/files/{}/passwords.xlsx", canary.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .get(server.url(&format!("/api/download/{}", normal.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = client
+        .get(server.url("/api/admin/canaries/alerts"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let alerts = body["data"].as_array().unwrap();
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0]["path"], format!("/files/{}/passwords.xlsx", canary.id));
+    assert_eq!(alerts[1]["file_id"], canary.id);
+    assert_eq!(alerts[1]["client_ip"], "127.0.0.1");
+    assert_eq!(alerts[1]["method"], "GET");
+    assert_eq!(alerts[1]["user_agent"], "snooper/1.0");
+    assert!(alerts[1]["headers"].get("cookie").is_none());
+
+    let body: Value = client
+        .get(server.url(&format!("/api/admin/canaries/alerts?file_id={}&limit=1", normal.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body["data"].as_array().unwrap().is_empty());
+
+    // 预览、缩略图、转换、对比和打包同样读出文件内容, 每次都产生告警
+    let thumbnail = server.storage_dir().join(".cache").join("thumbnails").join(format!("{}.jpg", canary.id));
+    std::fs::create_dir_all(thumbnail.parent().unwrap()).unwrap();
+    std::fs::write(&thumbnail, b"thumb").unwrap();
+    assert!(server.files().set_thumbnail_path(&canary.id, 1, &thumbnail.to_string_lossy()).await.unwrap());
+    let previews = [
+        format!("/api/files/{}/thumbnail", canary.id),
+        format!("/api/files/{}/archive/entries", canary.id),
+        format!("/api/files/{}/hexdump", canary.id),
+        format!("/api/files/{}/tail", canary.id),
+        format!("/api/files/{}/table", canary.id),
+        format!("/api/files/{}/text", canary.id),
+        format!("/api/files/{}/image?width=10", canary.id),
+        format!("/api/files/{}/convert?format=png", canary.id),
+        format!("/api/files/diff?a={}&b={}", normal.id, canary.id),
+    ];
+    for path in &previews {
+        client.get(server.url(path)).send().await.unwrap();
+    }
+    let response = client.get(server.url(&previews[0])).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"thumb");
+    let response = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({ "name": "passwords" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = client
+        .get(server.url(&format!("/api/admin/canaries/alerts?file_id={}", canary.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let paths: Vec<&str> = body["data"].as_array().unwrap().iter().map(|a| a["path"].as_str().unwrap()).collect();
+    assert_eq!(paths.len(), 2 + previews.len() + 2);
+    for path in &previews {
+        assert!(paths.contains(&path.as_str()), "{} 没有产生告警", path);
+    }
+    assert!(paths.contains(&"/api/search/download"));
+
+    let response = client
+        .delete(server.url(&format!("/api/admin/canaries/{}", canary.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .delete(server.url(&format!("/api/admin/canaries/{}", canary.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let response = client
+        .put(server.url("/api/admin/canaries/00000000-0000-0000-0000-000000000000"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_canary_alert_redacts_api_key() {
+    use rust_internal_file_server::config::{ApiKeyConfig, KeyAccess};
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.auth.keys = vec![ApiKeyConfig {
+        name: "ops".to_string(),
+        key: "ops-secret".to_string(),
+        key_file: None,
+        key_command: None,
+        access: KeyAccess::ReadWrite,
+    }];
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let canary = seed_file(&server, "passwords.xlsx", b"bait").await;
+    let response = client
+        .put(server.url(&format!("/api/admin/canaries/{}", canary.id)))
+        .bearer_auth("ops-secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 两种携带密钥的方式都不保存到告警中
+    let download_url = server.url(&format!("/api/download/{}", canary.id));
+    let response = client.get(&download_url).header("x-api-key", "ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&download_url).bearer_auth("ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = client
+        .get(server.url("/api/admin/canaries/alerts"))
+        .bearer_auth("ops-secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let alerts = body["data"].as_array().unwrap();
+    assert_eq!(alerts.len(), 2);
+    for alert in alerts {
+        assert!(alert["headers"].get("x-api-key").is_none());
+        assert!(alert["headers"].get("authorization").is_none());
+        assert!(!alert.to_string().contains("ops-secret"));
+    }
+}
+
+#[tokio::test]
+async fn test_search_files() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let report = seed_file(&server, "季度报告-final.pdf", b"quarterly report").await;
+    let notes = seed_file(&server, "meeting-notes.txt", b"notes").await;
+    let clip = seed_file(&server, "Report walkthrough.mp4", b"not really a video").await;
+
+    let search = |query: &str| {
+        let client = client.clone();
+        let url = server.url(&format!("/api/search?{}", query));
+        async move {
+            let body: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| file["id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // 任意位置的子串都能匹配, 不区分大小写, 最新上传的在前
+    assert_eq!(search("q=REPORT").await, [clip.id.as_str()]);
+    assert_eq!(search("q=%E6%8A%A5%E5%91%8A").await, [report.id.as_str()]);
+    assert_eq!(search("q=pdf").await, [report.id.as_str()]);
+    assert_eq!(search("q=text%2Fplain").await, [notes.id.as_str()]);
+    // 少于 3 个字符的词同样可以搜索
+    assert_eq!(search("q=mp").await, [clip.id.as_str()]);
+    assert_eq!(search("q=notes%20mp4").await, Vec::<String>::new());
+
+    assert_eq!(search("is_video=true").await, [clip.id.as_str()]);
+    assert_eq!(search("min_size=6&max_size=16").await, [report.id.as_str()]);
+    assert_eq!(search("limit=1&offset=1").await, [notes.id.as_str()]);
+    let after = (Utc::now() + Duration::minutes(1)).to_rfc3339().replace('+', "%2B");
+    assert!(search(&format!("uploaded_after={}", after)).await.is_empty());
+
+    let response = client.get(server.url("/api/search?min_size=10&max_size=1")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.get(server.url("/api/search?limit=0")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    // 删除后不再出现在搜索结果中
+    let response = client
+        .delete(server.url(&format!("/api/files/{}", report.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(search("q=pdf").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_unknown_file_returns_404() {
+    let server = TestServer::start().await.unwrap();
+
+    let response = reqwest::get(server.url("/api/files/does-not-exist")).await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], false);
+    assert!(body["error"].as_str().unwrap().starts_with("获取文件信息失败"));
+
+    let response = reqwest::Client::new()
+        .get(server.url("/api/files/does-not-exist"))
+        .header("Accept-Language", "en-US,en;q=0.9")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Failed to get file info: Resource not found: file does-not-exist");
+}
+
+// 生成带 EXIF 拍摄时间和 GPS 坐标的 JPEG: 在 SOI 之后插入 APP1 段
+fn jpeg_with_exif(time: &str, location: Option<(f64, f64)>) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    image::RgbImage::new(8, 8)
+        .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .unwrap();
+
+    let field = |tag, value| exif::Field { tag, ifd_num: exif::In::PRIMARY, value };
+    let ascii = |text: &str| exif::Value::Ascii(vec![text.as_bytes().to_vec()]);
+    let degrees = |value: f64| {
+        let micro = (value.abs() * 1_000_000.0).round() as u32;
+        exif::Value::Rational(vec![
+            exif::Rational { num: micro, denom: 1_000_000 },
+            exif::Rational { num: 0, denom: 1 },
+            exif::Rational { num: 0, denom: 1 },
+        ])
+    };
+
+    let mut fields = vec![field(exif::Tag::DateTimeOriginal, ascii(time))];
+    if let Some((lat, lon)) = location {
+        fields.push(field(exif::Tag::GPSLatitudeRef, ascii(if lat < 0.0 { "S" } else { "N" })));
+        fields.push(field(exif::Tag::GPSLatitude, degrees(lat)));
+        fields.push(field(exif::Tag::GPSLongitudeRef, ascii(if lon < 0.0 { "W" } else { "E" })));
+        fields.push(field(exif::Tag::GPSLongitude, degrees(lon)));
+    }
+
+    let mut writer = exif::experimental::Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+
+    let mut app1 = b"Exif\0\0".to_vec();
+    app1.extend(tiff.into_inner());
+    let mut output = jpeg[..2].to_vec();
+    output.extend([0xFF, 0xE1]);
+    output.extend(((app1.len() + 2) as u16).to_be_bytes());
+    output.extend(app1);
+    output.extend(&jpeg[2..]);
+    output
+}
+
+#[tokio::test]
+async fn test_photo_timeline() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let first = seed_file(&server, "a.jpg", &jpeg_with_exif("2021:06:01 09:30:00", None)).await;
+    seed_file(&server, "b.jpg", &jpeg_with_exif("2021:06:01 18:00:00", None)).await;
+    seed_file(&server, "c.jpg", &jpeg_with_exif("2021:05:20 12:00:00", None)).await;
+    seed_file(&server, "notes.txt", b"not a photo").await;
+
+    let body: Value = client
+        .post(server.url("/api/admin/photos/reindex"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 3);
+    assert_eq!(body["data"]["updated"], 3);
+
+    let record = server.file_manager().get_file_by_id(&first.id).await.unwrap().unwrap();
+    assert_eq!(record.capture_time.unwrap().to_rfc3339(), "2021-06-01T09:30:00+00:00");
+
+    let body: Value = reqwest::get(server.url("/api/photos/timeline")).await.unwrap().json().await.unwrap();
+    let periods = body["data"].as_array().unwrap();
+    assert_eq!(periods.len(), 2);
+    assert_eq!(periods[0]["period"], "2021-06-01");
+    assert_eq!(periods[0]["count"], 2);
+    assert!(periods[0]["cover_url"].as_str().unwrap().contains("fit=cover"));
+
+    let body: Value = reqwest::get(server.url("/api/photos/timeline?group=month")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"][0]["period"], "2021-06");
+    assert_eq!(body["data"][1]["period"], "2021-05");
+    assert_eq!(body["data"][1]["count"], 1);
+}
+
+#[tokio::test]
+async fn test_files_in_bounding_box() {
+    let server = TestServer::start().await.unwrap();
+    let shanghai = seed_file(&server, "site-a.jpg", &jpeg_with_exif("2022:03:01 10:00:00", Some((31.23, 121.47)))).await;
+    seed_file(&server, "site-b.jpg", &jpeg_with_exif("2022:03:02 10:00:00", Some((-33.86, -70.65)))).await;
+    seed_file(&server, "notes.txt", b"no location").await;
+
+    let response = reqwest::Client::new()
+        .post(server.url("/api/admin/photos/reindex"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = reqwest::get(server.url("/api/files?bbox=120,30,122,32")).await.unwrap().json().await.unwrap();
+    let files = body["data"].as_array().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0]["id"], shanghai.id.as_str());
+    assert!((files[0]["latitude"].as_f64().unwrap() - 31.23).abs() < 1e-6);
+
+    let body: Value = reqwest::get(server.url("/api/files?bbox=-80,-40,-60,-30")).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"][0]["original_name"], "site-b.jpg");
+
+    let response = reqwest::get(server.url("/api/files?bbox=1,2,3")).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_similar_images() {
+    let server = TestServer::start().await.unwrap();
+    let png = |f: &dyn Fn(u32, u32) -> u8| {
+        let img = image::GrayImage::from_fn(64, 64, |x, y| image::Luma([f(x, y)]));
+        let mut bytes = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    };
+
+    // 同一张截图的亮度微调版本应当相似, 方向相反的渐变不相似
+    let original = seed_file(&server, "shot.png", &png(&|x, y| (x * 2 + y) as u8)).await;
+    let tweaked = seed_file(&server, "shot-2.png", &png(&|x, y| (x * 2 + y + 10) as u8)).await;
+    seed_file(&server, "other.png", &png(&|x, y| 255 - (x * 2 + y) as u8)).await;
+
+    let body: Value = reqwest::get(server.url(&format!("/api/files/{}/similar", original.id)))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    // 其他图片还没有哈希, 需要先建立索引
+    assert_eq!(body["data"].as_array().unwrap().len(), 0);
+
+    let body: Value = reqwest::Client::new()
+        .post(server.url("/api/admin/photos/reindex"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["hashed"], 2);
+
+    let body: Value = reqwest::get(server.url(&format!("/api/files/{}/similar", original.id)))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let similar = body["data"].as_array().unwrap();
+    assert_eq!(similar.len(), 1);
+    assert_eq!(similar[0]["id"], tweaked.id.as_str());
+    assert!(similar[0]["distance"].as_u64().unwrap() <= 10);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_ocr_reindex() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // 用脚本代替 tesseract, 输出固定文本
+    let tools = tempfile::tempdir().unwrap();
+    let script = tools.path().join("fake-tesseract");
+    std::fs::write(&script, "#!/bin/sh\necho \"error[E0308]: mismatched types ($4)\"\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.image.ocr.enabled = true;
+    config.image.ocr.command = script.to_string_lossy().to_string();
+    config.image.ocr.languages = vec!["eng".to_string()];
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let shot = seed_file(&server, "terminal.png", &[0u8; 16]).await;
+    seed_file(&server, "notes.txt", b"text").await;
+    let text_url = server.url(&format!("/api/files/{}/text", shot.id));
+    assert_eq!(client.get(&text_url).send().await.unwrap().status(), 404);
+
+    let body: Value = client
+        .post(server.url("/api/admin/ocr/reindex"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 1);
+    assert_eq!(body["data"]["indexed"], 1);
+
+    let body: Value = client.get(&text_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["source"], "ocr");
+    assert_eq!(body["data"]["content"], "error[E0308]: mismatched types (eng)");
+
+    // 已识别的图片不会重复处理
+    let body: Value = client
+        .post(server.url("/api/admin/ocr/reindex"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_extract_audio() {
+    // 用脚本代替 ffmpeg: 只支持 aac 编码器, 输出写到最后一个参数指定的位置
+    let tools = tempfile::tempdir().unwrap();
+    let script = fake_ffmpeg(
+        tools.path(),
+        &[" A..... aac  AAC"],
+        r#"if [ "$output" = "pipe:1" ]; then printf 'streamed'; else printf 'cached' > "$output"; fi"#,
+    );
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.video.ffmpeg_path = Some(script);
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let video = seed_file(&server, "meeting.mp4", b"not really a video").await;
+    let text = seed_file(&server, "notes.txt", b"text").await;
+    let audio_url = server.url(&format!("/api/video/{}/audio", video.id));
+
+    // 尚未提取时边转换边发送
+    let response = client.get(&audio_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "audio/mp4");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"meeting.m4a\""
+    );
+    assert_eq!(response.text().await.unwrap(), "streamed");
+
+    let body: Value = client
+        .post(server.url(&format!("/api/video/{}/extract-audio", video.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["format"], "m4a");
+    assert_eq!(body["data"]["size"], 6);
+    assert_eq!(body["data"]["url"], format!("/api/video/{}/audio?format=m4a", video.id));
+
+    // 提取后返回缓存文件
+    let response = client.get(&audio_url).send().await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "cached");
+
+    let response = client
+        .post(server.url(&format!("/api/video/{}/extract-audio?format=mp3", video.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 500);
+    let response = client
+        .post(server.url(&format!("/api/video/{}/extract-audio", text.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_video_thumbnail_on_upload() {
+    // 用脚本代替 ffmpeg: 把收到的参数写到最后一个参数指定的位置
+    let tools = tempfile::tempdir().unwrap();
+    let script = fake_ffmpeg(tools.path(), &[" V..... mjpeg  Motion JPEG"], r#"printf '%s' "$*" > "$output""#);
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.video.ffmpeg_path = Some(script);
+    config.video.thumbnail_formats = vec![
+        rust_internal_file_server::config::ThumbnailFormat::Webp,
+        rust_internal_file_server::config::ThumbnailFormat::Jpeg,
+    ];
+    config.video.thumbnail_sizes.insert("small".to_string(), "160x120".to_string());
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let boundary = "thumbnail-boundary";
+    let body: Value = client
+        .post(server.url("/api/upload"))
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(multipart_body(boundary, &[("file", Some("holiday.mp4"), b"not really a video")]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["is_video"], true);
+    let jobs = body["data"]["processing"]["jobs"].as_array().unwrap();
+    assert!(jobs.iter().any(|job| job["name"] == "thumbnail" && job["status"] == "pending"));
+
+    // 缩略图在后台生成, 没有 libwebp 时退回 JPEG
+    let thumbnail_url = server.url(&format!("/api/files/{}/thumbnail", id));
+    let mut response = client.get(&thumbnail_url).send().await.unwrap();
+    for _ in 0..50 {
+        if response.status() == 200 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        response = client.get(&thumbnail_url).send().await.unwrap();
+    }
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+    let args = response.text().await.unwrap();
+    assert!(args.contains("scale=320:240:force_original_aspect_ratio=decrease"));
+    assert!(args.contains("-c:v mjpeg"));
+
+    // 命名尺寸一并生成
+    let thumbnails = server.storage_dir().join(".cache").join("thumbnails");
+    let small = std::fs::read_to_string(thumbnails.join(format!("{}-small.jpg", id))).unwrap();
+    assert!(small.contains("scale=160:120:force_original_aspect_ratio=decrease"));
+    assert!(!thumbnails.join(format!("{}.webp", id)).exists());
+
+    // 按名称取命名尺寸, 请求 WebP 时只有已生成的 JPEG 可用
+    let response = client
+        .get(format!("{}?size=small", thumbnail_url))
+        .header("accept", "image/webp")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+    assert_eq!(response.text().await.unwrap(), small);
+    let response = client.get(format!("{}?size=huge", thumbnail_url)).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    // 替换内容后清空旧缩略图并重新生成
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", id)))
+        .body("another video")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert!(body["data"]["thumbnail_path"].is_null());
+    let mut response = client.get(&thumbnail_url).send().await.unwrap();
+    for _ in 0..50 {
+        if response.status() == 200 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        response = client.get(&thumbnail_url).send().await.unwrap();
+    }
+    assert_eq!(response.status(), 200);
+    let record = server.files().get_file_by_id(&id).await.unwrap().unwrap();
+    assert_eq!(record.version, 2);
+    assert!(record.thumbnail_path.is_some());
+
+    // 非视频文件不生成缩略图
+    let text = seed_file(&server, "notes.txt", b"text").await;
+    let response = client
+        .get(server.url(&format!("/api/files/{}/thumbnail", text.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_video_metadata() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // 用脚本代替 ffprobe, 任何文件都报告 12.6 秒的 1920x1080 视频
+    let tools = tempfile::tempdir().unwrap();
+    let script = tools.path().join("fake-ffprobe");
+    std::fs::write(
+        &script,
+        r#"#!/bin/sh
+case "$*" in
+    -version) echo "ffprobe version 9.9 Copyright"; exit 0 ;;
+esac
+echo '{"streams": [{"width": 1920, "height": 1080}], "format": {"duration": "12.6"}}'
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.video.ffprobe_path = Some(script);
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 已有的记录通过管理接口补读
+    let video = seed_file(&server, "existing.mp4", b"not really a video").await;
+    seed_file(&server, "notes.txt", b"text").await;
+    let body: Value = client
+        .post(server.url("/api/admin/reindex-video-metadata"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 1);
+    assert_eq!(body["data"]["updated"], 1);
+    assert_eq!(body["data"]["failed"], 0);
+
+    let record = server.files().get_file_by_id(&video.id).await.unwrap().unwrap();
+    assert_eq!(record.video_duration, Some(13));
+    assert_eq!(record.video_resolution.as_deref(), Some("1920x1080"));
+
+    // 已有元数据的记录不再处理
+    let body: Value = client
+        .post(server.url("/api/admin/reindex-video-metadata"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 0);
+
+    // 新上传的视频在后台读取
+    let boundary = "metadata-boundary";
+    let body: Value = client
+        .post(server.url("/api/upload"))
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(multipart_body(boundary, &[("file", Some("new.mp4"), b"not really a video")]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+    let mut record = server.files().get_file_by_id(&id).await.unwrap().unwrap();
+    for _ in 0..50 {
+        if record.video_duration.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        record = server.files().get_file_by_id(&id).await.unwrap().unwrap();
+    }
+    assert_eq!(record.video_duration, Some(13));
+    assert_eq!(record.video_resolution.as_deref(), Some("1920x1080"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_video_clip() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // ffprobe 报告 0 秒和 10 秒处的关键帧, ffmpeg 把收到的参数写入输出文件
+    let tools = tempfile::tempdir().unwrap();
+    let ffprobe = tools.path().join("fake-ffprobe");
+    std::fs::write(
+        &ffprobe,
+        "#!/bin/sh\n[ \"$1\" = -version ] && echo 'ffprobe version 9.9 Copyright' && exit 0\nprintf '0.000000\\n10.000000\\n'\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&ffprobe, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let ffmpeg = fake_ffmpeg(tools.path(), &[" V..... libx264  H.264", " A..... aac  AAC"], r#"echo "$*" > "$output""#);
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.video.ffmpeg_path = Some(ffmpeg);
+    config.video.ffprobe_path = Some(ffprobe);
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let video = seed_file(&server, "keynote.mp4", b"not really a video").await;
+    let clip_url = server.url(&format!("/api/video/{}/clip", video.id));
+
+    // 起点落在关键帧上时复制码流, 否则重新编码
+    let body: Value = client
+        .post(&clip_url)
+        .json(&json!({ "start": 10, "end": 20.5 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["mode"], "copy");
+    assert_eq!(body["data"]["url"], format!("/api/video/{}/clip?start=10&end=20.5", video.id));
+    let args = client.get(format!("{}?start=10&end=20.5", clip_url)).send().await.unwrap().text().await.unwrap();
+    assert!(args.contains("-ss 10 ") && args.contains("-t 10.5 ") && args.contains("-c copy"), "{}", args);
+
+    let body: Value = client
+        .post(&clip_url)
+        .json(&json!({ "start": 12, "end": 20 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["mode"], "reencode");
+    let response = client.get(format!("{}?start=12&end=20", clip_url)).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "video/mp4");
+    assert!(response.text().await.unwrap().contains("-c:v libx264"));
+
+    // 已截取过的片段直接使用缓存
+    let body: Value = client
+        .post(&clip_url)
+        .json(&json!({ "start": 10, "end": 20.5 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body["data"].get("mode").is_none());
+
+    let response = client.post(&clip_url).json(&json!({ "start": 5, "end": 5 })).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.get(format!("{}?start=1&end=2", clip_url)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_heif_compatibility() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // 用脚本代替 heif-convert, 把准备好的 JPEG 复制到最后一个参数指定的位置
+    let tools = tempfile::tempdir().unwrap();
+    let mut jpeg = Vec::new();
+    image::RgbImage::new(60, 30)
+        .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .unwrap();
+    let sample = tools.path().join("sample.jpg");
+    std::fs::write(&sample, &jpeg).unwrap();
+    let script = tools.path().join("fake-heif-convert");
+    std::fs::write(
+        &script,
+        format!("#!/bin/sh\nfor output; do :; done\ncp '{}' \"$output\"\n", sample.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.image.heif.enabled = true;
+    config.image.heif.download_jpeg = true;
+    config.image.heif.command = script.to_string_lossy().to_string();
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 预览时按需生成 JPEG 副本
+    let mut photo = seed_file(&server, "site.heic", b"heic bytes").await;
+    let response = client
+        .get(server.url(&format!("/api/files/{}/image?w=20", photo.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+    let resized = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!((resized.width(), resized.height()), (20, 10));
+
+    // 按哈希下载默认返回 JPEG 副本, 原文件保持不变
+    photo.id = uuid::Uuid::new_v4().to_string();
+    photo.sha256 = Some("cd".repeat(32));
+    server.files().save_file_record(&photo).await.unwrap();
+    let url = server.url(&format!("/files/by-hash/{}", "cd".repeat(32)));
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+    assert_eq!(response.bytes().await.unwrap().as_ref(), jpeg.as_slice());
+    let response = client.get(format!("{}?original=true", url)).send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "image/heic");
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"heic bytes");
+
+    seed_file(&server, "survey.heif", b"heif bytes").await;
+    seed_file(&server, "notes.txt", b"text").await;
+    let body: Value = client
+        .post(server.url("/api/admin/heif/reindex"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 3);
+    assert_eq!(body["data"]["converted"], 1);
+    assert_eq!(body["data"]["failed"], 0);
+}
+
+#[tokio::test]
+async fn test_file_lock() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "asset.psd", b"layers").await;
+    let info_url = server.url(&format!("/api/files/{}", record.id));
+    let lock_url = server.url(&format!("/api/files/{}/lock", record.id));
+
+    let body: Value = client.get(&lock_url).send().await.unwrap().json().await.unwrap();
+    assert!(body["data"].is_null());
+
+    let response = client.post(&lock_url).json(&json!({ "owner": "alice" })).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 其他人不能加锁、解锁或删除
+    let response = client.post(&lock_url).json(&json!({ "owner": "bob" })).send().await.unwrap();
+    assert_eq!(response.status(), 409);
+    let response = client.delete(&lock_url).header("X-Lock-Owner", "bob").send().await.unwrap();
+    assert_eq!(response.status(), 409);
+    let response = client.delete(&info_url).header("X-Lock-Owner", "bob").send().await.unwrap();
+    assert_eq!(response.status(), 409);
+
+    // 持有者可以续期和释放
+    let response = client
+        .post(&lock_url)
+        .json(&json!({ "owner": "alice", "ttl_seconds": 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = client.get(&lock_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["owner"], "alice");
+
+    let response = client.delete(&lock_url).header("X-Lock-Owner", "alice").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(client.delete(&info_url).send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_replace_file_content() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "build.log", b"v1").await;
+    let content_url = server.url(&format!("/api/files/{}/content", record.id));
+
+    // 大于默认请求体限制的内容也能流式写入
+    let content = vec![b'x'; 3 * 1024 * 1024];
+    let response = client
+        .put(&content_url)
+        .header("If-Match", "\"1\"")
+        .body(content.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["id"], record.id.as_str());
+    assert_eq!(body["data"]["file_size"], content.len());
+    assert_eq!(body["data"]["sha256"].as_str().unwrap().len(), 64);
+    assert_eq!(body["data"]["processing"]["scan"], "not_configured");
+    assert_eq!(body["data"]["processing"]["jobs"], json!([]));
+    assert_eq!(std::fs::read(&record.file_path).unwrap(), content);
+
+    // 旧版本号被拒绝, 临时文件被清理
+    let response = client.put(&content_url).header("If-Match", "\"1\"").body("v3").send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let leftovers = std::fs::read_dir(server.storage_dir())
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(".upload-"))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    // 被其他人锁定时不能替换
+    client
+        .post(server.url(&format!("/api/files/{}/lock", record.id)))
+        .json(&json!({ "owner": "alice" }))
+        .send()
+        .await
+        .unwrap();
+    let response = client.put(&content_url).header("X-Lock-Owner", "bob").body("v3").send().await.unwrap();
+    assert_eq!(response.status(), 409);
+    let response = client.put(&content_url).header("X-Lock-Owner", "alice").body("v3").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(std::fs::read(&record.file_path).unwrap(), b"v3");
+
+    // 旧内容的视频信息被清空
+    let video = seed_file(&server, "demo.mp4", b"old video").await;
+    assert!(server.files().set_video_metadata(&video.id, 1, Some(42), Some("1920x1080")).await.unwrap());
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", video.id)))
+        .body("new video")
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert!(body["data"]["video_duration"].is_null());
+    assert!(body["data"]["video_resolution"].is_null());
+    assert!(!std::path::Path::new(&format!("{}.replaced", video.file_path)).exists());
+}
+
+#[tokio::test]
+async fn test_database_maintenance() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    for i in 0..20 {
+        let record = seed_file(&server, &format!("{}.txt", i), b"data").await;
+        client.delete(server.url(&format!("/api/files/{}", record.id))).send().await.unwrap();
+    }
+
+    let body: Value = client
+        .post(server.url("/api/admin/db/maintain"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"]["full_vacuum"], true);
+    assert!(body["data"]["size_after"].as_u64().unwrap() > 0);
+    assert_eq!(body["data"]["free_after"], 0);
+
+    // 切换为增量回收后不再执行完整 VACUUM
+    let body: Value = client
+        .post(server.url("/api/admin/db/maintain"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["full_vacuum"], false);
+}
+
+#[tokio::test]
+async fn test_replace_reports_processing_jobs() {
+    let server = TestServer::start().await.unwrap();
+    let record = seed_file(&server, "screen.png", b"png").await;
+
+    let body: Value = reqwest::Client::new()
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body("new bytes")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let processing = &body["data"]["processing"];
+    assert_eq!(processing["policies"], json!(["max_file_size"]));
+    assert_eq!(processing["dedup"], "not_checked");
+    let jobs = processing["jobs"].as_array().unwrap();
+    assert!(jobs.contains(&json!({ "name": "photo_metadata", "status": "pending" })));
+    assert!(jobs.contains(&json!({ "name": "ocr", "status": "skipped", "reason": "ocr_disabled" })));
+}
+
+#[tokio::test]
+async fn test_usage_accounting() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "report.bin", b"v1").await;
+
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body(vec![0u8; 1000])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    // 统计响应字节数不影响 Content-Length
+    assert!(response.content_length().is_some());
+
+    let body: Value = client
+        .get(server.url("/api/usage?subject=127.0.0.1"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"]["uploaded_bytes"], 1000);
+    let entries = body["data"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["subject"], "127.0.0.1");
+    assert!(entries[0]["requests"].as_i64().unwrap() >= 1);
+
+    let body: Value = client
+        .get(server.url("/api/usage?subject=10.0.0.1"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["entries"], json!([]));
+
+    let response = client.get(server.url("/api/usage?from=yesterday")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_playback_position() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let video = seed_file(&server, "all-hands.mp4", b"mp4").await;
+    let position_url = server.url(&format!("/api/video/{}/position", video.id));
+
+    let body: Value = client
+        .get(format!("{}?viewer=alice", position_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"], Value::Null);
+
+    let response = client
+        .post(&position_url)
+        .json(&json!({ "viewer": "alice", "position": 1834.5, "duration": 3600.0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 另一台设备上用同一个观看者标识读取
+    let body: Value = client
+        .get(format!("{}?viewer=alice", position_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["position"], 1834.5);
+    assert_eq!(body["data"]["duration"], 3600.0);
+
+    let body: Value = client
+        .get(format!("{}?viewer=bob", position_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"], Value::Null);
+
+    let response = client
+        .post(&position_url)
+        .json(&json!({ "viewer": "alice", "position": -1.0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let document = seed_file(&server, "notes.txt", b"text").await;
+    let response = client
+        .post(server.url(&format!("/api/video/{}/position", document.id)))
+        .json(&json!({ "viewer": "alice", "position": 1.0 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_collections() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let intro = seed_file(&server, "01-intro.mp4", b"intro").await;
+    let setup = seed_file(&server, "02-setup.mp4", b"setup").await;
+
+    let body: Value = client
+        .post(server.url("/api/collections"))
+        .json(&json!({
+            "name": "Onboarding",
+            "description": "New hire course",
+            "file_ids": [setup.id, intro.id],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["success"], true);
+    let collection_url = server.url(&format!("/api/collections/{}", body["data"]["id"].as_str().unwrap()));
+
+    // 详情按集合中的顺序返回文件记录
+    let body: Value = client.get(&collection_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["name"], "Onboarding");
+    let names: Vec<&str> = body["data"]["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["original_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["02-setup.mp4", "01-intro.mp4"]);
+
+    let response = client
+        .put(&collection_url)
+        .json(&json!({ "name": "Onboarding", "file_ids": [intro.id, setup.id] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 删除文件后自动从集合中移除
+    client.delete(server.url(&format!("/api/files/{}", intro.id))).send().await.unwrap();
+    let body: Value = client.get(server.url("/api/collections")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"][0]["file_ids"], json!([setup.id]));
+
+    let response = client
+        .post(server.url("/api/collections"))
+        .json(&json!({ "name": "Broken", "file_ids": ["missing"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    assert_eq!(client.delete(&collection_url).send().await.unwrap().status(), 200);
+    assert_eq!(client.get(&collection_url).send().await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn test_file_links() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let dump = seed_file(&server, "crash.dmp", b"dump").await;
+    let symbols = seed_file(&server, "app.pdb", b"pdb").await;
+
+    let response = client
+        .post(server.url(&format!("/api/files/{}/links", dump.id)))
+        .json(&json!({ "target_id": symbols.id, "kind": "belongs-with" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 两端的文件信息中都能看到关联
+    for id in [&dump.id, &symbols.id] {
+        let body: Value = client
+            .get(server.url(&format!("/api/files/{}", id)))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let links = body["data"]["links"].as_array().unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0]["source_id"], dump.id.as_str());
+        assert_eq!(links[0]["target_id"], symbols.id.as_str());
+        assert_eq!(links[0]["kind"], "belongs-with");
+    }
+
+    for link in [
+        json!({ "target_id": symbols.id, "kind": "Belongs With" }),
+        json!({ "target_id": dump.id, "kind": "derived-from" }),
+        json!({ "target_id": "missing", "kind": "derived-from" }),
+    ] {
+        let response = client
+            .post(server.url(&format!("/api/files/{}/links", dump.id)))
+            .json(&link)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    // 删除一端后关联随之删除
+    client.delete(server.url(&format!("/api/files/{}", symbols.id))).send().await.unwrap();
+    let body: Value = client
+        .get(server.url(&format!("/api/files/{}", dump.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["links"], json!([]));
+}
+
+#[tokio::test]
+async fn test_download_by_hash() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "artifact.tar", b"old").await;
+
+    let body: Value = client
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body("release bytes")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let sha256 = body["data"]["sha256"].as_str().unwrap().to_string();
+    let hash_url = server.url(&format!("/files/by-hash/{}", sha256));
+
+    let response = client.get(&hash_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-tar");
+    assert_eq!(response.headers()["content-length"], "13");
+    assert!(response.headers()["cache-control"].to_str().unwrap().contains("immutable"));
+    assert_eq!(response.bytes().await.unwrap(), "release bytes");
+
+    // 大写哈希和 Range 请求
+    let response = client
+        .get(server.url(&format!("/files/by-hash/{}", sha256.to_uppercase())))
+        .header("Range", "bytes=0-6")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.bytes().await.unwrap(), "release");
+
+    let response = client.get(server.url(&format!("/files/by-hash/{}", "0".repeat(64)))).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let response = client.get(server.url("/files/by-hash/not-a-hash")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_temp_directory_cleanup() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.temp.max_size = 64;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "notes.txt", b"v1").await;
+
+    // 替换内容后临时文件已被重命名, 不留在临时目录中
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body("v2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = client.get(server.url("/api/admin/temp")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["files"], 0);
+
+    // 模拟崩溃遗留的过期临时文件
+    let upload_dir = server.storage_dir().join(".tmp").join("upload");
+    std::fs::create_dir_all(&upload_dir).unwrap();
+    let stale = std::fs::File::create(upload_dir.join("orphan")).unwrap();
+    stale.set_len(100).unwrap();
+    stale
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3 * 24 * 60 * 60))
+        .unwrap();
+
+    let body: Value = client.get(server.url("/api/admin/temp")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["kinds"]["upload"]["bytes"], 100);
+
+    // 超过大小上限时拒绝新的上传
+    let response = client
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body("v3")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 507);
+
+    let body: Value = client
+        .post(server.url("/api/admin/temp/cleanup"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["removed_files"], 1);
+    assert_eq!(body["data"]["removed_bytes"], 100);
+
+    let body: Value = client.get(server.url("/api/admin/temp")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["files"], 0);
+    assert_eq!(body["data"]["rejected"], 1);
+    assert_eq!(body["data"]["removed_files"], 1);
+}
+
+#[tokio::test]
+async fn test_orphaned_derivative_cleanup() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "photo.png", b"png").await;
+    let orphan = uuid::Uuid::new_v4().to_string();
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 60 * 60);
+
+    let cache = server.storage_dir().join(".cache");
+    let write = |relative: String, len: u64, modified: Option<std::time::SystemTime>| {
+        let path = cache.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(len).unwrap();
+        if let Some(modified) = modified {
+            file.set_modified(modified).unwrap();
+        }
+    };
+    // 仍有所属记录、不是以文件 ID 命名、以及刚生成的条目都应保留
+    write(format!("thumbnails/{}.webp", record.id), 10, Some(old));
+    write("thumbnails/README".to_string(), 10, Some(old));
+    write(format!("images/{}-v1-320x0-contain.png", orphan), 10, None);
+    // 孤儿缩略图和按文件 ID 存放的目录
+    write(format!("thumbnails/{}.webp", orphan), 20, Some(old));
+    write(format!("hls/{}/segment-0.ts", orphan), 30, Some(old));
+    std::fs::File::open(cache.join("hls").join(&orphan))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    let body: Value = client
+        .post(server.url("/api/admin/derived/cleanup"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["scanned"], 4);
+    assert_eq!(body["data"]["removed_files"], 2);
+    assert_eq!(body["data"]["removed_bytes"], 50);
+    assert_eq!(body["data"]["kinds"]["hls"]["bytes"], 30);
+
+    assert!(cache.join(format!("thumbnails/{}.webp", record.id)).exists());
+    assert!(cache.join("thumbnails/README").exists());
+    assert!(cache.join(format!("images/{}-v1-320x0-contain.png", orphan)).exists());
+    assert!(!cache.join(format!("thumbnails/{}.webp", orphan)).exists());
+    assert!(!cache.join("hls").join(&orphan).exists());
+}
+
+#[tokio::test]
+async fn test_stats_capacity_breakdown() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let sha256 = "cd".repeat(32);
+
+    let notes = seed_file(&server, "notes.txt", b"abc").await;
+
+    // 两条记录共用一个存储文件, 另一条记录内容相同但单独存储
+    let mut records = Vec::new();
+    for name in ["build.zip", "build.zip", "build-copy.zip"] {
+        let file_path = server.file_manager().get_file_path(name);
+        std::fs::write(&file_path, b"0123456789").unwrap();
+        let record = FileRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            original_name: name.to_string(),
+            stored_name: name.to_string(),
+            file_path: file_path.to_string_lossy().to_string(),
+            file_size: 10,
+            sha256: Some(sha256.clone()),
+            ..notes.clone()
+        };
+        server.file_manager().save_file_record(&record).await.unwrap();
+        records.push(record);
+    }
+
+    let thumbnails = server.storage_dir().join(".cache").join("thumbnails");
+    std::fs::create_dir_all(&thumbnails).unwrap();
+    std::fs::write(thumbnails.join(format!("{}.webp", records[0].id)), b"thumb").unwrap();
+    let upload_dir = server.storage_dir().join(".tmp").join("upload");
+    std::fs::create_dir_all(&upload_dir).unwrap();
+    std::fs::write(upload_dir.join("partial"), b"pa").unwrap();
+
+    let body: Value = client.get(server.url("/api/stats")).send().await.unwrap().json().await.unwrap();
+    let stats = &body["data"];
+    assert_eq!(stats["total_files"], 4);
+    assert_eq!(stats["total_size"], 33);
+    assert_eq!(stats["physical_size"], 23);
+    assert_eq!(stats["dedup_savings"], 10);
+    assert_eq!(stats["unique_content_size"], 13);
+    assert_eq!(stats["backends"]["local"], 23);
+    assert_eq!(stats["derived_size"], 5);
+    assert_eq!(stats["temp_size"], 2);
+    assert_eq!(stats["trash_files"], 0);
+
+    // 回收站中的文件仍占用空间, 共用的存储文件只计一次
+    for id in [&notes.id, &records[0].id] {
+        let response = client.delete(server.url(&format!("/api/files/{}", id))).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let body: Value = client.get(server.url("/api/stats")).send().await.unwrap().json().await.unwrap();
+    let stats = &body["data"];
+    assert_eq!(stats["total_files"], 2);
+    assert_eq!(stats["total_size"], 20);
+    assert_eq!(stats["trash_files"], 2);
+    assert_eq!(stats["trash_size"], 13);
+    assert_eq!(stats["physical_size"], 23);
+    assert_eq!(stats["dedup_savings"], 10);
+}
+
+#[tokio::test]
+async fn test_delete_protection() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.delete_protection.name_contains = vec!["release".to_string()];
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let protected = seed_file(&server, "app-1.0-release.zip", b"ga").await;
+    let scratch = seed_file(&server, "scratch.txt", b"tmp").await;
+
+    let response = client
+        .delete(server.url(&format!("/api/files/{}", protected.id)))
+        .header("Accept-Language", "en")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("name contains release"));
+    assert!(server.file_manager().get_file_by_id(&protected.id).await.unwrap().is_some());
+
+    // 未命中规则的文件照常删除
+    let response = client.delete(server.url(&format!("/api/files/{}", scratch.id))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // 本机带 force=true 可以删除
+    let response = client
+        .delete(server.url(&format!("/api/files/{}?force=true", protected.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(server.file_manager().get_file_by_id(&protected.id).await.unwrap().is_none());
+
+    // 启用密钥认证后, 本机访问也必须使用 admin 密钥
+    use rust_internal_file_server::config::{ApiKeyConfig, KeyAccess};
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.delete_protection.name_contains = vec!["release".to_string()];
+    config.auth.keys = [("ci", KeyAccess::ReadWrite), ("ops", KeyAccess::Admin)]
+        .into_iter()
+        .map(|(name, access)| ApiKeyConfig {
+            name: name.to_string(),
+            key: format!("{}-secret", name),
+            key_file: None,
+            key_command: None,
+            access,
+        })
+        .collect();
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let protected = seed_file(&server, "app-2.0-release.zip", b"ga").await;
+    let force_url = server.url(&format!("/api/files/{}?force=true", protected.id));
+    let response = client.delete(&force_url).bearer_auth("ci-secret").send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("强制删除需要管理员密钥"));
+    let response = client.delete(&force_url).bearer_auth("ops-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(server.file_manager().get_file_by_id(&protected.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_diff_files() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let a = seed_file(&server, "before.conf", b"port = 80\nhost = a\nlog = info\n").await;
+    let b = seed_file(&server, "after.conf", b"port = 8080\nhost = a\nlog = info\n").await;
+    let binary = seed_file(&server, "blob.bin", &[0, 159, 146, 150]).await;
+
+    let body: Value = client
+        .get(server.url(&format!("/api/files/diff?a={}&b={}", a.id, b.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["identical"], false);
+    assert_eq!(body["data"]["additions"], 1);
+    assert_eq!(body["data"]["deletions"], 1);
+    let diff = body["data"]["diff"].as_str().unwrap();
+    assert!(diff.starts_with("--- before.conf\n+++ after.conf\n"));
+    assert!(diff.contains("-port = 80\n+port = 8080\n"));
+
+    let body: Value = client
+        .get(server.url(&format!("/api/files/diff?a={}&b={}", a.id, a.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["identical"], true);
+    assert_eq!(body["data"]["diff"], "");
+
+    let response = client
+        .get(server.url(&format!("/api/files/diff?a={}&b={}", a.id, binary.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .get(server.url(&format!("/api/files/diff?a={}&b=missing", a.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_archive_entries() {
+    use std::io::Write;
+
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    zip.add_directory("logs/", options).unwrap();
+    zip.start_file("logs/app.log", options).unwrap();
+    zip.write_all(&b"line\n".repeat(1000)).unwrap();
+    zip.start_file("README.txt", options).unwrap();
+    zip.write_all(b"support bundle").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let bundle = seed_file(&server, "bundle.zip", &bytes).await;
+    let notes = seed_file(&server, "notes.txt", b"plain").await;
+
+    let body: Value = client
+        .get(server.url(&format!("/api/files/{}/archive/entries", bundle.id)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body["data"],
+        json!([
+            {"path": "logs/", "size": 0, "is_dir": true},
+            {"path": "logs/app.log", "size": 5000, "is_dir": false},
+            {"path": "README.txt", "size": 14, "is_dir": false},
+        ])
+    );
+
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries/logs/app.log", bundle.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-disposition"].to_str().unwrap().contains("app.log"));
+    assert_eq!(response.bytes().await.unwrap(), b"line\n".repeat(1000));
+
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries/missing.txt", bundle.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries", notes.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_search_download() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.storage.max_archive_size = 64;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    seed_file(&server, "incident-42.log", b"disk full").await;
+    seed_file(&server, "incident-42.log", b"retry").await;
+    seed_file(&server, "incident_report.pdf", b"%PDF").await;
+    seed_file(&server, "unrelated.log", b"ok").await;
+
+    let body: Value = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({"name": "INCIDENT-", "mime_type": "text/", "dry_run": true}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"], json!({"count": 2, "total_size": 14, "max_size": 64, "within_limit": true}));
+
+    let response = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({"name": "incident-"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/zip");
+    let bytes = response.bytes().await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes.to_vec())).unwrap();
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"incident-42.log".to_string()));
+    // 同名文件以文件 ID 前缀区分
+    assert!(names.iter().any(|name| name != "incident-42.log" && name.ends_with("-incident-42.log")));
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("incident-42.log").unwrap(), &mut content).unwrap();
+    assert!(content == "disk full" || content == "retry");
+
+    // 下划线按字面匹配, 不作为 LIKE 通配符
+    let body: Value = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({"name": "incident_", "dry_run": true}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["count"], 1);
+
+    let response = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({"name": "nothing-matches"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // 超过打包大小上限
+    seed_file(&server, "incident-big.log", &[b'x'; 100]).await;
+    let response = client
+        .post(server.url("/api/search/download"))
+        .json(&json!({"name": "incident-"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = client.get(server.url("/api/admin/temp")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["files"], 0);
+}
+
+#[tokio::test]
+async fn test_atom_feed() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let build = seed_file(&server, "build <1.2>.zip", b"zip").await;
+    seed_file(&server, "photo.jpg", b"jpg").await;
+
+    let response = client.get(server.url("/api/feed.atom")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("application/atom+xml"));
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+    assert!(feed.contains(&format!("<id>urn:uuid:{}</id>", build.id)));
+    assert!(feed.contains("<title>build &lt;1.2&gt;.zip</title>"));
+    assert!(feed.contains(&format!("href=\"http://{}/api/files/{}\"", server.addr(), build.id)));
+    assert_eq!(feed.matches("<entry>").count(), 2);
+
+    let feed = client
+        .get(server.url("/api/feed.atom?mime_type=application/zip"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(feed.matches("<entry>").count(), 1);
+    assert!(!feed.contains("photo.jpg"));
+}
+
+#[tokio::test]
+async fn test_share_qr_code() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let record = seed_file(&server, "firmware.bin", b"old").await;
+
+    // 还没有内容哈希时无法生成下载地址
+    let response = client
+        .get(server.url(&format!("/api/files/{}/share/qr", record.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+
+    client
+        .put(server.url(&format!("/api/files/{}/content", record.id)))
+        .body("firmware bytes")
+        .send()
+        .await
+        .unwrap();
+
+    let response = client
+        .get(server.url(&format!("/api/files/{}/share/qr?scale=4", record.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let png = image::load_from_memory(&response.bytes().await.unwrap()).unwrap().to_luma8();
+    assert_eq!(png.width(), png.height());
+    assert_eq!(png.width() % 4, 0);
+    // 左上角是空白区, 之后是定位图案的黑色模块
+    assert_eq!(png.get_pixel(0, 0).0, [255]);
+    assert_eq!(png.get_pixel(16, 16).0, [0]);
+
+    let response = client
+        .get(server.url(&format!("/api/files/{}/share/qr?scale=0", record.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_paste_snippet() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let body: Value = client
+        .post(server.url("/api/paste?syntax=rust&expires_in=3600"))
+        .body("fn main() { println!(\"<hi>\"); }")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+    assert!(body["data"]["original_name"].as_str().unwrap().ends_with(".rust"));
+    assert!(body["data"]["available_until"].is_string());
+    assert_eq!(body["data"]["view_url"], server.url(&format!("/paste/{}", id)));
+
+    let response = client.get(server.url(&format!("/paste/{}", id))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<code class=\"language-rust\">fn main() { println!(&quot;&lt;hi&gt;&quot;); }</code>"));
+
+    let response = client.post(server.url("/api/paste?syntax=../x")).body("x").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.post(server.url("/api/paste")).body(vec![0xff, 0xfe]).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.post(server.url("/api/paste")).body("").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_screenshot_upload() {
+    use rust_internal_file_server::config::ThumbnailFormat;
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.image.thumbnail_size = "40x40".to_string();
+    config.image.thumbnail_formats = vec![ThumbnailFormat::Jpeg, ThumbnailFormat::Webp];
+    config.image.thumbnail_sizes.insert("tiny".to_string(), "10x10".to_string());
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let mut png = Vec::new();
+    image::RgbaImage::from_pixel(200, 100, image::Rgba([10, 20, 30, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let body: Value = client
+        .post(server.url("/api/screenshot"))
+        .body(png.clone())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let data = &body["data"];
+    assert!(data["original_name"].as_str().unwrap().starts_with("screenshot-"));
+    assert!(data["original_name"].as_str().unwrap().ends_with(".png"));
+    assert_eq!(data["mime_type"], "image/png");
+
+    let view = client.get(data["view_url"].as_str().unwrap()).send().await.unwrap();
+    assert_eq!(view.status(), 200);
+    assert_eq!(view.bytes().await.unwrap(), png);
+
+    let thumbnail = client.get(data["thumbnail_url"].as_str().unwrap()).send().await.unwrap();
+    assert_eq!(thumbnail.headers()["content-type"], "image/jpeg");
+    let thumbnail = image::load_from_memory(&thumbnail.bytes().await.unwrap()).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (40, 20));
+
+    // 每个尺寸按每种格式各生成一张
+    let thumbnails = server.storage_dir().join(".cache").join("thumbnails");
+    let id = data["id"].as_str().unwrap();
+    for (name, size) in [(format!("{}.webp", id), (40, 20)), (format!("{}-tiny.jpg", id), (10, 5)), (format!("{}-tiny.webp", id), (10, 5))] {
+        let thumbnail = image::open(thumbnails.join(&name)).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), size, "{}", name);
+    }
+
+    // 按 Accept 选择格式, 都不接受时返回配置中的第一种
+    let thumbnail_url = data["thumbnail_url"].as_str().unwrap();
+    for (query, accept, mime, size) in [
+        ("", "image/webp,image/*;q=0.8", "image/webp", (40, 20)),
+        ("?size=tiny", "image/avif,image/webp", "image/webp", (10, 5)),
+        ("?size=tiny", "image/jpeg;q=0, image/*", "image/webp", (10, 5)),
+        ("?size=tiny", "text/html", "image/jpeg", (10, 5)),
+    ] {
+        let response = client.get(format!("{}{}", thumbnail_url, query)).header("accept", accept).send().await.unwrap();
+        assert_eq!(response.headers()["content-type"], mime, "{}", accept);
+        assert_eq!(response.headers()["vary"], "Accept");
+        let thumbnail = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), size);
+    }
+
+    // 缩略图地址在内容替换后不变, 只做短缓存并按 ETag 校验
+    let response = client.get(thumbnail_url).header("accept", "image/webp").send().await.unwrap();
+    assert_eq!(response.headers()["cache-control"], "public, max-age=300");
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}-v1-{}.webp\"", id, id));
+    let response = client
+        .get(thumbnail_url)
+        .header("accept", "image/webp")
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    let response = client.get(thumbnail_url).header("if-none-match", &etag).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/jpeg");
+
+    let response = client.post(server.url("/api/screenshot")).body("not an image").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = client.get(server.url("/api/files")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_cache_control_policies() {
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.web.cache_control.preview = "public, max-age=60".to_string();
+    config.web.cache_control.immutable = String::new();
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 列表接口不缓存
+    let response = client.get(server.url("/api/files")).send().await.unwrap();
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+
+    // 预览使用配置的短缓存
+    let body: Value = client
+        .post(server.url("/api/paste"))
+        .body("fn main() {}")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let paste_id = body["data"]["id"].as_str().unwrap();
+    let response = client.get(server.url(&format!("/paste/{}", paste_id))).send().await.unwrap();
+    assert_eq!(response.headers()["cache-control"], "public, max-age=60");
+
+    // 策略为空时不设置; 错误响应不设置
+    let sha256 = body["data"]["sha256"].as_str().unwrap();
+    let response = client.get(server.url(&format!("/files/by-hash/{}", sha256))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("cache-control").is_none());
+    let response = client.get(server.url("/api/files/missing")).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    assert!(response.headers().get("cache-control").is_none());
+}
+
+#[tokio::test]
+async fn test_speedtest() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let response = client.get(server.url("/api/speedtest/download?size_mb=2")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["cache-control"], "no-store");
+    let bytes = response.bytes().await.unwrap();
+    assert_eq!(bytes.len(), 2 * 1024 * 1024);
+
+    let response = client.get(server.url("/api/speedtest/download?size_mb=0")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let body: Value = client
+        .post(server.url("/api/speedtest/upload"))
+        .body(bytes)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["bytes"], 2 * 1024 * 1024);
+    assert!(body["data"]["mbps"].as_f64().unwrap() >= 0.0);
+
+    // 不写入存储
+    let body: Value = client.get(server.url("/api/stats")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["total_files"], 0);
+}
+
+#[cfg(feature = "profiling")]
+#[tokio::test]
+async fn test_cpu_profile() {
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let response = client.get(server.url("/api/admin/profile/cpu?seconds=0")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    // 采样期间让一个线程保持忙碌, 保证有样本
+    let busy = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let spinning = busy.clone();
+    let spinner = std::thread::spawn(move || {
+        let mut counter = 0u64;
+        while spinning.load(std::sync::atomic::Ordering::Relaxed) {
+            counter = std::hint::black_box(counter.wrapping_add(1));
+        }
+    });
+    let response = client.get(server.url("/api/admin/profile/cpu?seconds=1")).send().await.unwrap();
+    busy.store(false, std::sync::atomic::Ordering::Relaxed);
+    spinner.join().unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    assert!(response.text().await.unwrap().contains("<svg"));
+}
+
+#[tokio::test]
+async fn test_memory_budgets() {
+    use std::io::Write;
+
+    let mut config = rust_internal_file_server::config::Config::default();
+    config.memory.archive_index = 256;
+    config.memory.image_decode = 64 * 64 * 4;
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 一个成员的中央目录在预算内, 十个成员超出
+    let build_zip = |count: usize| {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for i in 0..count {
+            zip.start_file(format!("entry-{}.txt", i), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(b"x").unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    };
+    let small = seed_file(&server, "small.zip", &build_zip(1)).await;
+    let large = seed_file(&server, "large.zip", &build_zip(10)).await;
+
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries", small.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries", large.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+    let response = client
+        .get(server.url(&format!("/api/files/{}/archive/entries/entry-0.txt", large.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+
+    // 像素数超出解码预算的图片不解码
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::new(100, 100)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let photo = seed_file(&server, "photo.png", png.get_ref()).await;
+    let response = client
+        .get(server.url(&format!("/api/files/{}/image?w=10", photo.id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+
+    let body: Value = client.get(server.url("/api/admin/memory")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["archive_index"]["rejected"], 2);
+    assert_eq!(body["data"]["archive_index"]["limit"], 256);
+    assert!(body["data"]["archive_index"]["largest_accepted"].as_u64().unwrap() > 0);
+    assert_eq!(body["data"]["image_decode"]["rejected"], 1);
+    assert_eq!(body["data"]["ocr_output"]["rejected"], 0);
+}
+
+#[tokio::test]
+async fn test_seed_files() {
+    use rust_internal_file_server::seed::{seed_files, SeedOptions};
+
+    let server = TestServer::start().await.unwrap();
+    let client = reqwest::Client::new();
+    let options = SeedOptions {
+        count: 20,
+        seed: 7,
+        max_size: 64 * 1024,
+    };
+
+    let report = seed_files(server.file_manager(), &options).await.unwrap();
+    assert_eq!(report.created, 20);
+    assert_eq!(report.kinds.values().sum::<u64>(), 20);
+
+    // 相同种子重复执行不会重复生成
+    let again = seed_files(server.file_manager(), &options).await.unwrap();
+    assert_eq!(again.created, 0);
+    assert_eq!(again.skipped, 20);
+
+    let body: Value = client.get(server.url("/api/files?limit=100")).send().await.unwrap().json().await.unwrap();
+    let files = body["data"].as_array().unwrap();
+    assert_eq!(files.len(), 20);
+
+    // 生成的文件可以正常下载, 内容与记录的哈希一致
+    let file = &files[0];
+    let bytes = client
+        .get(server.url(&format!("/files/by-hash/{}", file["sha256"].as_str().unwrap())))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(bytes.len() as i64, file["file_size"].as_i64().unwrap());
+    assert!(files.iter().any(|f| f["is_video"] == true && f["video_resolution"].is_string()));
+}
+
+#[tokio::test]
+async fn test_directory_mounts() {
+    use rust_internal_file_server::config::{Config, MountConfig};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("datasets");
+    let outside = dir.path().join("outside");
+    std::fs::create_dir_all(root.join("sub")).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(root.join("readme.txt"), "hello mount").unwrap();
+    std::fs::write(root.join("sub/data.csv"), "a,b\n1,2\n").unwrap();
+    std::fs::write(root.join(".secret"), "hidden").unwrap();
+    std::fs::write(outside.join("escape.txt"), "outside").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+    let mut config = Config::default();
+    config.web.mounts.push(MountConfig {
+        route: "/datasets".to_string(),
+        path: root.clone(),
+        read_only: true,
+    });
+    let server = TestServer::start_with_config(config.clone()).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 目录返回 JSON 列表, 目录在前, 不含隐藏文件
+    let body: Value = client.get(server.url("/datasets")).send().await.unwrap().json().await.unwrap();
+    let names: Vec<&str> = body["data"]["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap())
+        .collect();
+    #[cfg(unix)]
+    assert_eq!(names, ["link", "sub", "readme.txt"]);
+    let body: Value = client.get(server.url("/datasets/sub/")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["path"], "sub");
+    assert_eq!(body["data"]["entries"][0]["name"], "data.csv");
+    assert_eq!(body["data"]["entries"][0]["size"], 8);
+
+    // 文件直接下载, 支持 Range
+    let response = client.get(server.url("/datasets/readme.txt")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "hello mount");
+    let response = client
+        .get(server.url("/datasets/readme.txt"))
+        .header("Range", "bytes=6-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.text().await.unwrap(), "mount");
+
+    // 隐藏文件、.. 和指向挂载目录之外的链接都不可访问
+    for path in ["/datasets/.secret", "/datasets/sub/%2e%2e/%2e%2e/etc", "/datasets/link/escape.txt"] {
+        let response = client.get(server.url(path)).send().await.unwrap();
+        assert_eq!(response.status(), 404, "{}", path);
+    }
+
+    // 启用密钥认证后挂载目录同样需要密钥
+    let mut keyed = config.clone();
+    keyed.auth.keys = vec![rust_internal_file_server::config::ApiKeyConfig {
+        name: "ci".to_string(),
+        key: "ci-secret".to_string(),
+        key_file: None,
+        key_command: None,
+        access: rust_internal_file_server::config::KeyAccess::Read,
+    }];
+    let keyed_server = TestServer::start_with_config(keyed).await.unwrap();
+    for path in ["/datasets", "/datasets/", "/datasets/readme.txt", "/datasets/sub/data.csv"] {
+        let response = client.get(keyed_server.url(path)).send().await.unwrap();
+        assert_eq!(response.status(), 401, "{}", path);
+    }
+    let response = client.get(keyed_server.url("/datasets/readme.txt")).bearer_auth("ci-secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "hello mount");
+
+    // 挂载路由不能占用内置接口
+    config.web.mounts[0].route = "/api/datasets".to_string();
+    assert!(config.validate().is_err());
+    config.web.mounts[0].route = "/datasets".to_string();
+    config.web.mounts[0].read_only = false;
+    assert!(config.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_mount_index_pages() {
+    use rust_internal_file_server::config::{Config, MountConfig};
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("photos/2024")).unwrap();
+    std::fs::write(dir.path().join("photos/small.txt"), "1").unwrap();
+    std::fs::write(dir.path().join("photos/big <draft>.png"), vec![0u8; 4096]).unwrap();
+    std::fs::write(dir.path().join("photos/a b&c.mp4"), vec![0u8; 100]).unwrap();
+
+    let mut config = Config::default();
+    config.web.mounts.push(MountConfig {
+        route: "/shared".to_string(),
+        path: dir.path().to_path_buf(),
+        read_only: true,
+    });
+    let server = TestServer::start_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // 程序访问按指定字段排序, 目录始终在前
+    let body: Value = client
+        .get(server.url("/shared/photos?sort=size&order=desc"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<&str> = body["data"]["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["2024", "big <draft>.png", "a b&c.mp4", "small.txt"]);
+    let response = client.get(server.url("/shared/photos?sort=owner")).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    // 浏览器访问返回索引页: 面包屑、排序表头、转义和编码后的链接
+    let response = client
+        .get(server.url("/shared/photos?sort=name&order=asc"))
+        .header("Accept", "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<a href=\"/shared/\">/shared</a> / <a href=\"/shared/photos/\">photos</a>"));
+    assert!(html.contains("<a href=\"?sort=name&amp;order=desc\">名称</a> &#x25B2;"));
+    assert!(html.contains("<a href=\"/shared/photos/2024/\">2024</a>"));
+    assert!(html.contains("<a href=\"/shared/photos/a%20b%26c.mp4\">a b&amp;c.mp4</a>"));
+    assert!(html.contains("big &lt;draft&gt;.png</a></td><td>4.0 KB</td>"));
+    assert!(html.contains("<a href=\"/shared/\">上级目录</a>"));
+    assert!(html.find("2024").unwrap() < html.find("a b&amp;c.mp4").unwrap());
+
+    // 界面文字跟随请求语言
+    let html = client
+        .get(server.url("/shared/"))
+        .header("Accept", "text/html")
+        .header("Accept-Language", "en")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(">Name</a>"));
+    assert!(!html.contains("Parent directory"));
+
+    // 链接指向的文件可以直接下载
+    let response = client.get(server.url("/shared/photos/a%20b%26c.mp4")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "video/mp4");
+}
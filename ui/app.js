@@ -0,0 +1,304 @@
+// 内置网页界面: 文件列表、拖放上传、删除和视频播放, 只使用服务器的公开接口
+"use strict";
+
+const PAGE_SIZE = 50;
+
+const state = {
+  offset: 0,
+  readOnly: false,
+};
+
+const $ = (selector) => document.querySelector(selector);
+
+const API_KEY_STORAGE = "file-server-api-key";
+const API_KEY_COOKIE = "file_server_api_key";
+
+// 服务器启用 API 密钥认证时, 密钥保存在本地并随每个接口请求发送
+function authHeaders() {
+  const key = localStorage.getItem(API_KEY_STORAGE);
+  return key ? { "X-API-Key": key } : {};
+}
+
+// 缩略图、视频和下载链接由浏览器直接加载, 不能带请求头, 服务器对这类 GET 请求接受 Cookie 中的密钥
+function syncApiKeyCookie() {
+  const key = localStorage.getItem(API_KEY_STORAGE);
+  if (key) {
+    document.cookie = `${API_KEY_COOKIE}=${encodeURIComponent(key)}; path=/; SameSite=Strict`;
+  }
+}
+
+// 返回 401 时询问密钥, 输入后重试一次
+function askForApiKey() {
+  const key = prompt("服务器需要 API 密钥");
+  if (!key) {
+    return false;
+  }
+  localStorage.setItem(API_KEY_STORAGE, key.trim());
+  syncApiKeyCookie();
+  return true;
+}
+
+// 接口统一返回 {success, data, error}, 失败时抛出服务器给出的错误信息
+async function api(url, options, retried) {
+  const request = { ...options, headers: { ...(options && options.headers), ...authHeaders() } };
+  const response = await fetch(url, request);
+  if (response.status === 401 && !retried && askForApiKey()) {
+    return api(url, options, true);
+  }
+  const body = await response.json().catch(() => null);
+  if (!response.ok || !body || !body.success) {
+    throw new Error((body && body.error) || `${response.status} ${response.statusText}`);
+  }
+  return body.data;
+}
+
+function showError(error) {
+  const element = $("#error");
+  element.textContent = error ? String(error.message || error) : "";
+  element.hidden = !error;
+}
+
+function formatSize(bytes) {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let size = bytes;
+  let unit = 0;
+  while (size >= 1024 && unit < units.length - 1) {
+    size /= 1024;
+    unit += 1;
+  }
+  return `${unit === 0 ? size : size.toFixed(1)} ${units[unit]}`;
+}
+
+// 浏览器内直接打开的地址, 视频播放通过它发送 Range 请求
+function inlineUrl(file) {
+  return `/files/${encodeURIComponent(file.id)}/${encodeURIComponent(file.original_name)}`;
+}
+
+function isVideo(file) {
+  return file.is_video || file.mime_type.startsWith("video/");
+}
+
+async function loadConfig() {
+  try {
+    const config = await api("/api/ui-config");
+    document.title = config.title;
+    $("#title").textContent = config.title;
+    document.documentElement.style.setProperty("--accent", config.accent_color);
+    if (config.logo_url) {
+      $("#logo").src = config.logo_url;
+      $("#logo").hidden = false;
+    }
+    if (config.footer_text) {
+      $("#footer").textContent = config.footer_text;
+      $("#footer").hidden = false;
+    }
+    state.readOnly = config.read_only;
+  } catch (error) {
+    showError(error);
+  }
+  $("#read-only").hidden = !state.readOnly;
+  $("#drop-zone").hidden = state.readOnly;
+}
+
+function fileRow(file) {
+  const row = document.createElement("tr");
+  row.dataset.id = file.id;
+
+  const thumb = document.createElement("td");
+  thumb.className = "thumb";
+  if (file.thumbnail_path) {
+    const img = document.createElement("img");
+    img.loading = "lazy";
+    img.alt = "";
+    img.src = `/api/files/${encodeURIComponent(file.id)}/thumbnail`;
+    thumb.append(img);
+  }
+
+  const name = document.createElement("td");
+  const link = document.createElement("a");
+  link.textContent = file.original_name;
+  link.href = inlineUrl(file);
+  if (isVideo(file)) {
+    link.addEventListener("click", (event) => {
+      event.preventDefault();
+      play(file);
+    });
+  } else {
+    link.target = "_blank";
+  }
+  name.append(link);
+
+  const size = document.createElement("td");
+  size.className = "size";
+  size.textContent = formatSize(file.file_size);
+
+  const time = document.createElement("td");
+  time.className = "time";
+  time.textContent = new Date(file.upload_time).toLocaleString();
+
+  const actions = document.createElement("td");
+  actions.className = "actions";
+  const download = document.createElement("a");
+  download.textContent = "下载";
+  // 启用认证时由 Cookie 中的密钥授权
+  download.href = inlineUrl(file);
+  download.download = file.original_name;
+  actions.append(download);
+  if (!state.readOnly) {
+    const remove = document.createElement("button");
+    remove.type = "button";
+    remove.className = "danger";
+    remove.textContent = "删除";
+    remove.addEventListener("click", () => deleteFile(file, row));
+    actions.append(" ", remove);
+  }
+
+  row.append(thumb, name, size, time, actions);
+  return row;
+}
+
+async function loadFiles(reset) {
+  if (reset) {
+    state.offset = 0;
+    $("#files tbody").replaceChildren();
+  }
+  try {
+    const files = await api(`/api/files?limit=${PAGE_SIZE}&offset=${state.offset}`);
+    state.offset += files.length;
+    $("#files tbody").append(...files.map(fileRow));
+    $("#more").hidden = files.length < PAGE_SIZE;
+    $("#empty").hidden = state.offset > 0;
+    showError(null);
+  } catch (error) {
+    showError(error);
+  }
+}
+
+async function deleteFile(file, row) {
+  if (!confirm(`删除 ${file.original_name}?`)) {
+    return;
+  }
+  try {
+    // 带上列表中的版本号, 文件在此期间被修改时服务器拒绝删除
+    await api(`/api/files/${encodeURIComponent(file.id)}`, {
+      method: "DELETE",
+      headers: { "If-Match": `"${file.version}"` },
+    });
+    row.remove();
+    state.offset = Math.max(0, state.offset - 1);
+    $("#empty").hidden = state.offset > 0;
+    showError(null);
+  } catch (error) {
+    showError(error);
+  }
+}
+
+function play(file) {
+  $("#player-title").textContent = file.original_name;
+  $("#video").src = inlineUrl(file);
+  $("#player").hidden = false;
+  $("#player").scrollIntoView({ behavior: "smooth" });
+  $("#video").play().catch(() => {});
+}
+
+function closePlayer() {
+  const video = $("#video");
+  video.pause();
+  video.removeAttribute("src");
+  video.load();
+  $("#player").hidden = true;
+}
+
+// 每次请求上传一个文件, 用 XMLHttpRequest 以便显示进度
+function uploadFile(file) {
+  const item = document.createElement("li");
+  const label = document.createElement("span");
+  label.textContent = file.name;
+  const progress = document.createElement("progress");
+  progress.max = 1;
+  progress.value = 0;
+  const status = document.createElement("span");
+  item.append(label, progress, status);
+  $("#uploads").append(item);
+
+  return new Promise((resolve) => {
+    const form = new FormData();
+    form.append("file", file, file.name);
+
+    const xhr = new XMLHttpRequest();
+    xhr.open("POST", "/api/upload");
+    for (const [name, value] of Object.entries(authHeaders())) {
+      xhr.setRequestHeader(name, value);
+    }
+    xhr.responseType = "json";
+    xhr.upload.addEventListener("progress", (event) => {
+      if (event.lengthComputable) {
+        progress.value = event.loaded / event.total;
+      }
+    });
+    xhr.addEventListener("load", () => {
+      const body = xhr.response;
+      if (xhr.status === 200 && body && body.success) {
+        progress.value = 1;
+        status.textContent = "完成";
+        setTimeout(() => item.remove(), 3000);
+      } else {
+        status.className = "error";
+        status.textContent = (body && body.error) || `${xhr.status} ${xhr.statusText}`;
+      }
+      resolve();
+    });
+    xhr.addEventListener("error", () => {
+      status.className = "error";
+      status.textContent = "网络错误";
+      resolve();
+    });
+    xhr.send(form);
+  });
+}
+
+async function uploadFiles(files) {
+  for (const file of files) {
+    await uploadFile(file);
+  }
+  await loadFiles(true);
+}
+
+function setupDropZone() {
+  const zone = $("#drop-zone");
+  const input = $("#file-input");
+
+  input.addEventListener("change", () => {
+    uploadFiles([...input.files]);
+    input.value = "";
+  });
+
+  // 拖到页面任意位置都可以上传, 避免浏览器直接打开文件
+  document.addEventListener("dragover", (event) => {
+    event.preventDefault();
+    if (!state.readOnly) {
+      zone.classList.add("active");
+    }
+  });
+  document.addEventListener("dragleave", (event) => {
+    if (event.relatedTarget === null) {
+      zone.classList.remove("active");
+    }
+  });
+  document.addEventListener("drop", (event) => {
+    event.preventDefault();
+    zone.classList.remove("active");
+    if (!state.readOnly && event.dataTransfer.files.length > 0) {
+      uploadFiles([...event.dataTransfer.files]);
+    }
+  });
+}
+
+document.addEventListener("DOMContentLoaded", async () => {
+  $("#more").addEventListener("click", () => loadFiles(false));
+  $("#player-close").addEventListener("click", closePlayer);
+  syncApiKeyCookie();
+  setupDropZone();
+  await loadConfig();
+  await loadFiles(true);
+});